@@ -0,0 +1,683 @@
+//! Whole-workspace export and import.
+//!
+//! A workspace archive is a zip file laid out as:
+//!
+//! ```text
+//! manifest.json                    format and schema version, row counts
+//! tables/<table>.jsonl             one JSON object per row, keyed by column name
+//! attachments/<id>/<file name>     the file referenced by attachments.path, if it exists
+//! ```
+//!
+//! Rows are written column-for-column, so an archive can be imported into any
+//! install whose schema is at least as new as the one it was exported from.
+//! Columns that no longer exist are dropped and new columns get their defaults.
+//!
+//! Imports merge into the current workspace rather than replacing it: rows whose
+//! primary key already exists are left alone, so importing the same archive
+//! twice is harmless.
+
+use chrono::Utc;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::attachment_store;
+use crate::migrations;
+
+/// Identifies our archives, so we don't try to import some other zip file.
+pub const ARCHIVE_FORMAT: &str = "camp-workspace";
+
+/// Bump when the archive layout itself changes (not the database schema).
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// Tables included in the archive, in an order that satisfies the triggers and
+/// foreign keys on import (e.g. verify_chats_project_id_insert needs projects first).
+const TABLES: [&str; 8] = [
+    "projects",
+    "chats",
+    "message_sets",
+    "messages",
+    "message_parts",
+    "attachments",
+    "message_attachments",
+    "project_attachments",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub format_version: u32,
+    /// Latest migration version applied to the exporting database.
+    pub schema_version: i64,
+    pub app_version: String,
+    pub exported_at: String,
+    /// Number of rows written for each table.
+    pub tables: BTreeMap<String, usize>,
+    pub attachment_files: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceImportSummary {
+    pub schema_version: i64,
+    /// Number of new rows inserted for each table.
+    pub inserted: BTreeMap<String, usize>,
+    /// Rows that were already present in this workspace.
+    pub skipped: usize,
+    pub attachment_files: usize,
+}
+
+/// The latest schema version this build knows about.
+pub fn current_schema_version() -> i64 {
+    migrations::migrations()
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Writes the whole workspace to `archive_path`.
+///
+/// The archive is written next to the destination and renamed into place once
+/// complete, so a failed export never leaves a truncated file behind.
+pub fn export_workspace(
+    conn: &mut Connection,
+    archive_path: &Path,
+    app_version: &str,
+) -> Result<ArchiveManifest, String> {
+    let partial_path = archive_path.with_extension("partial");
+    match write_archive(conn, &partial_path, app_version) {
+        Ok(manifest) => {
+            fs::rename(&partial_path, archive_path).map_err(|e| e.to_string())?;
+            log::info!(
+                "Exported workspace to {} ({} attachment files)",
+                archive_path.display(),
+                manifest.attachment_files
+            );
+            Ok(manifest)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial_path);
+            Err(e)
+        }
+    }
+}
+
+fn write_archive(
+    conn: &mut Connection,
+    path: &Path,
+    app_version: &str,
+) -> Result<ArchiveManifest, String> {
+    // Read everything inside one transaction so the snapshot is consistent even
+    // if the frontend writes to the database while we export
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut tables = BTreeMap::new();
+    let mut attachment_paths: Vec<(String, String)> = Vec::new();
+    for table in TABLES {
+        zip.start_file(format!("tables/{}.jsonl", table), options)
+            .map_err(|e| e.to_string())?;
+
+        let mut stmt = tx
+            .prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))
+            .map_err(|e| e.to_string())?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+
+        let mut count = 0;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            let mut object = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                let value: SqlValue = row.get(i).map_err(|e| e.to_string())?;
+                object.insert(column.clone(), sql_to_json(value));
+            }
+
+            if table == "attachments" {
+                if let (Some(Value::String(id)), Some(Value::String(path))) =
+                    (object.get("id"), object.get("path"))
+                {
+                    attachment_paths.push((id.clone(), path.clone()));
+                }
+            }
+
+            serde_json::to_writer(&mut zip, &object).map_err(|e| e.to_string())?;
+            zip.write_all(b"\n").map_err(|e| e.to_string())?;
+            count += 1;
+        }
+        tables.insert(table.to_string(), count);
+    }
+
+    // Webpage attachments store a URL rather than a path, and uploads may have been
+    // cleaned up since, so only files that actually exist are bundled
+    let mut attachment_files = 0;
+    for (id, path) in attachment_paths {
+        let source = Path::new(&path);
+        let Some(file_name) = source.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !source.is_file() {
+            continue;
+        }
+
+        zip.start_file(format!("attachments/{}/{}", id, file_name), options)
+            .map_err(|e| e.to_string())?;
+        let mut source = File::open(source).map_err(|e| e.to_string())?;
+        std::io::copy(&mut source, &mut zip).map_err(|e| e.to_string())?;
+        attachment_files += 1;
+    }
+
+    let manifest = ArchiveManifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        format_version: ARCHIVE_FORMAT_VERSION,
+        schema_version: current_schema_version(),
+        app_version: app_version.to_string(),
+        exported_at: Utc::now().to_rfc3339(),
+        tables,
+        attachment_files,
+    };
+    zip.start_file(MANIFEST_ENTRY, options)
+        .map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(&mut zip, &manifest).map_err(|e| e.to_string())?;
+
+    zip.finish()
+        .map_err(|e| e.to_string())?
+        .flush()
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(manifest)
+}
+
+/// Reads and validates the manifest of a workspace archive.
+pub fn read_manifest(archive_path: &Path) -> Result<ArchiveManifest, String> {
+    let file = File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut zip = ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    manifest_from_zip(&mut zip)
+}
+
+fn manifest_from_zip<R: std::io::Read + std::io::Seek>(
+    zip: &mut ZipArchive<R>,
+) -> Result<ArchiveManifest, String> {
+    let entry = zip
+        .by_name(MANIFEST_ENTRY)
+        .map_err(|_| "Not a workspace archive: manifest.json is missing".to_string())?;
+    let manifest: ArchiveManifest =
+        serde_json::from_reader(entry).map_err(|e| format!("Invalid manifest: {}", e))?;
+
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(format!("Not a workspace archive: {}", manifest.format));
+    }
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(format!(
+            "Archive format version {} is newer than this version of the app supports ({}). Please update the app and try again.",
+            manifest.format_version, ARCHIVE_FORMAT_VERSION
+        ));
+    }
+    let schema_version = current_schema_version();
+    if manifest.schema_version > schema_version {
+        return Err(format!(
+            "Archive was exported from a newer database schema (version {}, this app is at {}). Please update the app and try again.",
+            manifest.schema_version, schema_version
+        ));
+    }
+    Ok(manifest)
+}
+
+/// Merges a workspace archive into the database, adding bundled attachment
+/// files to the attachment store in `uploads_dir` and pointing their rows at them.
+///
+/// Everything happens in a single transaction; if anything fails, no rows are
+/// imported and any files we already copied are removed again.
+pub fn import_workspace(
+    conn: &mut Connection,
+    archive_path: &Path,
+    uploads_dir: &Path,
+) -> Result<WorkspaceImportSummary, String> {
+    let open = || -> Result<_, String> {
+        let file = File::open(archive_path).map_err(|e| e.to_string())?;
+        ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())
+    };
+    let mut zip = open()?;
+    let manifest = manifest_from_zip(&mut zip)?;
+    // Rows are read straight out of their table's entry, so attachment files
+    // come from a second handle on the archive
+    let mut files = open()?;
+
+    let mut written_files: Vec<PathBuf> = Vec::new();
+    let result = import_tables(
+        conn,
+        &mut zip,
+        &mut files,
+        &manifest,
+        uploads_dir,
+        &mut written_files,
+    );
+    if result.is_err() {
+        for path in written_files {
+            let _ = fs::remove_file(path);
+        }
+    }
+    result
+}
+
+fn import_tables<R: std::io::Read + std::io::Seek>(
+    conn: &mut Connection,
+    zip: &mut ZipArchive<R>,
+    files: &mut ZipArchive<R>,
+    manifest: &ArchiveManifest,
+    uploads_dir: &Path,
+    written_files: &mut Vec<PathBuf>,
+) -> Result<WorkspaceImportSummary, String> {
+    // attachment id -> zip entry holding its file
+    let attachment_entries: HashMap<String, String> = zip
+        .file_names()
+        .filter_map(|name| {
+            let rest = name.strip_prefix("attachments/")?;
+            let (id, file_name) = rest.split_once('/')?;
+            (!file_name.is_empty()).then(|| (id.to_string(), name.to_string()))
+        })
+        .collect();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    // chats.parent_chat_id can point at a chat that appears later in the file
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON")
+        .map_err(|e| e.to_string())?;
+
+    let mut summary = WorkspaceImportSummary {
+        schema_version: manifest.schema_version,
+        ..Default::default()
+    };
+    // chats whose updated_at/is_new_chat the message triggers will clobber
+    let mut imported_chats: Vec<(SqlValue, SqlValue, SqlValue)> = Vec::new();
+
+    for table in TABLES {
+        let entry_name = format!("tables/{}.jsonl", table);
+        if zip.index_for_name(&entry_name).is_none() {
+            continue;
+        }
+
+        let columns = table_columns(&tx, table)?;
+        let entry = zip.by_name(&entry_name).map_err(|e| e.to_string())?;
+
+        let mut inserted = 0;
+        for (i, line) in BufReader::new(entry).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut row: Map<String, Value> = serde_json::from_str(&line)
+                .map_err(|e| format!("{} line {}: {}", entry_name, i + 1, e))?;
+
+            if table == "attachments" {
+                // Sources are paths on the machine that exported the archive, and
+                // the watcher only follows ones the user attached here
+                row.remove("source_path");
+                restore_attachment_file(
+                    files,
+                    &attachment_entries,
+                    &mut row,
+                    uploads_dir,
+                    &tx,
+                    written_files,
+                )?;
+            }
+
+            let row: Vec<(&str, SqlValue)> = row
+                .iter()
+                .filter(|(column, _)| columns.contains(column.as_str()))
+                .map(|(column, value)| (column.as_str(), json_to_sql(value)))
+                .collect();
+            if row.is_empty() {
+                continue;
+            }
+
+            let sql = format!(
+                "INSERT OR IGNORE INTO {} ({}) VALUES ({})",
+                table,
+                row.iter()
+                    .map(|(column, _)| format!("\"{}\"", column))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                vec!["?"; row.len()].join(", ")
+            );
+            let changed = tx
+                .prepare_cached(&sql)
+                .and_then(|mut stmt| stmt.execute(params_from_iter(row.iter().map(|(_, v)| v))))
+                .map_err(|e| format!("Failed to import {}: {}", table, e))?;
+            if changed == 0 {
+                summary.skipped += 1;
+                continue;
+            }
+            inserted += 1;
+
+            let value_of = |name: &str| {
+                row.iter()
+                    .find(|(column, _)| *column == name)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or(SqlValue::Null)
+            };
+            match table {
+                // ensure_message_selected_on_insert selects the first message of every
+                // set as it's inserted; put back whichever one was actually selected
+                "messages" => {
+                    tx.execute(
+                        "UPDATE messages SET selected = ?1 WHERE id = ?2",
+                        (value_of("selected"), value_of("id")),
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+                "chats" => {
+                    imported_chats.push((
+                        value_of("id"),
+                        value_of("updated_at"),
+                        value_of("is_new_chat"),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        summary.inserted.insert(table.to_string(), inserted);
+    }
+
+    // Restore what update_chats_timestamp_on_message and set_chat_not_new_on_message overrode
+    for (id, updated_at, is_new_chat) in imported_chats {
+        tx.execute(
+            "UPDATE chats SET updated_at = ?1, is_new_chat = COALESCE(?2, is_new_chat) WHERE id = ?3",
+            (updated_at, is_new_chat, id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    summary.attachment_files = written_files.len();
+    log::info!(
+        "Imported workspace: {:?} ({} rows already present, {} attachment files)",
+        summary.inserted,
+        summary.skipped,
+        summary.attachment_files
+    );
+    Ok(summary)
+}
+
+/// Adds a bundled attachment file to the attachment store and rewrites the row's
+/// path to match. Attachments already in this workspace are left as they are.
+fn restore_attachment_file<R: std::io::Read + std::io::Seek>(
+    zip: &mut ZipArchive<R>,
+    attachment_entries: &HashMap<String, String>,
+    row: &mut Map<String, Value>,
+    uploads_dir: &Path,
+    tx: &Transaction,
+    written_files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let Some(Value::String(id)) = row.get("id") else {
+        return Ok(());
+    };
+    let Some(entry_name) = attachment_entries.get(id) else {
+        return Ok(());
+    };
+    let exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM attachments WHERE id = ?1)",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if exists {
+        return Ok(());
+    }
+
+    let extension = Path::new(entry_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned());
+    let entry = zip.by_name(entry_name).map_err(|e| e.to_string())?;
+    let blob = attachment_store::store_reader(uploads_dir, entry, extension.as_deref())?;
+    // Blobs that were already stored may belong to other attachments, so only
+    // newly written ones are removed if the import fails
+    if !blob.deduplicated {
+        written_files.push(PathBuf::from(&blob.path));
+    }
+
+    row.insert("path".to_string(), Value::String(blob.path));
+    Ok(())
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(columns)
+}
+
+fn sql_to_json(value: SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(i) => Value::from(i),
+        SqlValue::Real(f) => Value::from(f),
+        SqlValue::Text(s) => Value::String(s),
+        SqlValue::Blob(bytes) => Value::from(bytes),
+    }
+}
+
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        // Blobs are exported as arrays of bytes
+        Value::Array(items) => SqlValue::Blob(
+            items
+                .iter()
+                .filter_map(|item| item.as_u64().map(|byte| byte as u8))
+                .collect(),
+        ),
+        Value::Object(_) => SqlValue::Text(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+
+    /// A fresh temporary folder, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("camp-archive-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Every row of `table` as exported, leaving out the projects every new
+    /// database starts with and the attachment paths that are rewritten on import.
+    fn rows(conn: &Connection, table: &str) -> Vec<Map<String, Value>> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))
+            .unwrap();
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        stmt.query_map([], |row| {
+            let mut object = Map::new();
+            for (i, column) in columns.iter().enumerate() {
+                object.insert(column.clone(), sql_to_json(row.get(i)?));
+            }
+            object.remove("path");
+            Ok(object)
+        })
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+        .into_iter()
+        .filter(|row| table != "projects" || row["id"] == "p1")
+        .collect()
+    }
+
+    fn seed(conn: &Connection, attachment_path: &Path) {
+        conn.execute_batch(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'Project');
+            INSERT INTO chats (id, title, project_id, is_new_chat) VALUES ('c1', 'Chat', 'p1', 1);
+            INSERT INTO chats (id, title, project_id, parent_chat_id) VALUES ('c2', 'Branch', 'p1', 'c1');
+            INSERT INTO message_sets (id, chat_id, type, level) VALUES ('s1', 'c1', 'user', 0);
+            INSERT INTO message_sets (id, chat_id, type, level) VALUES ('s2', 'c1', 'ai', 1);
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, state)
+                VALUES ('m1', 's1', 'c1', 'Hello', 'user', 'idle');
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, state)
+                VALUES ('m2', 's2', 'c1', 'First reply', 'a::one', 'idle');
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, state)
+                VALUES ('m3', 's2', 'c1', 'Second reply', 'b::two', 'idle');
+            UPDATE messages SET selected = (id = 'm3') WHERE message_set_id = 's2';
+            INSERT INTO message_parts (chat_id, message_id, level, content)
+                VALUES ('c1', 'm3', 0, 'Second reply');
+            INSERT INTO attachments (id, type, original_name, path)
+                VALUES ('a2', 'webpage', 'Example', 'https://example.com');
+            INSERT INTO project_attachments (project_id, attachment_id) VALUES ('p1', 'a2');
+            UPDATE chats SET updated_at = '2024-01-02 03:04:05', is_new_chat = 0 WHERE id = 'c1';
+            UPDATE chats SET updated_at = '2024-01-03 00:00:00' WHERE id = 'c2';",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO attachments (id, type, original_name, path) VALUES ('a1', 'text', 'notes.txt', ?1)",
+            [attachment_path.to_string_lossy()],
+        )
+        .unwrap();
+        conn.execute_batch(
+            "INSERT INTO message_attachments (message_id, attachment_id) VALUES ('m1', 'a1');",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn round_trips_a_workspace() {
+        let dir = TempDir::new();
+        let attachment_path = dir.0.join("notes.txt");
+        fs::write(&attachment_path, "attached notes").unwrap();
+        let archive_path = dir.0.join("workspace.zip");
+        let uploads_dir = dir.0.join("uploads");
+
+        let mut source = migrator::open_in_memory();
+        seed(&source, &attachment_path);
+        let manifest = export_workspace(&mut source, &archive_path, "1.2.3").unwrap();
+        assert!(!archive_path.with_extension("partial").exists());
+        assert_eq!(manifest.schema_version, current_schema_version());
+        assert_eq!(manifest.tables["chats"], 2);
+        assert_eq!(manifest.tables["messages"], 3);
+        assert_eq!(manifest.attachment_files, 1);
+        assert_eq!(read_manifest(&archive_path).unwrap().app_version, "1.2.3");
+
+        let mut target = migrator::open_in_memory();
+        let summary = import_workspace(&mut target, &archive_path, &uploads_dir).unwrap();
+        assert_eq!(summary.inserted["projects"], 1);
+        assert_eq!(summary.inserted["chats"], 2);
+        assert_eq!(summary.inserted["messages"], 3);
+        assert_eq!(summary.inserted["attachments"], 2);
+        // The default and quick chat projects were already there
+        assert_eq!(summary.skipped, 2);
+        assert_eq!(summary.attachment_files, 1);
+
+        for table in TABLES {
+            assert_eq!(rows(&target, table), rows(&source, table), "{}", table);
+        }
+        let selected: Vec<(String, bool)> = target
+            .prepare("SELECT id, selected FROM messages ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            selected,
+            [
+                ("m1".to_string(), true),
+                ("m2".to_string(), false),
+                ("m3".to_string(), true)
+            ]
+        );
+        let (updated_at, is_new_chat): (String, bool) = target
+            .query_row(
+                "SELECT updated_at, is_new_chat FROM chats WHERE id = 'c1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(updated_at, "2024-01-02 03:04:05");
+        assert!(!is_new_chat);
+
+        let path: String = target
+            .query_row("SELECT path FROM attachments WHERE id = 'a1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(Path::new(&path).starts_with(&uploads_dir));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "attached notes");
+        let webpage: String = target
+            .query_row("SELECT path FROM attachments WHERE id = 'a2'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(webpage, "https://example.com");
+
+        // Importing again only finds rows that are already there
+        let total_rows: usize = manifest.tables.values().sum();
+        let again = import_workspace(&mut target, &archive_path, &uploads_dir).unwrap();
+        assert!(again.inserted.values().all(|inserted| *inserted == 0));
+        assert_eq!(again.skipped, total_rows);
+        assert_eq!(again.attachment_files, 0);
+        for table in TABLES {
+            assert_eq!(rows(&target, table), rows(&source, table), "{}", table);
+        }
+        let blobs = fs::read_dir(uploads_dir.join(attachment_store::BLOBS_DIR))
+            .unwrap()
+            .count();
+        assert_eq!(blobs, 1);
+    }
+
+    #[test]
+    fn rejects_archives_from_a_newer_schema() {
+        let dir = TempDir::new();
+        let archive_path = dir.0.join("workspace.zip");
+        let mut source = migrator::open_in_memory();
+        let mut manifest = export_workspace(&mut source, &archive_path, "1.2.3").unwrap();
+        manifest.schema_version += 1;
+
+        let newer_path = dir.0.join("newer.zip");
+        let mut zip = ZipWriter::new(File::create(&newer_path).unwrap());
+        zip.start_file(MANIFEST_ENTRY, SimpleFileOptions::default())
+            .unwrap();
+        serde_json::to_writer(&mut zip, &manifest).unwrap();
+        zip.finish().unwrap();
+
+        let mut target = migrator::open_in_memory();
+        let error = import_workspace(&mut target, &newer_path, &dir.0.join("uploads")).unwrap_err();
+        assert!(error.contains("newer database schema"), "{}", error);
+    }
+}
@@ -0,0 +1,540 @@
+//! Content-addressed storage for attachment files.
+//!
+//! Each distinct file is stored once, at `uploads/blobs/<aa>/<sha256>.<ext>`, so
+//! attaching the same PDF to ten chats keeps a single copy on disk. The extension
+//! is part of the name because the frontend works out attachment types from it.
+//!
+//! The store doesn't keep reference counts of its own. A blob is in use while an
+//! attachments row points at it and that row is attached to a message, project
+//! or draft; `collect_garbage` works that out from the database and deletes
+//! everything else, including files left in the older `uploads/originals`.
+
+use rusqlite::{Connection, Transaction};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Where blobs live, under the uploads directory.
+pub const BLOBS_DIR: &str = "blobs";
+
+/// Where the frontend used to copy attachments to, one uuid-named file per upload.
+pub const LEGACY_DIR: &str = "originals";
+
+/// Files and rows younger than this are never collected. The frontend stores a
+/// file before it creates the row pointing at it, and creates an attachments row
+/// before attaching it to anything.
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Ephemeral attachments (quick chat screenshots) are only sent to the model with
+/// the user message they were taken for. Once a later user message exists in that
+/// chat (see llmConversation in ChatState.ts), nothing uses them again.
+const EXPIRED_EPHEMERAL_QUERY: &str = "SELECT a.id FROM attachments a
+    WHERE a.ephemeral = 1
+        AND a.created_at < datetime('now', ?1)
+        AND NOT EXISTS (SELECT 1 FROM draft_attachments WHERE attachment_id = a.id)
+        AND NOT EXISTS (SELECT 1 FROM project_attachments WHERE attachment_id = a.id)
+        AND NOT EXISTS (
+            SELECT 1 FROM message_attachments ma
+            JOIN messages m ON m.id = ma.message_id
+            JOIN message_sets ms ON ms.id = m.message_set_id
+            WHERE ma.attachment_id = a.id
+                AND NOT EXISTS (
+                    SELECT 1 FROM message_sets later
+                    WHERE later.chat_id = ms.chat_id AND later.type = 'user' AND later.level > ms.level
+                )
+        )";
+
+const ORPHANED_QUERY: &str = "SELECT a.id FROM attachments a
+    WHERE a.created_at < datetime('now', ?1)
+        AND NOT EXISTS (SELECT 1 FROM message_attachments WHERE attachment_id = a.id)
+        AND NOT EXISTS (SELECT 1 FROM project_attachments WHERE attachment_id = a.id)
+        AND NOT EXISTS (SELECT 1 FROM draft_attachments WHERE attachment_id = a.id)";
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredBlob {
+    /// What to put in attachments.path.
+    pub path: String,
+    /// Hex SHA-256 of the contents.
+    pub hash: String,
+    pub size: u64,
+    /// True if the store already had this file, so nothing new was written.
+    pub deduplicated: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcReport {
+    /// Ephemeral attachments deleted because no future message will send them.
+    pub expired_ephemeral: usize,
+    /// Attachments deleted because nothing was attached to them.
+    pub orphaned_rows: usize,
+    /// Files moved from uploads/originals into the store.
+    pub adopted_files: usize,
+    pub deleted_files: usize,
+    pub freed_bytes: u64,
+    /// Nothing was changed. Files in uploads/originals aren't moved into the store
+    /// on a dry run, so duplicates among them aren't counted in `freed_bytes`.
+    pub dry_run: bool,
+}
+
+/// Copies the file at `source` into the store. `extension` defaults to the
+/// source's own.
+pub fn store_file(
+    uploads_dir: &Path,
+    source: &Path,
+    extension: Option<&str>,
+) -> Result<StoredBlob, String> {
+    let file = File::open(source).map_err(|e| e.to_string())?;
+    let extension = extension.map(str::to_string).or_else(|| {
+        source
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+    });
+    store_reader(uploads_dir, file, extension.as_deref())
+}
+
+/// Hashes `reader` while copying it to a temporary file in the store, then moves
+/// it into place, or throws it away if an identical blob is already there.
+pub fn store_reader(
+    uploads_dir: &Path,
+    mut reader: impl Read,
+    extension: Option<&str>,
+) -> Result<StoredBlob, String> {
+    let blobs_dir = uploads_dir.join(BLOBS_DIR);
+    fs::create_dir_all(&blobs_dir).map_err(|e| e.to_string())?;
+
+    // Temp files start with a dot and sit outside the shard directories, and are
+    // swept like any other unreferenced file if we crash before renaming them
+    let temp_path = blobs_dir.join(format!(".tmp-{}", uuid::Uuid::new_v4()));
+    let result = (|| {
+        let mut temp = File::create(&temp_path).map_err(|e| e.to_string())?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf).map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            temp.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+            size += n as u64;
+        }
+        temp.sync_all().map_err(|e| e.to_string())?;
+        drop(temp);
+
+        let hash = hex::encode(hasher.finalize());
+        let target = blob_path(uploads_dir, &hash, extension);
+        let deduplicated = target.is_file();
+        if deduplicated {
+            fs::remove_file(&temp_path).map_err(|e| e.to_string())?;
+            // A blob nothing refers to yet is about to be referenced again, so
+            // make sure collect_garbage treats it as new
+            File::options()
+                .write(true)
+                .open(&target)
+                .and_then(|file| file.set_modified(SystemTime::now()))
+                .map_err(|e| e.to_string())?;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::rename(&temp_path, &target).map_err(|e| e.to_string())?;
+        }
+
+        Ok(StoredBlob {
+            path: target.to_string_lossy().into_owned(),
+            hash,
+            size,
+            deduplicated,
+        })
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn blob_path(uploads_dir: &Path, hash: &str, extension: Option<&str>) -> PathBuf {
+    // Only keep something that's safe in a file name; the default matches
+    // generateStorePath's old fallback
+    let extension: String = extension
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(16)
+        .collect::<String>()
+        .to_lowercase();
+    let extension = if extension.is_empty() {
+        "bin".to_string()
+    } else {
+        extension
+    };
+
+    uploads_dir
+        .join(BLOBS_DIR)
+        .join(&hash[..2])
+        .join(format!("{}.{}", hash, extension))
+}
+
+/// Deletes expired ephemeral attachments and attachments that aren't attached to
+/// anything, moves files still in uploads/originals into the store, then deletes
+/// every file in the store or uploads/originals that no attachment points at.
+pub fn collect_garbage(
+    conn: &mut Connection,
+    uploads_dir: &Path,
+    dry_run: bool,
+) -> Result<GcReport, String> {
+    let mut report = GcReport {
+        dry_run,
+        ..Default::default()
+    };
+    let grace = format!("-{} seconds", GRACE_PERIOD.as_secs());
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    report.expired_ephemeral = delete_attachments(&tx, EXPIRED_EPHEMERAL_QUERY, &grace)?;
+    report.orphaned_rows = delete_attachments(&tx, ORPHANED_QUERY, &grace)?;
+    if !dry_run {
+        report.adopted_files = adopt_legacy_files(&tx, uploads_dir)?;
+    }
+    // Read inside the transaction, so a dry run sees what would be left
+    let referenced = referenced_paths(&tx)?;
+    if dry_run {
+        tx.rollback().map_err(|e| e.to_string())?;
+    } else {
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    // Only delete files once the rows that pointed at them are really gone
+    let cutoff = SystemTime::now() - GRACE_PERIOD;
+    for dir in [BLOBS_DIR, LEGACY_DIR] {
+        sweep(&uploads_dir.join(dir), &referenced, cutoff, &mut report)?;
+    }
+
+    log::info!(
+        "{}Attachment GC: {} expired ephemeral, {} orphaned rows, {} files adopted, {} files ({} bytes) deleted",
+        if dry_run { "[dry run] " } else { "" },
+        report.expired_ephemeral,
+        report.orphaned_rows,
+        report.adopted_files,
+        report.deleted_files,
+        report.freed_bytes
+    );
+    Ok(report)
+}
+
+/// Deletes the attachments `query` selects, along with their join rows and
+/// extracted text.
+fn delete_attachments(tx: &Transaction, query: &str, grace: &str) -> Result<usize, String> {
+    let ids: Vec<String> = tx
+        .prepare(query)
+        .and_then(|mut stmt| stmt.query_map([grace], |row| row.get(0))?.collect())
+        .map_err(|e| e.to_string())?;
+
+    for id in &ids {
+        for table in [
+            "message_attachments",
+            "project_attachments",
+            "draft_attachments",
+            "attachment_text_extractions",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE attachment_id = ?1", table),
+                [id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.execute("DELETE FROM attachments WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(ids.len())
+}
+
+/// Moves files attachments still point at out of uploads/originals and into the
+/// store, collapsing duplicates. The originals are swept afterwards.
+fn adopt_legacy_files(tx: &Transaction, uploads_dir: &Path) -> Result<usize, String> {
+    let legacy_dir = uploads_dir.join(LEGACY_DIR);
+    let rows: Vec<(String, String)> = tx
+        .prepare("SELECT id, path FROM attachments")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut adopted = HashSet::new();
+    for (id, path) in rows {
+        let path = PathBuf::from(path);
+        if !path.starts_with(&legacy_dir) || !path.is_file() {
+            continue;
+        }
+        let blob = store_file(uploads_dir, &path, None)?;
+        tx.execute(
+            "UPDATE attachments SET path = ?1 WHERE id = ?2",
+            (&blob.path, &id),
+        )
+        .map_err(|e| e.to_string())?;
+        adopted.insert(path);
+    }
+    Ok(adopted.len())
+}
+
+fn referenced_paths(tx: &Transaction) -> Result<HashSet<PathBuf>, String> {
+    let paths: Vec<String> = tx
+        .prepare("SELECT path FROM attachments")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| e.to_string())?;
+    Ok(paths
+        .iter()
+        .map(|path| normalize(Path::new(path)))
+        .collect())
+}
+
+/// Paths in the database were built by the frontend, so compare canonical forms.
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Deletes files under `dir` that aren't referenced and haven't been touched
+/// since `cutoff`, then any shard directories left empty.
+fn sweep(
+    dir: &Path,
+    referenced: &HashSet<PathBuf>,
+    cutoff: SystemTime,
+    report: &mut GcReport,
+) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        let path = entry.path();
+        if metadata.is_dir() {
+            sweep(&path, referenced, cutoff, report)?;
+            if !report.dry_run {
+                // Fails, harmlessly, unless the directory is now empty
+                let _ = fs::remove_dir(&path);
+            }
+            continue;
+        }
+
+        let recent = metadata.modified().is_ok_and(|modified| modified > cutoff);
+        if recent || referenced.contains(&normalize(&path)) {
+            continue;
+        }
+        if !report.dry_run {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        report.deleted_files += 1;
+        report.freed_bytes += metadata.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+
+    /// A fresh uploads folder, removed when dropped.
+    struct TempUploads(PathBuf);
+
+    impl TempUploads {
+        fn new() -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("camp-attachment-store-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempUploads(dir)
+        }
+    }
+
+    impl Drop for TempUploads {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn files_under(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(files_under(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files.sort();
+        files
+    }
+
+    fn make_old(path: &Path) {
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now() - GRACE_PERIOD * 2))
+            .unwrap();
+    }
+
+    /// Adds an attachments row created well before the grace period, attached to
+    /// the default project if `attached`.
+    fn add_attachment(conn: &Connection, id: &str, path: &str, attached: bool) {
+        conn.execute(
+            "INSERT INTO attachments (id, type, path, created_at)
+                VALUES (?1, 'file', ?2, datetime('now', '-1 day'))",
+            (id, path),
+        )
+        .unwrap();
+        if attached {
+            conn.execute(
+                "INSERT INTO project_attachments (project_id, attachment_id) VALUES ('default', ?1)",
+                [id],
+            )
+            .unwrap();
+        }
+    }
+
+    fn attachment_ids(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT id FROM attachments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn stores_identical_contents_once() {
+        let uploads = TempUploads::new();
+        let first = store_reader(&uploads.0, &b"same bytes"[..], Some("PDF")).unwrap();
+        let second = store_reader(&uploads.0, &b"same bytes"[..], Some("pdf")).unwrap();
+
+        assert!(!first.deduplicated);
+        assert!(second.deduplicated);
+        assert_eq!(first.path, second.path);
+        assert_eq!(first.hash, hex::encode(Sha256::digest(b"same bytes")));
+        assert_eq!(second.size, 10);
+        assert_eq!(
+            Path::new(&first.path),
+            uploads
+                .0
+                .join(BLOBS_DIR)
+                .join(&first.hash[..2])
+                .join(format!("{}.pdf", first.hash))
+        );
+        // No temp files are left behind
+        assert_eq!(files_under(&uploads.0), [PathBuf::from(&first.path)]);
+
+        let other = store_reader(&uploads.0, &b"same bytes"[..], Some("../t x*t")).unwrap();
+        assert!(!other.deduplicated);
+        assert!(other.path.ends_with(".txt"));
+    }
+
+    #[test]
+    fn sweeps_only_old_unreferenced_blobs() {
+        let uploads = TempUploads::new();
+        let mut conn = migrator::open_in_memory();
+
+        let unreferenced = store_reader(&uploads.0, &b"unreferenced"[..], Some("txt")).unwrap();
+        let recent = store_reader(&uploads.0, &b"recent"[..], Some("txt")).unwrap();
+        let referenced = store_reader(&uploads.0, &b"referenced"[..], Some("txt")).unwrap();
+        let orphaned = store_reader(&uploads.0, &b"orphaned"[..], Some("txt")).unwrap();
+        for blob in [&unreferenced, &referenced, &orphaned] {
+            make_old(Path::new(&blob.path));
+        }
+        add_attachment(&conn, "kept", &referenced.path, true);
+        add_attachment(&conn, "orphan", &orphaned.path, false);
+
+        let dry_run = collect_garbage(&mut conn, &uploads.0, true).unwrap();
+        assert!(dry_run.dry_run);
+        assert_eq!(dry_run.orphaned_rows, 1);
+        assert_eq!(dry_run.deleted_files, 2);
+        assert_eq!(dry_run.freed_bytes, unreferenced.size + orphaned.size);
+        assert_eq!(files_under(&uploads.0).len(), 4);
+        assert_eq!(attachment_ids(&conn), ["kept", "orphan"]);
+
+        let report = collect_garbage(&mut conn, &uploads.0, false).unwrap();
+        assert_eq!(report.orphaned_rows, 1);
+        assert_eq!(report.deleted_files, 2);
+        assert_eq!(report.freed_bytes, unreferenced.size + orphaned.size);
+        assert_eq!(attachment_ids(&conn), ["kept"]);
+        let mut expected = vec![PathBuf::from(&recent.path), PathBuf::from(&referenced.path)];
+        expected.sort();
+        assert_eq!(files_under(&uploads.0), expected);
+        // Shard directories that were emptied are removed too
+        assert!(!Path::new(&unreferenced.path).parent().unwrap().exists());
+    }
+
+    #[test]
+    fn storing_again_protects_an_unreferenced_blob() {
+        let uploads = TempUploads::new();
+        let mut conn = migrator::open_in_memory();
+        let blob = store_reader(&uploads.0, &b"again"[..], Some("txt")).unwrap();
+        make_old(Path::new(&blob.path));
+
+        assert!(
+            store_reader(&uploads.0, &b"again"[..], Some("txt"))
+                .unwrap()
+                .deduplicated
+        );
+        let report = collect_garbage(&mut conn, &uploads.0, false).unwrap();
+        assert_eq!(report.deleted_files, 0);
+        assert!(Path::new(&blob.path).is_file());
+    }
+
+    #[test]
+    fn adopts_legacy_files() {
+        let uploads = TempUploads::new();
+        let mut conn = migrator::open_in_memory();
+        let legacy_dir = uploads.0.join(LEGACY_DIR);
+        fs::create_dir_all(&legacy_dir).unwrap();
+        let legacy = legacy_dir.join("0b6c3d5e.png");
+        let duplicate = legacy_dir.join("7f2a9c01.png");
+        let stray = legacy_dir.join("stray.png");
+        for path in [&legacy, &duplicate] {
+            fs::write(path, "legacy image").unwrap();
+            make_old(path);
+        }
+        fs::write(&stray, "nobody's").unwrap();
+        make_old(&stray);
+        add_attachment(&conn, "a1", &legacy.to_string_lossy(), true);
+        add_attachment(&conn, "a2", &duplicate.to_string_lossy(), true);
+
+        let dry_run = collect_garbage(&mut conn, &uploads.0, true).unwrap();
+        assert_eq!(dry_run.adopted_files, 0);
+        assert_eq!(dry_run.deleted_files, 1);
+        assert!(legacy.is_file() && duplicate.is_file() && stray.is_file());
+        assert!(!uploads.0.join(BLOBS_DIR).exists());
+
+        let report = collect_garbage(&mut conn, &uploads.0, false).unwrap();
+        assert_eq!(report.adopted_files, 2);
+        assert_eq!(report.deleted_files, 3);
+        assert!(files_under(&legacy_dir).is_empty());
+
+        let paths: Vec<String> = conn
+            .prepare("SELECT path FROM attachments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(paths[0], paths[1]);
+        assert!(Path::new(&paths[0]).starts_with(uploads.0.join(BLOBS_DIR)));
+        assert!(paths[0].ends_with(".png"));
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), "legacy image");
+        assert_eq!(files_under(&uploads.0), [PathBuf::from(&paths[0])]);
+    }
+}
@@ -0,0 +1,500 @@
+//! Keeps project attachments in step with the files they were attached from.
+//!
+//! Attachments are copies in the attachment store, so editing the original
+//! doesn't change what models see. Files and folders attached to a project from
+//! disk remember where they came from (`attachments.source_path`), and we watch
+//! those paths. When one changes it's stored again, the attachment is pointed at
+//! the new copy, the project's chats have their context summaries marked stale,
+//! and `ATTACHMENT_UPDATED_EVENT` is emitted so the UI can refresh.
+//!
+//! Changes are debounced, so saving a file several times in a row, or a checkout
+//! that touches hundreds of files in a folder, is stored once. Files are watched
+//! through their parent folder, since many editors save by writing a new file
+//! and renaming it over the old one, which ends a watch on the file itself.
+//!
+//! A source that's deleted or can't be read keeps its last copy.
+
+use crate::attachment_store::{self, StoredBlob};
+use crate::folder_bundle;
+use crate::image_pipeline::{self, OutputFormat};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Emitted with an `AttachmentUpdate` whenever a watched attachment is stored
+/// again.
+pub const ATTACHMENT_UPDATED_EVENT: &str = "project_attachment_updated";
+
+/// How long a source has to stay unchanged before it's stored again.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentUpdate {
+    pub attachment_id: String,
+    pub project_ids: Vec<String>,
+    /// The new attachments.path.
+    pub path: String,
+    /// attachments.source_updated_at, in UTC like every other sqlite timestamp.
+    pub updated_at: String,
+}
+
+/// A project attachment with a source on disk.
+struct Source {
+    attachment_id: String,
+    path: PathBuf,
+    attachment_type: String,
+}
+
+impl Source {
+    fn is_folder(&self) -> bool {
+        self.attachment_type == "folder"
+    }
+
+    /// The folder to watch for changes to this source.
+    fn watch_target(&self) -> Option<(PathBuf, RecursiveMode)> {
+        if self.is_folder() {
+            Some((self.path.clone(), RecursiveMode::Recursive))
+        } else {
+            self.path
+                .parent()
+                .map(|parent| (parent.to_path_buf(), RecursiveMode::NonRecursive))
+        }
+    }
+
+    /// Whether a change to `changed` could change this source's copy. Changes
+    /// inside hidden folders (`.git` above all) are ignored, since folder
+    /// bundles leave those out anyway.
+    fn is_affected_by(&self, changed: &Path) -> bool {
+        if !self.is_folder() {
+            return changed == self.path;
+        }
+        match changed.strip_prefix(&self.path) {
+            Ok(relative) => !relative
+                .parent()
+                .into_iter()
+                .flat_map(Path::components)
+                .any(|component| component.as_os_str().to_string_lossy().starts_with('.')),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Managed state for the running watcher.
+pub struct AttachmentWatcher {
+    debouncer: Mutex<Debouncer<RecommendedWatcher, RecommendedCache>>,
+    /// Folders currently watched, and how.
+    watched: Mutex<HashMap<PathBuf, RecursiveMode>>,
+}
+
+/// Starts watching the sources of every project attachment that has one.
+pub fn start(app_handle: &AppHandle) -> Result<(), String> {
+    let handle = app_handle.clone();
+    let debouncer = new_debouncer(
+        DEBOUNCE,
+        None,
+        move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                let changed: Vec<PathBuf> = events
+                    .into_iter()
+                    .filter(|event| !matches!(event.kind, EventKind::Access(_)))
+                    .flat_map(|event| event.event.paths)
+                    .collect();
+                if !changed.is_empty() {
+                    refresh_changed(&handle, &changed);
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    log::error!("Attachment watcher error: {}", error);
+                }
+            }
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    app_handle.manage(AttachmentWatcher {
+        debouncer: Mutex::new(debouncer),
+        watched: Mutex::new(HashMap::new()),
+    });
+    sync(app_handle)
+}
+
+/// Brings the watched folders in line with the database, after a source is
+/// recorded or an attachment or project removed.
+pub fn sync(app_handle: &AppHandle) -> Result<(), String> {
+    let watcher = app_handle
+        .try_state::<AttachmentWatcher>()
+        .ok_or("Attachment watcher isn't running")?;
+    let conn = crate::db::open(app_handle)?;
+
+    let mut wanted: HashMap<PathBuf, RecursiveMode> = HashMap::new();
+    for (path, mode) in load_sources(&conn)?.iter().filter_map(Source::watch_target) {
+        let entry = wanted.entry(path).or_insert(mode);
+        if mode == RecursiveMode::Recursive {
+            *entry = mode;
+        }
+    }
+
+    let mut debouncer = watcher.debouncer.lock().unwrap();
+    let mut watched = watcher.watched.lock().unwrap();
+    watched.retain(|path, mode| {
+        if wanted.get(path) == Some(mode) {
+            return true;
+        }
+        // Fails harmlessly if the folder is already gone
+        let _ = debouncer.unwatch(path);
+        false
+    });
+    for (path, mode) in wanted {
+        if watched.contains_key(&path) {
+            continue;
+        }
+        match debouncer.watch(&path, mode) {
+            Ok(()) => {
+                watched.insert(path, mode);
+            }
+            // Usually a source on a drive that isn't mounted; it's tried again
+            // at the next sync
+            Err(e) => log::warn!("Failed to watch {}: {}", path.display(), e),
+        }
+    }
+    log::info!("Watching {} folders for attachment changes", watched.len());
+    Ok(())
+}
+
+/// Records where a project attachment came from, so it's kept up to date. The
+/// path has to have been checked already (see file_access::check_read).
+pub fn record_source(
+    app_handle: &AppHandle,
+    attachment_id: &str,
+    source_path: &Path,
+) -> Result<(), String> {
+    let conn = crate::db::open(app_handle)?;
+    conn.execute(
+        "UPDATE attachments SET source_path = ?1 WHERE id = ?2",
+        (source_path.to_string_lossy(), attachment_id),
+    )
+    .map_err(|e| e.to_string())?;
+    sync(app_handle)
+}
+
+fn load_sources(conn: &Connection) -> Result<Vec<Source>, String> {
+    conn.prepare(
+        "SELECT DISTINCT a.id, a.source_path, a.type FROM attachments a
+        JOIN project_attachments pa ON pa.attachment_id = a.id
+        -- Deleting a project leaves its project_attachments rows behind
+        JOIN projects p ON p.id = pa.project_id
+        WHERE a.source_path IS NOT NULL AND a.is_loading = 0",
+    )
+    .and_then(|mut stmt| {
+        stmt.query_map([], |row| {
+            Ok(Source {
+                attachment_id: row.get(0)?,
+                path: PathBuf::from(row.get::<_, String>(1)?),
+                attachment_type: row.get(2)?,
+            })
+        })?
+        .collect()
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Stores every source affected by `changed` again, and tells the frontend
+/// about the ones whose contents actually changed.
+fn refresh_changed(app_handle: &AppHandle, changed: &[PathBuf]) {
+    let result = (|| {
+        let mut conn = crate::db::open(app_handle)?;
+        let uploads_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?
+            .join("uploads");
+        let sources = load_sources(&conn)?;
+        for source in sources
+            .iter()
+            .filter(|source| changed.iter().any(|path| source.is_affected_by(path)))
+        {
+            let blob = match ingest(&uploads_dir, source) {
+                Ok(blob) => blob,
+                Err(e) => {
+                    log::warn!("Keeping the last copy of {}: {}", source.path.display(), e);
+                    continue;
+                }
+            };
+            if let Some(update) = record_update(&mut conn, source, &blob)? {
+                log::info!(
+                    "Updated attachment {} from {}",
+                    update.attachment_id,
+                    source.path.display()
+                );
+                let _ = app_handle.emit(ATTACHMENT_UPDATED_EVENT, update);
+            }
+        }
+        Ok::<_, String>(())
+    })();
+    if let Err(e) = result {
+        log::error!("Failed to refresh changed attachments: {}", e);
+    }
+}
+
+/// Copies a source into the attachment store the way the frontend did when it
+/// was attached.
+fn ingest(uploads_dir: &Path, source: &Source) -> Result<StoredBlob, String> {
+    if source.is_folder() {
+        let bundle = folder_bundle::bundle_folder(&source.path, folder_bundle::DEFAULT_MAX_TOKENS)?;
+        return attachment_store::store_reader(uploads_dir, bundle.text.as_bytes(), Some("txt"));
+    }
+    if source.attachment_type == "image" {
+        let data = fs::read(&source.path).map_err(|e| e.to_string())?;
+        if data.len() as u64 > crate::command::TARGET_SIZE_BYTES {
+            let encoded = image_pipeline::fit_image(&data, crate::command::TARGET_SIZE_BYTES)?;
+            let extension = match encoded.format {
+                OutputFormat::Png => "png",
+                _ => "jpg",
+            };
+            return attachment_store::store_reader(
+                uploads_dir,
+                encoded.data.as_slice(),
+                Some(extension),
+            );
+        }
+    }
+    attachment_store::store_file(uploads_dir, &source.path, None)
+}
+
+/// Points the attachment at its new copy, if it's different, and marks the
+/// context summaries of the chats in its projects as stale.
+fn record_update(
+    conn: &mut Connection,
+    source: &Source,
+    blob: &StoredBlob,
+) -> Result<Option<AttachmentUpdate>, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let current: Option<String> = tx
+        .query_row(
+            "SELECT path FROM attachments WHERE id = ?1",
+            [&source.attachment_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    // Removed while we were storing it, or saved without any real change
+    if current.is_none_or(|path| path == blob.path) {
+        return Ok(None);
+    }
+
+    tx.execute(
+        "UPDATE attachments SET path = ?1, source_updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        (&blob.path, &source.attachment_id),
+    )
+    .map_err(|e| e.to_string())?;
+    // Text extracted from the old copy (see document_text.rs)
+    tx.execute(
+        "DELETE FROM attachment_text_extractions WHERE attachment_id = ?1",
+        [&source.attachment_id],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "UPDATE chats SET project_context_summary_is_stale = 1 WHERE project_id IN (
+            SELECT project_id FROM project_attachments WHERE attachment_id = ?1
+        )",
+        [&source.attachment_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let project_ids: Vec<String> = tx
+        .prepare("SELECT project_id FROM project_attachments WHERE attachment_id = ?1")
+        .and_then(|mut stmt| {
+            stmt.query_map([&source.attachment_id], |row| row.get(0))?
+                .collect()
+        })
+        .map_err(|e| e.to_string())?;
+    let updated_at: String = tx
+        .query_row(
+            "SELECT source_updated_at FROM attachments WHERE id = ?1",
+            [&source.attachment_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(Some(AttachmentUpdate {
+        attachment_id: source.attachment_id.clone(),
+        project_ids,
+        path: blob.path.clone(),
+        updated_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir = std::env::temp_dir()
+                .join(format!("camp-attachment-watcher-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(dir.join("uploads")).unwrap();
+            TempDir(dir)
+        }
+
+        fn uploads(&self) -> PathBuf {
+            self.0.join("uploads")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A text attachment from `source` in project p1, whose chat c1 has an
+    /// up-to-date context summary, next to an unrelated project p2 and chat c2.
+    fn attach(conn: &Connection, uploads: &Path, source: &Path) -> StoredBlob {
+        let blob = attachment_store::store_file(uploads, source, None).unwrap();
+        conn.execute_batch(
+            "INSERT INTO projects (id, name) VALUES ('p1', 'One'), ('p2', 'Two');
+            INSERT INTO chats (id, title, project_id, project_context_summary_is_stale)
+                VALUES ('c1', 'In p1', 'p1', 0), ('c2', 'In p2', 'p2', 0);",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO attachments (id, type, original_name, path, source_path)
+            VALUES ('a1', 'text', 'notes.txt', ?1, ?2)",
+            (&blob.path, source.to_string_lossy()),
+        )
+        .unwrap();
+        conn.execute_batch(
+            "INSERT INTO project_attachments (project_id, attachment_id) VALUES ('p1', 'a1');
+            INSERT INTO attachment_text_extractions (attachment_id, format, text, section_count)
+                VALUES ('a1', 'docx', 'old text', 1);",
+        )
+        .unwrap();
+        blob
+    }
+
+    fn stale_chats(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT id FROM chats WHERE project_context_summary_is_stale = 1 ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|id| id.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn stores_a_changed_source_again() {
+        let dir = TempDir::new();
+        let source_path = dir.0.join("notes.txt");
+        fs::write(&source_path, "first draft").unwrap();
+        let mut conn = migrator::open_in_memory();
+        let original = attach(&conn, &dir.uploads(), &source_path);
+        assert!(stale_chats(&conn).is_empty());
+
+        fs::write(&source_path, "second draft").unwrap();
+        let sources = load_sources(&conn).unwrap();
+        assert_eq!(sources.len(), 1);
+        let source = &sources[0];
+        assert!(source.is_affected_by(&source_path));
+        let blob = ingest(&dir.uploads(), source).unwrap();
+        assert_ne!(blob.path, original.path);
+        assert_eq!(fs::read_to_string(&blob.path).unwrap(), "second draft");
+
+        let update = record_update(&mut conn, source, &blob).unwrap().unwrap();
+        assert_eq!(update.attachment_id, "a1");
+        assert_eq!(update.project_ids, ["p1"]);
+        assert_eq!(update.path, blob.path);
+        let (path, updated_at): (String, Option<String>) = conn
+            .query_row(
+                "SELECT path, source_updated_at FROM attachments WHERE id = 'a1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(path, blob.path);
+        assert_eq!(updated_at, Some(update.updated_at));
+        let extractions: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM attachment_text_extractions",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(extractions, 0);
+        assert_eq!(stale_chats(&conn), ["c1"]);
+        // The old copy is left for garbage collection
+        assert!(Path::new(&original.path).exists());
+
+        // Saving again without changing anything stores nothing new
+        conn.execute("UPDATE chats SET project_context_summary_is_stale = 0", [])
+            .unwrap();
+        let same = ingest(&dir.uploads(), source).unwrap();
+        assert_eq!(same.path, blob.path);
+        assert!(record_update(&mut conn, source, &same).unwrap().is_none());
+        assert!(stale_chats(&conn).is_empty());
+    }
+
+    #[test]
+    fn only_watches_sources_of_existing_projects() {
+        let dir = TempDir::new();
+        let source_path = dir.0.join("notes.txt");
+        fs::write(&source_path, "draft").unwrap();
+        let conn = migrator::open_in_memory();
+        attach(&conn, &dir.uploads(), &source_path);
+        assert_eq!(load_sources(&conn).unwrap().len(), 1);
+
+        conn.execute("UPDATE attachments SET is_loading = 1", [])
+            .unwrap();
+        assert!(load_sources(&conn).unwrap().is_empty());
+        conn.execute("UPDATE attachments SET is_loading = 0", [])
+            .unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+            DELETE FROM projects WHERE id = 'p1';
+            PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+        assert!(load_sources(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn folders_ignore_changes_in_hidden_folders() {
+        let folder = Source {
+            attachment_id: "a1".to_string(),
+            path: PathBuf::from("/projects/app"),
+            attachment_type: "folder".to_string(),
+        };
+        assert!(folder.is_affected_by(Path::new("/projects/app/src/main.rs")));
+        assert!(folder.is_affected_by(Path::new("/projects/app/.campignore")));
+        assert!(!folder.is_affected_by(Path::new("/projects/app/.git/index")));
+        assert!(!folder.is_affected_by(Path::new("/projects/other/main.rs")));
+        assert_eq!(
+            folder.watch_target(),
+            Some((PathBuf::from("/projects/app"), RecursiveMode::Recursive))
+        );
+
+        let file = Source {
+            attachment_id: "a2".to_string(),
+            path: PathBuf::from("/projects/notes.txt"),
+            attachment_type: "text".to_string(),
+        };
+        assert!(file.is_affected_by(Path::new("/projects/notes.txt")));
+        assert!(!file.is_affected_by(Path::new("/projects/other.txt")));
+        assert_eq!(
+            file.watch_target(),
+            Some((PathBuf::from("/projects"), RecursiveMode::NonRecursive))
+        );
+    }
+}
@@ -1,36 +1,132 @@
 use tauri_plugin_sql::MigrationKind;
 use rusqlite::{Connection, Result};
-use std::fs::File;
-use std::io::Write;
+use serde::Serialize;
+use std::fmt::Write;
 use std::path::Path;
 
 // Import migrations from the main module
 #[path = "../migrations.rs"]
 mod migrations;
 
+// Shared with the app, which only uses part of it
 #[allow(dead_code)]
+#[path = "../migrator.rs"]
+mod migrator;
+
+#[path = "../invariants.rs"]
+mod invariants;
+
+// Generated files, relative to src-tauri (where `pnpm generate-schema` runs from)
+const MARKDOWN_PATH: &str = "../SQL_SCHEMA.md";
+const JSON_PATH: &str = "../SQL_SCHEMA.json";
+const TYPESCRIPT_PATH: &str = "../src/core/chorus/DBSchema.generated.ts";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Schema {
+    /// Latest migration version
+    version: i64,
+    tables: Vec<TableInfo>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct TableInfo {
     name: String,
-    sql: String,
+    /// FTS5 tables and the like. Their shadow tables are left out.
+    #[serde(rename = "virtual")]
+    is_virtual: bool,
+    columns: Vec<ColumnInfo>,
+    foreign_keys: Vec<ForeignKeyInfo>,
+    /// CHECK constraint expressions, column and table level alike
+    checks: Vec<String>,
+    indices: Vec<IndexInfo>,
+    triggers: Vec<TriggerInfo>,
 }
 
-#[allow(dead_code)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ColumnInfo {
-    cid: i32,
     name: String,
+    #[serde(rename = "type")]
     data_type: String,
     not_null: bool,
     default_value: Option<String>,
     is_primary: bool,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ForeignKeyInfo {
+    columns: Vec<String>,
+    references_table: String,
+    /// Empty when the key references the other table's primary key implicitly
+    references_columns: Vec<String>,
+    on_update: String,
+    on_delete: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct IndexInfo {
     name: String,
-    table_name: String,
-    sql: Option<String>,
+    unique: bool,
+    columns: Vec<String>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TriggerInfo {
+    name: String,
+    /// BEFORE, AFTER or INSTEAD OF
+    timing: String,
+    /// e.g. DELETE, or UPDATE OF title, summary
+    event: String,
+    /// What the trigger body does, so the docs can say e.g. that deleting a
+    /// project deletes its chats
+    actions: Vec<TriggerAction>,
+    sql: String,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct TriggerAction {
+    /// DELETE, UPDATE, INSERT or RAISE
+    statement: String,
+    /// Table written to; None for RAISE
+    table: Option<String>,
+}
+
+const USAGE: &str = "Usage:
+  generate_schema [--check]                          write SQL_SCHEMA.md, SQL_SCHEMA.json and DBSchema.generated.ts
+                                                     from migrations.rs, or with --check fail if they're out of date
+  generate_schema migrate check                      check migrations.rs is well formed
+  generate_schema migrate status <db>                show applied and pending migrations
+  generate_schema migrate up <db> [--to <version>] [--dry-run] [--no-backup]
+  generate_schema migrate down <db> --to <version> [--dry-run] [--no-backup]
+  generate_schema check <db> [--repair]              check a database for inconsistent data";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => generate_schema(false),
+        Some("--check") => generate_schema(true),
+        Some("migrate") => {
+            if let Err(e) = migrate(&args[1..]) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some("check") => check(&args[1..]),
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn generate_schema(check: bool) -> Result<(), Box<dyn std::error::Error>> {
     // Create an in-memory SQLite database
     let conn = Connection::open_in_memory()?;
     
@@ -40,48 +136,85 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Apply all migrations
     for migration in &migrations {
         if matches!(migration.kind, MigrationKind::Up) {
-            println!("Applying migration {}: {}", migration.version, migration.description);
+            if !check {
+                println!("Applying migration {}: {}", migration.version, migration.description);
+            }
             conn.execute_batch(&migration.sql)?;
         }
     }
     
     // Query the schema
-    let tables = get_tables(&conn)?;
-    let indices = get_indices(&conn)?;
-    
-    // Generate SQL_SCHEMA.md in the root directory
-    let schema_path = Path::new("../SQL_SCHEMA.md");
-    let mut file = File::create(schema_path)?;
-    
-    // Write header
-    writeln!(file, "# Database Schema")?;
-    writeln!(file)?;
-    writeln!(file, "_This file is auto-generated from migrations.rs. Do not edit manually._")?;
-    writeln!(file)?;
-    writeln!(file, "Last updated: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"))?;
-    writeln!(file)?;
+    let schema = Schema {
+        version: migrations.iter().map(|m| m.version).max().unwrap_or(0),
+        tables: get_tables(&conn)?,
+    };
+
+    let outputs = [
+        (MARKDOWN_PATH, render_markdown(&schema)?),
+        (JSON_PATH, render_json(&schema)?),
+        (TYPESCRIPT_PATH, render_typescript(&schema)?),
+    ];
+
+    if check {
+        let stale: Vec<&str> = outputs
+            .iter()
+            .filter(|(path, contents)| std::fs::read_to_string(path).ok().as_ref() != Some(contents))
+            .map(|(path, _)| *path)
+            .collect();
+        if stale.is_empty() {
+            println!("Generated schema files match migrations.rs (version {})", schema.version);
+            return Ok(());
+        }
+        for path in stale {
+            eprintln!("Out of date: {}", path);
+        }
+        eprintln!("Run `pnpm generate-schema` and commit the result");
+        std::process::exit(1);
+    }
+
+    for (path, contents) in &outputs {
+        std::fs::write(path, contents)?;
+        println!("Schema generated successfully at {}", path);
+    }
+    Ok(())
+}
+
+fn render_markdown(schema: &Schema) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+
+    // Write header. No timestamp, so regenerating an unchanged schema is a no-op
+    // and --check can compare files directly
+    writeln!(out, "# Database Schema")?;
+    writeln!(out)?;
+    writeln!(out, "_This file is auto-generated from migrations.rs. Do not edit manually._")?;
+    writeln!(out)?;
+    writeln!(out, "Schema version: {}", schema.version)?;
+    writeln!(out)?;
     
     // Write table of contents
-    writeln!(file, "## Tables")?;
-    writeln!(file)?;
-    for table in &tables {
-        writeln!(file, "- [{}](#{})", table.name, table.name.to_lowercase())?;
+    writeln!(out, "## Tables")?;
+    writeln!(out)?;
+    for table in &schema.tables {
+        writeln!(out, "- {}", table_link(&table.name))?;
     }
-    writeln!(file)?;
+    writeln!(out)?;
+
+    write_diagram(&mut out, schema)?;
     
     // Write detailed table information
-    for table in &tables {
-        writeln!(file, "## {}", table.name)?;
-        writeln!(file)?;
-        
-        // Get column information
-        let columns = get_columns(&conn, &table.name)?;
+    for table in &schema.tables {
+        writeln!(out, "## {}", table.name)?;
+        writeln!(out)?;
+        if table.is_virtual {
+            writeln!(out, "_Virtual table_")?;
+            writeln!(out)?;
+        }
         
         // Write table
-        writeln!(file, "| Column | Type | Constraints | Default |")?;
-        writeln!(file, "|--------|------|-------------|---------|")?;
+        writeln!(out, "| Column | Type | Constraints | Default |")?;
+        writeln!(out, "|--------|------|-------------|---------|")?;
         
-        for col in &columns {
+        for col in &table.columns {
             let constraints = format!("{}{}",
                 if col.not_null { "NOT NULL " } else { "" },
                 if col.is_primary { "PRIMARY KEY" } else { "" }
@@ -89,69 +222,333 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             let default = col.default_value.as_deref().unwrap_or("-");
             
-            writeln!(file, "| {} | {} | {} | {} |", 
+            writeln!(out, "| {} | {} | {} | {} |", 
                 col.name, 
                 col.data_type, 
                 if constraints.is_empty() { "-" } else { &constraints },
                 default
             )?;
         }
-        writeln!(file)?;
+        writeln!(out)?;
         
         // Write indices for this table
-        let table_indices: Vec<&IndexInfo> = indices.iter()
-            .filter(|idx| idx.table_name == table.name && !idx.name.starts_with("sqlite_autoindex"))
-            .collect();
-        
-        if !table_indices.is_empty() {
-            writeln!(file, "### Indices")?;
-            writeln!(file)?;
-            for idx in table_indices {
-                writeln!(file, "- **{}**", idx.name)?;
-                if let Some(sql) = &idx.sql {
-                    // Extract the column list from CREATE INDEX statement
-                    if let Some(start) = sql.find('(') {
-                        if let Some(end) = sql.find(')') {
-                            let columns = &sql[start+1..end];
-                            writeln!(file, "  - Columns: {}", columns)?;
-                        }
-                    }
+        if !table.indices.is_empty() {
+            writeln!(out, "### Indices")?;
+            writeln!(out)?;
+            for idx in &table.indices {
+                writeln!(out, "- **{}**", idx.name)?;
+                writeln!(out, "  - Columns: {}", idx.columns.join(", "))?;
+            }
+            writeln!(out)?;
+        }
+
+        if !table.foreign_keys.is_empty() {
+            writeln!(out, "### Foreign Keys")?;
+            writeln!(out)?;
+            for fk in &table.foreign_keys {
+                let references = if fk.references_columns.is_empty() {
+                    table_link(&fk.references_table)
+                } else {
+                    format!("{} ({})", table_link(&fk.references_table), fk.references_columns.join(", "))
+                };
+                writeln!(out, "- {} -> {}, on delete {}, on update {}",
+                    fk.columns.join(", "),
+                    references,
+                    fk.on_delete,
+                    fk.on_update
+                )?;
+            }
+            writeln!(out)?;
+        }
+
+        if !table.checks.is_empty() {
+            writeln!(out, "### Check Constraints")?;
+            writeln!(out)?;
+            for check in &table.checks {
+                writeln!(out, "- `{}`", check)?;
+            }
+            writeln!(out)?;
+        }
+
+        if !table.triggers.is_empty() {
+            writeln!(out, "### Triggers")?;
+            writeln!(out)?;
+            for trigger in &table.triggers {
+                writeln!(out, "- **{}**: {} {}{}", trigger.name, trigger.timing, trigger.event, describe_actions(&trigger.actions))?;
+                writeln!(out)?;
+                writeln!(out, "  ```sql")?;
+                for line in dedent(&trigger.sql).lines() {
+                    writeln!(out, "  {}", line)?;
                 }
+                writeln!(out, "  ```")?;
+                writeln!(out)?;
+            }
+        }
+
+        // The easy ones to miss: rows in this table changing because of a write somewhere else
+        let external: Vec<(&TableInfo, &TriggerInfo)> = schema.tables.iter()
+            .filter(|other| other.name != table.name)
+            .flat_map(|other| other.triggers.iter().map(move |trigger| (other, trigger)))
+            .filter(|(_, trigger)| trigger.actions.iter().any(|action| action.table.as_deref() == Some(&table.name)))
+            .collect();
+        if !external.is_empty() {
+            writeln!(out, "### Changed by Triggers on Other Tables")?;
+            writeln!(out)?;
+            for (other, trigger) in external {
+                writeln!(out, "- **{}** on {}: {} {}{}",
+                    trigger.name,
+                    table_link(&other.name),
+                    trigger.timing,
+                    trigger.event,
+                    describe_actions(&trigger.actions)
+                )?;
             }
-            writeln!(file)?;
+            writeln!(out)?;
         }
     }
     
-    println!("Schema generated successfully at ../SQL_SCHEMA.md");
+    Ok(out)
+}
+
+fn table_link(name: &str) -> String {
+    format!("[{}](#{})", name, name.to_lowercase())
+}
+
+/// e.g. ", deletes from [chats](#chats)"
+fn describe_actions(actions: &[TriggerAction]) -> String {
+    actions
+        .iter()
+        .map(|action| match (action.statement.as_str(), &action.table) {
+            ("DELETE", Some(table)) => format!(", deletes from {}", table_link(table)),
+            ("UPDATE", Some(table)) => format!(", updates {}", table_link(table)),
+            ("INSERT", Some(table)) => format!(", inserts into {}", table_link(table)),
+            _ => ", can fail the statement with RAISE".to_string(),
+        })
+        .collect()
+}
+
+/// Mermaid ER diagram of every table. Solid lines are foreign keys, dotted lines
+/// are triggers on one table that write to another.
+fn write_diagram(out: &mut String, schema: &Schema) -> std::fmt::Result {
+    writeln!(out, "## Relationships")?;
+    writeln!(out)?;
+    writeln!(out, "Solid lines are foreign keys. Dotted lines are triggers that write to another table.")?;
+    writeln!(out)?;
+    writeln!(out, "```mermaid")?;
+    writeln!(out, "erDiagram")?;
+    for table in &schema.tables {
+        writeln!(out, "    {} {{", table.name)?;
+        for col in &table.columns {
+            let is_foreign = table.foreign_keys.iter().any(|fk| fk.columns.contains(&col.name));
+            let keys = match (col.is_primary, is_foreign) {
+                (true, true) => " PK, FK",
+                (true, false) => " PK",
+                (false, true) => " FK",
+                (false, false) => "",
+            };
+            writeln!(out, "        {} {}{}", mermaid_type(&col.data_type), col.name, keys)?;
+        }
+        writeln!(out, "    }}")?;
+    }
+
+    for table in &schema.tables {
+        for fk in &table.foreign_keys {
+            let required = fk.columns.iter().all(|name| {
+                table.columns.iter().any(|col| &col.name == name && col.not_null)
+            });
+            writeln!(out, "    {} }}o--{} {} : \"{}\"",
+                table.name,
+                if required { "||" } else { "o|" },
+                fk.references_table,
+                fk.columns.join(", ")
+            )?;
+        }
+    }
+
+    for table in &schema.tables {
+        let mut edges: Vec<(&str, Vec<&str>)> = Vec::new();
+        for action in table.triggers.iter().flat_map(|trigger| &trigger.actions) {
+            let Some(target) = action.table.as_deref().filter(|target| *target != table.name) else {
+                continue;
+            };
+            let verb = match action.statement.as_str() {
+                "DELETE" => "deletes",
+                "UPDATE" => "updates",
+                _ => "inserts",
+            };
+            match edges.iter_mut().find(|(t, _)| *t == target) {
+                Some((_, verbs)) if !verbs.contains(&verb) => verbs.push(verb),
+                Some(_) => {}
+                None => edges.push((target, vec![verb])),
+            }
+        }
+        for (target, verbs) in edges {
+            writeln!(out, "    {} ||..o{{ {} : \"trigger {}\"", table.name, target, verbs.join("/"))?;
+        }
+    }
+    writeln!(out, "```")?;
+    writeln!(out)?;
     Ok(())
 }
 
+/// Mermaid attribute types have to be a single word
+fn mermaid_type(data_type: &str) -> String {
+    if data_type.is_empty() {
+        return "ANY".to_string();
+    }
+    data_type
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Triggers are stored with the indentation they had inside migrations.rs
+fn dedent(sql: &str) -> String {
+    let mut lines = sql.lines();
+    let first = lines.next().unwrap_or_default().trim();
+    let rest: Vec<&str> = lines.map(str::trim_end).collect();
+    let indent = rest
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let mut result = first.to_string();
+    for line in rest {
+        result.push('\n');
+        result.push_str(line.get(indent..).unwrap_or(""));
+    }
+    result
+}
+
+fn render_json(schema: &Schema) -> Result<String, serde_json::Error> {
+    // Four spaces, like the rest of the repo's JSON
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    schema.serialize(&mut serializer)?;
+    buf.push(b'\n');
+    Ok(String::from_utf8(buf).expect("serde_json writes UTF-8"))
+}
+
+/// One `<Table>DBRow` type per table, shaped like the rows tauri-plugin-sql
+/// returns from `db.select`, plus a `DBTables` map from table name to row type.
+fn render_typescript(schema: &Schema) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+    writeln!(out, "// This file is auto-generated from migrations.rs by `pnpm generate-schema`.")?;
+    writeln!(out, "// Do not edit manually.")?;
+    writeln!(out, "//")?;
+    writeln!(out, "// Schema version: {}", schema.version)?;
+
+    for table in &schema.tables {
+        writeln!(out)?;
+        writeln!(out, "export type {} = {{", ts_row_type_name(&table.name))?;
+        for col in &table.columns {
+            // SQLite lets non-INTEGER primary keys be NULL, but nothing in the app inserts one
+            let nullable = !col.not_null && !col.is_primary;
+            writeln!(
+                out,
+                "    {}: {}{};",
+                ts_property_name(&col.name),
+                ts_type(&col.data_type),
+                if nullable { " | null" } else { "" }
+            )?;
+        }
+        writeln!(out, "}};")?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "export type DBTables = {{")?;
+    for table in &schema.tables {
+        writeln!(out, "    {}: {};", ts_property_name(&table.name), ts_row_type_name(&table.name))?;
+    }
+    writeln!(out, "}};")?;
+    Ok(out)
+}
+
+/// e.g. message_sets -> MessageSetsDBRow, matching the hand-written ChatDBRow etc.
+fn ts_row_type_name(table: &str) -> String {
+    let mut name: String = table
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+        })
+        .collect();
+    name.push_str("DBRow");
+    name
+}
+
+fn ts_property_name(name: &str) -> String {
+    let is_identifier = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_identifier {
+        name.to_string()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+/// What tauri-plugin-sql hands back for a column. It converts values by their storage
+/// class rather than the declared type, so this follows SQLite's affinity rules:
+/// BOOLEANs come back as 0/1, and DATETIMEs and JSON are stored (and returned) as text.
+fn ts_type(data_type: &str) -> &'static str {
+    let data_type = data_type.to_uppercase();
+    let has = |s: &str| data_type.contains(s);
+    if has("INT") {
+        "number"
+    } else if has("CHAR") || has("CLOB") || has("TEXT") || has("DATE") || has("TIME") || has("JSON") {
+        "string"
+    } else if has("BLOB") {
+        "number[]"
+    } else if data_type.is_empty() {
+        // No declared type, e.g. FTS5 columns: whatever was inserted
+        "unknown"
+    } else {
+        // REAL and NUMERIC affinity, including BOOLEAN
+        "number"
+    }
+}
+
 fn get_tables(conn: &Connection) -> Result<Vec<TableInfo>, rusqlite::Error> {
-    let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type='table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+    // table_list (unlike sqlite_master) says which tables are virtual, and which
+    // are shadow tables backing them
+    let mut stmt = conn.prepare(
+        "SELECT tl.name, tl.type, m.sql FROM pragma_table_list tl
+        JOIN sqlite_master m ON m.name = tl.name
+        WHERE tl.schema = 'main' AND tl.type IN ('table', 'virtual') AND tl.name NOT LIKE 'sqlite_%'
+        ORDER BY tl.name",
+    )?;
     let tables = stmt.query_map([], |row| {
-        Ok(TableInfo {
-            name: row.get(0)?,
-            sql: row.get(1)?,
-        })
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
     })?;
     
     let mut result = Vec::new();
     for table in tables {
-        result.push(table?);
+        let (name, kind, sql) = table?;
+        result.push(TableInfo {
+            columns: get_columns(conn, &name)?,
+            foreign_keys: get_foreign_keys(conn, &name)?,
+            checks: check_constraints(&sql),
+            indices: get_indices(conn, &name)?,
+            triggers: get_triggers(conn, &name)?,
+            is_virtual: kind == "virtual",
+            name,
+        });
     }
     Ok(result)
 }
 
 fn get_columns(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>, rusqlite::Error> {
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table_name))?;
-    let columns = stmt.query_map([], |row| {
+    let mut stmt = conn.prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid")?;
+    let columns = stmt.query_map([table_name], |row| {
         Ok(ColumnInfo {
-            cid: row.get(0)?,
-            name: row.get(1)?,
-            data_type: row.get(2)?,
-            not_null: row.get(3)?,
-            default_value: row.get(4)?,
-            is_primary: row.get(5)?,
+            name: row.get(0)?,
+            data_type: row.get(1)?,
+            not_null: row.get(2)?,
+            default_value: row.get(3)?,
+            is_primary: row.get::<_, i64>(4)? > 0,
         })
     })?;
     
@@ -162,19 +559,358 @@ fn get_columns(conn: &Connection, table_name: &str) -> Result<Vec<ColumnInfo>, r
     Ok(result)
 }
 
-fn get_indices(conn: &Connection) -> Result<Vec<IndexInfo>, rusqlite::Error> {
-    let mut stmt = conn.prepare("SELECT name, tbl_name, sql FROM sqlite_master WHERE type='index' ORDER BY name")?;
-    let indices = stmt.query_map([], |row| {
-        Ok(IndexInfo {
-            name: row.get(0)?,
-            table_name: row.get(1)?,
-            sql: row.get(2)?,
-        })
+fn get_foreign_keys(conn: &Connection, table_name: &str) -> Result<Vec<ForeignKeyInfo>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete FROM pragma_foreign_key_list(?1) ORDER BY id, seq",
+    )?;
+    let rows = stmt.query_map([table_name], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+        ))
     })?;
+
+    // Composite keys come back as one row per column, sharing an id
+    let mut result: Vec<(i64, ForeignKeyInfo)> = Vec::new();
+    for row in rows {
+        let (id, references_table, from, to, on_update, on_delete) = row?;
+        match result.last_mut() {
+            Some((last_id, fk)) if *last_id == id => {
+                fk.columns.push(from);
+                fk.references_columns.extend(to);
+            }
+            _ => result.push((id, ForeignKeyInfo {
+                columns: vec![from],
+                references_table,
+                references_columns: to.into_iter().collect(),
+                on_update,
+                on_delete,
+            })),
+        }
+    }
+    // SQLite numbers them last-declared first
+    Ok(result.into_iter().rev().map(|(_, fk)| fk).collect())
+}
+
+fn get_indices(conn: &Connection, table_name: &str) -> Result<Vec<IndexInfo>, rusqlite::Error> {
+    // origin 'c' skips the sqlite_autoindex_* indices behind PRIMARY KEY and UNIQUE
+    let mut stmt = conn.prepare("SELECT name, \"unique\" FROM pragma_index_list(?1) WHERE origin = 'c' ORDER BY name")?;
+    let indices = stmt.query_map([table_name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)))?;
     
     let mut result = Vec::new();
     for idx in indices {
-        result.push(idx?);
+        let (name, unique) = idx?;
+        let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+        let columns = stmt
+            .query_map([&name], |row| row.get::<_, Option<String>>(0))?
+            .map(|column| column.map(|column| column.unwrap_or_else(|| "<expression>".to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        result.push(IndexInfo { name, unique, columns });
+    }
+    Ok(result)
+}
+
+fn get_triggers(conn: &Connection, table_name: &str) -> Result<Vec<TriggerInfo>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ?1 ORDER BY name")?;
+    let triggers = stmt.query_map([table_name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+    let mut result = Vec::new();
+    for trigger in triggers {
+        let (name, sql) = trigger?;
+        result.push(parse_trigger(name, sql));
     }
     Ok(result)
-}
\ No newline at end of file
+}
+
+/// Reads the timing, event and actions out of a CREATE TRIGGER statement.
+fn parse_trigger(name: String, sql: String) -> TriggerInfo {
+    let words = sql_words(&sql);
+    let upper: Vec<String> = words.iter().map(|word| word.to_uppercase()).collect();
+    let body_start = upper.iter().position(|word| word == "BEGIN").unwrap_or(upper.len());
+    let is = |i: usize, word: &str| upper.get(i).is_some_and(|w| w == word);
+
+    // CREATE [TEMP] TRIGGER [IF NOT EXISTS] name
+    let mut i = upper.iter().position(|word| word == "TRIGGER").map_or(0, |i| i + 1);
+    if is(i, "IF") {
+        i += 3;
+    }
+    i += 1;
+
+    let timing = if is(i, "BEFORE") || is(i, "AFTER") {
+        i += 1;
+        upper[i - 1].clone()
+    } else if is(i, "INSTEAD") {
+        i += 2;
+        "INSTEAD OF".to_string()
+    } else {
+        // SQLite's default
+        "BEFORE".to_string()
+    };
+
+    let mut event = upper.get(i).cloned().unwrap_or_default();
+    if is(i + 1, "OF") {
+        let columns: Vec<&str> = words[i + 2..body_start]
+            .iter()
+            .take_while(|word| !word.eq_ignore_ascii_case("ON"))
+            .map(String::as_str)
+            .collect();
+        event = format!("{} OF {}", event, columns.join(", "));
+    }
+
+    let mut actions = Vec::new();
+    for j in body_start..upper.len() {
+        let action = match upper[j].as_str() {
+            "DELETE" if is(j + 1, "FROM") => words.get(j + 2).map(|table| ("DELETE", Some(table.clone()))),
+            // UPDATE [OR ...] table, but not ON CONFLICT ... DO UPDATE SET
+            "UPDATE" if !is(j + 1, "SET") => {
+                let table = if is(j + 1, "OR") { j + 3 } else { j + 1 };
+                words.get(table).map(|table| ("UPDATE", Some(table.clone())))
+            }
+            "INSERT" | "REPLACE" => (j + 1..(j + 4).min(upper.len()))
+                .find(|&k| is(k, "INTO"))
+                .and_then(|k| words.get(k + 1))
+                .map(|table| ("INSERT", Some(table.clone()))),
+            "RAISE" => Some(("RAISE", None)),
+            _ => None,
+        };
+        if let Some((statement, table)) = action {
+            let action = TriggerAction { statement: statement.to_string(), table };
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+    }
+
+    TriggerInfo { name, timing, event, actions, sql }
+}
+
+/// Keywords and identifiers in a statement, in order. String literals, comments and
+/// punctuation are dropped; quoted identifiers are unquoted.
+fn sql_words(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some((end, is_comment)) = skip_quoted(&chars, i) {
+            if !is_comment && chars[i] != '\'' {
+                words.push(chars[i + 1..end.saturating_sub(1).max(i + 1)].iter().collect());
+            }
+            i = end;
+        } else if chars[i].is_alphanumeric() || chars[i] == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            words.push(chars[start..i].iter().collect());
+        } else {
+            i += 1;
+        }
+    }
+    words
+}
+
+/// Pulls the expression out of every CHECK constraint in a CREATE TABLE statement.
+/// SQLite doesn't expose these through a pragma, so this matches parentheses itself,
+/// skipping over string literals, quoted identifiers and comments.
+fn check_constraints(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut checks = Vec::new();
+    let mut after_check = false;
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some((end, is_comment)) = skip_quoted(&chars, i) {
+            after_check &= is_comment;
+            i = end;
+        } else if chars[i].is_alphabetic() || chars[i] == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            after_check = chars[start..i].iter().collect::<String>().eq_ignore_ascii_case("CHECK");
+        } else if chars[i] == '(' && after_check {
+            let (expression, end) = parenthesized(&chars, i);
+            checks.push(expression);
+            after_check = false;
+            i = end;
+        } else {
+            after_check &= chars[i].is_whitespace();
+            i += 1;
+        }
+    }
+    checks
+}
+
+/// Returns what's inside the parentheses opening at `open`, with comments dropped
+/// and whitespace collapsed, and the index just past the closing parenthesis.
+fn parenthesized(chars: &[char], open: usize) -> (String, usize) {
+    let mut expression = String::new();
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        if let Some((end, is_comment)) = skip_quoted(chars, i) {
+            if is_comment {
+                push_space(&mut expression);
+            } else {
+                expression.extend(&chars[i..end]);
+            }
+            i = end;
+            continue;
+        }
+        match chars[i] {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return (expression.trim().to_string(), i + 1);
+        }
+        if chars[i].is_whitespace() {
+            push_space(&mut expression);
+        } else if i != open {
+            expression.push(chars[i]);
+        }
+        i += 1;
+    }
+    (expression.trim().to_string(), chars.len())
+}
+
+fn push_space(s: &mut String) {
+    if !s.is_empty() && !s.ends_with(' ') {
+        s.push(' ');
+    }
+}
+
+/// If a string literal, quoted identifier or comment starts at `i`, returns the
+/// index just past it and whether it was a comment.
+fn skip_quoted(chars: &[char], i: usize) -> Option<(usize, bool)> {
+    let close = match chars[i] {
+        '\'' | '"' | '`' => chars[i],
+        '[' => ']',
+        '-' if chars.get(i + 1) == Some(&'-') => {
+            let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |n| i + n + 1);
+            return Some((end, true));
+        }
+        '/' if chars.get(i + 1) == Some(&'*') => {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                .map_or(chars.len(), |j| j + 2);
+            return Some((end, true));
+        }
+        _ => return None,
+    };
+    let mut j = i + 1;
+    while j < chars.len() {
+        if chars[j] == close {
+            // Quotes inside quotes are escaped by doubling them
+            if close != ']' && chars.get(j + 1) == Some(&close) {
+                j += 2;
+                continue;
+            }
+            return Some((j + 1, false));
+        }
+        j += 1;
+    }
+    Some((chars.len(), false))
+}
+
+fn migrate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let migrations = migrations::migrations();
+    let command = args.first().map(String::as_str);
+
+    if command == Some("check") {
+        migrator::validate_migrations(&migrations)?;
+        let ups = migrations.iter().filter(|m| matches!(m.kind, MigrationKind::Up)).count();
+        println!("{} migrations ({} with down migrations), all well formed", ups, migrations.len() - ups);
+        return Ok(());
+    }
+
+    let Some(db_path) = args.get(1).map(Path::new) else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let flags = &args[2..];
+    let dry_run = flags.iter().any(|flag| flag == "--dry-run");
+    let target = match flags.iter().position(|flag| flag == "--to") {
+        Some(i) => Some(flags.get(i + 1).ok_or("--to needs a version")?.parse::<i64>()?),
+        None => None,
+    };
+    let options = migrator::RunOptions {
+        // Same place the app keeps its backups: next to the database
+        backup_dir: if flags.iter().any(|flag| flag == "--no-backup") {
+            None
+        } else {
+            db_path.parent().map(|dir| dir.join("backups"))
+        },
+        dry_run,
+    };
+
+    let report = match command {
+        Some("status") => {
+            if !db_path.exists() {
+                return Err(format!("No database at {}", db_path.display()).into());
+            }
+            let conn = Connection::open(db_path)?;
+            let applied = migrator::verify(&conn, &migrations)?;
+            let pending = migrator::pending(&conn, &migrations)?;
+            println!("Applied: {} (latest {})", applied.len(), applied.last().map_or("none".to_string(), |m| m.version.to_string()));
+            println!("All applied checksums match migrations.rs");
+            println!("Pending: {}", pending.len());
+            for migration in pending {
+                println!("  {}: {}", migration.version, migration.description);
+            }
+            return Ok(());
+        }
+        Some("up") => migrator::migrate_up(db_path, &migrations, target, &options)?,
+        Some("down") => {
+            let target = target.ok_or("migrate down needs --to <version>")?;
+            migrator::migrate_down(db_path, &migrations, target, &options)?
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if report.versions.is_empty() {
+        println!("Nothing to do, database is at version {:?}", report.from_version);
+        return Ok(());
+    }
+    if let Some(backup) = &report.backup {
+        println!("Backup written to {}", backup.display());
+    }
+    println!(
+        "{}{} migration(s): version {:?} -> {:?}",
+        if report.dry_run { "[dry run] " } else { "" },
+        report.versions.len(),
+        report.from_version,
+        report.to_version
+    );
+    if report.dry_run {
+        println!("[dry run] The database was not modified");
+    }
+    Ok(())
+}
+
+fn check(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(db_path) = args.first().map(Path::new) else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    if !db_path.exists() {
+        return Err(format!("No database at {}", db_path.display()).into());
+    }
+    let repair = args.iter().any(|arg| arg == "--repair");
+
+    let mut conn = Connection::open(db_path)?;
+    let report = invariants::check_database(&mut conn, repair)?;
+    report.print();
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -1,7 +1,9 @@
+use crate::display_layout::{display_bounds, display_for_window, Rect};
+use crate::file_access::FileAccessError;
+use crate::redaction::{RedactionSettings, RedactionStyle};
 #[cfg(target_os = "macos")]
 use crate::window::WebviewWindowExt;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
-#[cfg(not(target_os = "macos"))]
 use screenshots::Screen;
 use tauri::{AppHandle, Emitter, Manager};
 #[cfg(target_os = "macos")]
@@ -13,7 +15,7 @@ use crate::SPOTLIGHT_LABEL;
 // This is used as the maximum size for images in the application
 // and should match TARGET_IMAGE_SIZE_BYTES in src/ui/hooks/useAttachments.ts
 // Changing this value will affect the size of all images processed by the application
-const TARGET_SIZE_BYTES: u64 = 4_500_000;
+pub const TARGET_SIZE_BYTES: u64 = 4_500_000;
 
 #[tauri::command]
 pub fn show(app_handle: AppHandle) {
@@ -66,6 +68,36 @@ pub fn new_quick_chat(app_handle: AppHandle) {
     app_handle.emit_to("main", "new_quick_chat", ()).unwrap();
 }
 
+/// Applies the quick chat settings without a restart, returning the shortcut in
+/// canonical form. On error (an invalid shortcut, or one another app has taken)
+/// the previous shortcut stays registered.
+#[tauri::command]
+pub async fn update_quick_chat_settings(
+    app_handle: AppHandle,
+    enabled: bool,
+    shortcut: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::quick_chat::update_settings(&app_handle, enabled, &shortcut)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Replaces the global shortcuts other than the quick chat's, returning them as
+/// they should be saved. On error every shortcut stays as it was.
+#[tauri::command]
+pub async fn update_global_shortcuts(
+    app_handle: AppHandle,
+    bindings: Vec<crate::global_shortcuts::ShortcutBinding>,
+) -> Result<Vec<crate::global_shortcuts::ShortcutBinding>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::global_shortcuts::update_bindings(&app_handle, bindings)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
 #[tauri::command]
 pub fn refresh_projects_state(app_handle: AppHandle) {
     app_handle.emit("refresh_projects_state", ()).unwrap();
@@ -118,11 +150,12 @@ pub fn capture_window() -> Result<String, String> {
         return Err("Screen recording permission denied. Please enable it in System Preferences > Security & Privacy > Privacy > Screen Recording".to_string());
     }
 
-    // Use our resize_image function to handle the resizing
-    let resized_path = resize_image(
+    // Use our resize_capture function to handle the resizing
+    let resized_path = resize_capture(
         raw_screenshot_path.to_string_lossy().to_string(),
         TARGET_SIZE_BYTES,
-    )?;
+    )?
+    .path;
 
     // Read the resized file and convert to base64
     let image_data = fs::read(&resized_path).map_err(|e| e.to_string())?;
@@ -140,156 +173,201 @@ pub fn capture_window() -> Result<String, String> {
     Ok(BASE64.encode(&image_data))
 }
 
+/// Lists the windows `capture_window` can capture, topmost first, so the user can
+/// pick one. macOS doesn't need this, as `screencapture -w` has its own picker.
 #[tauri::command]
 #[cfg(not(target_os = "macos"))]
-pub fn capture_window() -> Result<String, String> {
-    // For non-macOS platforms, just capture the active window
-    // This is a placeholder - you may want to implement platform-specific window capture
-    Err("Window capture not implemented for this platform".to_string())
+pub fn list_windows() -> Result<Vec<crate::window_capture::WindowInfo>, String> {
+    crate::window_capture::list_windows()
 }
 
+/// Captures the window with `window_id` (from `list_windows`), or the topmost
+/// window if none is given.
 #[tauri::command]
-#[cfg(target_os = "macos")]
-pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
-    use std::fs;
-    use std::process::Command;
+#[cfg(not(target_os = "macos"))]
+pub fn capture_window(window_id: Option<u64>) -> Result<String, String> {
     use std::time::Instant;
 
-    // Start timing the operation
     let start_time = Instant::now();
-    println!("Starting screenshot capture...");
+    log::debug!("Starting window capture...");
 
-    // Create temporary file path for raw screenshot
+    let capture_time = Instant::now();
+    let image = crate::window_capture::capture_window(window_id)?;
+    log::debug!("Raw capture completed in: {:?}", capture_time.elapsed());
+
+    let encoded = encode_screenshot(image)?;
+    log::info!(
+        "Total window capture process took: {:?}",
+        start_time.elapsed()
+    );
+    Ok(encoded)
+}
+
+/// Saves a captured image, shrinks it with `resize_capture` and returns it as base64.
+#[cfg(not(target_os = "macos"))]
+fn encode_screenshot(image: image::RgbaImage) -> Result<String, String> {
     let raw_screenshot_path = std::env::temp_dir().join("screenshot_raw.png");
+    image
+        .save(&raw_screenshot_path)
+        .map_err(|e| e.to_string())?;
 
-    // Get information about the current window
-    if let Some(window) = app_handle.get_webview_window(SPOTLIGHT_LABEL) {
-        if let Ok(position) = window.outer_position() {
-            // Log window position for debugging
-            println!("Window position: ({}, {})", position.x, position.y);
-
-            // First, get the main display bounds to determine if we're on a secondary display
-            // Use a temporary script to get this info
-            let script_path = std::env::temp_dir().join("display_info.sh");
-            let script_content = r#"#!/bin/bash
-/usr/sbin/system_profiler SPDisplaysDataType | grep -A 15 "Display Type: Built-in" | grep "Resolution:" | head -n 1 | awk -F': ' '{print $2}' | sed 's/ Retina//' | awk -F' x ' '{print $1, $2}'
-"#;
-            fs::write(&script_path, script_content).map_err(|e| e.to_string())?;
-            Command::new("chmod")
-                .arg("+x")
-                .arg(&script_path)
-                .output()
-                .map_err(|e| e.to_string())?;
-
-            let main_display_output = Command::new(&script_path)
-                .output()
-                .map_err(|e| e.to_string())?;
-            let _ = fs::remove_file(&script_path);
-
-            // Parse main display resolution
-            let main_display_resolution = String::from_utf8_lossy(&main_display_output.stdout);
-            let parts: Vec<&str> = main_display_resolution.trim().split_whitespace().collect();
-
-            let main_width = if parts.len() >= 1 {
-                parts[0].parse::<i32>().unwrap_or(3456)
-            } else {
-                3456
-            };
-            let main_height = if parts.len() >= 2 {
-                parts[1].parse::<i32>().unwrap_or(2234)
-            } else {
-                2234
-            };
-
-            println!("Main display resolution: {}x{}", main_width, main_height);
-
-            // Simple heuristic: If window position is outside main display bounds,
-            // it's likely on a secondary display
-            let target_display_id = if position.x > main_width || position.y > main_height {
-                // It's likely on secondary display (typically ID 2)
-                2
-            } else {
-                // It's likely on main display
-                1
-            };
-
-            println!("Detected window on display ID: {}", target_display_id);
-
-            // Run screencapture command for the specific display
-            println!("Taking screenshot of display ID: {}", target_display_id);
-
-            let capture_time = Instant::now();
-            let output = Command::new("screencapture")
-                .arg("-D") // Specify display
-                .arg(target_display_id.to_string())
-                .arg(raw_screenshot_path.to_str().unwrap())
-                .output()
-                .map_err(|e| e.to_string())?;
-
-            println!("Raw capture completed in: {:?}", capture_time.elapsed());
-
-            // Check if the command failed
-            if !output.status.success() {
-                // If the specific display capture failed, try without a display ID
-                println!(
-                    "Failed to capture display {}. Falling back to main display.",
-                    target_display_id
-                );
-
-                let fallback_output = Command::new("screencapture")
-                    .arg("-m") // Capture main display as fallback
-                    .arg(raw_screenshot_path.to_str().unwrap())
-                    .output()
-                    .map_err(|e| e.to_string())?;
-
-                if !fallback_output.status.success() {
-                    return Err("Screen recording permission is required. Please enable it in System Preferences > Security & Privacy > Privacy > Screen Recording".to_string());
-                }
-            }
+    let resized_path = resize_capture(
+        raw_screenshot_path.to_string_lossy().to_string(),
+        TARGET_SIZE_BYTES,
+    )?
+    .path;
+    let image_data = std::fs::read(&resized_path).map_err(|e| e.to_string())?;
 
-            // Check if file exists and has content
-            if !raw_screenshot_path.exists() {
-                return Err("Screen recording permission denied. Please enable it in System Preferences > Security & Privacy > Privacy > Screen Recording".to_string());
-            }
+    // Clean up the temporary files
+    let _ = std::fs::remove_file(&raw_screenshot_path);
+    if resized_path != raw_screenshot_path.to_string_lossy().to_string() {
+        let _ = std::fs::remove_file(&resized_path);
+    }
+    Ok(BASE64.encode(&image_data))
+}
 
-            // Use our new resize_image function to handle the resizing
-            let resized_path = resize_image(
-                raw_screenshot_path.to_string_lossy().to_string(),
-                TARGET_SIZE_BYTES,
-            )?;
+/// Where the quick chat panel is, in the same coordinate space as the displays
+/// from `Screen::all`: points on macOS and Linux, pixels on Windows.
+fn spotlight_bounds(app_handle: &AppHandle) -> Option<Rect> {
+    let window = app_handle.get_webview_window(SPOTLIGHT_LABEL)?;
+    let position = window.outer_position().ok()?;
+    let size = window.outer_size().ok()?;
+
+    #[cfg(not(target_os = "windows"))]
+    let (position, size) = {
+        let scale_factor = window.scale_factor().ok()?;
+        (
+            position.to_logical::<i32>(scale_factor),
+            size.to_logical::<u32>(scale_factor),
+        )
+    };
+
+    Some(Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
 
-            // Read the resized file and convert to base64
-            let image_data = fs::read(&resized_path).map_err(|e| e.to_string())?;
+/// Index in `screens` of the display the quick chat panel is on, falling back to
+/// the main display.
+fn spotlight_display(app_handle: &AppHandle, screens: &[Screen]) -> usize {
+    let main_display = screens
+        .iter()
+        .position(|screen| screen.display_info.is_primary)
+        .unwrap_or(0);
+
+    let Some(bounds) = spotlight_bounds(app_handle) else {
+        log::debug!("Window information not available, using main display");
+        return main_display;
+    };
+    log::debug!(
+        "Window bounds: ({}, {}), {}x{}",
+        bounds.x, bounds.y, bounds.width, bounds.height
+    );
 
-            // Clean up the resized file if it's not the same as the raw screenshot
-            if resized_path != raw_screenshot_path.to_string_lossy().to_string() {
-                let _ = fs::remove_file(&resized_path);
-            }
+    let displays: Vec<Rect> = screens.iter().map(display_bounds).collect();
+    display_for_window(bounds, &displays).unwrap_or(main_display)
+}
 
-            println!("Total screenshot process took: {:?}", start_time.elapsed());
-            return Ok(BASE64.encode(&image_data));
-        }
-    }
+/// Captures the display the quick chat panel is on to `path` with `screencapture`,
+/// falling back to the main display. Returns the bounds and scale factor of the
+/// display that was captured.
+#[cfg(target_os = "macos")]
+fn capture_spotlight_display(
+    app_handle: &AppHandle,
+    path: &std::path::Path,
+) -> Result<(Rect, f64), String> {
+    use std::process::Command;
+    use std::time::Instant;
+
+    let screens = Screen::all().map_err(|e| e.to_string())?;
+    let index = spotlight_display(app_handle, &screens);
+    let screen = screens.get(index).ok_or("No screen found")?;
+
+    // screencapture numbers displays from 1, in the order CoreGraphics lists
+    // them, which is also the order `Screen::all` returns them in
+    let display_number = index + 1;
+    log::debug!(
+        "Taking screenshot of display {} ({}x{} at ({}, {}), scale {})",
+        display_number,
+        screen.display_info.width,
+        screen.display_info.height,
+        screen.display_info.x,
+        screen.display_info.y,
+        screen.display_info.scale_factor
+    );
+
+    let capture_time = Instant::now();
+    let output = Command::new("screencapture")
+        .arg("-D") // Specify display
+        .arg(display_number.to_string())
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())?;
 
-    // Fallback to the main display if window not found
-    println!("Window information not available, using main display");
+    if output.status.success() && path.exists() {
+        log::debug!("Raw capture completed in: {:?}", capture_time.elapsed());
+        return Ok((display_bounds(screen), screen.display_info.scale_factor as f64));
+    }
 
+    // If the specific display capture failed, try the main display
+    log::warn!(
+        "Failed to capture display {}. Falling back to main display.",
+        display_number
+    );
     let output = Command::new("screencapture")
         .arg("-m") // Capture the main display only
-        .arg(raw_screenshot_path.to_str().unwrap())
+        .arg(path)
         .output()
         .map_err(|e| e.to_string())?;
+    log::debug!("Raw capture completed in: {:?}", capture_time.elapsed());
 
     // Check if the command failed
     if !output.status.success() {
         return Err("Screen recording permission is required. Please enable it in System Preferences > Security & Privacy > Privacy > Screen Recording".to_string());
     }
 
-    // Use our new resize_image function to handle the resizing
-    let resized_path = resize_image(
+    // Check if file exists and has content
+    if !path.exists() {
+        return Err("Screen recording permission denied. Please enable it in System Preferences > Security & Privacy > Privacy > Screen Recording".to_string());
+    }
+    let main_screen = screens
+        .iter()
+        .find(|screen| screen.display_info.is_primary)
+        .unwrap_or(screen);
+    Ok((
+        display_bounds(main_screen),
+        main_screen.display_info.scale_factor as f64,
+    ))
+}
+
+#[tauri::command]
+#[cfg(target_os = "macos")]
+pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
+    use std::fs;
+    use std::time::Instant;
+
+    // Start timing the operation
+    let start_time = Instant::now();
+    println!("Starting screenshot capture...");
+
+    // Create temporary file path for raw screenshot
+    let raw_screenshot_path = std::env::temp_dir().join("screenshot_raw.png");
+    let (display, _) = capture_spotlight_display(&app_handle, &raw_screenshot_path)?;
+    crate::redaction::mask_app_windows_in_file(
+        &raw_screenshot_path,
+        display,
+        &RedactionSettings::load(&app_handle),
+    )?;
+
+    // Use our resize_capture function to handle the resizing
+    let resized_path = resize_capture(
         raw_screenshot_path.to_string_lossy().to_string(),
         TARGET_SIZE_BYTES,
-    )?;
+    )?
+    .path;
 
     // Read the resized file and convert to base64
     let image_data = fs::read(&resized_path).map_err(|e| e.to_string())?;
@@ -303,10 +381,20 @@ pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
     Ok(BASE64.encode(&image_data))
 }
 
+/// Finds the screen the quick chat panel is on, falling back to the main screen.
+#[cfg(not(target_os = "macos"))]
+fn spotlight_screen(app_handle: &AppHandle) -> Result<Screen, String> {
+    let mut screens = Screen::all().map_err(|e| e.to_string())?;
+    let index = spotlight_display(app_handle, &screens);
+    if index >= screens.len() {
+        return Err("No screen found".to_string());
+    }
+    Ok(screens.swap_remove(index))
+}
+
 #[tauri::command]
 #[cfg(not(target_os = "macos"))]
 pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
-    use image::{DynamicImage, ImageBuffer, ImageFormat};
     use std::time::Instant;
 
     // Start timing the operation
@@ -314,93 +402,12 @@ pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
     println!("Starting screenshot capture...");
 
     // For non-macOS platforms, use the screenshots crate to capture the entire screen
-    let screens = Screen::all().map_err(|e| e.to_string())?;
-
-    // Try to get the window position to determine which screen it's on
-    if let Some(window) = app_handle.get_webview_window(SPOTLIGHT_LABEL) {
-        if let Ok(position) = window.outer_position() {
-            println!("Window position: ({}, {})", position.x, position.y);
-
-            // Try to find which screen contains the window
-            for screen in &screens {
-                let display_info = screen.display_info;
-
-                // Check if the window's position is within this screen's bounds
-                if position.x >= display_info.x as i32
-                    && position.y >= display_info.y as i32
-                    && position.x < (display_info.x + display_info.width) as i32
-                    && position.y < (display_info.y + display_info.height) as i32
-                {
-                    // Log the display information
-                    println!(
-                        "Taking screenshot of display at position ({}, {})",
-                        display_info.x, display_info.y
-                    );
-                    println!(
-                        "Display dimensions: {}x{}",
-                        display_info.width, display_info.height
-                    );
-
-                    // Capture this specific screen
-                    let capture_time = Instant::now();
-                    let image = screen.capture().map_err(|e| e.to_string())?;
-                    println!("Raw capture completed in: {:?}", capture_time.elapsed());
-
-                    // Get image dimensions and raw pixels
-                    let width = image.width();
-                    let height = image.height();
-                    let pixels = image.as_raw();
-
-                    // Create a temporary file to save the raw screenshot
-                    let temp_dir = std::env::temp_dir();
-                    let raw_screenshot_path = temp_dir.join("screenshot_raw.png");
-
-                    // Create a buffer to store the image data
-                    let compress_time = Instant::now();
-
-                    // Convert from RGBA to an image file
-                    let img_buffer = ImageBuffer::from_raw(width, height, pixels.to_vec())
-                        .ok_or("Failed to create image buffer")?;
-
-                    let dynamic_image = DynamicImage::ImageRgba8(img_buffer);
-
-                    // Save the image to a temporary file
-                    dynamic_image
-                        .save(&raw_screenshot_path)
-                        .map_err(|e| e.to_string())?;
-
-                    println!("Raw image saved in: {:?}", compress_time.elapsed());
-
-                    // Use our new resize_image function to handle the resizing
-                    let resized_path = resize_image(
-                        raw_screenshot_path.to_string_lossy().to_string(),
-                        TARGET_SIZE_BYTES,
-                    )?;
-
-                    // Read the resized file and convert to base64
-                    let image_data = std::fs::read(&resized_path).map_err(|e| e.to_string())?;
-
-                    // Clean up the temporary files
-                    let _ = std::fs::remove_file(&raw_screenshot_path);
-                    if resized_path != raw_screenshot_path.to_string_lossy().to_string() {
-                        let _ = std::fs::remove_file(&resized_path);
-                    }
-
-                    println!("Total screenshot process took: {:?}", start_time.elapsed());
-                    return Ok(BASE64.encode(&image_data));
-                }
-            }
-        }
-    }
-
-    // Fallback to the main screen if we couldn't find the right screen
-    println!("Window not found on any display, using main display");
-    let screen = screens.first().ok_or("No screen found")?;
+    let screen = spotlight_screen(&app_handle)?;
 
     // Log the display information
     let display_info = screen.display_info;
     println!(
-        "Taking screenshot of main display at position ({}, {})",
+        "Taking screenshot of display at position ({}, {})",
         display_info.x, display_info.y
     );
     println!(
@@ -410,293 +417,187 @@ pub fn capture_whole_screen(app_handle: AppHandle) -> Result<String, String> {
 
     // Capture the screen
     let capture_time = Instant::now();
-    let image = screen.capture().map_err(|e| e.to_string())?;
+    let mut image = screen.capture().map_err(|e| e.to_string())?;
     println!("Raw capture completed in: {:?}", capture_time.elapsed());
 
-    // Get image dimensions and raw pixels
-    let width = image.width();
-    let height = image.height();
-    let pixels = image.as_raw();
-
-    // Create a temporary file to save the raw screenshot
-    let temp_dir = std::env::temp_dir();
-    let raw_screenshot_path = temp_dir.join("screenshot_raw.png");
-
-    // Create a buffer to store the image data
-    let compress_time = Instant::now();
-
-    // Convert from RGBA to an image file
-    let img_buffer = ImageBuffer::from_raw(width, height, pixels.to_vec())
-        .ok_or("Failed to create image buffer")?;
-
-    let dynamic_image = DynamicImage::ImageRgba8(img_buffer);
-
-    // Save the image to a temporary file
-    dynamic_image
-        .save(&raw_screenshot_path)
-        .map_err(|e| e.to_string())?;
-
-    println!("Raw image saved in: {:?}", compress_time.elapsed());
-
-    // Use our new resize_image function to handle the resizing
-    let resized_path = resize_image(
-        raw_screenshot_path.to_string_lossy().to_string(),
-        TARGET_SIZE_BYTES,
+    crate::redaction::mask_app_windows(
+        &mut image,
+        display_bounds(&screen),
+        &RedactionSettings::load(&app_handle),
     )?;
-
-    // Read the resized file and convert to base64
-    let image_data = std::fs::read(&resized_path).map_err(|e| e.to_string())?;
-
-    // Clean up the temporary files
-    let _ = std::fs::remove_file(&raw_screenshot_path);
-    if resized_path != raw_screenshot_path.to_string_lossy().to_string() {
-        let _ = std::fs::remove_file(&resized_path);
-    }
-
+    let encoded = encode_screenshot(image)?;
     println!("Total screenshot process took: {:?}", start_time.elapsed());
-    Ok(BASE64.encode(&image_data))
+    Ok(encoded)
 }
 
+/// Captures the display the quick chat panel is on at full resolution, for the
+/// user to select a region of. Pass the capture's id and the selection to
+/// `crop_screenshot`.
 #[tauri::command]
-pub fn resize_image(file_path: String, target_size_bytes: u64) -> Result<String, String> {
-    use std::fs;
-    use std::path::Path;
-    use std::process::Command;
-    use std::time::Instant;
-
-    // Start timing the operation
-    let start_time = Instant::now();
-    println!("Starting image resize for: {}", file_path);
-
-    // Create temporary file paths
-    let input_path = Path::new(&file_path);
-    let file_stem = input_path.file_stem().ok_or("Invalid file path")?;
-
-    // Create temporary path for output
-    let temp_dir = std::env::temp_dir();
-    let output_path = temp_dir.join(format!("{}_resized.jpg", file_stem.to_string_lossy()));
-
-    // Check if file exists
-    if !input_path.exists() {
-        return Err(format!("File not found: {}", file_path));
-    }
-
-    // Get file size
-    let file_size = fs::metadata(&file_path).map_err(|e| e.to_string())?.len();
-    println!(
-        "Original file size: {} bytes ({:.2} MB)",
-        file_size,
-        file_size as f64 / 1_048_576.0
-    );
+pub async fn capture_display_for_region(
+    app_handle: AppHandle,
+) -> Result<crate::region_capture::DisplayCapture, String> {
+    use crate::region_capture::PendingCaptures;
 
-    // If file is already small enough, just return the original path
-    if file_size <= target_size_bytes {
-        println!("File already under target size, skipping compression");
-        return Ok(file_path);
-    }
-
-    // For very small size reductions, just use compression
-    if file_size < target_size_bytes * 2 {
-        // Use high quality for small reductions
-        let quality = "85%";
-
-        #[cfg(target_os = "macos")]
-        {
-            println!("Using compression only with quality: {}", quality);
-            let sips_output = Command::new("sips")
-                .arg("-s")
-                .arg("format")
-                .arg("jpeg")
-                .arg("-s")
-                .arg("formatOptions")
-                .arg(quality)
-                .arg(input_path.to_str().unwrap())
-                .arg("--out")
-                .arg(output_path.to_str().unwrap())
-                .output()
-                .map_err(|e| e.to_string())?;
-
-            if !sips_output.status.success() {
-                println!("Compression failed, using original image");
-                return Ok(file_path);
+    tauri::async_runtime::spawn_blocking(move || {
+        let cache_dir = app_handle
+            .path()
+            .app_cache_dir()
+            .map_err(|e| e.to_string())?;
+        let raw_screenshot_path = PendingCaptures::new_path(&cache_dir)?;
+        let capture = capture_display_to(&app_handle, &raw_screenshot_path)
+            .and_then(|scale_factor| {
+                crate::region_capture::prepare(&raw_screenshot_path, scale_factor)
+            });
+        match capture {
+            Ok(capture) => {
+                app_handle
+                    .state::<PendingCaptures>()
+                    .insert(&capture, raw_screenshot_path);
+                Ok(capture)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&raw_screenshot_path);
+                Err(e)
             }
         }
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
 
-        #[cfg(not(target_os = "macos"))]
-        {
-            use image::{io::Reader as ImageReader, ImageFormat};
-            println!("Using compression only with quality: {}", quality);
-
-            // Parse quality percentage
-            let quality_value = quality.trim_end_matches('%').parse::<u8>().unwrap_or(85);
-
-            // Read the image
-            let img = ImageReader::open(input_path)
-                .map_err(|e| e.to_string())?
-                .decode()
-                .map_err(|e| e.to_string())?;
-
-            // Save with compression
-            img.save_with_format(output_path.to_str().unwrap(), ImageFormat::Jpeg)
-                .map_err(|e| e.to_string())?;
-        }
-
-        let compressed_size = fs::metadata(&output_path).map_err(|e| e.to_string())?.len();
-        println!(
-            "Compressed size: {} bytes ({:.2} MB)",
-            compressed_size,
-            compressed_size as f64 / 1_048_576.0
-        );
-
-        if compressed_size <= target_size_bytes {
-            println!("Compression successful, under target size");
-            return Ok(output_path.to_string_lossy().to_string());
-        }
-
-        println!("Simple compression not sufficient, proceeding to resize");
-    }
-
-    // Simple resize strategy: calculate dimensions based on target size
-    // For JPEG: ~0.5 bytes per pixel at high quality is a reasonable estimate
-    // Typical scaling factor for JPEG compression at good quality
-    let bytes_per_pixel_estimation = 0.5;
-
-    // When we need to do both dimension reduction and compression
+/// Saves a redacted capture of the display the quick chat panel is on to
+/// `raw_screenshot_path`, returning its scale factor.
+fn capture_display_to(
+    app_handle: &AppHandle,
+    raw_screenshot_path: &std::path::Path,
+) -> Result<f64, String> {
     #[cfg(target_os = "macos")]
     {
-        // First, get the image dimensions
-        let sips_info = Command::new("sips")
-            .arg("-g")
-            .arg("pixelWidth")
-            .arg("-g")
-            .arg("pixelHeight")
-            .arg(input_path.to_str().unwrap())
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        let info_str = String::from_utf8_lossy(&sips_info.stdout);
-
-        // Parse dimensions from sips output
-        let width_line = info_str.lines().find(|line| line.contains("pixelWidth"));
-        let height_line = info_str.lines().find(|line| line.contains("pixelHeight"));
-
-        let parse_dimension = |line: Option<&str>| -> Result<u32, String> {
-            let value = line
-                .ok_or("Could not find dimension in sips output")?
-                .split(':')
-                .nth(1)
-                .ok_or("Invalid sips output format")?
-                .trim()
-                .parse::<u32>()
-                .map_err(|e| e.to_string())?;
-            Ok(value)
-        };
-
-        let original_width = parse_dimension(width_line)?;
-        let original_height = parse_dimension(height_line)?;
-
-        println!(
-            "Original dimensions: {}x{}",
-            original_width, original_height
-        );
-
-        // Calculate the area in pixels and estimate the size reduction needed
-        let original_pixels = original_width as f64 * original_height as f64;
-        let target_pixels = target_size_bytes as f64 / bytes_per_pixel_estimation;
-
-        // Calculate the scale factor - square root because we're scaling in 2D
-        let scale_factor = ((target_pixels / original_pixels) as f64).sqrt() * 0.9; // 10% safety margin
-
-        // Never go below 30% quality
-        let scale_factor = scale_factor.max(0.3);
-
-        // Calculate new dimensions
-        let new_width = (original_width as f64 * scale_factor).round() as u32;
-
-        println!(
-            "Using scale factor {:.2}, new width: {}",
-            scale_factor, new_width
-        );
-
-        // Resize and compress in a single step with high quality
-        let sips_output = Command::new("sips")
-            .arg("-s")
-            .arg("format")
-            .arg("jpeg")
-            .arg("-s")
-            .arg("formatOptions")
-            .arg("85%") // High quality
-            .arg("--resampleWidth")
-            .arg(new_width.to_string())
-            .arg(input_path.to_str().unwrap())
-            .arg("--out")
-            .arg(output_path.to_str().unwrap())
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        if !sips_output.status.success() {
-            println!("Resizing failed, using original image");
-            return Ok(file_path);
-        }
+        let (display, scale_factor) =
+            capture_spotlight_display(app_handle, raw_screenshot_path)?;
+        crate::redaction::mask_app_windows_in_file(
+            raw_screenshot_path,
+            display,
+            &RedactionSettings::load(app_handle),
+        )?;
+        Ok(scale_factor)
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        use image::{imageops::FilterType, io::Reader as ImageReader, ImageFormat};
-
-        // Read the image
-        let img = ImageReader::open(input_path)
-            .map_err(|e| e.to_string())?
-            .decode()
+        let screen = spotlight_screen(app_handle)?;
+        let mut image = screen.capture().map_err(|e| e.to_string())?;
+        crate::redaction::mask_app_windows(
+            &mut image,
+            display_bounds(&screen),
+            &RedactionSettings::load(app_handle),
+        )?;
+        image
+            .save(raw_screenshot_path)
             .map_err(|e| e.to_string())?;
+        Ok(screen.display_info.scale_factor as f64)
+    }
+}
 
-        // Get original dimensions
-        let original_width = img.width();
-        let original_height = img.height();
-
-        println!(
-            "Original dimensions: {}x{}",
-            original_width, original_height
-        );
-
-        // Calculate the area in pixels and estimate the size reduction needed
-        let original_pixels = original_width as f64 * original_height as f64;
-        let target_pixels = target_size_bytes as f64 / bytes_per_pixel_estimation;
-
-        // Calculate the scale factor - square root because we're scaling in 2D
-        let scale_factor = ((target_pixels / original_pixels) as f64).sqrt() * 0.9; // 10% safety margin
-
-        // Never go below 30% quality
-        let scale_factor = scale_factor.max(0.3);
-
-        // Calculate new dimensions
-        let new_width = (original_width as f64 * scale_factor).round() as u32;
-        let new_height = (original_height as f64 * scale_factor).round() as u32;
-
-        println!(
-            "Using scale factor {:.2}, new dimensions: {}x{}",
-            scale_factor, new_width, new_height
-        );
+/// Crops the capture with `capture_id` from `capture_display_for_region` to
+/// `selection` (in logical points), shrinks it with `resize_capture` and returns
+/// it as base64. The capture is deleted once it's been cropped.
+#[tauri::command]
+pub async fn crop_screenshot(
+    app_handle: AppHandle,
+    capture_id: String,
+    selection: crate::region_capture::Selection,
+    logical_resolution: Option<bool>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let pending = app_handle.state::<crate::region_capture::PendingCaptures>();
+        let (raw_screenshot_path, scale_factor) = pending
+            .get(&capture_id)
+            .ok_or("That screenshot is no longer available. Please take it again.")?;
+        let cropped_path = crate::region_capture::crop(
+            &raw_screenshot_path,
+            selection,
+            scale_factor,
+            logical_resolution.unwrap_or(false),
+        )?;
+        pending.remove(&capture_id);
+
+        let resized_path = resize_capture(
+            cropped_path.to_string_lossy().to_string(),
+            TARGET_SIZE_BYTES,
+        )?
+        .path;
+        let image_data = std::fs::read(&resized_path).map_err(|e| e.to_string())?;
+
+        // Clean up the temporary files
+        let _ = std::fs::remove_file(&cropped_path);
+        if resized_path != cropped_path.to_string_lossy().to_string() {
+            let _ = std::fs::remove_file(&resized_path);
+        }
+        Ok(BASE64.encode(&image_data))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
 
-        // Resize the image
-        let resized = img.resize(new_width, new_height, FilterType::Lanczos3);
+/// Blurs or blacks out `areas` (in pixels) of any image the webview is allowed to
+/// read (see `file_access::check_read`), returning the path of a redacted copy.
+/// Run it before `resize_image`.
+#[tauri::command]
+pub fn redact_image(
+    app_handle: AppHandle,
+    file_path: String,
+    areas: Vec<Rect>,
+    style: Option<RedactionStyle>,
+) -> Result<String, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    let output_dir = image_output_dir(&app_handle)?;
+    let output_path = crate::redaction::redact_file(
+        &file_path,
+        &areas,
+        style.unwrap_or_default(),
+        &output_dir,
+    )?;
+    Ok(output_path.to_string_lossy().into_owned())
+}
 
-        // Save with high quality compression
-        resized
-            .save_with_format(output_path.to_str().unwrap(), ImageFormat::Jpeg)
-            .map_err(|e| e.to_string())?;
-    }
+/// Shrinks an image the webview is allowed to read to fit under
+/// `target_size_bytes`, returning the path of the result (the input itself if it
+/// already fits) along with its dimensions and format.
+#[tauri::command]
+pub fn resize_image(
+    app_handle: AppHandle,
+    file_path: String,
+    target_size_bytes: u64,
+) -> Result<crate::image_pipeline::ResizedImage, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    let output_dir = image_output_dir(&app_handle)?;
+    crate::image_pipeline::resize_file(&file_path, &output_dir, target_size_bytes)
+}
 
-    // Check final size
-    let final_size = fs::metadata(&output_path).map_err(|e| e.to_string())?.len();
-    println!(
-        "Final size: {} bytes ({:.2} MB)",
-        final_size,
-        final_size as f64 / 1_048_576.0
-    );
+/// The shared folder for redact_image and resize_image output. Outputs older
+/// than an hour are removed first, so callers need to read or store them straight away.
+fn image_output_dir(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    let dir = crate::file_access::image_output_dir(app_handle).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    crate::image_pipeline::remove_expired_outputs(&dir);
+    Ok(dir)
+}
 
-    println!("Total image processing took: {:?}", start_time.elapsed());
-    Ok(output_path.to_string_lossy().to_string())
+/// Shrinks one of our own captures the same way as `resize_image`, leaving the
+/// result in the temp dir for the caller to clean up.
+fn resize_capture(
+    file_path: String,
+    target_size_bytes: u64,
+) -> Result<crate::image_pipeline::ResizedImage, String> {
+    crate::image_pipeline::resize_file(
+        std::path::Path::new(&file_path),
+        &std::env::temp_dir(),
+        target_size_bytes,
+    )
 }
 
 #[tauri::command]
@@ -729,44 +630,422 @@ pub fn get_instance_name() -> Result<String, String> {
     }
 }
 
+/// Writes `content`, or a copy of the file at `source_path`, to `path`. Only
+/// paths that pass `file_access::check_write` can be written, and the source
+/// has to pass `check_read`.
 #[tauri::command]
-pub async fn write_file_async(path: String, content: Option<Vec<u8>>, source_path: Option<String>) -> Result<(), String> {
+pub async fn write_file_async(
+    app_handle: AppHandle,
+    path: String,
+    content: Option<Vec<u8>>,
+    source_path: Option<String>,
+) -> Result<(), FileAccessError> {
     use std::path::Path;
-    
+
+    let destination = crate::file_access::check_write(&app_handle, Path::new(&path))?;
+    let source = source_path
+        .map(|source_path| crate::file_access::check_read(&app_handle, Path::new(&source_path)))
+        .transpose()?;
+
     // Use Tauri's async runtime to perform the write operation
     tauri::async_runtime::spawn_blocking(move || {
-        // Ensure parent directory exists
-        if let Some(parent) = Path::new(&path).parent() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
-        
-        if let Some(src_path) = source_path {
+        if let Some(source) = source {
             // Copy file from source path (avoids IPC for large files)
-            std::fs::copy(&src_path, &path).map_err(|e| e.to_string())?;
+            let mut reader = std::fs::File::open(&source)
+                .map_err(|e| FileAccessError::io("read", &source, e))?;
+            crate::file_access::write_atomic(&destination, |file| {
+                std::io::copy(&mut reader, file).map(|_| ())
+            })
         } else if let Some(data) = content {
             // Write content directly (backward compatibility)
-            std::fs::write(&path, data).map_err(|e| e.to_string())?;
+            crate::file_access::write_atomic(&destination, |file| {
+                std::io::Write::write_all(file, &data)
+            })
         } else {
-            return Err("Either content or source_path must be provided".to_string());
+            Err(FileAccessError::MissingContent)
+        }
+    })
+    .await
+    .map_err(|e| FileAccessError::io("write", Path::new(&path), e))?
+}
+
+/// Inspects a file the webview is allowed to read (see `file_access::check_read`):
+/// its type, hash and contents summary, and which enabled models can take it.
+/// `model_ids` limits the check to those models.
+#[tauri::command]
+pub async fn get_file_metadata(
+    app_handle: AppHandle,
+    path: String,
+    model_ids: Option<Vec<String>>,
+) -> Result<crate::file_inspection::FileInspection, FileAccessError> {
+    let path = crate::file_access::check_read(&app_handle, std::path::Path::new(&path))?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut inspection = crate::file_inspection::inspect(&path)
+            .map_err(|e| FileAccessError::io("inspect", &path, e))?;
+        // Without the database we can still say what the file is
+        match crate::db::open(&app_handle).and_then(|conn| {
+            crate::file_inspection::load_models(&conn, model_ids.as_deref())
+        }) {
+            Ok(models) => crate::file_inspection::check_models(&mut inspection, &models),
+            Err(e) => log::error!("Failed to load models for file inspection: {}", e),
+        }
+        Ok(inspection)
+    })
+    .await
+    .map_err(|e| FileAccessError::io("inspect", std::path::Path::new("file"), e))?
+}
+
+/// Asks the user where to save an export, and lets `write_file_async` write there.
+/// Returns None if the dialog was cancelled.
+#[tauri::command]
+pub async fn choose_export_destination(
+    app_handle: AppHandle,
+    default_name: Option<String>,
+) -> Result<Option<String>, FileAccessError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let mut dialog = app_handle.dialog().file();
+    if let Some(default_name) = default_name {
+        dialog = dialog.set_file_name(default_name);
+    }
+    let Some(path) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let path = path
+        .into_path()
+        .map_err(|e| FileAccessError::io("save", std::path::Path::new("export"), e))?;
+    crate::file_access::approve_export_destination(&path)?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+#[tauri::command]
+pub async fn import_claude_conversations(
+    app_handle: AppHandle,
+    file_path: String,
+    project_id: Option<String>,
+) -> Result<crate::importers::ImportSummary, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = crate::db::open(&app_handle)?;
+        crate::importers::claude::import_conversations(
+            &mut conn,
+            &file_path,
+            project_id.as_deref(),
+            |progress| {
+                let _ = app_handle.emit("import_progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn import_openai_conversations(
+    app_handle: AppHandle,
+    file_path: String,
+    project_id: Option<String>,
+) -> Result<crate::importers::ImportSummary, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = crate::db::open(&app_handle)?;
+        crate::importers::openai::import_conversations(
+            &mut conn,
+            &file_path,
+            project_id.as_deref(),
+            |progress| {
+                let _ = app_handle.emit("import_progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Exports the workspace to `file_path`, which has to come from
+/// `choose_export_destination`.
+#[tauri::command]
+pub async fn export_workspace(
+    app_handle: AppHandle,
+    file_path: String,
+) -> Result<crate::archive::ArchiveManifest, String> {
+    let file_path = crate::file_access::check_export(std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = crate::db::open(&app_handle)?;
+        let app_version = app_handle.package_info().version.to_string();
+        crate::archive::export_workspace(&mut conn, &file_path, &app_version)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub fn read_workspace_archive(
+    app_handle: AppHandle,
+    file_path: String,
+) -> Result<crate::archive::ArchiveManifest, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    crate::archive::read_manifest(&file_path)
+}
+
+#[tauri::command]
+pub async fn import_workspace(
+    app_handle: AppHandle,
+    file_path: String,
+) -> Result<crate::archive::WorkspaceImportSummary, String> {
+    let file_path = crate::file_access::check_read(&app_handle, std::path::Path::new(&file_path))
+        .map_err(|e| e.to_string())?;
+    let summary = tauri::async_runtime::spawn_blocking({
+        let app_handle = app_handle.clone();
+        move || {
+            let mut conn = crate::db::open(&app_handle)?;
+            crate::archive::import_workspace(
+                &mut conn,
+                &file_path,
+                &uploads_dir(&app_handle)?,
+            )
         }
-        
-        Ok::<(), String>(())
     })
     .await
     .map_err(|e| format!("Task join error: {}", e))??;
-    
-    Ok(())
+
+    app_handle.emit("refresh_projects_state", ()).unwrap();
+    Ok(summary)
+}
+
+/// Attachment files live here, in the attachment store (and, for older files, in `originals`).
+fn uploads_dir(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    Ok(app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("uploads"))
 }
 
+/// Adds a file to the attachment store, from `source_path` or `content`, and returns
+/// where it ended up. Identical files are only stored once. `source_path` has to
+/// pass `file_access::check_read`.
 #[tauri::command]
-pub fn get_file_metadata(path: String) -> Result<serde_json::Value, String> {
-    use std::fs;
-    
-    let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
-    
-    Ok(serde_json::json!({
-        "size": metadata.len(),
-        "isFile": metadata.is_file(),
-        "isDirectory": metadata.is_dir()
-    }))
+pub async fn store_attachment(
+    app_handle: AppHandle,
+    content: Option<Vec<u8>>,
+    source_path: Option<String>,
+    extension: Option<String>,
+) -> Result<crate::attachment_store::StoredBlob, String> {
+    let source_path = source_path
+        .map(|source_path| {
+            crate::file_access::check_read(&app_handle, std::path::Path::new(&source_path))
+        })
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let uploads_dir = uploads_dir(&app_handle)?;
+        if let Some(source_path) = source_path {
+            // Copy from source path (avoids IPC for large files)
+            crate::attachment_store::store_file(&uploads_dir, &source_path, extension.as_deref())
+        } else if let Some(content) = content {
+            crate::attachment_store::store_reader(
+                &uploads_dir,
+                content.as_slice(),
+                extension.as_deref(),
+            )
+        } else {
+            Err("Either content or source_path must be provided".to_string())
+        }
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// A folder bundle once it's in the attachment store.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredFolder {
+    /// What to put in attachments.path.
+    stored_path: String,
+    #[serde(flatten)]
+    bundle: crate::folder_bundle::FolderBundle,
+}
+
+/// Bundles a folder the user dropped or picked into one text file in the
+/// attachment store, for an attachment of type "folder". `max_tokens` caps the
+/// file contents included, and defaults to `folder_bundle::DEFAULT_MAX_TOKENS`.
+#[tauri::command]
+pub async fn bundle_folder(
+    app_handle: AppHandle,
+    path: String,
+    max_tokens: Option<usize>,
+) -> Result<StoredFolder, FileAccessError> {
+    let path = crate::file_access::check_read(&app_handle, std::path::Path::new(&path))?;
+    let max_tokens = max_tokens
+        .unwrap_or(crate::folder_bundle::DEFAULT_MAX_TOKENS)
+        .min(crate::folder_bundle::MAX_TOKENS_LIMIT);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let bundle = crate::folder_bundle::bundle_folder(&path, max_tokens)
+            .map_err(|e| FileAccessError::io("bundle", &path, e))?;
+        let blob = uploads_dir(&app_handle)
+            .and_then(|uploads_dir| {
+                crate::attachment_store::store_reader(
+                    &uploads_dir,
+                    bundle.text.as_bytes(),
+                    Some("txt"),
+                )
+            })
+            .map_err(|e| FileAccessError::io("store", &path, e))?;
+        log::info!(
+            "Bundled {} files from {} (~{} tokens)",
+            bundle.file_count,
+            path.display(),
+            bundle.estimated_tokens
+        );
+        Ok(StoredFolder {
+            stored_path: blob.path,
+            bundle,
+        })
+    })
+    .await
+    .map_err(|e| FileAccessError::io("bundle", std::path::Path::new("folder"), e))?
+}
+
+/// Text of a PDF or office document attachment, for models that can't read the
+/// file itself. Extracted once per attachment and cached in the database.
+#[tauri::command]
+pub async fn extract_attachment_text(
+    app_handle: AppHandle,
+    attachment_id: String,
+) -> Result<crate::document_text::ExtractedText, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = crate::db::open(&app_handle)?;
+        crate::document_text::extract_attachment(&conn, &attachment_id)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Keeps a project attachment up to date with the file or folder at `source_path`,
+/// which it was copied from (see attachment_watcher.rs).
+#[tauri::command]
+pub async fn watch_attachment_source(
+    app_handle: AppHandle,
+    attachment_id: String,
+    source_path: String,
+) -> Result<(), FileAccessError> {
+    let source_path =
+        crate::file_access::check_read(&app_handle, std::path::Path::new(&source_path))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::attachment_watcher::record_source(&app_handle, &attachment_id, &source_path)
+            .map_err(|e| FileAccessError::io("watch", &source_path, e))
+    })
+    .await
+    .map_err(|e| FileAccessError::io("watch", std::path::Path::new("source"), e))?
+}
+
+/// Stops watching sources of attachments that were removed from their projects.
+#[tauri::command]
+pub async fn sync_attachment_watches(app_handle: AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || crate::attachment_watcher::sync(&app_handle))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn collect_attachment_garbage(
+    app_handle: AppHandle,
+    dry_run: bool,
+) -> Result<crate::attachment_store::GcReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = crate::db::open(&app_handle)?;
+        crate::attachment_store::collect_garbage(&mut conn, &uploads_dir(&app_handle)?, dry_run)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn search_messages(
+    app_handle: AppHandle,
+    query: String,
+    project_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<crate::search::SearchResult>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = crate::db::open(&app_handle)?;
+        crate::search::search_messages(&conn, &query, project_id.as_deref(), limit)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn check_database(
+    app_handle: AppHandle,
+    repair: bool,
+) -> Result<crate::invariants::InvariantReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut conn = crate::db::open(&app_handle)?;
+        crate::invariants::check_database(&mut conn, repair)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Stores a secret in the vault and returns the reference to save in its place.
+#[tauri::command]
+pub async fn put_secret(
+    app_handle: AppHandle,
+    name: String,
+    value: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::secrets::with_vault(&app_handle, |vault| vault.put(&name, &value))?;
+        Ok(crate::secrets::reference(&name))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_secret(app_handle: AppHandle, name: String) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::secrets::with_vault(&app_handle, |vault| {
+            Ok(vault.get(&name).map(|value| value.to_string()))
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Lists secret names (never values), optionally only those under `prefix`.
+#[tauri::command]
+pub async fn list_secrets(
+    app_handle: AppHandle,
+    prefix: Option<String>,
+) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::secrets::with_vault(&app_handle, |vault| {
+            Ok(vault
+                .names()
+                .into_iter()
+                .filter(|name| name.starts_with(prefix.as_deref().unwrap_or("")))
+                .collect())
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+#[tauri::command]
+pub async fn delete_secret(app_handle: AppHandle, name: String) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::secrets::with_vault(&app_handle, |vault| vault.delete(&name))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
 }
@@ -0,0 +1,74 @@
+use rusqlite::Connection;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::migrations;
+use crate::migrator::{self, MigrationReport, RunOptions};
+
+/// File name of the main database. Must match the `sqlite:chats.db` preload in tauri.conf.json.
+pub const DB_FILE_NAME: &str = "chats.db";
+
+/// Resolves the on-disk location of chats.db.
+///
+/// tauri_plugin_sql resolves `sqlite:` URLs relative to the app config dir,
+/// so we do the same here to end up at the same file the frontend uses.
+pub fn db_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| e.to_string())?;
+    Ok(config_dir.join(DB_FILE_NAME))
+}
+
+/// Pre-migration backups of chats.db go in a `backups` folder next to it.
+pub fn backup_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let path = db_path(app_handle)?;
+    path.parent()
+        .map(|dir| dir.join("backups"))
+        .ok_or_else(|| "Database path has no parent directory".to_string())
+}
+
+/// Opens a direct connection to chats.db for work that is too heavy to do
+/// through the SQL plugin from the frontend (imports, exports, search).
+///
+/// The plugin keeps its own pool open on the same file, so we set a busy
+/// timeout rather than failing immediately when the frontend holds a lock.
+pub fn open(app_handle: &AppHandle) -> Result<Connection, String> {
+    let path = db_path(app_handle)?;
+    if !path.exists() {
+        return Err(format!("Database not found at {}", path.display()));
+    }
+
+    let conn = Connection::open(&path).map_err(|e| e.to_string())?;
+    conn.busy_timeout(Duration::from_secs(10))
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+/// Brings chats.db up to date with migrations.rs. This has to run before the SQL
+/// plugin opens the database, so it's called from a plugin registered ahead of it.
+pub fn migrate(app_handle: &AppHandle) -> Result<MigrationReport, String> {
+    let path = db_path(app_handle)?;
+    let backup_dir = backup_dir(app_handle)?;
+
+    let report = migrator::migrate_up(
+        &path,
+        &migrations::migrations(),
+        None,
+        &RunOptions {
+            backup_dir: Some(backup_dir),
+            dry_run: false,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    if !report.versions.is_empty() {
+        log::info!(
+            "Migrated database from version {:?} to {:?}",
+            report.from_version,
+            report.to_version
+        );
+    }
+    Ok(report)
+}
@@ -0,0 +1,215 @@
+//! Working out which display something is on. Displays can be arranged in any
+//! way: secondary displays left of or above the main one have negative origins.
+//!
+//! Everything here is plain geometry, so it works the same for whatever
+//! coordinate space the caller uses, as long as windows and displays agree. The
+//! displays from `screenshots::Screen::all` are in points on macOS and Linux and
+//! in pixels on Windows.
+
+use screenshots::Screen;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).min(other.y + other.height as i32);
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(Rect {
+            x: left,
+            y: top,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Twice the center, to stay in integers.
+    fn center2(&self) -> (i64, i64) {
+        (
+            2 * self.x as i64 + self.width as i64,
+            2 * self.y as i64 + self.height as i64,
+        )
+    }
+
+    /// Squared distance from a point (in doubled coordinates, like `center2`) to
+    /// the nearest point of this rect. Zero if the point is inside.
+    fn distance2_squared(&self, (x, y): (i64, i64)) -> i64 {
+        let left = 2 * self.x as i64;
+        let top = 2 * self.y as i64;
+        let right = left + 2 * self.width as i64;
+        let bottom = top + 2 * self.height as i64;
+        let dx = (left - x).max(x - right).max(0);
+        let dy = (top - y).max(y - bottom).max(0);
+        dx * dx + dy * dy
+    }
+}
+
+/// The bounds of a display from `Screen::all`.
+pub fn display_bounds(screen: &Screen) -> Rect {
+    Rect {
+        x: screen.display_info.x,
+        y: screen.display_info.y,
+        width: screen.display_info.width,
+        height: screen.display_info.height,
+    }
+}
+
+/// Picks the display a window belongs on: the one showing the largest part of
+/// it, or if it's entirely off screen, the one closest to its center. Ties go to
+/// the display that comes first. None only if there are no displays.
+pub fn display_for_window(window: Rect, displays: &[Rect]) -> Option<usize> {
+    let overlapping = displays
+        .iter()
+        .enumerate()
+        .filter_map(|(index, display)| {
+            window
+                .intersect(display)
+                .map(|visible| (index, visible.area()))
+        })
+        .fold(
+            None,
+            |best: Option<(usize, u64)>, (index, area)| match best {
+                Some((_, best_area)) if best_area >= area => best,
+                _ => Some((index, area)),
+            },
+        );
+    if let Some((index, _)) = overlapping {
+        return Some(index);
+    }
+
+    let center = window.center2();
+    displays
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, display)| display.distance2_squared(center))
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A 1920x1080 main display, a 1280x1024 one to its left and a 2560x1440 one
+    /// above it, shifted right.
+    fn displays() -> [Rect; 3] {
+        [
+            rect(0, 0, 1920, 1080),
+            rect(-1280, 56, 1280, 1024),
+            rect(400, -1440, 2560, 1440),
+        ]
+    }
+
+    #[test]
+    fn intersects_rects_with_negative_origins() {
+        assert_eq!(
+            rect(-300, -200, 400, 300).intersect(&rect(-1280, -1024, 1280, 1024)),
+            Some(rect(-300, -200, 300, 200))
+        );
+        assert_eq!(
+            rect(-50, -50, 100, 100).intersect(&rect(0, 0, 1920, 1080)),
+            Some(rect(0, 0, 50, 50))
+        );
+    }
+
+    #[test]
+    fn rects_that_only_touch_dont_intersect() {
+        let main = rect(0, 0, 1920, 1080);
+        assert_eq!(rect(-100, 0, 100, 100).intersect(&main), None);
+        assert_eq!(rect(0, 1080, 100, 100).intersect(&main), None);
+        assert_eq!(rect(-500, -500, 10, 10).intersect(&main), None);
+    }
+
+    #[test]
+    fn picks_the_display_containing_the_window() {
+        let displays = displays();
+        assert_eq!(
+            display_for_window(rect(100, 100, 800, 600), &displays),
+            Some(0)
+        );
+        assert_eq!(
+            display_for_window(rect(-1000, 200, 600, 400), &displays),
+            Some(1)
+        );
+        assert_eq!(
+            display_for_window(rect(600, -1000, 800, 600), &displays),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn picks_the_display_showing_most_of_a_window_spanning_two() {
+        let displays = displays();
+        // 200px on the left display, 400px on the main one
+        assert_eq!(
+            display_for_window(rect(-200, 300, 600, 400), &displays),
+            Some(0)
+        );
+        // 400px on the left display, 200px on the main one
+        assert_eq!(
+            display_for_window(rect(-400, 300, 600, 400), &displays),
+            Some(1)
+        );
+        // Mostly above the main display
+        assert_eq!(
+            display_for_window(rect(800, -300, 600, 400), &displays),
+            Some(2)
+        );
+        // A tie goes to the display listed first
+        assert_eq!(
+            display_for_window(rect(-300, 300, 600, 400), &displays),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn picks_the_nearest_display_for_a_window_off_screen() {
+        let displays = displays();
+        assert_eq!(
+            display_for_window(rect(-3000, 300, 400, 300), &displays),
+            Some(1)
+        );
+        assert_eq!(
+            display_for_window(rect(800, -3000, 400, 300), &displays),
+            Some(2)
+        );
+        assert_eq!(
+            display_for_window(rect(500, 2000, 400, 300), &displays),
+            Some(0)
+        );
+        // Below the left display and left of the main one: the left display is
+        // closer to its center
+        assert_eq!(
+            display_for_window(rect(-600, 1200, 400, 300), &displays),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn has_no_display_without_displays() {
+        assert_eq!(display_for_window(rect(0, 0, 100, 100), &[]), None);
+    }
+}
@@ -0,0 +1,840 @@
+//! Text extraction for PDFs and Office documents, so they can be sent to models
+//! that can't read the files themselves (those without `pdf` in
+//! `models.supported_attachment_types`, and every model for DOCX, XLSX and PPTX).
+//!
+//! Text is split into sections, one per PDF page, DOCX page, spreadsheet or
+//! slide, each starting with a marker like `--- Page 3 ---` so the model can
+//! cite where something came from. Results are cached in
+//! `attachment_text_extractions` by attachment ID; attachment files never change
+//! once stored, so the cache is only cleared when the attachment is deleted.
+//!
+//! Office files are zipped XML. We only need the text out of them, so they're
+//! read with a small tag scanner rather than a full XML parser.
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Larger zip entries are treated as corrupt, so a zip bomb can't exhaust memory.
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Pdf,
+    Docx,
+    Xlsx,
+    Pptx,
+}
+
+impl DocumentFormat {
+    /// Sniffs the format from the file's contents.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if infer::get_from_path(path).ok()??.mime_type() == "application/pdf" {
+            return Some(DocumentFormat::Pdf);
+        }
+        // infer only guesses Office formats from the order of the first few zip
+        // entries, which varies between apps, so look for the main part instead
+        let zip = open_zip(path).ok()?;
+        let names: Vec<&str> = zip.file_names().collect();
+        [
+            (DocumentFormat::Docx, "word/document.xml"),
+            (DocumentFormat::Xlsx, "xl/workbook.xml"),
+            (DocumentFormat::Pptx, "ppt/presentation.xml"),
+        ]
+        .into_iter()
+        .find(|(_, part)| names.contains(part))
+        .map(|(format, _)| format)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DocumentFormat::Pdf => "pdf",
+            DocumentFormat::Docx => "docx",
+            DocumentFormat::Xlsx => "xlsx",
+            DocumentFormat::Pptx => "pptx",
+        }
+    }
+
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "pdf" => Some(DocumentFormat::Pdf),
+            "docx" => Some(DocumentFormat::Docx),
+            "xlsx" => Some(DocumentFormat::Xlsx),
+            "pptx" => Some(DocumentFormat::Pptx),
+            _ => None,
+        }
+    }
+}
+
+/// A page, sheet or slide.
+#[derive(Clone, Debug)]
+pub struct Section {
+    /// Like "Page 3" or "Sheet: Budget".
+    pub label: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedText {
+    pub format: DocumentFormat,
+    /// All sections, each after its marker.
+    pub text: String,
+    pub section_count: usize,
+    /// True if this came from the cache.
+    pub cached: bool,
+}
+
+/// Extracts the text of the document at `path`, by section.
+pub fn extract(path: &Path) -> Result<(DocumentFormat, Vec<Section>), String> {
+    let format = DocumentFormat::detect(path)
+        .ok_or_else(|| "Not a PDF, DOCX, XLSX or PPTX file".to_string())?;
+    let sections = match format {
+        DocumentFormat::Pdf => extract_pdf(path)?,
+        DocumentFormat::Docx => extract_docx(&mut open_zip(path)?)?,
+        DocumentFormat::Xlsx => extract_xlsx(&mut open_zip(path)?)?,
+        DocumentFormat::Pptx => extract_pptx(&mut open_zip(path)?)?,
+    };
+    if sections
+        .iter()
+        .all(|section| section.text.trim().is_empty())
+    {
+        // Most often a PDF of scanned pages
+        return Err("The document has no text to extract".to_string());
+    }
+    Ok((format, sections))
+}
+
+/// Joins sections into the text sent to the model.
+pub fn render(sections: &[Section]) -> String {
+    sections
+        .iter()
+        .map(|section| format!("--- {} ---\n{}", section.label, section.text.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Extracted text of an attachment, from the cache if it's been extracted before.
+pub fn extract_attachment(conn: &Connection, attachment_id: &str) -> Result<ExtractedText, String> {
+    let cached = conn
+        .query_row(
+            "SELECT format, text, section_count FROM attachment_text_extractions
+            WHERE attachment_id = ?1",
+            [attachment_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, usize>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some((format, text, section_count)) = cached {
+        if let Some(format) = DocumentFormat::parse(&format) {
+            return Ok(ExtractedText {
+                format,
+                text,
+                section_count,
+                cached: true,
+            });
+        }
+    }
+
+    let path: String = conn
+        .query_row(
+            "SELECT path FROM attachments WHERE id = ?1",
+            [attachment_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Attachment {} not found", attachment_id))?;
+
+    let (format, sections) = extract(Path::new(&path))?;
+    let text = render(&sections);
+    conn.execute(
+        "INSERT OR REPLACE INTO attachment_text_extractions
+            (attachment_id, format, text, section_count)
+        VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![attachment_id, format.as_str(), text, sections.len()],
+    )
+    .map_err(|e| e.to_string())?;
+    log::info!(
+        "Extracted {} sections of text from attachment {}",
+        sections.len(),
+        attachment_id
+    );
+
+    Ok(ExtractedText {
+        format,
+        text,
+        section_count: sections.len(),
+        cached: false,
+    })
+}
+
+fn extract_pdf(path: &Path) -> Result<Vec<Section>, String> {
+    let path = path.to_path_buf();
+    // pdf-extract panics on some malformed fonts and encodings rather than
+    // returning an error
+    let pages = std::panic::catch_unwind(move || pdf_extract::extract_text_by_pages(&path))
+        .map_err(|_| "Failed to read the PDF".to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(pages
+        .into_iter()
+        .enumerate()
+        .map(|(index, text)| Section {
+            label: format!("Page {}", index + 1),
+            text,
+        })
+        .collect())
+}
+
+type Zip = ZipArchive<BufReader<File>>;
+
+fn open_zip(path: &Path) -> Result<Zip, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())
+}
+
+/// The entry `name` as a string, or None if there isn't one.
+fn read_entry(zip: &mut Zip, name: &str) -> Result<Option<String>, String> {
+    let entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    if entry.size() > MAX_ENTRY_BYTES {
+        return Err(format!("{} is too large", name));
+    }
+    let mut xml = String::new();
+    entry
+        .take(MAX_ENTRY_BYTES)
+        .read_to_string(&mut xml)
+        .map_err(|e| e.to_string())?;
+    Ok(Some(xml))
+}
+
+/// Targets of the relationships in a `.rels` entry, by ID, resolved against
+/// `base_dir` (the folder of the part the relationships belong to).
+fn relationships(
+    zip: &mut Zip,
+    rels_name: &str,
+    base_dir: &str,
+) -> Result<HashMap<String, String>, String> {
+    let Some(xml) = read_entry(zip, rels_name)? else {
+        return Ok(HashMap::new());
+    };
+    let mut targets = HashMap::new();
+    for token in tokens(&xml) {
+        if let Token::Open {
+            name: "Relationship",
+            attrs,
+            ..
+        } = token
+        {
+            if let (Some(id), Some(target)) = (attr(attrs, "Id"), attr(attrs, "Target")) {
+                let target = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None => format!("{}/{}", base_dir, target),
+                };
+                targets.insert(id.to_string(), target);
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// Paragraphs become lines. Page breaks, whether explicit or where Word last
+/// laid out a new page, start a new section.
+fn extract_docx(zip: &mut Zip) -> Result<Vec<Section>, String> {
+    let xml = read_entry(zip, "word/document.xml")?
+        .ok_or_else(|| "The document has no word/document.xml".to_string())?;
+    let mut pages = vec![String::new()];
+    let mut in_text = false;
+    for token in tokens(&xml) {
+        let page = pages.last_mut().unwrap();
+        match token {
+            Token::Open {
+                name: "w:t",
+                self_closing,
+                ..
+            } => in_text = !self_closing,
+            Token::Close("w:t") => in_text = false,
+            Token::Text(text) if in_text => page.push_str(&unescape(text)),
+            Token::Open { name: "w:tab", .. } => page.push('\t'),
+            Token::Open {
+                name: "w:br",
+                attrs,
+                ..
+            } if attr(attrs, "w:type") == Some("page") => new_page(&mut pages),
+            Token::Open {
+                name: "w:lastRenderedPageBreak",
+                ..
+            } => new_page(&mut pages),
+            Token::Open {
+                name: "w:br" | "w:cr",
+                ..
+            } => page.push('\n'),
+            Token::Close("w:p") => page.push('\n'),
+            _ => {}
+        }
+    }
+    Ok(pages
+        .into_iter()
+        .enumerate()
+        .map(|(index, text)| Section {
+            label: format!("Page {}", index + 1),
+            text,
+        })
+        .collect())
+}
+
+/// Word marks where it last laid out a new page, and usually does so right after
+/// an explicit page break too, so breaks with nothing between them count once.
+fn new_page(pages: &mut Vec<String>) {
+    if pages.last().is_some_and(|page| !page.trim().is_empty()) {
+        pages.push(String::new());
+    }
+}
+
+/// Slides in presentation order, with their text one paragraph per line.
+fn extract_pptx(zip: &mut Zip) -> Result<Vec<Section>, String> {
+    let presentation = read_entry(zip, "ppt/presentation.xml")?
+        .ok_or_else(|| "The presentation has no ppt/presentation.xml".to_string())?;
+    let targets = relationships(zip, "ppt/_rels/presentation.xml.rels", "ppt")?;
+    let slide_names: Vec<String> = tokens(&presentation)
+        .filter_map(|token| match token {
+            Token::Open {
+                name: "p:sldId",
+                attrs,
+                ..
+            } => attr(attrs, "r:id"),
+            _ => None,
+        })
+        .filter_map(|id| targets.get(id).cloned())
+        .collect();
+
+    let mut sections = Vec::new();
+    for (index, slide_name) in slide_names.iter().enumerate() {
+        let Some(xml) = read_entry(zip, slide_name)? else {
+            continue;
+        };
+        let mut text = String::new();
+        let mut in_text = false;
+        for token in tokens(&xml) {
+            match token {
+                Token::Open {
+                    name: "a:t",
+                    self_closing,
+                    ..
+                } => in_text = !self_closing,
+                Token::Close("a:t") => in_text = false,
+                Token::Text(run) if in_text => text.push_str(&unescape(run)),
+                Token::Open { name: "a:br", .. } | Token::Close("a:p") => text.push('\n'),
+                _ => {}
+            }
+        }
+        sections.push(Section {
+            label: format!("Slide {}", index + 1),
+            text,
+        });
+    }
+    Ok(sections)
+}
+
+/// Sheets in workbook order, one row per line with cells separated by tabs.
+/// Cells show their stored values, so formulas show their last result and dates
+/// show as serial numbers.
+fn extract_xlsx(zip: &mut Zip) -> Result<Vec<Section>, String> {
+    let workbook = read_entry(zip, "xl/workbook.xml")?
+        .ok_or_else(|| "The workbook has no xl/workbook.xml".to_string())?;
+    let targets = relationships(zip, "xl/_rels/workbook.xml.rels", "xl")?;
+    let shared_strings = match read_entry(zip, "xl/sharedStrings.xml")? {
+        Some(xml) => shared_strings(&xml),
+        None => Vec::new(),
+    };
+    let sheets: Vec<(String, String)> = tokens(&workbook)
+        .filter_map(|token| match token {
+            Token::Open {
+                name: "sheet",
+                attrs,
+                ..
+            } => {
+                let target = targets.get(attr(attrs, "r:id")?)?;
+                Some((unescape(attr(attrs, "name")?), target.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut sections = Vec::new();
+    for (sheet_name, entry_name) in sheets {
+        let Some(xml) = read_entry(zip, &entry_name)? else {
+            continue;
+        };
+        sections.push(Section {
+            label: format!("Sheet: {}", sheet_name),
+            text: sheet_text(&xml, &shared_strings),
+        });
+    }
+    Ok(sections)
+}
+
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = None;
+    let mut in_text = false;
+    // Phonetic guides repeat the text in another script
+    let mut in_phonetic = false;
+    for token in tokens(xml) {
+        match token {
+            Token::Open {
+                name: "si",
+                self_closing,
+                ..
+            } => {
+                if self_closing {
+                    strings.push(String::new());
+                } else {
+                    current = Some(String::new());
+                }
+            }
+            Token::Close("si") => strings.extend(current.take()),
+            Token::Open { name: "rPh", .. } => in_phonetic = true,
+            Token::Close("rPh") => in_phonetic = false,
+            Token::Open {
+                name: "t",
+                self_closing,
+                ..
+            } => in_text = !self_closing,
+            Token::Close("t") => in_text = false,
+            Token::Text(text) if in_text && !in_phonetic => {
+                if let Some(current) = current.as_mut() {
+                    current.push_str(&unescape(text));
+                }
+            }
+            _ => {}
+        }
+    }
+    strings
+}
+
+fn sheet_text(xml: &str, shared_strings: &[String]) -> String {
+    let mut lines = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut cell: Option<(usize, Option<&str>)> = None;
+    let mut value = String::new();
+    let mut in_value = false;
+    for token in tokens(xml) {
+        match token {
+            Token::Open { name: "row", .. } => row.clear(),
+            Token::Close("row") if row.iter().any(|value| !value.is_empty()) => {
+                lines.push(row.join("\t"));
+            }
+            Token::Open {
+                name: "c",
+                attrs,
+                self_closing,
+            } => {
+                let column = attr(attrs, "r").and_then(column_index).unwrap_or(row.len());
+                cell = (!self_closing).then_some((column, attr(attrs, "t")));
+                value.clear();
+            }
+            Token::Close("c") => {
+                if let Some((column, cell_type)) = cell.take() {
+                    let text = match cell_type {
+                        Some("s") => value
+                            .trim()
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|index| shared_strings.get(index))
+                            .cloned()
+                            .unwrap_or_default(),
+                        Some("b") => {
+                            (if value.trim() == "1" { "TRUE" } else { "FALSE" }).to_string()
+                        }
+                        _ => value.clone(),
+                    };
+                    if row.len() <= column {
+                        row.resize(column + 1, String::new());
+                    }
+                    // Tabs and newlines in a cell would break the layout
+                    row[column] = text.replace(['\t', '\n', '\r'], " ");
+                }
+            }
+            Token::Open {
+                name: "v" | "t",
+                self_closing: false,
+                ..
+            } if cell.is_some() => in_value = true,
+            Token::Close("v" | "t") => in_value = false,
+            Token::Text(text) if in_value => value.push_str(&unescape(text)),
+            _ => {}
+        }
+    }
+    lines.join("\n")
+}
+
+/// Zero-based column of a cell reference like "AB12".
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference
+        .bytes()
+        .take_while(|byte| byte.is_ascii_alphabetic())
+        .collect();
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+    let column = letters.iter().fold(0, |column, letter| {
+        column * 26 + (letter.to_ascii_uppercase() - b'A') as usize + 1
+    });
+    Some(column - 1)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Open {
+        name: &'a str,
+        attrs: &'a str,
+        self_closing: bool,
+    },
+    Close(&'a str),
+    /// Still escaped.
+    Text(&'a str),
+}
+
+/// Tags and text of an XML document. Comments, processing instructions and
+/// doctypes are skipped; CDATA sections aren't used by Office and come through
+/// as text.
+fn tokens(xml: &str) -> impl Iterator<Item = Token<'_>> {
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        if rest.is_empty() {
+            return None;
+        }
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let (text, after) = rest.split_at(end);
+            rest = after;
+            return Some(Token::Text(text));
+        }
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let end = rest.find('>')?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            return Some(Token::Close(name.trim()));
+        }
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(tag.len());
+        return Some(Token::Open {
+            name: &tag[..name_end],
+            attrs: &tag[name_end..],
+            self_closing,
+        });
+    })
+}
+
+/// The value of attribute `name`, still escaped.
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    loop {
+        let start = rest.find(name)?;
+        let preceded_by_space = rest[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_ascii_whitespace());
+        let after = rest[start + name.len()..].trim_start();
+        rest = &rest[start + name.len()..];
+        let Some(after) = after.strip_prefix('=') else {
+            continue;
+        };
+        if !preceded_by_space {
+            continue;
+        }
+        let after = after.trim_start();
+        let quote = after.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value = &after[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        /// A zip file made of `entries`.
+        fn zip(extension: &str, entries: &[(&str, &str)]) -> TempFile {
+            let path = std::env::temp_dir().join(format!(
+                "camp-document-text-{}.{}",
+                uuid::Uuid::new_v4(),
+                extension
+            ));
+            let mut zip = ZipWriter::new(File::create(&path).unwrap());
+            for (name, contents) in entries {
+                zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn labels_and_text(sections: &[Section]) -> Vec<(&str, &str)> {
+        sections
+            .iter()
+            .map(|section| (section.label.as_str(), section.text.trim()))
+            .collect()
+    }
+
+    #[test]
+    fn unescapes_entities_and_character_references() {
+        assert_eq!(
+            unescape("&lt;b&gt; &amp;amp; &quot;x&quot; &apos;y&apos;"),
+            "<b> &amp; \"x\" 'y'"
+        );
+        assert_eq!(unescape("&#65;&#x42;&#X43;&#x1F600;"), "ABC\u{1F600}");
+        // Left alone: unknown entities, invalid code points and stray ampersands
+        assert_eq!(
+            unescape("&nbsp; &#xD800; &#xZZ; a & b"),
+            "&nbsp; &#xD800; &#xZZ; a & b"
+        );
+        assert_eq!(unescape("R&D; &amp"), "R&D; &amp");
+    }
+
+    #[test]
+    fn finds_attributes_by_exact_name() {
+        let attrs = r#" TargetMode="External" Target="slides/slide1.xml" xId="x" Id='rId2'"#;
+        assert_eq!(attr(attrs, "Target"), Some("slides/slide1.xml"));
+        assert_eq!(attr(attrs, "TargetMode"), Some("External"));
+        assert_eq!(attr(attrs, "Id"), Some("rId2"));
+        assert_eq!(attr(attrs, "Type"), None);
+        assert_eq!(attr(r#" r:id = "rId1""#, "r:id"), Some("rId1"));
+        assert_eq!(attr(r#" name="a &amp; b""#, "name"), Some("a &amp; b"));
+    }
+
+    #[test]
+    fn tokenizes_tags_and_text() {
+        let xml = r#"<?xml version="1.0"?><!-- note --><a x="1"><b/>t &amp; u</a ><!DOCTYPE c>"#;
+        assert_eq!(
+            tokens(xml).collect::<Vec<_>>(),
+            [
+                Token::Open {
+                    name: "a",
+                    attrs: r#" x="1""#,
+                    self_closing: false
+                },
+                Token::Open {
+                    name: "b",
+                    attrs: "",
+                    self_closing: true
+                },
+                Token::Text("t &amp; u"),
+                Token::Close("a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_columns_from_cell_references() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("Z9"), Some(25));
+        assert_eq!(column_index("AA1"), Some(26));
+        assert_eq!(column_index("AB12"), Some(27));
+        assert_eq!(column_index("ab12"), Some(27));
+        assert_eq!(column_index("XFD1048576"), Some(16383));
+        assert_eq!(column_index("12"), None);
+        assert_eq!(column_index("ABCD1"), None);
+    }
+
+    #[test]
+    fn reads_shared_strings_without_phonetic_guides() {
+        let xml = "<sst><si><t>Name</t></si><si><r><t>Hel</t></r><r><t>lo &amp; bye</t></r>\
+            <rPh><t>ハロー</t></rPh></si><si/></sst>";
+        assert_eq!(shared_strings(xml), ["Name", "Hello & bye", ""]);
+    }
+
+    #[test]
+    fn lays_out_sheet_cells_by_column() {
+        let shared = ["Name".to_string()];
+        let xml = r#"<worksheet><sheetData>
+            <row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="b"><v>1</v></c></row>
+            <row r="2"><c r="AB2"><v>3.5</v></c></row>
+            <row r="3"><c r="A3"/><c r="B3" t="s"><v>7</v></c></row>
+            <row r="4"><c r="B4" t="inlineStr"><is><t>tab&#9;and
+newline</t></is></c><c t="b"><v>0</v></c></row>
+        </sheetData></worksheet>"#;
+        assert_eq!(
+            sheet_text(xml, &shared),
+            format!(
+                "Name\t\tTRUE\n{}3.5\n\ttab and newline\tFALSE",
+                "\t".repeat(27)
+            )
+        );
+    }
+
+    #[test]
+    fn collapses_docx_page_breaks() {
+        let document = r#"<w:document><w:body>
+            <w:p><w:r><w:br w:type="page"/><w:t>One</w:t><w:tab/><w:t xml:space="preserve">a &lt; b</w:t></w:r></w:p>
+            <w:p><w:r><w:br w:type="page"/></w:r></w:p>
+            <w:p><w:r><w:lastRenderedPageBreak/><w:t>Two</w:t><w:br/><w:t>lines</w:t></w:r></w:p>
+            <w:p><w:r><w:lastRenderedPageBreak/><w:t>Three</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let file = TempFile::zip("docx", &[("word/document.xml", document)]);
+
+        let (format, sections) = extract(&file.0).unwrap();
+        assert_eq!(format, DocumentFormat::Docx);
+        assert_eq!(
+            labels_and_text(&sections),
+            [
+                ("Page 1", "One\ta < b"),
+                ("Page 2", "Two\nlines"),
+                ("Page 3", "Three")
+            ]
+        );
+    }
+
+    #[test]
+    fn orders_pptx_slides_by_the_presentation() {
+        let presentation = r#"<p:presentation><p:sldIdLst>
+            <p:sldId id="256" r:id="rId3"/><p:sldId id="257" r:id="rId2"/>
+        </p:sldIdLst></p:presentation>"#;
+        let rels = r#"<Relationships>
+            <Relationship Id="rId1" Type="slideMaster" Target="slideMasters/slideMaster1.xml"/>
+            <Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId3" Type="slide" Target="/ppt/slides/slide2.xml"/>
+        </Relationships>"#;
+        let slide = |text: &str| {
+            format!(
+                "<p:sld><a:p><a:r><a:t>{}</a:t></a:r><a:br/><a:r><a:t>more</a:t></a:r></a:p></p:sld>",
+                text
+            )
+        };
+        let (first, second) = (slide("First"), slide("Second"));
+        let file = TempFile::zip(
+            "pptx",
+            &[
+                ("ppt/presentation.xml", presentation),
+                ("ppt/_rels/presentation.xml.rels", rels),
+                ("ppt/slides/slide1.xml", &second),
+                ("ppt/slides/slide2.xml", &first),
+            ],
+        );
+
+        let (format, sections) = extract(&file.0).unwrap();
+        assert_eq!(format, DocumentFormat::Pptx);
+        assert_eq!(
+            labels_and_text(&sections),
+            [("Slide 1", "First\nmore"), ("Slide 2", "Second\nmore")]
+        );
+    }
+
+    #[test]
+    fn extracts_each_attachment_once() {
+        let document =
+            "<w:document><w:body><w:p><w:r><w:t>Hello</w:t></w:r></w:p></w:body></w:document>";
+        let file = TempFile::zip("docx", &[("word/document.xml", document)]);
+        let conn = migrator::open_in_memory();
+        conn.execute(
+            "INSERT INTO attachments (id, type, original_name, path) VALUES ('a1', 'pdf', 'a.docx', ?1)",
+            [file.0.to_string_lossy()],
+        )
+        .unwrap();
+
+        let extracted = extract_attachment(&conn, "a1").unwrap();
+        assert!(!extracted.cached);
+        assert_eq!(extracted.format, DocumentFormat::Docx);
+        assert_eq!(extracted.text, "--- Page 1 ---\nHello");
+        assert_eq!(extracted.section_count, 1);
+
+        // Served from the cache even once the file is gone
+        std::fs::remove_file(&file.0).unwrap();
+        let cached = extract_attachment(&conn, "a1").unwrap();
+        assert!(cached.cached);
+        assert_eq!(cached.format, DocumentFormat::Docx);
+        assert_eq!(cached.text, extracted.text);
+        assert_eq!(cached.section_count, 1);
+
+        assert_eq!(
+            extract_attachment(&conn, "missing").unwrap_err(),
+            "Attachment missing not found"
+        );
+    }
+}
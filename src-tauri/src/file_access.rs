@@ -0,0 +1,387 @@
+//! Which files the webview can have us write or inspect. Without this, a buggy
+//! or compromised frontend could write anywhere the user can.
+//!
+//! Writes may go to the uploads folder (which holds the attachment store) and to
+//! export destinations the user picked in `choose_export_destination`, but not
+//! elsewhere in app data, which holds the secret vault and its key file (and, on
+//! macOS, chats.db). Reads may also use images we processed for the webview (see
+//! `image_output_dir`) and files the user dropped on the window or picked in an
+//! open dialog: Tauri adds those to the asset protocol scope, which is otherwise
+//! limited to folders in app data.
+//!
+//! Paths must be absolute and can't contain `..`. They're checked both as given
+//! and with symlinks resolved, so a link inside an allowed folder can't be used
+//! to reach outside it.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+
+/// Destinations picked in `choose_export_destination` this session.
+static EXPORT_DESTINATIONS: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+#[derive(Debug, Error)]
+pub enum FileAccessError {
+    #[error("Invalid path {path}: {reason}")]
+    InvalidPath { path: String, reason: &'static str },
+    #[error("{path} is outside the folders Camp can access")]
+    OutsideAllowedRoots { path: String },
+    #[error("{path} links to {resolved}, outside the folders Camp can access")]
+    SymlinkEscape { path: String, resolved: String },
+    #[error("Either content or source_path must be provided")]
+    MissingContent,
+    #[error("Failed to {operation} {path}: {message}")]
+    Io {
+        operation: &'static str,
+        path: String,
+        message: String,
+    },
+}
+
+impl FileAccessError {
+    fn kind(&self) -> &'static str {
+        match self {
+            FileAccessError::InvalidPath { .. } => "invalidPath",
+            FileAccessError::OutsideAllowedRoots { .. } => "outsideAllowedRoots",
+            FileAccessError::SymlinkEscape { .. } => "symlinkEscape",
+            FileAccessError::MissingContent => "missingContent",
+            FileAccessError::Io { .. } => "io",
+        }
+    }
+
+    fn path(&self) -> Option<&str> {
+        match self {
+            FileAccessError::InvalidPath { path, .. }
+            | FileAccessError::OutsideAllowedRoots { path }
+            | FileAccessError::SymlinkEscape { path, .. }
+            | FileAccessError::Io { path, .. } => Some(path),
+            FileAccessError::MissingContent => None,
+        }
+    }
+
+    pub fn io(operation: &'static str, path: &Path, error: impl ToString) -> Self {
+        FileAccessError::Io {
+            operation,
+            path: path.display().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Reaches the frontend as `{ kind, message, path }`.
+impl Serialize for FileAccessError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("FileAccessError", 3)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("path", &self.path())?;
+        error.end()
+    }
+}
+
+/// Resolves symlinks in `path`, which doesn't have to exist yet: the deepest
+/// ancestor that exists is resolved and the rest appended.
+fn resolve(path: &Path) -> Result<PathBuf, FileAccessError> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match fs::canonicalize(existing) {
+            Ok(resolved) => {
+                return Ok(rest
+                    .iter()
+                    .rev()
+                    .fold(resolved, |resolved, name| resolved.join(name)))
+            }
+            Err(error) => match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name);
+                    existing = parent;
+                }
+                _ => return Err(FileAccessError::io("resolve", path, error)),
+            },
+        }
+    }
+}
+
+/// Checks that `path` is inside one of `roots` or approved by `is_approved`, and
+/// still is once symlinks are resolved. Returns the resolved path to use.
+pub fn check_path(
+    path: &Path,
+    roots: &[PathBuf],
+    is_approved: impl Fn(&Path) -> bool,
+) -> Result<PathBuf, FileAccessError> {
+    let invalid = |reason| FileAccessError::InvalidPath {
+        path: path.display().to_string(),
+        reason,
+    };
+    if !path.is_absolute() {
+        return Err(invalid("must be absolute"));
+    }
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(invalid("must not contain .."));
+    }
+    if path.file_name().is_none() {
+        return Err(invalid("must name a file"));
+    }
+
+    let resolved = resolve(path)?;
+    let resolved_roots: Vec<PathBuf> = roots
+        .iter()
+        .map(|root| resolve(root).unwrap_or_else(|_| root.clone()))
+        .collect();
+    let allowed = |path: &Path, roots: &[PathBuf]| {
+        is_approved(path) || roots.iter().any(|root| path.starts_with(root))
+    };
+
+    if allowed(&resolved, &resolved_roots) {
+        Ok(resolved)
+    } else if allowed(path, roots) {
+        Err(FileAccessError::SymlinkEscape {
+            path: path.display().to_string(),
+            resolved: resolved.display().to_string(),
+        })
+    } else {
+        Err(FileAccessError::OutsideAllowedRoots {
+            path: path.display().to_string(),
+        })
+    }
+}
+
+/// Writes `path` through a temporary file in the same folder, so it never holds
+/// a partial file, even if `write` fails or the app quits part way.
+pub fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut fs::File) -> std::io::Result<()>,
+) -> Result<(), FileAccessError> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| FileAccessError::io("write", path, "no parent folder"))?;
+    fs::create_dir_all(dir).map_err(|e| FileAccessError::io("create", dir, e))?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = dir.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    let result = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.flush()?;
+            file.sync_all()
+        })
+        .map_err(|e| FileAccessError::io("write", path, e))
+        .and_then(|()| {
+            fs::rename(&temp_path, path).map_err(|e| FileAccessError::io("replace", path, e))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn uploads_dir(app_handle: &AppHandle) -> Result<PathBuf, FileAccessError> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("uploads"))
+        .map_err(|e| FileAccessError::io("find", Path::new("app data"), e))
+}
+
+/// Where `redact_image` and `resize_image` put their output, so the webview can
+/// read it back (or pass it to the other one) without being able to write there.
+pub fn image_output_dir(app_handle: &AppHandle) -> Result<PathBuf, FileAccessError> {
+    app_handle
+        .path()
+        .app_cache_dir()
+        .map(|dir| dir.join("images"))
+        .map_err(|e| FileAccessError::io("find", Path::new("app cache"), e))
+}
+
+fn is_export_destination(path: &Path) -> bool {
+    EXPORT_DESTINATIONS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|destinations| destinations.contains(path))
+}
+
+/// Checks a path the webview wants to write to.
+pub fn check_write(app_handle: &AppHandle, path: &Path) -> Result<PathBuf, FileAccessError> {
+    check_path(path, &[uploads_dir(app_handle)?], is_export_destination)
+}
+
+/// Checks a path to export to, which unlike other writes has to be one the user
+/// picked in `choose_export_destination`.
+pub fn check_export(path: &Path) -> Result<PathBuf, FileAccessError> {
+    check_path(path, &[], is_export_destination)
+}
+
+/// Checks a path the webview wants to read or inspect.
+pub fn check_read(app_handle: &AppHandle, path: &Path) -> Result<PathBuf, FileAccessError> {
+    let scope = app_handle.asset_protocol_scope();
+    let roots = [uploads_dir(app_handle)?, image_output_dir(app_handle)?];
+    check_path(path, &roots, |path| {
+        scope.is_allowed(path) || is_export_destination(path)
+    })
+}
+
+/// Lets the webview write to `path` for the rest of the session. Only called
+/// with paths the user picked in a save dialog.
+pub fn approve_export_destination(path: &Path) -> Result<(), FileAccessError> {
+    let resolved = resolve(path)?;
+    let mut destinations = EXPORT_DESTINATIONS.lock().unwrap();
+    let destinations = destinations.get_or_insert_with(HashSet::new);
+    destinations.insert(path.to_path_buf());
+    destinations.insert(resolved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir =
+                std::env::temp_dir().join(format!("camp-file-access-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(dir.join("root")).unwrap();
+            fs::create_dir_all(dir.join("outside")).unwrap();
+            TempDir(dir)
+        }
+
+        fn root(&self) -> PathBuf {
+            self.0.join("root")
+        }
+
+        fn outside(&self) -> PathBuf {
+            self.0.join("outside")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn not_approved(_: &Path) -> bool {
+        false
+    }
+
+    #[test]
+    fn rejects_relative_paths_and_parent_components() {
+        let dir = TempDir::new();
+        let roots = [dir.root()];
+        for path in [
+            PathBuf::from("notes.txt"),
+            dir.root().join("../outside/notes.txt"),
+            dir.root().join("sub/../../outside/notes.txt"),
+        ] {
+            assert!(
+                matches!(
+                    check_path(&path, &roots, not_approved),
+                    Err(FileAccessError::InvalidPath { .. })
+                ),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn allows_files_that_dont_exist_yet_under_a_root() {
+        let dir = TempDir::new();
+        let path = dir.root().join("new/deeper/file.png");
+        let resolved = check_path(&path, &[dir.root()], not_approved).unwrap();
+        assert_eq!(
+            resolved,
+            fs::canonicalize(dir.root())
+                .unwrap()
+                .join("new/deeper/file.png")
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_every_root() {
+        let dir = TempDir::new();
+        let path = dir.outside().join("notes.txt");
+        let error = check_path(&path, &[dir.root()], not_approved).unwrap_err();
+        assert!(matches!(error, FileAccessError::OutsideAllowedRoots { .. }));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap()["kind"],
+            "outsideAllowedRoots"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_a_root() {
+        let dir = TempDir::new();
+        std::os::unix::fs::symlink(dir.outside(), dir.root().join("link")).unwrap();
+        for path in [dir.root().join("link"), dir.root().join("link/secret.txt")] {
+            assert!(
+                matches!(
+                    check_path(&path, &[dir.root()], not_approved),
+                    Err(FileAccessError::SymlinkEscape { .. })
+                ),
+                "{}",
+                path.display()
+            );
+        }
+
+        // A link to somewhere else inside the root is fine
+        fs::create_dir_all(dir.root().join("target")).unwrap();
+        std::os::unix::fs::symlink(dir.root().join("target"), dir.root().join("inner")).unwrap();
+        assert_eq!(
+            check_path(&dir.root().join("inner/a.txt"), &[dir.root()], not_approved).unwrap(),
+            fs::canonicalize(dir.root()).unwrap().join("target/a.txt")
+        );
+    }
+
+    #[test]
+    fn allows_approved_export_destinations() {
+        let dir = TempDir::new();
+        let destination = dir.outside().join("export.zip");
+        assert!(matches!(
+            check_export(&destination),
+            Err(FileAccessError::OutsideAllowedRoots { .. })
+        ));
+
+        approve_export_destination(&destination).unwrap();
+        assert_eq!(
+            check_export(&destination).unwrap(),
+            fs::canonicalize(dir.outside()).unwrap().join("export.zip")
+        );
+        // Only the file that was picked, not its folder
+        assert!(check_export(&dir.outside().join("other.zip")).is_err());
+    }
+
+    #[test]
+    fn failed_writes_leave_no_temp_file() {
+        let dir = TempDir::new();
+        let path = dir.root().join("nested/data.bin");
+        write_atomic(&path, |file| file.write_all(b"first")).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+
+        let error = write_atomic(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(std::io::Error::other("disk full"))
+        })
+        .unwrap_err();
+        assert!(matches!(error, FileAccessError::Io { .. }));
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        let files: Vec<_> = fs::read_dir(dir.root().join("nested"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["data.bin"]);
+    }
+}
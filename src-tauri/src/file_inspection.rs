@@ -0,0 +1,696 @@
+//! Looks inside a file before it's attached, so the frontend can reject or
+//! convert it without reading the whole thing itself: what kind of file it is,
+//! its hash, and whether the models the user has enabled can take it.
+//!
+//! Attachment types match `AttachmentType` in Models.ts. Which types a model
+//! takes comes from `models.supported_attachment_types`; the size limits are the
+//! strictest ones among the providers we support, since the table doesn't record
+//! per-model limits.
+
+use crate::document_text::DocumentFormat;
+use crate::pdf_pages;
+use rusqlite::Connection;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Same as MAX_FILE_SIZE in useAttachments.ts.
+pub const MAX_ATTACHMENT_BYTES: u64 = 30 * 1024 * 1024;
+/// PDFs over this are rejected by Anthropic, and are slow everywhere else.
+pub const MAX_PDF_PAGES: usize = 100;
+/// Images are downscaled before they're sent, but past this most providers
+/// refuse them outright.
+pub const MAX_IMAGE_DIMENSION: u32 = 8000;
+
+/// How much of the start of the file is used to sniff its type and encoding.
+const SNIFF_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentType {
+    Image,
+    Pdf,
+    Text,
+}
+
+impl AttachmentType {
+    fn as_str(self) -> &'static str {
+        match self {
+            AttachmentType::Image => "image",
+            AttachmentType::Pdf => "pdf",
+            AttachmentType::Text => "text",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfInfo {
+    pub page_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextInfo {
+    /// WHATWG name, like "UTF-8" or "windows-1252".
+    pub encoding: String,
+    pub has_bom: bool,
+    pub line_count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInspection {
+    pub size: u64,
+    pub is_file: bool,
+    pub is_directory: bool,
+    /// Sniffed from the contents, or from the extension for text files.
+    pub mime_type: Option<String>,
+    /// None for directories and for binary files we can't attach.
+    pub attachment_type: Option<AttachmentType>,
+    pub sha256: Option<String>,
+    pub image: Option<ImageInfo>,
+    pub pdf: Option<PdfInfo>,
+    pub text: Option<TextInfo>,
+    /// Filled in by `check_models`.
+    pub models: Vec<ModelFit>,
+}
+
+/// Why a model can't take a file as it is.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LimitIssue {
+    /// Not a file type any model takes (a directory or a binary file).
+    #[serde(rename_all = "camelCase")]
+    UnsupportedFile,
+    #[serde(rename_all = "camelCase")]
+    UnsupportedType { attachment_type: AttachmentType },
+    #[serde(rename_all = "camelCase")]
+    TooLarge { size: u64, max_size: u64 },
+    #[serde(rename_all = "camelCase")]
+    TooManyPages { page_count: usize, max_pages: usize },
+    #[serde(rename_all = "camelCase")]
+    ImageTooLarge {
+        width: u32,
+        height: u32,
+        max_dimension: u32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFit {
+    pub model_id: String,
+    pub display_name: String,
+    /// Empty if the model can take the file.
+    pub issues: Vec<LimitIssue>,
+}
+
+/// A row of the models table.
+pub struct ModelLimits {
+    pub id: String,
+    pub display_name: String,
+    pub supported_attachment_types: Vec<String>,
+}
+
+/// Inspects the file at `path`. Contents are read once, in chunks, however large
+/// the file is; images and PDFs are opened again only to read their dimensions
+/// or page count.
+pub fn inspect(path: &Path) -> Result<FileInspection, String> {
+    let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+    let mut inspection = FileInspection {
+        size: metadata.len(),
+        is_file: metadata.is_file(),
+        is_directory: metadata.is_dir(),
+        mime_type: None,
+        attachment_type: None,
+        sha256: None,
+        image: None,
+        pdf: None,
+        text: None,
+        models: Vec::new(),
+    };
+    if !metadata.is_file() {
+        return Ok(inspection);
+    }
+
+    let scan = scan(path)?;
+    inspection.sha256 = Some(scan.sha256);
+    let sniffed = infer::get(&scan.head);
+
+    if let Some(kind) = sniffed {
+        inspection.mime_type = Some(kind.mime_type().to_string());
+        match kind.mime_type() {
+            "image/png" | "image/jpeg" | "image/gif" | "image/webp" => {
+                inspection.attachment_type = Some(AttachmentType::Image);
+                inspection.image = image::image_dimensions(path)
+                    .ok()
+                    .map(|(width, height)| ImageInfo { width, height });
+            }
+            "application/pdf" => {
+                inspection.attachment_type = Some(AttachmentType::Pdf);
+                inspection.pdf =
+                    pdf_page_count(path, metadata.len()).map(|page_count| PdfInfo { page_count });
+            }
+            // Office documents are attached as their extracted text
+            _ if DocumentFormat::detect(path).is_some() => {
+                inspection.attachment_type = Some(AttachmentType::Text);
+            }
+            _ => {}
+        }
+        return Ok(inspection);
+    }
+
+    if let Some(encoding) = detect_text_encoding(&scan.head, scan.truncated) {
+        inspection.mime_type = Some(text_mime_type(path).to_string());
+        inspection.attachment_type = Some(AttachmentType::Text);
+        inspection.text = Some(TextInfo {
+            encoding: encoding.encoding.name().to_string(),
+            has_bom: encoding.has_bom,
+            line_count: scan.line_count,
+        });
+    }
+    Ok(inspection)
+}
+
+/// Pages in the PDF at `path`. If its cross-reference data is more than
+/// `pdf_pages` can read, a PDF small enough to attach is parsed in full instead.
+fn pdf_page_count(path: &Path, size: u64) -> Option<usize> {
+    match pdf_pages::page_count(path) {
+        Ok(page_count) => Some(page_count),
+        Err(e) if size <= MAX_ATTACHMENT_BYTES => {
+            log::debug!("Loading {} to count its pages: {}", path.display(), e);
+            lopdf::Document::load(path)
+                .ok()
+                .map(|document| document.get_pages().len())
+        }
+        Err(_) => None,
+    }
+}
+
+struct Scan {
+    sha256: String,
+    /// The first SNIFF_BYTES of the file.
+    head: Vec<u8>,
+    /// Whether the file is longer than `head`.
+    truncated: bool,
+    line_count: usize,
+}
+
+fn scan(path: &Path) -> Result<Scan, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    (&mut file)
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .map_err(|e| e.to_string())?;
+
+    let mut hasher = Sha256::new();
+    let mut lines = LineCounter::new(&head);
+    hasher.update(&head);
+    lines.feed(&head, false);
+    let mut truncated = false;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        truncated = true;
+        hasher.update(&buffer[..read]);
+        lines.feed(&buffer[..read], false);
+    }
+    Ok(Scan {
+        sha256: hex::encode(hasher.finalize()),
+        head,
+        truncated,
+        line_count: lines.finish(),
+    })
+}
+
+/// Counts lines as the file is read, including a last line without a newline.
+/// UTF-16 is decoded as it goes, since its newlines aren't single bytes; every
+/// other encoding we detect is ASCII-compatible.
+enum LineCounter {
+    Bytes {
+        newlines: usize,
+        last_byte: Option<u8>,
+    },
+    Utf16 {
+        decoder: encoding_rs::Decoder,
+        /// Reused for each chunk of decoded text.
+        text: String,
+        newlines: usize,
+        last_char: Option<char>,
+    },
+}
+
+impl LineCounter {
+    fn new(head: &[u8]) -> Self {
+        match encoding_rs::Encoding::for_bom(head) {
+            Some((encoding, _))
+                if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE =>
+            {
+                LineCounter::Utf16 {
+                    decoder: encoding.new_decoder_with_bom_removal(),
+                    text: String::with_capacity(64 * 1024),
+                    newlines: 0,
+                    last_char: None,
+                }
+            }
+            _ => LineCounter::Bytes {
+                newlines: 0,
+                last_byte: None,
+            },
+        }
+    }
+
+    fn feed(&mut self, mut chunk: &[u8], last: bool) {
+        match self {
+            LineCounter::Bytes {
+                newlines,
+                last_byte,
+            } => {
+                *newlines += chunk.iter().filter(|&&byte| byte == b'\n').count();
+                *last_byte = chunk.last().copied().or(*last_byte);
+            }
+            LineCounter::Utf16 {
+                decoder,
+                text,
+                newlines,
+                last_char,
+            } => loop {
+                text.clear();
+                let (result, read, _) = decoder.decode_to_string(chunk, text, last);
+                chunk = &chunk[read..];
+                *newlines += text.bytes().filter(|&byte| byte == b'\n').count();
+                *last_char = text.chars().next_back().or(*last_char);
+                if result == encoding_rs::CoderResult::InputEmpty {
+                    break;
+                }
+            },
+        }
+    }
+
+    fn finish(mut self) -> usize {
+        self.feed(&[], true);
+        match self {
+            LineCounter::Bytes {
+                newlines,
+                last_byte,
+            } => newlines + last_byte.is_some_and(|byte| byte != b'\n') as usize,
+            LineCounter::Utf16 {
+                newlines,
+                last_char,
+                ..
+            } => newlines + last_char.is_some_and(|char| char != '\n') as usize,
+        }
+    }
+}
+
+pub struct TextEncoding {
+    pub encoding: &'static encoding_rs::Encoding,
+    pub has_bom: bool,
+}
+
+/// Works out the encoding of a text file from its first bytes, or None if it
+/// looks binary. `truncated` means `head` stops part way through the file, so a
+/// multi-byte character may be cut off at the end.
+pub fn detect_text_encoding(head: &[u8], truncated: bool) -> Option<TextEncoding> {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(head) {
+        return Some(TextEncoding {
+            encoding,
+            has_bom: true,
+        });
+    }
+    // Text in any encoding we'd guess below has no NUL bytes; binary files
+    // almost always do
+    if head.contains(&0) {
+        return None;
+    }
+    let utf8_error = std::str::from_utf8(head).err();
+    let valid_utf8 = match utf8_error {
+        None => true,
+        // Only an incomplete character right at the end of a truncated head
+        Some(error) => truncated && error.error_len().is_none(),
+    };
+    if valid_utf8 {
+        return Some(TextEncoding {
+            encoding: encoding_rs::UTF_8,
+            has_bom: false,
+        });
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(head, !truncated);
+    Some(TextEncoding {
+        encoding: detector.guess(None, true),
+        has_bom: false,
+    })
+}
+
+fn text_mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "js" | "mjs" | "cjs" | "jsx" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        _ => "text/plain",
+    }
+}
+
+/// Why `model` can't take the file, if it can't.
+pub fn check_limits(inspection: &FileInspection, model: &ModelLimits) -> Vec<LimitIssue> {
+    let Some(attachment_type) = inspection.attachment_type else {
+        return vec![LimitIssue::UnsupportedFile];
+    };
+    let mut issues = Vec::new();
+    if !model
+        .supported_attachment_types
+        .iter()
+        .any(|supported| supported == attachment_type.as_str())
+    {
+        issues.push(LimitIssue::UnsupportedType { attachment_type });
+    }
+    // Images are downscaled to fit before they're sent, so only their
+    // dimensions matter
+    if attachment_type != AttachmentType::Image && inspection.size > MAX_ATTACHMENT_BYTES {
+        issues.push(LimitIssue::TooLarge {
+            size: inspection.size,
+            max_size: MAX_ATTACHMENT_BYTES,
+        });
+    }
+    if let Some(pdf) = &inspection.pdf {
+        if pdf.page_count > MAX_PDF_PAGES {
+            issues.push(LimitIssue::TooManyPages {
+                page_count: pdf.page_count,
+                max_pages: MAX_PDF_PAGES,
+            });
+        }
+    }
+    if let Some(image) = &inspection.image {
+        if image.width > MAX_IMAGE_DIMENSION || image.height > MAX_IMAGE_DIMENSION {
+            issues.push(LimitIssue::ImageTooLarge {
+                width: image.width,
+                height: image.height,
+                max_dimension: MAX_IMAGE_DIMENSION,
+            });
+        }
+    }
+    issues
+}
+
+/// Enabled models, or only those in `model_ids` if given. Internal and
+/// deprecated models are never offered to the user, so they're left out.
+pub fn load_models(
+    conn: &Connection,
+    model_ids: Option<&[String]>,
+) -> Result<Vec<ModelLimits>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, display_name, supported_attachment_types FROM models
+            WHERE is_enabled = 1 AND is_internal = 0 AND is_deprecated = 0
+            ORDER BY display_name",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut models = Vec::new();
+    for row in rows {
+        let (id, display_name, supported_attachment_types) = row.map_err(|e| e.to_string())?;
+        if model_ids.is_some_and(|model_ids| !model_ids.contains(&id)) {
+            continue;
+        }
+        models.push(ModelLimits {
+            id,
+            display_name,
+            // Kept valid by an invariant, see invariants.rs
+            supported_attachment_types: serde_json::from_str(&supported_attachment_types)
+                .unwrap_or_default(),
+        });
+    }
+    Ok(models)
+}
+
+/// Fills in `inspection.models`.
+pub fn check_models(inspection: &mut FileInspection, models: &[ModelLimits]) {
+    inspection.models = models
+        .iter()
+        .map(|model| ModelFit {
+            model_id: model.id.clone(),
+            display_name: model.display_name.clone(),
+            issues: check_limits(inspection, model),
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding_of(head: &[u8], truncated: bool) -> Option<(&'static str, bool)> {
+        detect_text_encoding(head, truncated).map(|text| (text.encoding.name(), text.has_bom))
+    }
+
+    fn inspection(attachment_type: Option<AttachmentType>, size: u64) -> FileInspection {
+        FileInspection {
+            size,
+            is_file: true,
+            is_directory: false,
+            mime_type: None,
+            attachment_type,
+            sha256: None,
+            image: None,
+            pdf: None,
+            text: None,
+            models: Vec::new(),
+        }
+    }
+
+    fn model(supported_attachment_types: &[&str]) -> ModelLimits {
+        ModelLimits {
+            id: "model".to_string(),
+            display_name: "Model".to_string(),
+            supported_attachment_types: supported_attachment_types
+                .iter()
+                .map(|attachment_type| attachment_type.to_string())
+                .collect(),
+        }
+    }
+
+    /// Scans `contents` from a file, to count its lines.
+    fn scan_lines(contents: &[u8]) -> usize {
+        let path = std::env::temp_dir().join(format!("camp-scan-{}.txt", uuid::Uuid::new_v4()));
+        fs::write(&path, contents).unwrap();
+        let scan = scan(&path);
+        fs::remove_file(&path).unwrap();
+        scan.unwrap().line_count
+    }
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        let bom: [u8; 2] = if little_endian {
+            [0xff, 0xfe]
+        } else {
+            [0xfe, 0xff]
+        };
+        bom.into_iter()
+            .chain(text.encode_utf16().flat_map(|unit| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            }))
+            .collect()
+    }
+
+    #[test]
+    fn detects_encodings_from_boms() {
+        assert_eq!(
+            encoding_of(b"\xef\xbb\xbfhello", false),
+            Some(("UTF-8", true))
+        );
+        assert_eq!(
+            encoding_of(&utf16("hello", true), false),
+            Some(("UTF-16LE", true))
+        );
+        assert_eq!(
+            encoding_of(&utf16("hello", false), false),
+            Some(("UTF-16BE", true))
+        );
+    }
+
+    #[test]
+    fn detects_utf8_without_a_bom() {
+        assert_eq!(encoding_of(b"plain ascii\n", false), Some(("UTF-8", false)));
+        assert_eq!(
+            encoding_of("caf\u{e9} \u{1f3d5}\n".as_bytes(), false),
+            Some(("UTF-8", false))
+        );
+        assert_eq!(encoding_of(b"", false), Some(("UTF-8", false)));
+    }
+
+    #[test]
+    fn allows_a_cut_off_character_only_at_the_end_of_a_truncated_head() {
+        let text = "caf\u{e9}".as_bytes();
+        let cut = &text[..text.len() - 1];
+        assert_eq!(encoding_of(cut, true), Some(("UTF-8", false)));
+        // Not at the end of the whole file, so it's not UTF-8
+        assert_ne!(encoding_of(cut, false), Some(("UTF-8", false)));
+    }
+
+    #[test]
+    fn guesses_legacy_encodings() {
+        let text = b"Le caf\xe9 est tr\xe8s bon, et la cr\xe8me br\xfbl\xe9e aussi. \
+            \xc0 bient\xf4t, ch\xe8re \xe9l\xe8ve.\n";
+        assert_eq!(encoding_of(text, false), Some(("windows-1252", false)));
+    }
+
+    #[test]
+    fn treats_files_with_nul_bytes_as_binary() {
+        assert_eq!(encoding_of(b"\x7fELF\x02\x01\x01\x00\x00", false), None);
+        assert_eq!(encoding_of(b"text\0with a nul", true), None);
+    }
+
+    #[test]
+    fn counts_lines_in_ascii_compatible_files() {
+        assert_eq!(scan_lines(b""), 0);
+        assert_eq!(scan_lines(b"one"), 1);
+        assert_eq!(scan_lines(b"one\ntwo\n"), 2);
+        assert_eq!(scan_lines(b"one\r\ntwo\r\nthree"), 3);
+
+        // Lines past the sniffed head still count
+        let long = "line\n".repeat(3 * SNIFF_BYTES / 5 + 1);
+        assert_eq!(scan_lines(long.as_bytes()), 3 * SNIFF_BYTES / 5 + 1);
+    }
+
+    #[test]
+    fn counts_lines_in_utf16_files() {
+        for little_endian in [true, false] {
+            assert_eq!(scan_lines(&utf16("", little_endian)), 0);
+            assert_eq!(scan_lines(&utf16("one\ntwo", little_endian)), 2);
+            // U+0A00 has a 0x0a byte, which isn't a newline
+            assert_eq!(scan_lines(&utf16("\u{a00}\n\u{a0a}\n", little_endian)), 2);
+
+            // Long enough to be decoded in several chunks, with characters
+            // split across them
+            let long = "\u{1f3d5} camp\n".repeat(SNIFF_BYTES / 3);
+            assert_eq!(scan_lines(&utf16(&long, little_endian)), SNIFF_BYTES / 3);
+        }
+    }
+
+    #[test]
+    fn takes_files_of_supported_types_within_limits() {
+        let mut pdf = inspection(Some(AttachmentType::Pdf), 1024);
+        pdf.pdf = Some(PdfInfo {
+            page_count: MAX_PDF_PAGES,
+        });
+        assert_eq!(check_limits(&pdf, &model(&["image", "pdf"])), vec![]);
+
+        let text = inspection(Some(AttachmentType::Text), MAX_ATTACHMENT_BYTES);
+        assert_eq!(check_limits(&text, &model(&["text"])), vec![]);
+    }
+
+    #[test]
+    fn rejects_files_no_model_takes() {
+        let directory = FileInspection {
+            is_file: false,
+            is_directory: true,
+            ..inspection(None, 0)
+        };
+        assert_eq!(
+            check_limits(&directory, &model(&["image", "pdf", "text"])),
+            vec![LimitIssue::UnsupportedFile]
+        );
+    }
+
+    #[test]
+    fn rejects_types_the_model_doesnt_take() {
+        let image = inspection(Some(AttachmentType::Image), 1024);
+        assert_eq!(
+            check_limits(&image, &model(&["text"])),
+            vec![LimitIssue::UnsupportedType {
+                attachment_type: AttachmentType::Image
+            }]
+        );
+        assert_eq!(
+            check_limits(&image, &model(&[])),
+            vec![LimitIssue::UnsupportedType {
+                attachment_type: AttachmentType::Image
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_files_over_the_limits() {
+        let size = MAX_ATTACHMENT_BYTES + 1;
+        let mut pdf = inspection(Some(AttachmentType::Pdf), size);
+        pdf.pdf = Some(PdfInfo {
+            page_count: MAX_PDF_PAGES + 1,
+        });
+        assert_eq!(
+            check_limits(&pdf, &model(&["text"])),
+            vec![
+                LimitIssue::UnsupportedType {
+                    attachment_type: AttachmentType::Pdf
+                },
+                LimitIssue::TooLarge {
+                    size,
+                    max_size: MAX_ATTACHMENT_BYTES
+                },
+                LimitIssue::TooManyPages {
+                    page_count: MAX_PDF_PAGES + 1,
+                    max_pages: MAX_PDF_PAGES
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_only_the_dimensions_of_images() {
+        let mut image = inspection(Some(AttachmentType::Image), MAX_ATTACHMENT_BYTES * 2);
+        image.image = Some(ImageInfo {
+            width: MAX_IMAGE_DIMENSION,
+            height: MAX_IMAGE_DIMENSION,
+        });
+        assert_eq!(check_limits(&image, &model(&["image"])), vec![]);
+
+        image.image = Some(ImageInfo {
+            width: 200,
+            height: MAX_IMAGE_DIMENSION + 1,
+        });
+        assert_eq!(
+            check_limits(&image, &model(&["image"])),
+            vec![LimitIssue::ImageTooLarge {
+                width: 200,
+                height: MAX_IMAGE_DIMENSION + 1,
+                max_dimension: MAX_IMAGE_DIMENSION
+            }]
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! Bundles a folder (usually part of a repository) into a single text
+//! attachment: a file tree, then every text file in its own section.
+//!
+//! Files ignored by `.gitignore` (and the global and per-repo git excludes) or by
+//! `.campignore` are left out entirely, as are hidden files, which is where
+//! secrets like `.env` tend to live. `.campignore` uses the same syntax as
+//! `.gitignore` and works outside git repositories too. Binary files and lock
+//! files are listed in the tree but not included.
+//!
+//! Models have limited context, so the bundle is capped by a token budget,
+//! estimated from byte counts. The tree comes out of the budget first, then
+//! files are taken in tree order; ones that don't fit in what's left are
+//! skipped, and smaller ones after them may still fit.
+
+use crate::file_inspection::detect_text_encoding;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_MAX_TOKENS: usize = 100_000;
+
+/// The most anyone can ask for, above the context of any model we support.
+pub const MAX_TOKENS_LIMIT: usize = 1_000_000;
+
+/// Ignore file for things that should stay in git but not be sent to models.
+pub const IGNORE_FILE_NAME: &str = ".campignore";
+
+/// Rough bytes per token for code and English prose.
+const BYTES_PER_TOKEN: usize = 4;
+
+/// Walking stops after this many files, so dropping a home folder by mistake
+/// doesn't hang the app.
+const MAX_FILES: usize = 5_000;
+
+/// Generated files that are large and rarely worth a model's attention.
+const SKIPPED_FILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "Gemfile.lock",
+    "bun.lockb",
+    "composer.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "yarn.lock",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    Binary,
+    LockFile,
+    OverBudget,
+    Unreadable,
+}
+
+impl SkipReason {
+    fn describe(self) -> &'static str {
+        match self {
+            SkipReason::Binary => "binary",
+            SkipReason::LockFile => "lock file",
+            SkipReason::OverBudget => "over budget",
+            SkipReason::Unreadable => "unreadable",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFile {
+    /// Relative to the bundled folder, with `/` separators.
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderBundle {
+    #[serde(skip)]
+    pub text: String,
+    /// Files whose contents are included.
+    pub file_count: usize,
+    pub skipped: Vec<SkippedFile>,
+    pub estimated_tokens: usize,
+    /// The folder had more than MAX_FILES files, so the rest weren't looked at.
+    pub truncated: bool,
+}
+
+/// Relative path with `/` separators on every platform, since it's shown to the
+/// model rather than used to open anything.
+fn display_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn estimate_tokens(bytes: usize) -> usize {
+    bytes.div_ceil(BYTES_PER_TOKEN)
+}
+
+/// Files under `root` that aren't ignored, relative to it, in tree order.
+/// The bool is true if there were more than MAX_FILES.
+fn list_files(root: &Path) -> Result<(Vec<PathBuf>, bool), String> {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(true)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        // Honor .gitignore files even in folders that aren't checked out repos
+        .require_git(false)
+        .follow_links(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .sort_by_file_name(|a, b| a.cmp(b));
+
+    let mut files = Vec::new();
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                // An unreadable subfolder or a malformed ignore file shouldn't
+                // stop the rest from being bundled
+                log::warn!("Skipping entry while bundling folder: {}", e);
+                continue;
+            }
+        };
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        if files.len() == MAX_FILES {
+            return Ok((files, true));
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(root)
+            .map_err(|e| e.to_string())?
+            .to_path_buf();
+        files.push(relative);
+    }
+    Ok((files, false))
+}
+
+/// Reads a file as text, or says why it can't be included. `budget` is the
+/// number of tokens left.
+fn read_text(path: &Path, budget: usize) -> Result<String, SkipReason> {
+    let size = fs::metadata(path)
+        .map_err(|_| SkipReason::Unreadable)?
+        .len();
+    // Checked before reading so large files are never loaded
+    if estimate_tokens(size as usize) > budget {
+        return Err(SkipReason::OverBudget);
+    }
+    let bytes = fs::read(path).map_err(|_| SkipReason::Unreadable)?;
+    let encoding = detect_text_encoding(&bytes, false).ok_or(SkipReason::Binary)?;
+    let (text, _, _) = encoding.encoding.decode(&bytes);
+    // Decoding to UTF-8 can make text longer than the file was
+    if estimate_tokens(text.len()) > budget {
+        return Err(SkipReason::OverBudget);
+    }
+    Ok(text.into_owned())
+}
+
+/// The summary line and tree at the top of a bundle.
+fn header(root_name: &str, file_count: usize, total: usize, truncated: bool, tree: &str) -> String {
+    format!(
+        "Folder {}: {} of {} files included{}\n\n{}\n",
+        root_name,
+        file_count,
+        total,
+        if truncated {
+            format!(" (stopped after the first {})", MAX_FILES)
+        } else {
+            String::new()
+        },
+        tree
+    )
+}
+
+/// A file's contents wrapped in tags naming it.
+fn file_section(path: &str, text: &str) -> String {
+    format!("<file path=\"{}\">\n{}\n</file>\n\n", path, text.trim_end())
+}
+
+/// Bundles the folder at `root`, keeping the estimated tokens of the whole
+/// bundle within `max_tokens`. Only a tree too big for the budget on its own
+/// can take it over.
+pub fn bundle_folder(root: &Path, max_tokens: usize) -> Result<FolderBundle, String> {
+    if !root.is_dir() {
+        return Err(format!("{} is not a folder", root.display()));
+    }
+    let (files, truncated) = list_files(root)?;
+    let root_name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string());
+
+    // Charge the header at its longest, with every file included and marked
+    // with the longest skip reason, since which files fit isn't known yet
+    let longest_tree: Vec<(String, Option<SkipReason>)> = files
+        .iter()
+        .map(|relative| (display_path(relative), Some(SkipReason::OverBudget)))
+        .collect();
+    let longest_header = header(
+        &root_name,
+        files.len(),
+        files.len(),
+        truncated,
+        &render_tree(&root_name, &longest_tree),
+    );
+    let mut budget = max_tokens.saturating_sub(estimate_tokens(longest_header.len()));
+
+    let mut sections = String::new();
+    let mut tree_entries = Vec::new();
+    let mut skipped = Vec::new();
+    let mut file_count = 0;
+    for relative in &files {
+        let path = display_path(relative);
+        let file_name = relative
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let overhead = estimate_tokens(file_section(&path, "").len());
+        let result = if SKIPPED_FILE_NAMES.contains(&file_name.as_ref()) {
+            Err(SkipReason::LockFile)
+        } else if overhead > budget {
+            Err(SkipReason::OverBudget)
+        } else {
+            read_text(&root.join(relative), budget - overhead)
+        };
+        match result {
+            Ok(text) => {
+                let section = file_section(&path, &text);
+                budget -= estimate_tokens(section.len());
+                file_count += 1;
+                sections.push_str(&section);
+                tree_entries.push((path, None));
+            }
+            Err(reason) => {
+                tree_entries.push((path.clone(), Some(reason)));
+                skipped.push(SkippedFile { path, reason });
+            }
+        }
+    }
+
+    let mut text = header(
+        &root_name,
+        file_count,
+        files.len(),
+        truncated,
+        &render_tree(&root_name, &tree_entries),
+    );
+    text.push_str(sections.trim_end());
+    text.push('\n');
+
+    Ok(FolderBundle {
+        estimated_tokens: estimate_tokens(text.len()),
+        text,
+        file_count,
+        skipped,
+        truncated,
+    })
+}
+
+#[derive(Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    skipped: Option<SkipReason>,
+}
+
+/// Draws `entries` (paths with `/` separators, and why each was skipped, if it
+/// was) as a tree, like the `tree` command does.
+pub fn render_tree(root_name: &str, entries: &[(String, Option<SkipReason>)]) -> String {
+    let mut root = TreeNode::default();
+    for (path, skipped) in entries {
+        let node = path.split('/').fold(&mut root, |node, part| {
+            node.children.entry(part.to_string()).or_default()
+        });
+        node.skipped = *skipped;
+    }
+
+    let mut tree = format!("{}/\n", root_name);
+    render_children(&root, "", &mut tree);
+    tree
+}
+
+fn render_children(node: &TreeNode, prefix: &str, tree: &mut String) {
+    let count = node.children.len();
+    for (index, (name, child)) in node.children.iter().enumerate() {
+        let is_last = index + 1 == count;
+        let is_dir = !child.children.is_empty();
+        let _ = writeln!(
+            tree,
+            "{}{}{}{}{}",
+            prefix,
+            if is_last { "└── " } else { "├── " },
+            name,
+            if is_dir { "/" } else { "" },
+            child
+                .skipped
+                .map(|reason| format!(" ({})", reason.describe()))
+                .unwrap_or_default()
+        );
+        if is_dir {
+            let prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            render_children(child, &prefix, tree);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir =
+                std::env::temp_dir().join(format!("camp-folder-bundle-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, relative: &str, contents: &[u8]) {
+            let path = self.0.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A project with one file for each way of being left out.
+    fn project() -> TempDir {
+        let dir = TempDir::new();
+        dir.write("proj/.campignore", b"generated/\n*.log\n");
+        dir.write("proj/.gitignore", b"target/\n");
+        dir.write("proj/.env", b"API_KEY=hidden-secret");
+        dir.write("proj/generated/schema.txt", b"campignored folder");
+        dir.write("proj/debug.log", b"campignored pattern");
+        dir.write("proj/target/out.txt", b"gitignored");
+        dir.write("proj/Cargo.lock", b"# lock file contents");
+        dir.write("proj/logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        dir.write("proj/README.md", b"# Project\n");
+        dir.write("proj/src/big.rs", "// padding\n".repeat(400).as_bytes());
+        dir.write("proj/src/main.rs", b"fn main() {}\n");
+        dir
+    }
+
+    #[test]
+    fn renders_a_tree_with_skip_reasons() {
+        let entries = [
+            ("b.txt".to_string(), None),
+            ("a/x.rs".to_string(), None),
+            ("a/deep/y.bin".to_string(), Some(SkipReason::Binary)),
+            ("c/z.lock".to_string(), Some(SkipReason::LockFile)),
+        ];
+        assert_eq!(
+            render_tree("root", &entries),
+            "root/
+├── a/
+│   ├── deep/
+│   │   └── y.bin (binary)
+│   └── x.rs
+├── b.txt
+└── c/
+    └── z.lock (lock file)
+"
+        );
+        assert_eq!(render_tree("empty", &[]), "empty/\n");
+    }
+
+    #[test]
+    fn leaves_out_ignored_hidden_binary_lock_and_oversized_files() {
+        let dir = project();
+        let bundle = bundle_folder(&dir.0.join("proj"), 500).unwrap();
+
+        assert!(bundle.text.starts_with(
+            "Folder proj: 2 of 5 files included
+
+proj/
+├── Cargo.lock (lock file)
+├── README.md
+├── logo.png (binary)
+└── src/
+    ├── big.rs (over budget)
+    └── main.rs
+"
+        ));
+        assert!(bundle
+            .text
+            .contains("<file path=\"README.md\">\n# Project\n</file>"));
+        assert!(bundle
+            .text
+            .contains("<file path=\"src/main.rs\">\nfn main() {}\n</file>"));
+        for left_out in [
+            "hidden-secret",
+            "campignored",
+            "gitignored",
+            "lock file contents",
+            "padding",
+        ] {
+            assert!(!bundle.text.contains(left_out), "{}", left_out);
+        }
+        assert_eq!(bundle.file_count, 2);
+        assert!(!bundle.truncated);
+        let skipped: Vec<(&str, SkipReason)> = bundle
+            .skipped
+            .iter()
+            .map(|file| (file.path.as_str(), file.reason))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                ("Cargo.lock", SkipReason::LockFile),
+                ("logo.png", SkipReason::Binary),
+                ("src/big.rs", SkipReason::OverBudget),
+            ]
+        );
+    }
+
+    #[test]
+    fn stays_within_the_budget() {
+        let dir = project();
+        let root = dir.0.join("proj");
+        let tree_only = bundle_folder(&root, 0).unwrap();
+        assert_eq!(tree_only.file_count, 0);
+
+        let mut file_count = 0;
+        for max_tokens in (tree_only.estimated_tokens..1_500).step_by(7) {
+            let bundle = bundle_folder(&root, max_tokens).unwrap();
+            assert!(
+                bundle.estimated_tokens <= max_tokens,
+                "{} tokens with a budget of {}",
+                bundle.estimated_tokens,
+                max_tokens
+            );
+            assert_eq!(bundle.estimated_tokens, estimate_tokens(bundle.text.len()));
+            assert!(bundle.file_count >= file_count);
+            file_count = bundle.file_count;
+        }
+        // Enough for everything but the lock file and the binary
+        assert_eq!(file_count, 3);
+    }
+
+    #[test]
+    fn rejects_files() {
+        let dir = project();
+        assert!(bundle_folder(&dir.0.join("proj/README.md"), 100).is_err());
+    }
+}
@@ -0,0 +1,580 @@
+//! Global shortcuts: keys that do something in Camp from anywhere, even while
+//! another app is in front.
+//!
+//! The quick chat shortcut comes from `quickChat` in the settings store, and the
+//! rest from `globalShortcuts`, a list of shortcuts and the actions they
+//! trigger. Every shortcut goes through one handler, which toggles the quick
+//! chat itself and emits anything else as `ACTION_EVENT` to the window that
+//! carries it out.
+//!
+//! Changing shortcuts registers the new ones before the old ones are forgotten,
+//! so if one can't be registered (usually because another app has it) the
+//! previous shortcuts are put back and keep working.
+
+use crate::command;
+use crate::quick_chat::{self, QuickChatSettings};
+use crate::shortcut::{format_shortcut, parse_shortcut};
+use crate::SPOTLIGHT_LABEL;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Wry};
+use tauri_plugin_global_shortcut::{
+    Code, GlobalShortcut, GlobalShortcutExt, Modifiers, Shortcut, ShortcutEvent, ShortcutState,
+};
+use tauri_plugin_store::StoreExt;
+
+/// Emitted with a `ShortcutAction` to the window that should carry it out: the
+/// quick chat for captures and pasting, the main window for the rest.
+pub const ACTION_EVENT: &str = "global_shortcut_action";
+
+/// What a global shortcut does. Mirrors `GlobalShortcutAction` in Settings.ts.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ShortcutAction {
+    /// Bound by `quickChat.shortcut` rather than `globalShortcuts`, and handled
+    /// here rather than by the frontend.
+    #[serde(skip)]
+    ToggleQuickChat,
+    /// Screenshots the display the quick chat is on and attaches it there.
+    CaptureScreen,
+    /// Screenshots the frontmost window (on macOS, one the user picks) and
+    /// attaches it to the quick chat.
+    CaptureWindow,
+    /// Attaches or pastes what's on the clipboard into the quick chat.
+    PasteClipboard,
+    /// Starts a new chat in the main window.
+    NewChat,
+    /// Opens a project in the main window.
+    OpenProject {
+        #[serde(rename = "projectId")]
+        project_id: String,
+    },
+}
+
+impl ShortcutAction {
+    fn describe(&self) -> &'static str {
+        match self {
+            ShortcutAction::ToggleQuickChat => "open the quick chat",
+            ShortcutAction::CaptureScreen => "capture the screen",
+            ShortcutAction::CaptureWindow => "capture a window",
+            ShortcutAction::PasteClipboard => "paste into the quick chat",
+            ShortcutAction::NewChat => "start a new chat",
+            ShortcutAction::OpenProject { .. } => "open a project",
+        }
+    }
+}
+
+/// An entry in `globalShortcuts` in the settings store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShortcutBinding {
+    pub shortcut: String,
+    pub action: ShortcutAction,
+}
+
+/// Shortcuts registered with the OS right now, and what they do.
+struct Registry(Mutex<Vec<(Shortcut, ShortcutAction)>>);
+
+/// Sets up the global shortcut plugin and registers the shortcuts from
+/// settings. Shortcuts that can't be registered are skipped; the quick chat can
+/// still be opened from the tray.
+pub fn setup(app_handle: &AppHandle) {
+    let plugin = tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(handle_shortcut)
+        .build();
+    if let Err(e) = app_handle.plugin(plugin) {
+        log::error!("Global shortcuts aren't available: {}", e);
+        return;
+    }
+    app_handle.manage(Registry(Mutex::new(Vec::new())));
+
+    #[cfg(target_os = "linux")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        log::warn!("Running under Wayland, so global shortcuts only work while an XWayland window has focus");
+    }
+
+    let quick_chat = QuickChatSettings::load(app_handle);
+    if quick_chat.enabled {
+        let shortcut = parse_shortcut(&quick_chat.shortcut).unwrap_or_else(|e| {
+            log::warn!("Using the default quick chat shortcut: {}", e);
+            Shortcut::new(Some(Modifiers::ALT), Code::Space)
+        });
+        if let Err(e) = set_quick_chat_shortcut(app_handle, Some(shortcut)) {
+            log::error!("{}", e);
+        }
+    }
+
+    for binding in load_bindings(app_handle) {
+        let result = parse_shortcut(&binding.shortcut)
+            .map_err(|e| e.to_string())
+            .and_then(|shortcut| rebind(app_handle, |_| false, vec![(shortcut, binding.action)]));
+        if let Err(e) = result {
+            log::warn!("Skipping global shortcut {}: {}", binding.shortcut, e);
+        }
+    }
+}
+
+/// `globalShortcuts` from the settings store. Entries that don't parse, say
+/// ones saved by a newer version of Camp, are left out.
+fn load_bindings(app_handle: &AppHandle) -> Vec<ShortcutBinding> {
+    let entries = app_handle
+        .store("settings")
+        .ok()
+        .and_then(|store| store.get("settings"))
+        .and_then(|settings| settings.get("globalShortcuts").cloned())
+        .and_then(|entries| serde_json::from_value::<Vec<serde_json::Value>>(entries).ok())
+        .unwrap_or_default();
+    entries
+        .into_iter()
+        .filter_map(|entry| match serde_json::from_value(entry.clone()) {
+            Ok(binding) => Some(binding),
+            Err(e) => {
+                log::warn!("Ignoring global shortcut {}: {}", entry, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn handle_shortcut(app_handle: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let Some(registry) = app_handle.try_state::<Registry>() else {
+        return;
+    };
+    // Busy means shortcuts are being changed, and this press can be dropped.
+    // Waiting could deadlock, since registering runs on the main thread, which
+    // is where this is called.
+    let action = match registry.0.try_lock() {
+        Ok(registered) => registered
+            .iter()
+            .find(|(registered, _)| registered == shortcut)
+            .map(|(_, action)| action.clone()),
+        Err(_) => None,
+    };
+    if let Some(action) = action {
+        dispatch(app_handle, &action);
+    }
+}
+
+/// Carries out an action, as if its shortcut had been pressed. The tray menu
+/// uses this too.
+pub fn dispatch(app_handle: &AppHandle, action: &ShortcutAction) {
+    match action {
+        ShortcutAction::ToggleQuickChat => quick_chat::toggle(app_handle),
+        ShortcutAction::CaptureScreen | ShortcutAction::CaptureWindow => {
+            // Out of the way of the capture. The quick chat shows itself again
+            // once it's attached the capture.
+            command::hide(app_handle.clone());
+            let _ = app_handle.emit_to(SPOTLIGHT_LABEL, ACTION_EVENT, action);
+        }
+        ShortcutAction::PasteClipboard => {
+            let _ = app_handle.emit_to(SPOTLIGHT_LABEL, ACTION_EVENT, action);
+            command::show(app_handle.clone());
+        }
+        ShortcutAction::NewChat | ShortcutAction::OpenProject { .. } => {
+            if let Some(window) = app_handle.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            let _ = app_handle.emit_to("main", ACTION_EVENT, action);
+        }
+    }
+}
+
+/// Swaps the quick chat shortcut for `shortcut` (None turns it off).
+pub fn set_quick_chat_shortcut(
+    app_handle: &AppHandle,
+    shortcut: Option<Shortcut>,
+) -> Result<(), String> {
+    rebind(
+        app_handle,
+        |action| *action == ShortcutAction::ToggleQuickChat,
+        shortcut
+            .map(|shortcut| vec![(shortcut, ShortcutAction::ToggleQuickChat)])
+            .unwrap_or_default(),
+    )
+}
+
+/// Replaces every shortcut but the quick chat's with `bindings`. Returns them
+/// with their shortcuts in canonical form, which is what should be saved.
+pub fn update_bindings(
+    app_handle: &AppHandle,
+    bindings: Vec<ShortcutBinding>,
+) -> Result<Vec<ShortcutBinding>, String> {
+    let parsed = bindings
+        .into_iter()
+        .map(|binding| {
+            parse_shortcut(&binding.shortcut)
+                .map(|shortcut| (shortcut, binding.action))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    rebind(
+        app_handle,
+        |action| *action != ShortcutAction::ToggleQuickChat,
+        parsed.clone(),
+    )?;
+    Ok(parsed
+        .into_iter()
+        .map(|(shortcut, action)| ShortcutBinding {
+            shortcut: format_shortcut(&shortcut),
+            action,
+        })
+        .collect())
+}
+
+/// Replaces the registered shortcuts whose action `replaces` matches with
+/// `bindings`. Nothing changes if a shortcut is used twice or can't be
+/// registered.
+fn rebind(
+    app_handle: &AppHandle,
+    replaces: impl Fn(&ShortcutAction) -> bool,
+    bindings: Vec<(Shortcut, ShortcutAction)>,
+) -> Result<(), String> {
+    let registry = app_handle
+        .try_state::<Registry>()
+        .ok_or("Global shortcuts aren't available")?;
+    let mut registered = registry.0.lock().unwrap();
+    replace_bindings(
+        app_handle.global_shortcut(),
+        &mut registered,
+        replaces,
+        bindings,
+    )
+}
+
+/// What registering needs from the global shortcut plugin, so tests can stand
+/// in for the OS.
+trait Registrar {
+    fn register(&self, shortcut: Shortcut) -> Result<(), String>;
+    fn unregister(&self, shortcut: Shortcut) -> Result<(), String>;
+}
+
+impl Registrar for GlobalShortcut<Wry> {
+    fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+        GlobalShortcut::register(self, shortcut).map_err(|e| e.to_string())
+    }
+
+    fn unregister(&self, shortcut: Shortcut) -> Result<(), String> {
+        GlobalShortcut::unregister(self, shortcut).map_err(|e| e.to_string())
+    }
+}
+
+/// `rebind`, on the list of registered shortcuts.
+fn replace_bindings(
+    registrar: &impl Registrar,
+    registered: &mut Vec<(Shortcut, ShortcutAction)>,
+    replaces: impl Fn(&ShortcutAction) -> bool,
+    bindings: Vec<(Shortcut, ShortcutAction)>,
+) -> Result<(), String> {
+    let (old, kept): (Vec<_>, Vec<_>) = registered
+        .iter()
+        .cloned()
+        .partition(|(_, action)| replaces(action));
+
+    for (index, (shortcut, _)) in bindings.iter().enumerate() {
+        let taken_by = kept
+            .iter()
+            .chain(&bindings[..index])
+            .find(|(other, _)| other == shortcut);
+        if let Some((_, action)) = taken_by {
+            return Err(format!(
+                "{} is already used to {}",
+                format_shortcut(shortcut),
+                action.describe()
+            ));
+        }
+    }
+
+    let old_shortcuts: Vec<Shortcut> = old.iter().map(|(shortcut, _)| *shortcut).collect();
+    let new_shortcuts: Vec<Shortcut> = bindings.iter().map(|(shortcut, _)| *shortcut).collect();
+    swap(registrar, &old_shortcuts, &new_shortcuts)?;
+
+    for (shortcut, action) in &bindings {
+        log::info!(
+            "Registered global shortcut {} to {}",
+            format_shortcut(shortcut),
+            action.describe()
+        );
+    }
+    *registered = kept;
+    registered.extend(bindings);
+    Ok(())
+}
+
+/// Registers `new` in place of `old`, leaving shortcuts that are in both alone.
+/// If anything fails, whatever was done is undone.
+fn swap(registrar: &impl Registrar, old: &[Shortcut], new: &[Shortcut]) -> Result<(), String> {
+    let released: Vec<Shortcut> = old.iter().filter(|s| !new.contains(s)).copied().collect();
+    let added: Vec<Shortcut> = new.iter().filter(|s| !old.contains(s)).copied().collect();
+
+    for (index, shortcut) in released.iter().enumerate() {
+        if let Err(e) = registrar.unregister(*shortcut) {
+            restore(registrar, &[], &released[..index]);
+            return Err(format!(
+                "Couldn't release {}: {}",
+                format_shortcut(shortcut),
+                e
+            ));
+        }
+    }
+    for (index, shortcut) in added.iter().enumerate() {
+        if let Err(e) = registrar.register(*shortcut) {
+            restore(registrar, &added[..index], &released);
+            // The OS doesn't say why, but it's almost always another app having
+            // taken the shortcut first
+            return Err(format!(
+                "{} is already taken by another app or the system ({})",
+                format_shortcut(shortcut),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Undoes part of a swap: unregisters `registered` and registers `released`
+/// again.
+fn restore(registrar: &impl Registrar, registered: &[Shortcut], released: &[Shortcut]) {
+    for shortcut in registered {
+        if let Err(e) = registrar.unregister(*shortcut) {
+            log::error!("Failed to unregister {}: {}", format_shortcut(shortcut), e);
+        }
+    }
+    for shortcut in released {
+        if let Err(e) = registrar.register(*shortcut) {
+            log::error!("Failed to restore {}: {}", format_shortcut(shortcut), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shortcut::parse_shortcut;
+    use std::cell::RefCell;
+
+    /// Stands in for the OS. Shortcuts in `taken` belong to another app, and
+    /// those in `stuck` can't be unregistered.
+    #[derive(Default)]
+    struct FakeRegistrar {
+        registered: RefCell<Vec<Shortcut>>,
+        taken: Vec<Shortcut>,
+        stuck: Vec<Shortcut>,
+        calls: RefCell<usize>,
+    }
+
+    impl Registrar for FakeRegistrar {
+        fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+            *self.calls.borrow_mut() += 1;
+            let mut registered = self.registered.borrow_mut();
+            if self.taken.contains(&shortcut) || registered.contains(&shortcut) {
+                return Err("taken".to_string());
+            }
+            registered.push(shortcut);
+            Ok(())
+        }
+
+        fn unregister(&self, shortcut: Shortcut) -> Result<(), String> {
+            *self.calls.borrow_mut() += 1;
+            let mut registered = self.registered.borrow_mut();
+            if self.stuck.contains(&shortcut) || !registered.contains(&shortcut) {
+                return Err("not registered".to_string());
+            }
+            registered.retain(|registered| *registered != shortcut);
+            Ok(())
+        }
+    }
+
+    impl FakeRegistrar {
+        fn with(registered: &[(Shortcut, ShortcutAction)]) -> Self {
+            FakeRegistrar {
+                registered: RefCell::new(
+                    registered.iter().map(|(shortcut, _)| *shortcut).collect(),
+                ),
+                ..Default::default()
+            }
+        }
+
+        /// What's registered, in a stable order.
+        fn registered(&self) -> Vec<String> {
+            let mut registered: Vec<String> = self
+                .registered
+                .borrow()
+                .iter()
+                .map(format_shortcut)
+                .collect();
+            registered.sort();
+            registered
+        }
+    }
+
+    fn shortcut(shortcut: &str) -> Shortcut {
+        parse_shortcut(shortcut).unwrap()
+    }
+
+    fn names(shortcuts: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = shortcuts
+            .iter()
+            .map(|name| format_shortcut(&shortcut(name)))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn is_quick_chat(action: &ShortcutAction) -> bool {
+        *action == ShortcutAction::ToggleQuickChat
+    }
+
+    /// The quick chat on Alt+Space, a new chat on F8 and a capture on F7.
+    fn initial() -> Vec<(Shortcut, ShortcutAction)> {
+        vec![
+            (shortcut("Alt+Space"), ShortcutAction::ToggleQuickChat),
+            (shortcut("F8"), ShortcutAction::NewChat),
+            (shortcut("F7"), ShortcutAction::CaptureScreen),
+        ]
+    }
+
+    #[test]
+    fn replaces_only_the_matching_bindings() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar::with(&registered);
+        let bindings = vec![(shortcut("F9"), ShortcutAction::PasteClipboard)];
+        replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            bindings,
+        )
+        .unwrap();
+        assert_eq!(registrar.registered(), names(&["Alt+Space", "F9"]));
+        assert_eq!(
+            registered,
+            vec![
+                (shortcut("Alt+Space"), ShortcutAction::ToggleQuickChat),
+                (shortcut("F9"), ShortcutAction::PasteClipboard),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_shortcuts_that_stay_registered_alone() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar::with(&registered);
+        // F8 moves to another action, so it's neither released nor registered
+        let bindings = vec![
+            (shortcut("F8"), ShortcutAction::CaptureWindow),
+            (shortcut("F7"), ShortcutAction::CaptureScreen),
+        ];
+        replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            bindings,
+        )
+        .unwrap();
+        assert_eq!(*registrar.calls.borrow(), 0);
+        assert_eq!(
+            registered[1],
+            (shortcut("F8"), ShortcutAction::CaptureWindow)
+        );
+    }
+
+    #[test]
+    fn rejects_shortcuts_used_twice_without_touching_the_os() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar::with(&registered);
+        let bindings = vec![(shortcut("Alt+Space"), ShortcutAction::NewChat)];
+        let error = replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            bindings,
+        )
+        .unwrap_err();
+        assert!(
+            error.ends_with("is already used to open the quick chat"),
+            "{}",
+            error
+        );
+
+        let bindings = vec![
+            (shortcut("F9"), ShortcutAction::NewChat),
+            (shortcut("F9"), ShortcutAction::PasteClipboard),
+        ];
+        let error = replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            bindings,
+        )
+        .unwrap_err();
+        assert!(
+            error.ends_with("is already used to start a new chat"),
+            "{}",
+            error
+        );
+
+        assert_eq!(*registrar.calls.borrow(), 0);
+        assert_eq!(registered, initial());
+    }
+
+    #[test]
+    fn puts_everything_back_when_a_shortcut_is_taken() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar {
+            taken: vec![shortcut("F10")],
+            ..FakeRegistrar::with(&registered)
+        };
+        // F7 is released and F9 registered before F10 fails
+        let bindings = vec![
+            (shortcut("F8"), ShortcutAction::NewChat),
+            (shortcut("F9"), ShortcutAction::CaptureScreen),
+            (shortcut("F10"), ShortcutAction::PasteClipboard),
+        ];
+        let error = replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            bindings,
+        )
+        .unwrap_err();
+        assert!(error.contains("is already taken"), "{}", error);
+        assert_eq!(registrar.registered(), names(&["Alt+Space", "F8", "F7"]));
+        assert_eq!(registered, initial());
+    }
+
+    #[test]
+    fn puts_everything_back_when_a_shortcut_cant_be_released() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar {
+            stuck: vec![shortcut("F7")],
+            ..FakeRegistrar::with(&registered)
+        };
+        // F8 is released before F7 fails
+        let error = replace_bindings(
+            &registrar,
+            &mut registered,
+            |action| !is_quick_chat(action),
+            vec![],
+        )
+        .unwrap_err();
+        assert!(error.starts_with("Couldn't release"), "{}", error);
+        assert_eq!(registrar.registered(), names(&["Alt+Space", "F8", "F7"]));
+        assert_eq!(registered, initial());
+    }
+
+    #[test]
+    fn turns_the_quick_chat_shortcut_off_and_on() {
+        let mut registered = initial();
+        let registrar = FakeRegistrar::with(&registered);
+        replace_bindings(&registrar, &mut registered, is_quick_chat, vec![]).unwrap();
+        assert_eq!(registrar.registered(), names(&["F8", "F7"]));
+
+        let bindings = vec![(shortcut("Ctrl+Space"), ShortcutAction::ToggleQuickChat)];
+        replace_bindings(&registrar, &mut registered, is_quick_chat, bindings).unwrap();
+        assert_eq!(registrar.registered(), names(&["Ctrl+Space", "F8", "F7"]));
+        assert_eq!(registered.len(), 3);
+    }
+}
@@ -0,0 +1,526 @@
+//! Shrinks images to fit under a size limit before they're attached to a chat or
+//! sent to a model.
+//!
+//! Everything goes through the image crate, so it behaves the same on every
+//! platform: decode, apply the EXIF orientation, then find the best encoding that
+//! fits. JPEG quality is lowered first, and the image is only scaled down if even
+//! the lowest acceptable quality is too big. PNG is kept whenever it comes out
+//! smaller than the JPEG would, which is typical for screenshots of text (and
+//! JPEG blurs text).
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageEncoder, ImageFormat, RgbImage};
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+/// JPEG quality bounds when searching for the best fit at a given scale.
+const MAX_QUALITY: u8 = 90;
+const MIN_QUALITY: u8 = 60;
+
+/// Quality used while searching for a scale; high enough that text stays legible.
+const SCALE_SEARCH_QUALITY: u8 = 80;
+
+/// How many encodes the scale search gets, and how close (as a fraction of the
+/// original size) it needs to get to the largest scale that fits.
+const SCALE_SEARCH_STEPS: usize = 8;
+const SCALE_PRECISION: f64 = 0.01;
+
+/// How long outputs are kept in the shared output folder. Callers read them back
+/// (or copy them into the attachment store) straight away, so anything older is
+/// left over from an earlier resize or redaction.
+const OUTPUT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    /// The input was left as it was.
+    Original,
+}
+
+/// An image encoded to fit, still in memory.
+#[derive(Clone, Debug)]
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: OutputFormat,
+}
+
+/// Where `resize_file` put its output, and what it is.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResizedImage {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// "jpeg" or "png", or "original" if the input already fit and was returned as is.
+    pub format: OutputFormat,
+    pub size: u64,
+}
+
+/// Fits the image at `input` under `target_size_bytes`, writing the result to
+/// `output_dir`. Images that already fit and need no rotating are returned as is.
+pub fn resize_file(
+    input: &Path,
+    output_dir: &Path,
+    target_size_bytes: u64,
+) -> Result<ResizedImage, String> {
+    let start_time = Instant::now();
+    log::debug!("Starting image resize for: {}", input.display());
+
+    let data = fs::read(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    log::debug!(
+        "Original file size: {} bytes ({:.2} MB)",
+        data.len(),
+        data.len() as f64 / 1_048_576.0
+    );
+
+    if data.len() as u64 <= target_size_bytes && exif_orientation(&data) == 1 {
+        log::debug!("File already under target size, skipping compression");
+        let (width, height) = image::load_from_memory(&data)
+            .map(|img| (img.width(), img.height()))
+            .map_err(|e| e.to_string())?;
+        return Ok(ResizedImage {
+            path: input.to_string_lossy().into_owned(),
+            width,
+            height,
+            format: OutputFormat::Original,
+            size: data.len() as u64,
+        });
+    }
+
+    let encoded = fit_image(&data, target_size_bytes)?;
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    let extension = match encoded.format {
+        OutputFormat::Png => "png",
+        _ => "jpg",
+    };
+    // Unique per call, so concurrent resizes of files with the same name can't
+    // overwrite each other's output
+    let output_path = output_dir.join(format!(
+        "{}_resized_{}.{}",
+        stem,
+        uuid::Uuid::new_v4(),
+        extension
+    ));
+    fs::write(&output_path, &encoded.data).map_err(|e| e.to_string())?;
+
+    log::info!(
+        "Final size: {} bytes ({:.2} MB), {}x{} {:?}",
+        encoded.data.len(),
+        encoded.data.len() as f64 / 1_048_576.0,
+        encoded.width,
+        encoded.height,
+        encoded.format
+    );
+    log::debug!("Total image processing took: {:?}", start_time.elapsed());
+
+    Ok(ResizedImage {
+        path: output_path.to_string_lossy().into_owned(),
+        width: encoded.width,
+        height: encoded.height,
+        format: encoded.format,
+        size: encoded.data.len() as u64,
+    })
+}
+
+/// Deletes files in `output_dir` that haven't been modified for OUTPUT_MAX_AGE,
+/// returning how many were removed. Failures are logged and skipped.
+pub fn remove_expired_outputs(output_dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(output_dir) else {
+        return 0;
+    };
+    let cutoff = SystemTime::now() - OUTPUT_MAX_AGE;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let expired = entry.metadata().is_ok_and(|metadata| {
+            metadata.is_file() && metadata.modified().is_ok_and(|modified| modified < cutoff)
+        });
+        if !expired {
+            continue;
+        }
+        match fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => log::warn!("Failed to remove {}: {}", entry.path().display(), e),
+        }
+    }
+    if removed > 0 {
+        log::debug!("Removed {} expired image outputs", removed);
+    }
+    removed
+}
+
+/// Decodes `data` (any format the image crate reads: PNG, JPEG, WebP, GIF, BMP,
+/// TIFF...), rotates it upright and encodes it to fit under `target_size_bytes`.
+pub fn fit_image(data: &[u8], target_size_bytes: u64) -> Result<EncodedImage, String> {
+    let format = image::guess_format(data).map_err(|e| e.to_string())?;
+    let img = image::load_from_memory_with_format(data, format).map_err(|e| e.to_string())?;
+    let img = apply_orientation(img, exif_orientation(data));
+    encode_to_fit(&img, format == ImageFormat::Png, target_size_bytes)
+}
+
+/// Finds the largest, highest quality encoding of `img` under `target_size_bytes`.
+/// With `allow_png`, a PNG is returned instead when it fits and beats the JPEG.
+pub fn encode_to_fit(
+    img: &DynamicImage,
+    allow_png: bool,
+    target_size_bytes: u64,
+) -> Result<EncodedImage, String> {
+    let fits = |encoded: &EncodedImage| encoded.data.len() as u64 <= target_size_bytes;
+
+    // Full size first: only quality needs to give
+    let rgb = flatten(img);
+    let (smallest, best) = best_quality(&rgb, MIN_QUALITY, target_size_bytes)?;
+    if let Some(jpeg) = best {
+        return prefer_png(img, allow_png, jpeg, &fits);
+    }
+
+    // Scale down. JPEG size is roughly proportional to pixel count, so the first
+    // guess comes from how far over the smallest full-size encoding was, and
+    // after that it's a bisection between the largest scale known to fit and
+    // the smallest known not to
+    let mut too_big = 1.0;
+    let mut largest_fit: Option<(f64, RgbImage)> = None;
+    let mut scale = (target_size_bytes as f64 / smallest.data.len() as f64).sqrt() * 0.95;
+    for _ in 0..SCALE_SEARCH_STEPS {
+        let scaled = scale_rgb(&rgb, scale);
+        let encoded = encode_jpeg(&scaled, SCALE_SEARCH_QUALITY)?;
+        let ratio = target_size_bytes as f64 / encoded.data.len() as f64;
+        if fits(&encoded) {
+            largest_fit = Some((scale, scaled));
+        } else {
+            too_big = scale;
+        }
+
+        scale = match &largest_fit {
+            Some((fit, _)) if too_big - fit < SCALE_PRECISION => break,
+            Some((fit, _)) => (fit + too_big) / 2.0,
+            None => scale * ratio.sqrt() * 0.95,
+        };
+    }
+
+    let Some((scale, scaled)) = largest_fit else {
+        return Err(format!(
+            "Couldn't shrink the image under {} bytes",
+            target_size_bytes
+        ));
+    };
+    // The scale was found at SCALE_SEARCH_QUALITY, so there may be room to go higher
+    let (_, best) = best_quality(&scaled, SCALE_SEARCH_QUALITY, target_size_bytes)?;
+    let jpeg = best.ok_or("Image no longer fits after scaling")?;
+    let scaled_img = img.resize_exact(jpeg.width, jpeg.height, FilterType::CatmullRom);
+    log::debug!("Scaled to {:.0}% to fit", scale * 100.0);
+    prefer_png(&scaled_img, allow_png, jpeg, &fits)
+}
+
+fn prefer_png(
+    img: &DynamicImage,
+    allow_png: bool,
+    jpeg: EncodedImage,
+    fits: &dyn Fn(&EncodedImage) -> bool,
+) -> Result<EncodedImage, String> {
+    if !allow_png {
+        return Ok(jpeg);
+    }
+    let png = encode_png(img)?;
+    if fits(&png) && png.data.len() <= jpeg.data.len() {
+        Ok(png)
+    } else {
+        Ok(jpeg)
+    }
+}
+
+/// Binary searches for the highest JPEG quality between `min_quality` and
+/// MAX_QUALITY that fits. Returns the encoding at `min_quality` too, so callers
+/// can see how far over it was when nothing fits.
+fn best_quality(
+    rgb: &RgbImage,
+    min_quality: u8,
+    target_size_bytes: u64,
+) -> Result<(EncodedImage, Option<EncodedImage>), String> {
+    let fits = |encoded: &EncodedImage| encoded.data.len() as u64 <= target_size_bytes;
+
+    let highest = encode_jpeg(rgb, MAX_QUALITY)?;
+    if fits(&highest) {
+        return Ok((highest.clone(), Some(highest)));
+    }
+    let lowest = encode_jpeg(rgb, min_quality)?;
+    if !fits(&lowest) {
+        return Ok((lowest, None));
+    }
+
+    // Invariant: `low` fits, `high` doesn't
+    let (mut low, mut high) = (min_quality, MAX_QUALITY);
+    let mut best = lowest.clone();
+    while high - low > 1 {
+        let quality = low + (high - low) / 2;
+        let encoded = encode_jpeg(rgb, quality)?;
+        if fits(&encoded) {
+            low = quality;
+            best = encoded;
+        } else {
+            high = quality;
+        }
+    }
+    Ok((lowest, Some(best)))
+}
+
+fn encode_jpeg(rgb: &RgbImage, quality: u8) -> Result<EncodedImage, String> {
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, quality)
+        .encode_image(rgb)
+        .map_err(|e| e.to_string())?;
+    Ok(EncodedImage {
+        data,
+        width: rgb.width(),
+        height: rgb.height(),
+        format: OutputFormat::Jpeg,
+    })
+}
+
+fn encode_png(img: &DynamicImage) -> Result<EncodedImage, String> {
+    let mut data = Vec::new();
+    let encoder = PngEncoder::new_with_quality(
+        Cursor::new(&mut data),
+        CompressionType::Best,
+        PngFilter::Adaptive,
+    );
+    // Keep transparency, but don't spend bytes on an alpha channel nobody uses
+    let result = if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        encoder.write_image(
+            rgba.as_raw(),
+            rgba.width(),
+            rgba.height(),
+            image::ColorType::Rgba8,
+        )
+    } else {
+        let rgb = img.to_rgb8();
+        encoder.write_image(
+            rgb.as_raw(),
+            rgb.width(),
+            rgb.height(),
+            image::ColorType::Rgb8,
+        )
+    };
+    result.map_err(|e| e.to_string())?;
+    Ok(EncodedImage {
+        data,
+        width: img.width(),
+        height: img.height(),
+        format: OutputFormat::Png,
+    })
+}
+
+/// JPEG has no alpha channel, so composite transparent pixels onto white rather
+/// than letting them come out black.
+fn flatten(img: &DynamicImage) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+fn scale_rgb(rgb: &RgbImage, scale: f64) -> RgbImage {
+    let width = ((rgb.width() as f64 * scale).round() as u32).max(1);
+    let height = ((rgb.height() as f64 * scale).round() as u32).max(1);
+    image::imageops::resize(rgb, width, height, FilterType::CatmullRom)
+}
+
+/// The EXIF orientation tag (1-8), or 1 if there isn't one. Cameras save photos
+/// sideways and rely on this tag; once we re-encode, the tag is gone, so the
+/// pixels have to be rotated to match.
+pub fn exif_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .filter(|orientation| (1..=8).contains(orientation))
+        .unwrap_or(1)
+}
+
+pub fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+            .iter()
+            .collect();
+        fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+    }
+
+    fn is_red(img: &DynamicImage, x: u32, y: u32) -> bool {
+        let [r, g, b] = img.to_rgb8().get_pixel(x, y).0;
+        r > 200 && g < 80 && b < 80
+    }
+
+    /// The fixtures are 32x16 with red in the top-left quadrant. Returns the
+    /// upright image and which of its corners is red.
+    fn upright(name: &str) -> (DynamicImage, [bool; 4]) {
+        let data = fixture(name);
+        let encoded = fit_image(&data, 1_000_000).unwrap();
+        assert_eq!(encoded.format, OutputFormat::Jpeg);
+        let img = image::load_from_memory(&encoded.data).unwrap();
+        let (right, bottom) = (img.width() - 2, img.height() - 2);
+        let corners = [
+            is_red(&img, 1, 1),
+            is_red(&img, right, 1),
+            is_red(&img, 1, bottom),
+            is_red(&img, right, bottom),
+        ];
+        (img, corners)
+    }
+
+    #[test]
+    fn reads_orientations_from_exif() {
+        assert_eq!(exif_orientation(&fixture("orientation-3.jpg")), 3);
+        assert_eq!(exif_orientation(&fixture("orientation-6.jpg")), 6);
+        assert_eq!(exif_orientation(&fixture("orientation-8.jpg")), 8);
+        assert_eq!(exif_orientation(&fixture("screenshot.png")), 1);
+    }
+
+    #[test]
+    fn rotates_orientation_3_by_180_degrees() {
+        let (img, corners) = upright("orientation-3.jpg");
+        assert_eq!((img.width(), img.height()), (32, 16));
+        assert_eq!(corners, [false, false, false, true]);
+    }
+
+    #[test]
+    fn rotates_orientation_6_clockwise() {
+        let (img, corners) = upright("orientation-6.jpg");
+        assert_eq!((img.width(), img.height()), (16, 32));
+        assert_eq!(corners, [false, true, false, false]);
+    }
+
+    #[test]
+    fn rotates_orientation_8_counterclockwise() {
+        let (img, corners) = upright("orientation-8.jpg");
+        assert_eq!((img.width(), img.height()), (16, 32));
+        assert_eq!(corners, [false, false, true, false]);
+    }
+
+    #[test]
+    fn keeps_png_when_smaller_than_jpeg() {
+        let data = fixture("screenshot.png");
+        let encoded = fit_image(&data, 1_000_000).unwrap();
+        assert_eq!(encoded.format, OutputFormat::Png);
+        assert_eq!((encoded.width, encoded.height), (240, 120));
+
+        let img = image::load_from_memory(&data).unwrap();
+        let jpeg = encode_jpeg(&flatten(&img), MAX_QUALITY).unwrap();
+        assert!(encoded.data.len() < jpeg.data.len());
+    }
+
+    #[test]
+    fn never_picks_png_for_jpeg_input() {
+        let encoded = fit_image(&fixture("photo.jpg"), 1_000_000).unwrap();
+        assert_eq!(encoded.format, OutputFormat::Jpeg);
+    }
+
+    #[test]
+    fn scales_an_oversized_photo_under_the_target() {
+        let data = fixture("photo.jpg");
+        // Half of what the lowest quality gets to, so only scaling can fit it
+        let img = image::load_from_memory(&data).unwrap();
+        let lowest = encode_jpeg(&img.to_rgb8(), MIN_QUALITY).unwrap();
+        let target = lowest.data.len() / 2;
+        assert!(data.len() > target);
+
+        let encoded = fit_image(&data, target as u64).unwrap();
+        assert!(encoded.data.len() <= target);
+        assert!(encoded.width < 160 && encoded.height < 120);
+        let decoded = image::load_from_memory(&encoded.data).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (encoded.width, encoded.height)
+        );
+    }
+
+    #[test]
+    fn writes_a_new_output_for_every_resize() {
+        let dir =
+            std::env::temp_dir().join(format!("camp-image-pipeline-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("photo.jpg");
+        let data = fixture("photo.jpg");
+        fs::write(&input, &data).unwrap();
+        let target = data.len() as u64 - 1;
+
+        let first = resize_file(&input, &dir, target).unwrap();
+        let second = resize_file(&input, &dir, target).unwrap();
+        let sizes: Vec<Option<u64>> = [&first, &second]
+            .iter()
+            .map(|resized| fs::metadata(&resized.path).ok().map(|m| m.len()))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_ne!(first.path, second.path);
+        assert_eq!(sizes, [Some(first.size), Some(second.size)]);
+        for resized in [&first, &second] {
+            let name = Path::new(&resized.path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy();
+            assert!(name.starts_with("photo_resized_"), "{}", name);
+            assert!(name.ends_with(".jpg"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn removes_only_expired_outputs() {
+        let dir =
+            std::env::temp_dir().join(format!("camp-image-pipeline-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("old_resized.jpg");
+        let new = dir.join("new_resized.jpg");
+        fs::write(&old, "old").unwrap();
+        fs::write(&new, "new").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .and_then(|file| file.set_modified(SystemTime::now() - OUTPUT_MAX_AGE * 2))
+            .unwrap();
+
+        let removed = remove_expired_outputs(&dir);
+        let (old_exists, new_exists) = (old.exists(), new.exists());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(removed, 1);
+        assert!(!old_exists);
+        assert!(new_exists);
+        assert_eq!(remove_expired_outputs(&dir), 0);
+    }
+}
@@ -0,0 +1,484 @@
+//! Imports conversations from Claude.ai conversation exports.
+//! You can always view an example format file in /resources/claude_conversations.json.
+//!
+//! This is a port of AnthropicImporter.ts and should stay in sync with it. As there,
+//! attachments can't be imported (the export only gives us file names), and tool
+//! use and artifacts are rendered as raw JSON rather than mapped onto our toolsets.
+
+use rusqlite::Connection;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::path::Path;
+
+use super::{
+    create_chat, import_each, insert_turn, new_id, resolve_project, restore_chat_updated_at,
+    sqlite_datetime_from_rfc3339, tool_call_parts, ExportedConversation, ImportProgress,
+    ImportSummary, ImportedReply, ImportedTurn, MessagePart, ToolCall, ToolMetadata, ToolResult,
+};
+
+pub const ANTHROPIC_IMPORT_PREFIX: &str = "anthropic_import";
+
+const DEFAULT_PROJECT_NAME: &str = "Anthropic imports";
+
+/// Model recorded on imported assistant messages, same as the TypeScript importer.
+const IMPORTED_MODEL: &str = "anthropic::claude-3-sonnet";
+
+/// XML tags Claude embeds in response text that we split out into their own parts.
+const XML_TAGS: [&str; 7] = [
+    "antthinking",
+    "antThinking", // at some point Anthropic stopped capitalizing this tag
+    "antartifact",
+    "antArtifact",
+    "antml:thinking",
+    "antml:function_calls",
+    "antml:artifact",
+];
+
+#[derive(Deserialize)]
+struct ClaudeConversation {
+    #[serde(default)]
+    name: String,
+    created_at: String,
+    updated_at: String,
+    #[serde(default)]
+    chat_messages: Vec<ClaudeMessage>,
+}
+
+impl ExportedConversation for ClaudeConversation {
+    fn title(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Deserialize)]
+struct ClaudeMessage {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    content: Vec<ClaudeContentItem>,
+    sender: String,
+    created_at: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct ClaudeContentItem {
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<String>,
+    name: Option<String>,
+    content: Option<Value>, // any sub content, only used for tool results, which we JSON encode to display
+}
+
+impl ClaudeMessage {
+    /// Older exports only have `text`; newer ones split it into content blocks.
+    fn content(&self) -> Cow<'_, [ClaudeContentItem]> {
+        if !self.content.is_empty() {
+            return Cow::Borrowed(&self.content);
+        }
+        Cow::Owned(vec![ClaudeContentItem {
+            kind: "text".to_string(),
+            text: Some(self.text.clone()),
+            name: None,
+            content: None,
+        }])
+    }
+}
+
+enum TextSegment<'a> {
+    Text(&'a str),
+    Xml { tag_name: String, raw: &'a str },
+}
+
+/// Imports every conversation in a Claude export into `project_id`, or into the
+/// shared "Anthropic imports" project if none is given.
+pub fn import_conversations(
+    conn: &mut Connection,
+    path: &Path,
+    project_id: Option<&str>,
+    on_progress: impl FnMut(ImportProgress),
+) -> Result<ImportSummary, String> {
+    let project_id = resolve_project(conn, project_id, DEFAULT_PROJECT_NAME)?;
+    import_each(conn, path, project_id, import_conversation, on_progress)
+}
+
+fn import_conversation(
+    conn: &Connection,
+    conversation: &ClaudeConversation,
+    project_id: &str,
+) -> Result<(), String> {
+    log::info!("Importing conversation: {}", conversation.name);
+
+    let created_at = sqlite_datetime_from_rfc3339(&conversation.created_at)
+        .ok_or_else(|| format!("Invalid created_at: {}", conversation.created_at))?;
+    let updated_at =
+        sqlite_datetime_from_rfc3339(&conversation.updated_at).unwrap_or(created_at.clone());
+
+    let chat_id = create_chat(conn, &conversation.name, &created_at, project_id, None)
+        .map_err(|e| e.to_string())?;
+
+    // Process each message pair (user message + assistant response)
+    let messages = &conversation.chat_messages;
+    let mut level = 0;
+    let mut i = 0;
+    while i < messages.len() {
+        let human = &messages[i];
+        if human.sender != "human" {
+            i += 1;
+            continue;
+        }
+
+        let reply = messages
+            .get(i + 1)
+            .filter(|message| message.sender == "assistant");
+        let turn = ImportedTurn {
+            user_text: extract_text(human),
+            user_created_at: human
+                .created_at
+                .as_deref()
+                .and_then(sqlite_datetime_from_rfc3339),
+            reply: reply.map(|assistant| ImportedReply {
+                text: extract_text(assistant),
+                model: IMPORTED_MODEL.to_string(),
+                created_at: assistant
+                    .created_at
+                    .as_deref()
+                    .and_then(sqlite_datetime_from_rfc3339),
+                parts: message_parts(assistant),
+            }),
+        };
+        insert_turn(conn, &chat_id, level, &turn, None).map_err(|e| e.to_string())?;
+
+        i += if reply.is_some() { 2 } else { 1 };
+        level += 1;
+    }
+
+    // reset updated_at since the messages trigger will have overridden it
+    restore_chat_updated_at(conn, &chat_id, &updated_at).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn pretty_xml_tag_name(tag_name: &str) -> String {
+    match tag_name {
+        "antartifact" => "Artifact".to_string(),
+        "antml:function_calls" => "Function Calls".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Splits text into plain text and the known XML tags embedded in it.
+fn parse_xml_tags(text: &str) -> Vec<TextSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut remaining = text;
+
+    loop {
+        // Find the earliest complete occurrence of any known tag
+        let earliest = XML_TAGS
+            .iter()
+            .filter_map(|tag| {
+                let open = remaining.find(&format!("<{}", tag))?;
+                let close_tag = format!("</{}>", tag);
+                let close = remaining[open..].find(&close_tag)? + open;
+                Some((open, close + close_tag.len(), tag.to_lowercase()))
+            })
+            .min_by_key(|(open, _, _)| *open);
+
+        let Some((start, end, tag_name)) = earliest else {
+            let rest = remaining.trim();
+            if !rest.is_empty() {
+                segments.push(TextSegment::Text(rest));
+            }
+            break;
+        };
+
+        let before = remaining[..start].trim();
+        if !before.is_empty() {
+            segments.push(TextSegment::Text(before));
+        }
+        segments.push(TextSegment::Xml {
+            tag_name,
+            raw: &remaining[start..end],
+        });
+        remaining = &remaining[end..];
+    }
+
+    if segments.is_empty() {
+        segments.push(TextSegment::Text(text));
+    }
+    segments
+}
+
+/// Returns the content between a tag's opening and closing tags.
+fn xml_inner_content(raw: &str) -> &str {
+    match (raw.find('>'), raw.rfind("</")) {
+        (Some(open_end), Some(close_start)) if close_start > open_end => {
+            raw[open_end + 1..close_start].trim()
+        }
+        _ => raw,
+    }
+}
+
+/// Converts a message's content blocks into message parts, following our pattern where
+/// assistant text carries optional tool calls and tool results get parts of their own.
+///
+/// Claude exports don't include ids linking tool calls to results, so we match them by order.
+fn message_parts(message: &ClaudeMessage) -> Vec<MessagePart> {
+    let content = message.content();
+    let mut parts: Vec<MessagePart> = Vec::new();
+    let mut current = MessagePart::default();
+    let mut pending_call_ids: VecDeque<String> = VecDeque::new();
+
+    for item in content.iter() {
+        match item.kind.as_str() {
+            "text" => {
+                let Some(text) = item.text.as_deref() else {
+                    continue;
+                };
+                for segment in parse_xml_tags(text) {
+                    match segment {
+                        TextSegment::Text(text) => {
+                            // If we have pending tool calls, finalize that part first
+                            if !current.tool_calls.is_empty() {
+                                parts.push(std::mem::take(&mut current));
+                            }
+                            current.content = text.to_string();
+                        }
+                        TextSegment::Xml { tag_name, raw } => {
+                            if !current.is_empty() {
+                                parts.push(std::mem::take(&mut current));
+                            }
+                            let inner = xml_inner_content(raw);
+                            if tag_name == "antthinking" || tag_name == "antml:thinking" {
+                                // The UI renders <think> blocks as thinking; imported ones are always complete
+                                parts.push(MessagePart::text(format!("<think>{}</think>", inner)));
+                            } else {
+                                parts.extend(tool_call_parts(
+                                    pretty_xml_tag_name(&tag_name),
+                                    format!("XML {} tag from Anthropic", tag_name),
+                                    json!({}),
+                                    inner.to_string(),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            "tool_use" => {
+                let id = new_id();
+                pending_call_ids.push_back(id.clone());
+                current.tool_calls.push(ToolCall {
+                    id,
+                    namespaced_tool_name: format!(
+                        "{}_{}",
+                        ANTHROPIC_IMPORT_PREFIX,
+                        item.name.as_deref().unwrap_or("unknown")
+                    ),
+                    args: json!({}),
+                    tool_metadata: ToolMetadata {
+                        description: "Imported tool call from Anthropic".to_string(),
+                    },
+                });
+            }
+            "tool_result" => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+                let id = pending_call_ids.pop_front().unwrap_or_else(new_id);
+                let content = json!({ "type": "tool_result", "content": item.content });
+                parts.push(MessagePart {
+                    tool_results: vec![ToolResult {
+                        id,
+                        content: serde_json::to_string_pretty(&content).unwrap_or_default(),
+                    }],
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    if parts.is_empty() {
+        parts.push(MessagePart::text(extract_text(message)));
+    }
+    parts
+}
+
+/// Extracts the plain text shown for a message, leaving out the XML tags that
+/// `message_parts` renders separately.
+fn extract_text(message: &ClaudeMessage) -> String {
+    let content = message.content();
+    let mut text_parts: Vec<String> = Vec::new();
+    for item in content.iter() {
+        match item.kind.as_str() {
+            "text" => {
+                let Some(text) = item.text.as_deref() else {
+                    continue;
+                };
+                for segment in parse_xml_tags(text) {
+                    if let TextSegment::Text(text) = segment {
+                        let text = text.trim();
+                        if !text.is_empty() {
+                            text_parts.push(text.to_string());
+                        }
+                    }
+                }
+            }
+            "tool_use" => text_parts.push(format!(
+                "[Tool used: {}]",
+                item.name.as_deref().unwrap_or("unknown")
+            )),
+            "tool_result" => text_parts.push("[Tool result received]".to_string()),
+            _ => {}
+        }
+    }
+
+    let text = text_parts.join("\n\n");
+    let text = text.trim();
+    if text.is_empty() {
+        "No content".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+    use rusqlite::params;
+
+    fn sample_export() -> &'static Path {
+        Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../resources/claude_conversations.json"
+        ))
+    }
+
+    fn count(conn: &Connection, sql: &str, project_id: &str) -> i64 {
+        conn.query_row(sql, params![project_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_the_sample_export() {
+        let mut conn = migrator::open_in_memory();
+        let mut progress = Vec::new();
+        let summary =
+            import_conversations(&mut conn, sample_export(), None, |p| progress.push(p)).unwrap();
+
+        assert_eq!(summary.imported, 3);
+        assert_eq!(summary.failed, 0);
+        assert_eq!(progress.len(), 4);
+        assert!(progress.iter().all(|p| p.total == 3 && p.succeeded));
+
+        let (name, is_imported): (String, bool) = conn
+            .query_row(
+                "SELECT name, is_imported FROM projects WHERE id = ?1",
+                params![summary.project_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(name, DEFAULT_PROJECT_NAME);
+        assert!(is_imported);
+
+        let project_id = summary.project_id.as_str();
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM chats WHERE project_id = ?1",
+                project_id
+            ),
+            3
+        );
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM messages m JOIN chats c ON c.id = m.chat_id WHERE c.project_id = ?1",
+                project_id
+            ),
+            8
+        );
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM message_sets s JOIN chats c ON c.id = s.chat_id WHERE c.project_id = ?1",
+                project_id
+            ),
+            8
+        );
+
+        // The last turn's messages would otherwise have bumped updated_at to now
+        let (chat_id, created_at, updated_at): (String, String, String) = conn
+            .query_row(
+                "SELECT id, created_at, updated_at FROM chats WHERE title = 'Today''s Top 5 News Stories'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(created_at, "2025-06-10 17:54:22");
+        assert_eq!(updated_at, "2025-06-10 17:55:05");
+
+        let messages: Vec<(String, i64, String)> = conn
+            .prepare(
+                "SELECT m.model, s.level, m.created_at FROM messages m
+                    JOIN message_sets s ON s.id = m.message_set_id
+                    WHERE m.chat_id = ?1 ORDER BY s.level",
+            )
+            .unwrap()
+            .query_map(params![chat_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].0, "user");
+        assert_eq!(messages[1].0, IMPORTED_MODEL);
+        assert_eq!(
+            messages.iter().map(|m| m.1).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(messages[0].2, "2025-06-10 17:54:23");
+        assert_eq!(messages[1].2, "2025-06-10 17:54:47");
+    }
+
+    #[test]
+    fn flags_a_chosen_project_as_imported() {
+        let mut conn = migrator::open_in_memory();
+        conn.execute(
+            "INSERT INTO projects (id, name, created_at, updated_at, is_collapsed, magic_projects_enabled)
+                VALUES ('mine', 'Mine', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 0, 0)",
+            [],
+        )
+        .unwrap();
+
+        let summary =
+            import_conversations(&mut conn, sample_export(), Some("mine"), |_| {}).unwrap();
+        assert_eq!(summary.project_id, "mine");
+        assert_eq!(summary.imported, 3);
+
+        let is_imported: bool = conn
+            .query_row(
+                "SELECT is_imported FROM projects WHERE id = 'mine'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(is_imported);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM chats WHERE project_id = ?1",
+                "mine"
+            ),
+            3
+        );
+
+        let missing = import_conversations(&mut conn, sample_export(), Some("missing"), |_| {});
+        assert_eq!(missing.unwrap_err(), "Project not found: missing");
+    }
+}
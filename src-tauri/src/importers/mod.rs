@@ -0,0 +1,469 @@
+//! Native importers for chat history exported from other LLM providers' UIs.
+//!
+//! These mirror the TypeScript importers in src/core/chorus/importers, but run
+//! entirely in Rust so that large exports (thousands of conversations, hundreds
+//! of megabytes of JSON) can be streamed straight into chats.db without ever
+//! holding the whole file in the webview.
+//!
+//! Each conversation is written inside its own transaction, so a malformed
+//! conversation only rolls back itself and the rest of the import carries on.
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::{DeserializeOwned, Error as _, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserializer, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::marker::PhantomData;
+use std::path::Path;
+
+pub mod claude;
+pub mod openai;
+
+/// Payload of the `import_progress` event, emitted once per conversation.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub current: usize,
+    pub total: usize,
+    pub title: String,
+    pub succeeded: bool,
+    pub error: Option<String>,
+}
+
+/// Result of a whole import, matching what the TypeScript importers return.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub project_id: String,
+    pub imported: usize,
+    pub failed: usize,
+}
+
+/// Serialized shape of `UserToolCall` from src/core/chorus/Toolsets.ts.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub id: String,
+    pub namespaced_tool_name: String,
+    pub args: Value,
+    pub tool_metadata: ToolMetadata,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolMetadata {
+    pub description: String,
+}
+
+/// Serialized shape of `UserToolResult` from src/core/chorus/Toolsets.ts.
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolResult {
+    pub id: String,
+    pub content: String,
+}
+
+/// One row of `message_parts` for an imported assistant message.
+#[derive(Clone, Debug, Default)]
+pub struct MessagePart {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub tool_results: Vec<ToolResult>,
+}
+
+impl MessagePart {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty() && self.tool_calls.is_empty() && self.tool_results.is_empty()
+    }
+}
+
+/// Builds the pair of parts (call, then result) we use to render opaque provider
+/// data such as tool invocations or reasoning as a collapsible tool block.
+pub fn tool_call_parts(
+    namespaced_tool_name: String,
+    description: String,
+    args: Value,
+    result: String,
+) -> [MessagePart; 2] {
+    let id = new_id();
+    [
+        MessagePart {
+            tool_calls: vec![ToolCall {
+                id: id.clone(),
+                namespaced_tool_name,
+                args,
+                tool_metadata: ToolMetadata { description },
+            }],
+            ..Default::default()
+        },
+        MessagePart {
+            tool_results: vec![ToolResult {
+                id,
+                content: result,
+            }],
+            ..Default::default()
+        },
+    ]
+}
+
+/// The assistant side of an imported turn.
+pub struct ImportedReply {
+    pub text: String,
+    pub model: String,
+    pub created_at: Option<String>,
+    pub parts: Vec<MessagePart>,
+}
+
+/// A user message and (optionally) the assistant reply to it. Each turn becomes
+/// a user message set followed by an ai message set.
+pub struct ImportedTurn {
+    pub user_text: String,
+    pub user_created_at: Option<String>,
+    pub reply: Option<ImportedReply>,
+}
+
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string().to_lowercase()
+}
+
+/// Formats a timestamp the way SQLite's CURRENT_TIMESTAMP does: "YYYY-MM-DD HH:MM:SS" in UTC.
+pub fn format_sqlite_datetime(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Parses an RFC 3339 timestamp (as used in Claude exports) into SQLite format.
+pub fn sqlite_datetime_from_rfc3339(value: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date| format_sqlite_datetime(date.with_timezone(&Utc)))
+}
+
+/// Converts fractional unix seconds (as used in ChatGPT exports) into SQLite format.
+pub fn sqlite_datetime_from_unix(seconds: f64) -> Option<String> {
+    let millis = (seconds * 1000.0).round() as i64;
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(format_sqlite_datetime)
+}
+
+/// Returns the id of the project imports should be written to.
+///
+/// If the caller chose a project we flag it as imported; otherwise we reuse (or
+/// create) a project with the given default name, like createProject in util.ts.
+pub fn resolve_project(
+    conn: &Connection,
+    project_id: Option<&str>,
+    default_name: &str,
+) -> Result<String, String> {
+    if let Some(project_id) = project_id {
+        let updated = conn
+            .execute(
+                "UPDATE projects SET is_imported = 1 WHERE id = ?1",
+                params![project_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err(format!("Project not found: {}", project_id));
+        }
+        return Ok(project_id.to_string());
+    }
+
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT id FROM projects WHERE name = ?1",
+            params![default_name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        log::info!("Using existing '{}' project", default_name);
+        return Ok(id);
+    }
+
+    let id = new_id();
+    let now = format_sqlite_datetime(Utc::now());
+    conn.execute(
+        "INSERT INTO projects (id, name, created_at, updated_at, is_collapsed, magic_projects_enabled, is_imported)
+            VALUES (?1, ?2, ?3, ?3, 0, 0, 1)",
+        params![id, default_name, now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    log::info!("Created '{}' project for conversation import", default_name);
+    Ok(id)
+}
+
+pub fn create_chat(
+    conn: &Connection,
+    title: &str,
+    created_at: &str,
+    project_id: &str,
+    parent_chat_id: Option<&str>,
+) -> rusqlite::Result<String> {
+    let id = new_id();
+    conn.execute(
+        "INSERT INTO chats (id, title, project_id, created_at, updated_at, quick_chat, is_new_chat, pinned, parent_chat_id)
+            VALUES (?1, ?2, ?3, ?4, ?4, 0, 0, 0, ?5)",
+        params![id, title, project_id, created_at, parent_chat_id],
+    )?;
+    Ok(id)
+}
+
+/// Restores a chat's original updated_at once all of its messages are written,
+/// since update_chats_timestamp_on_message bumps it on every insert.
+pub fn restore_chat_updated_at(
+    conn: &Connection,
+    chat_id: &str,
+    updated_at: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE chats SET updated_at = ?1 WHERE id = ?2",
+        params![updated_at, chat_id],
+    )?;
+    Ok(())
+}
+
+/// Ids of the messages written for one turn.
+#[derive(Clone, Debug)]
+pub struct TurnMessageIds {
+    pub user: String,
+    pub reply: Option<String>,
+}
+
+/// Writes one turn as a user message set at `level * 2` and, if there is a
+/// reply, an ai message set at `level * 2 + 1`, matching createMessageSet in util.ts.
+///
+/// When the turn is a copy of one already imported into a parent chat, pass that
+/// turn's ids as `branched_from` so messages link back the same way branching in
+/// the UI does.
+pub fn insert_turn(
+    conn: &Connection,
+    chat_id: &str,
+    level: i64,
+    turn: &ImportedTurn,
+    branched_from: Option<&TurnMessageIds>,
+) -> rusqlite::Result<TurnMessageIds> {
+    let user_message_set_id = new_id();
+    conn.execute(
+        "INSERT INTO message_sets (id, chat_id, level, type, selected_block_type, created_at)
+            VALUES (?1, ?2, ?3, 'user', 'user', COALESCE(?4, CURRENT_TIMESTAMP))",
+        params![
+            user_message_set_id,
+            chat_id,
+            level * 2,
+            turn.user_created_at
+        ],
+    )?;
+    let user_message_id = new_id();
+    conn.execute(
+        "INSERT INTO messages (id, chat_id, message_set_id, text, model, selected, block_type, state, is_review, level, created_at, branched_from_id)
+            VALUES (?1, ?2, ?3, ?4, 'user', 1, 'user', 'idle', 0, NULL, COALESCE(?5, CURRENT_TIMESTAMP), ?6)",
+        params![
+            user_message_id,
+            chat_id,
+            user_message_set_id,
+            turn.user_text,
+            turn.user_created_at,
+            branched_from.map(|ids| ids.user.as_str())
+        ],
+    )?;
+
+    let Some(reply) = &turn.reply else {
+        return Ok(TurnMessageIds {
+            user: user_message_id,
+            reply: None,
+        });
+    };
+
+    // 'tools' as the selected_block_type matches what the UI writes for new replies
+    let ai_message_set_id = new_id();
+    conn.execute(
+        "INSERT INTO message_sets (id, chat_id, level, type, selected_block_type, created_at)
+            VALUES (?1, ?2, ?3, 'ai', 'tools', COALESCE(?4, CURRENT_TIMESTAMP))",
+        params![ai_message_set_id, chat_id, level * 2 + 1, reply.created_at],
+    )?;
+
+    let assistant_message_id = new_id();
+    conn.execute(
+        "INSERT INTO messages (id, chat_id, message_set_id, text, model, selected, block_type, state, is_review, level, created_at, branched_from_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, 1, 'tools', 'idle', 0, 0, COALESCE(?6, CURRENT_TIMESTAMP), ?7)",
+        params![
+            assistant_message_id,
+            chat_id,
+            ai_message_set_id,
+            reply.text,
+            reply.model,
+            reply.created_at,
+            branched_from.and_then(|ids| ids.reply.as_deref())
+        ],
+    )?;
+
+    for (i, part) in reply.parts.iter().enumerate() {
+        let tool_calls = if part.tool_calls.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&part.tool_calls).ok()
+        };
+        let tool_results = if part.tool_results.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&part.tool_results).ok()
+        };
+        conn.execute(
+            "INSERT INTO message_parts (chat_id, message_id, level, content, tool_calls, tool_results)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                chat_id,
+                assistant_message_id,
+                i as i64,
+                part.content,
+                tool_calls,
+                tool_results
+            ],
+        )?;
+    }
+
+    Ok(TurnMessageIds {
+        user: user_message_id,
+        reply: Some(assistant_message_id),
+    })
+}
+
+/// A single conversation as it appears in a provider's export file.
+pub trait ExportedConversation: DeserializeOwned {
+    /// Display title, used for progress events.
+    fn title(&self) -> &str;
+}
+
+struct ArrayVisitor<T, F> {
+    on_item: F,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T, F> Visitor<'de> for ArrayVisitor<T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<(), String>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of conversations")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(item) = seq.next_element::<T>()? {
+            (self.on_item)(item).map_err(A::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams the elements of a top-level JSON array one at a time, so memory use
+/// is bounded by the largest single conversation rather than the whole export.
+pub fn for_each_array_item<T, F>(path: &Path, on_item: F) -> Result<(), String>
+where
+    T: DeserializeOwned,
+    F: FnMut(T) -> Result<(), String>,
+{
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+    deserializer
+        .deserialize_seq(ArrayVisitor {
+            on_item,
+            marker: PhantomData,
+        })
+        .map_err(|e| format!("Invalid export format: {}", e))
+}
+
+/// Counts the elements of a top-level JSON array without materializing them,
+/// so progress events can report a total before the real pass starts.
+pub fn count_array_items(path: &Path) -> Result<usize, String> {
+    let mut total = 0;
+    for_each_array_item::<IgnoredAny, _>(path, |_| {
+        total += 1;
+        Ok(())
+    })?;
+    Ok(total)
+}
+
+/// Runs an import over every conversation in `path`, writing each one inside its
+/// own transaction and reporting progress after each.
+///
+/// Individual conversations that fail to parse or write are counted as failed
+/// and skipped; only I/O errors and a malformed top-level array abort the import.
+pub fn import_each<T, F, P>(
+    conn: &mut Connection,
+    path: &Path,
+    project_id: String,
+    mut import_one: F,
+    mut on_progress: P,
+) -> Result<ImportSummary, String>
+where
+    T: ExportedConversation,
+    F: FnMut(&Connection, &T, &str) -> Result<(), String>,
+    P: FnMut(ImportProgress),
+{
+    let total = count_array_items(path)?;
+    let mut summary = ImportSummary {
+        project_id: project_id.clone(),
+        ..Default::default()
+    };
+
+    on_progress(ImportProgress {
+        current: 0,
+        total,
+        title: String::new(),
+        succeeded: true,
+        error: None,
+    });
+
+    for_each_array_item::<Value, _>(path, |raw| {
+        let result = serde_json::from_value::<T>(raw)
+            .map_err(|e| format!("Unrecognized conversation: {}", e))
+            .and_then(|conversation| {
+                let title = conversation.title().to_string();
+                let tx = conn.transaction().map_err(|e| e.to_string())?;
+                import_one(&tx, &conversation, &project_id)?;
+                tx.commit().map_err(|e| e.to_string())?;
+                Ok(title)
+            });
+
+        let progress = match result {
+            Ok(title) => {
+                summary.imported += 1;
+                ImportProgress {
+                    current: summary.imported + summary.failed,
+                    total,
+                    title,
+                    succeeded: true,
+                    error: None,
+                }
+            }
+            Err(error) => {
+                log::error!("Failed to import conversation: {}", error);
+                summary.failed += 1;
+                ImportProgress {
+                    current: summary.imported + summary.failed,
+                    total,
+                    title: String::new(),
+                    succeeded: false,
+                    error: Some(error),
+                }
+            }
+        };
+        on_progress(progress);
+        Ok(())
+    })?;
+
+    Ok(summary)
+}
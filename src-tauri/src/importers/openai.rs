@@ -0,0 +1,718 @@
+//! Imports conversations from ChatGPT `conversations.json` exports.
+//! You can always view an example format file in /resources/openai_conversations.json.
+//!
+//! This is a port of OpenAIImporter.ts, with one big difference: the TypeScript
+//! importer flattens each conversation's `mapping` tree, whereas here we keep
+//! every branch. ChatGPT stores a conversation as a tree of nodes (each edit or
+//! regeneration starts a new child), but our message sets are a flat list per
+//! chat (message_sets.parent_id was retired in migration 87). So we import
+//! branches the same way branching works in the UI:
+//!
+//! - the path ending at `current_node` becomes the main chat, selected as usual
+//! - every other leaf becomes a branch chat whose `parent_chat_id` is the chat it
+//!   shares the most turns with; shared turns are copied with `branched_from_id`
+//!   pointing at the original messages, just like duplicateMessagesForMessageSet
+//!
+//! System messages and nodes ChatGPT hides from the conversation (custom
+//! instructions, internal tool chatter) are skipped.
+
+use rusqlite::Connection;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use super::{
+    create_chat, import_each, insert_turn, resolve_project, restore_chat_updated_at,
+    sqlite_datetime_from_unix, tool_call_parts, ExportedConversation, ImportProgress,
+    ImportSummary, ImportedReply, ImportedTurn, MessagePart, TurnMessageIds,
+};
+
+pub const OPENAI_IMPORT_PREFIX: &str = "openai_import";
+
+const DEFAULT_PROJECT_NAME: &str = "OpenAI imports";
+
+/// Model recorded on imported assistant messages.
+const IMPORTED_MODEL: &str = "openai::gpt-4o";
+
+#[derive(Deserialize)]
+struct OpenAIConversation {
+    title: Option<String>,
+    create_time: Option<f64>,
+    update_time: Option<f64>,
+    #[serde(default)]
+    mapping: HashMap<String, OpenAINode>,
+    current_node: Option<String>,
+}
+
+impl ExportedConversation for OpenAIConversation {
+    fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or("Untitled Chat")
+    }
+}
+
+/// OpenAI conversations have many nodes that are connected to each other via the "mapping"
+/// key, which contains each part of the conversation with a "parent" and "children" key.
+#[derive(Deserialize)]
+struct OpenAINode {
+    message: Option<Value>,
+    parent: Option<String>,
+    #[serde(default)]
+    children: Vec<String>,
+}
+
+/// The fields of a raw message we care about. We keep the raw JSON around since
+/// tool messages are rendered as their full JSON, like the TypeScript importer does.
+struct OpenAIMessage<'a> {
+    raw: &'a Value,
+    role: &'a str,
+    content_type: &'a str,
+    recipient: Option<&'a str>,
+    create_time: Option<f64>,
+}
+
+impl<'a> OpenAIMessage<'a> {
+    fn from_node(node: &'a OpenAINode) -> Option<Self> {
+        let raw = node.message.as_ref()?;
+        Some(Self {
+            raw,
+            role: raw.pointer("/author/role")?.as_str()?,
+            content_type: raw
+                .pointer("/content/content_type")
+                .and_then(Value::as_str)
+                .unwrap_or("text"),
+            recipient: raw.get("recipient").and_then(Value::as_str),
+            create_time: raw.get("create_time").and_then(Value::as_f64),
+        })
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.role == "system"
+            || self
+                .raw
+                .pointer("/metadata/is_visually_hidden_from_conversation")
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+    }
+
+    fn content_str(&self, key: &str) -> Option<&'a str> {
+        self.raw.get("content")?.get(key)?.as_str()
+    }
+}
+
+/// A turn built from a run of nodes, along with the ids of those nodes so we can
+/// tell which turns two branches have in common.
+struct PathTurn<'a> {
+    node_ids: Vec<&'a str>,
+    turn: ImportedTurn,
+}
+
+/// A branch that has already been written to the database.
+struct ImportedBranch<'a> {
+    chat_id: String,
+    turn_node_ids: Vec<Vec<&'a str>>,
+    turn_message_ids: Vec<TurnMessageIds>,
+}
+
+/// Imports every conversation in a ChatGPT export into `project_id`, or into the
+/// shared "OpenAI imports" project if none is given.
+pub fn import_conversations(
+    conn: &mut Connection,
+    path: &Path,
+    project_id: Option<&str>,
+    on_progress: impl FnMut(ImportProgress),
+) -> Result<ImportSummary, String> {
+    let project_id = resolve_project(conn, project_id, DEFAULT_PROJECT_NAME)?;
+    import_each(conn, path, project_id, import_conversation, on_progress)
+}
+
+fn import_conversation(
+    conn: &Connection,
+    conversation: &OpenAIConversation,
+    project_id: &str,
+) -> Result<(), String> {
+    let title = conversation.title();
+    log::info!("Importing conversation: {}", title);
+
+    let conversation_created_at = conversation
+        .create_time
+        .and_then(sqlite_datetime_from_unix)
+        .ok_or("Conversation is missing create_time")?;
+    let conversation_updated_at = conversation
+        .update_time
+        .and_then(sqlite_datetime_from_unix)
+        .unwrap_or(conversation_created_at.clone());
+
+    let mut branches: Vec<ImportedBranch> = Vec::new();
+    for (i, node_path) in leaf_paths(conversation).into_iter().enumerate() {
+        let messages: Vec<(&str, OpenAIMessage)> = node_path
+            .iter()
+            .filter_map(|id| {
+                let message = OpenAIMessage::from_node(conversation.mapping.get(*id)?)?;
+                (!message.is_hidden()).then_some((*id, message))
+            })
+            .collect();
+        let turns = build_turns(&messages);
+        if turns.is_empty() {
+            continue;
+        }
+
+        // Find the already-imported branch this one shares the most turns with
+        let parent = branches
+            .iter()
+            .map(|branch| {
+                let shared = branch
+                    .turn_node_ids
+                    .iter()
+                    .zip(&turns)
+                    .take_while(|(imported, turn)| **imported == turn.node_ids)
+                    .count();
+                (branch, shared)
+            })
+            .max_by_key(|(_, shared)| *shared)
+            .filter(|(_, shared)| *shared > 0);
+
+        // A path that only differs from an imported one by hidden nodes adds nothing
+        if parent.is_some_and(|(_, shared)| shared == turns.len()) {
+            continue;
+        }
+
+        let created_at = if i == 0 {
+            conversation_created_at.clone()
+        } else {
+            messages
+                .iter()
+                .filter_map(|(_, message)| message.create_time)
+                .find_map(sqlite_datetime_from_unix)
+                .unwrap_or(conversation_created_at.clone())
+        };
+        let updated_at = if i == 0 {
+            conversation_updated_at.clone()
+        } else {
+            messages
+                .iter()
+                .filter_map(|(_, message)| message.create_time)
+                .fold(None, |latest: Option<f64>, time| {
+                    Some(latest.map_or(time, |latest| latest.max(time)))
+                })
+                .and_then(sqlite_datetime_from_unix)
+                .unwrap_or(conversation_updated_at.clone())
+        };
+
+        let chat_id = create_chat(
+            conn,
+            title,
+            &created_at,
+            project_id,
+            parent.map(|(branch, _)| branch.chat_id.as_str()),
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut turn_node_ids = Vec::with_capacity(turns.len());
+        let mut turn_message_ids = Vec::with_capacity(turns.len());
+        for (level, path_turn) in turns.into_iter().enumerate() {
+            let branched_from = parent.and_then(|(branch, shared)| {
+                (level < shared).then(|| &branch.turn_message_ids[level])
+            });
+            let ids = insert_turn(conn, &chat_id, level as i64, &path_turn.turn, branched_from)
+                .map_err(|e| e.to_string())?;
+            turn_node_ids.push(path_turn.node_ids);
+            turn_message_ids.push(ids);
+        }
+
+        // Restore the original updated_at timestamp since the messages trigger will have overridden it
+        restore_chat_updated_at(conn, &chat_id, &updated_at).map_err(|e| e.to_string())?;
+
+        branches.push(ImportedBranch {
+            chat_id,
+            turn_node_ids,
+            turn_message_ids,
+        });
+    }
+
+    log::info!(
+        "Imported {} branch(es) for \"{}\"",
+        branches.len(),
+        conversation.title()
+    );
+    Ok(())
+}
+
+/// Returns the node ids of every root-to-leaf path in the mapping, with the path
+/// ending at `current_node` first (falling back to the most recent leaf).
+fn leaf_paths(conversation: &OpenAIConversation) -> Vec<Vec<&str>> {
+    let mapping = &conversation.mapping;
+
+    let roots: Vec<&str> = {
+        let mut roots: Vec<&str> = mapping
+            .iter()
+            .filter(|(_, node)| {
+                node.parent
+                    .as_deref()
+                    .is_none_or(|parent| !mapping.contains_key(parent))
+            })
+            .map(|(id, _)| id.as_str())
+            .collect();
+        roots.sort_unstable();
+        roots
+    };
+
+    // Depth-first so sibling branches come out in the order ChatGPT lists them
+    let mut leaves: Vec<&str> = Vec::new();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = roots.into_iter().rev().collect();
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let children: Vec<&str> = mapping[id]
+            .children
+            .iter()
+            .map(String::as_str)
+            .filter(|child| mapping.contains_key(*child))
+            .collect();
+        if children.is_empty() {
+            leaves.push(id);
+        }
+        stack.extend(children.into_iter().rev());
+    }
+
+    let current = conversation
+        .current_node
+        .as_deref()
+        .filter(|id| mapping.contains_key(*id))
+        .or_else(|| {
+            leaves.iter().copied().max_by(|a, b| {
+                let time = |id: &str| {
+                    OpenAIMessage::from_node(&mapping[id])
+                        .and_then(|message| message.create_time)
+                        .unwrap_or(0.0)
+                };
+                time(a).total_cmp(&time(b))
+            })
+        });
+
+    let mut ends: Vec<&str> = current.into_iter().collect();
+    ends.extend(leaves.into_iter().filter(|leaf| Some(*leaf) != current));
+
+    ends.into_iter()
+        .map(|end| {
+            let mut path = Vec::new();
+            let mut seen = HashSet::new();
+            let mut next = Some(end);
+            while let Some(id) = next {
+                if !seen.insert(id) {
+                    break;
+                }
+                path.push(id);
+                next = mapping[id]
+                    .parent
+                    .as_deref()
+                    .filter(|parent| mapping.contains_key(*parent));
+            }
+            path.reverse();
+            path
+        })
+        .collect()
+}
+
+/// Groups a linear run of messages into turns: each user message plus the
+/// assistant and tool messages that follow it.
+fn build_turns<'a>(messages: &[(&'a str, OpenAIMessage)]) -> Vec<PathTurn<'a>> {
+    let mut turns = Vec::new();
+    let mut i = 0;
+    while i < messages.len() {
+        let (user_id, user_message) = &messages[i];
+        if user_message.role != "user" {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j < messages.len() && matches!(messages[j].1.role, "assistant" | "tool") {
+            j += 1;
+        }
+        let responses: Vec<&OpenAIMessage> = messages[i + 1..j].iter().map(|(_, m)| m).collect();
+
+        let reply = (!responses.is_empty()).then(|| {
+            let (text, parts) = process_response_messages(&responses);
+            ImportedReply {
+                text,
+                model: IMPORTED_MODEL.to_string(),
+                created_at: responses
+                    .iter()
+                    .find_map(|message| message.create_time)
+                    .and_then(sqlite_datetime_from_unix),
+                parts,
+            }
+        });
+
+        let mut node_ids = vec![*user_id];
+        node_ids.extend(messages[i + 1..j].iter().map(|(id, _)| *id));
+
+        turns.push(PathTurn {
+            node_ids,
+            turn: ImportedTurn {
+                user_text: extract_text(user_message),
+                user_created_at: user_message.create_time.and_then(sqlite_datetime_from_unix),
+                reply,
+            },
+        });
+        i = j;
+    }
+    turns
+}
+
+/// Processes response messages to extract text and tool usage.
+/// OpenAI clumps both tool calls and tool results into a single tool JSON object,
+/// so we split them out into separate parts here.
+fn process_response_messages(messages: &[&OpenAIMessage]) -> (String, Vec<MessagePart>) {
+    // build up text parts to display at the end after all tool calls are processed
+    let mut text_parts: Vec<String> = Vec::new();
+    let mut parts: Vec<MessagePart> = Vec::new();
+
+    for (i, message) in messages.iter().enumerate() {
+        let to_all = message.recipient.is_none_or(|recipient| recipient == "all");
+
+        // Tool messages and messages not meant for "all" recipients are shown as raw JSON,
+        // since they could have other useful data besides text
+        if message.role == "tool" || !to_all {
+            let description = match message.recipient {
+                Some(recipient) => format!("{} message to {}", message.role, recipient),
+                None => format!("{} message", message.role),
+            };
+            parts.extend(tool_call_parts(
+                format!("{}_{}_message", OPENAI_IMPORT_PREFIX, message.role),
+                description,
+                json!({}),
+                serde_json::to_string_pretty(message.raw).unwrap_or_default(),
+            ));
+            continue;
+        }
+
+        match message.content_type {
+            "thoughts" => {
+                let thoughts: Vec<String> = message
+                    .raw
+                    .pointer("/content/thoughts")
+                    .and_then(Value::as_array)
+                    .map(|thoughts| thoughts.iter().filter_map(thought_text).collect())
+                    .unwrap_or_default();
+                if !thoughts.is_empty() {
+                    parts.extend(tool_call_parts(
+                        format!("{}_thoughts", OPENAI_IMPORT_PREFIX),
+                        "OpenAI thoughts and reasoning".to_string(),
+                        json!({}),
+                        thoughts.join("\n\n"),
+                    ));
+                }
+            }
+            "reasoning_recap" => {
+                let content = message.content_str("content").unwrap_or("");
+                if !content.is_empty() && content != "Thought for 0 seconds" {
+                    parts.extend(tool_call_parts(
+                        format!("{}_reasoning_recap", OPENAI_IMPORT_PREFIX),
+                        "OpenAI reasoning summary".to_string(),
+                        json!({}),
+                        content.to_string(),
+                    ));
+                }
+            }
+            "text" | "multimodal_text" => {
+                let text = extract_text(message);
+                if !text.is_empty() {
+                    if message.role == "assistant" {
+                        text_parts.push(text.clone());
+                    }
+                    // Create a new part for each text message to maintain ordering
+                    parts.push(MessagePart::text(text));
+                }
+            }
+            "code" => {
+                let code = message.content_str("text").unwrap_or("");
+                let language = message.content_str("language").unwrap_or("unknown");
+
+                // Skip the first code block if it's just echoing the user's query,
+                // which typically appears as search("user's question")
+                if i <= 1 && code.starts_with("search(") && code.contains(')') {
+                    continue;
+                }
+
+                parts.extend(tool_call_parts(
+                    format!("{}_code_interpreter", OPENAI_IMPORT_PREFIX),
+                    "Imported code execution from OpenAI".to_string(),
+                    json!({ "language": language }),
+                    code.to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let text = text_parts.join("\n\n");
+
+    // If no parts were created but we have text, create a single part
+    if parts.is_empty() && !text.is_empty() {
+        parts.push(MessagePart::text(text.clone()));
+    }
+
+    let text = text.trim();
+    let text = if text.is_empty() { "No content" } else { text };
+    (text.to_string(), parts)
+}
+
+/// Thoughts are plain strings in older exports and `{ summary, content }` objects in newer ones.
+fn thought_text(thought: &Value) -> Option<String> {
+    if let Some(text) = thought.as_str() {
+        return Some(text.to_string());
+    }
+    let summary = thought.get("summary").and_then(Value::as_str);
+    let content = thought.get("content").and_then(Value::as_str);
+    match (summary, content) {
+        (Some(summary), Some(content)) => Some(format!("**{}**\n\n{}", summary, content)),
+        (None, Some(text)) | (Some(text), None) => Some(text.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// Extracts text from an OpenAI message. Non-string parts (e.g. image pointers in
+/// multimodal messages) are dropped, since we can't import the files they refer to.
+fn extract_text(message: &OpenAIMessage) -> String {
+    let parts: Vec<&str> = message
+        .raw
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .map(|parts| parts.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let text = if !parts.is_empty() {
+        parts.join("\n")
+    } else {
+        message.content_str("text").unwrap_or("").to_string()
+    };
+
+    clean_unicode_text(text.trim())
+}
+
+/// Cleans up Unicode characters that don't render well, and strips OpenAI's
+/// private-use citation markers (\u{e200}...\u{e201}).
+fn clean_unicode_text(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\u{2018}' | '\u{2019}' => cleaned.push('\''), // Smart single quotes
+            '\u{201C}' | '\u{201D}' => cleaned.push('"'),  // Smart double quotes
+            '\u{2013}' => cleaned.push('-'),               // En dash
+            '\u{2014}' => cleaned.push_str("--"),          // Em dash
+            '\u{2026}' => cleaned.push_str("..."),         // Ellipsis
+            '\u{00A0}' | '\u{202F}' => cleaned.push(' '),  // Non-breaking spaces
+            '\u{200B}' | '\u{00AD}' | '\u{E202}' => {} // Zero-width space, soft hyphen, separators
+            '\u{2028}' | '\u{2029}' => cleaned.push('\n'), // Line and paragraph separators
+            '\u{E200}' => {
+                // Drop the whole marker if it's closed, otherwise keep going as normal text
+                let rest: String = chars.clone().collect();
+                match rest.find('\u{E201}') {
+                    Some(end) => {
+                        let skip = rest[..end].chars().count() + 1;
+                        for _ in 0..skip {
+                            chars.next();
+                        }
+                    }
+                    None => cleaned.push(ch),
+                }
+            }
+            _ => cleaned.push(ch),
+        }
+    }
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+    use rusqlite::params;
+
+    fn node(parent: Option<&str>, children: &[&str], message: Option<Value>) -> Value {
+        json!({ "message": message, "parent": parent, "children": children })
+    }
+
+    fn message(role: &str, text: &str, create_time: f64) -> Option<Value> {
+        Some(json!({
+            "author": { "role": role },
+            "content": { "content_type": "text", "parts": [text] },
+            "create_time": create_time,
+        }))
+    }
+
+    /// u1 -> a1 -> u2 forks twice: the reply to u2 was regenerated (a2r), and
+    /// the follow-up to a2 was edited (u3e). The current path runs through a
+    /// hidden node on its way to a3.
+    fn forked_conversation() -> Value {
+        let mut hidden = message("assistant", "internal", 8.0).unwrap();
+        hidden["metadata"] = json!({ "is_visually_hidden_from_conversation": true });
+        json!({
+            "title": "Forked",
+            "create_time": 1.0,
+            "update_time": 20.0,
+            "current_node": "a3",
+            "mapping": {
+                "root": node(None, &["sys"], None),
+                "sys": node(Some("root"), &["u1"], message("system", "You are helpful", 1.0)),
+                "u1": node(Some("sys"), &["a1"], message("user", "First", 2.0)),
+                "a1": node(Some("u1"), &["u2"], message("assistant", "One", 3.0)),
+                "u2": node(Some("a1"), &["a2r", "a2"], message("user", "Second", 4.0)),
+                "a2r": node(Some("u2"), &[], message("assistant", "Two again", 12.0)),
+                "a2": node(Some("u2"), &["u3", "u3e"], message("assistant", "Two", 5.0)),
+                "u3": node(Some("a2"), &["h"], message("user", "Third", 6.0)),
+                "h": node(Some("u3"), &["a3"], Some(hidden)),
+                "a3": node(Some("h"), &[], message("assistant", "Three", 9.0)),
+                "u3e": node(Some("a2"), &["a3e"], message("user", "Third, edited", 14.0)),
+                "a3e": node(Some("u3e"), &[], message("assistant", "Three again", 15.0)),
+            },
+        })
+    }
+
+    fn visible_messages<'a>(
+        conversation: &'a OpenAIConversation,
+        path: &[&'a str],
+    ) -> Vec<(&'a str, OpenAIMessage<'a>)> {
+        path.iter()
+            .filter_map(|id| {
+                let message = OpenAIMessage::from_node(&conversation.mapping[*id])?;
+                (!message.is_hidden()).then_some((*id, message))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn leaf_paths_put_the_current_node_first() {
+        let conversation: OpenAIConversation =
+            serde_json::from_value(forked_conversation()).unwrap();
+        assert_eq!(
+            leaf_paths(&conversation),
+            vec![
+                vec!["root", "sys", "u1", "a1", "u2", "a2", "u3", "h", "a3"],
+                vec!["root", "sys", "u1", "a1", "u2", "a2r"],
+                vec!["root", "sys", "u1", "a1", "u2", "a2", "u3e", "a3e"],
+            ]
+        );
+    }
+
+    #[test]
+    fn leaf_paths_fall_back_to_the_latest_leaf() {
+        let mut value = forked_conversation();
+        value["current_node"] = json!("gone");
+        let conversation: OpenAIConversation = serde_json::from_value(value).unwrap();
+        let paths = leaf_paths(&conversation);
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0].last(), Some(&"a3e"));
+    }
+
+    #[test]
+    fn build_turns_drops_system_and_hidden_nodes() {
+        let conversation: OpenAIConversation =
+            serde_json::from_value(forked_conversation()).unwrap();
+        let path = leaf_paths(&conversation).remove(0);
+        let turns = build_turns(&visible_messages(&conversation, &path));
+
+        let node_ids: Vec<_> = turns.iter().map(|turn| turn.node_ids.clone()).collect();
+        assert_eq!(node_ids, [["u1", "a1"], ["u2", "a2"], ["u3", "a3"]]);
+        assert_eq!(turns[2].turn.user_text, "Third");
+        let reply = turns[2].turn.reply.as_ref().unwrap();
+        assert_eq!(reply.text, "Three");
+        assert_eq!(reply.created_at.as_deref(), Some("1970-01-01 00:00:09"));
+    }
+
+    #[test]
+    fn build_turns_keeps_a_trailing_user_message() {
+        let conversation: OpenAIConversation =
+            serde_json::from_value(forked_conversation()).unwrap();
+        let turns = build_turns(&visible_messages(&conversation, &["sys", "u1", "a1", "u2"]));
+        assert_eq!(turns.len(), 2);
+        assert!(turns[0].turn.reply.is_some());
+        assert!(turns[1].turn.reply.is_none());
+    }
+
+    #[test]
+    fn imports_each_leaf_as_a_branch_chat() {
+        let path = std::env::temp_dir().join(format!("camp-openai-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, json!([forked_conversation()]).to_string()).unwrap();
+        let mut conn = migrator::open_in_memory();
+        let summary = import_conversations(&mut conn, &path, None, |_| {});
+        std::fs::remove_file(&path).unwrap();
+        let summary = summary.unwrap();
+        assert_eq!((summary.imported, summary.failed), (1, 0));
+
+        // Chats come out in the order they were written: main, then a2r, then u3e
+        let chats: Vec<(String, Option<String>, String, String)> = conn
+            .prepare(
+                "SELECT id, parent_chat_id, created_at, updated_at FROM chats
+                    WHERE project_id = ?1 ORDER BY rowid",
+            )
+            .unwrap()
+            .query_map(params![summary.project_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(chats.len(), 3);
+        let main = &chats[0].0;
+        assert_eq!(chats[0].1, None);
+        assert_eq!(chats[0].2, "1970-01-01 00:00:01");
+        assert_eq!(chats[0].3, "1970-01-01 00:00:20");
+        assert_eq!(chats[1].1.as_ref(), Some(main));
+        assert_eq!(chats[2].1.as_ref(), Some(main));
+
+        let messages = |chat_id: &str| -> Vec<(String, String, bool, Option<String>)> {
+            conn.prepare(
+                "SELECT m.id, m.text, m.selected, m.branched_from_id FROM messages m
+                    JOIN message_sets s ON s.id = m.message_set_id
+                    WHERE m.chat_id = ?1 ORDER BY s.level",
+            )
+            .unwrap()
+            .query_map(params![chat_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+        };
+
+        let main_messages = messages(main);
+        let texts: Vec<&str> = main_messages.iter().map(|m| m.1.as_str()).collect();
+        assert_eq!(texts, ["First", "One", "Second", "Two", "Third", "Three"]);
+        assert!(main_messages.iter().all(|m| m.2 && m.3.is_none()));
+
+        // Shared turns link back to the main chat's messages; the rest are new
+        let regenerated = messages(&chats[1].0);
+        let texts: Vec<&str> = regenerated.iter().map(|m| m.1.as_str()).collect();
+        assert_eq!(texts, ["First", "One", "Second", "Two again"]);
+        assert_eq!(regenerated[0].3.as_ref(), Some(&main_messages[0].0));
+        assert_eq!(regenerated[1].3.as_ref(), Some(&main_messages[1].0));
+        assert_eq!(regenerated[2].3, None);
+        assert_eq!(regenerated[3].3, None);
+
+        let edited = messages(&chats[2].0);
+        let texts: Vec<&str> = edited.iter().map(|m| m.1.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "First",
+                "One",
+                "Second",
+                "Two",
+                "Third, edited",
+                "Three again"
+            ]
+        );
+        for level in 0..4 {
+            assert_eq!(edited[level].3.as_ref(), Some(&main_messages[level].0));
+        }
+        assert_eq!(edited[4].3, None);
+        assert_eq!(chats[2].2, "1970-01-01 00:00:02");
+        assert_eq!(chats[2].3, "1970-01-01 00:00:15");
+    }
+}
@@ -0,0 +1,453 @@
+//! Checks a chats.db for data the app assumes can't exist: rows pointing at
+//! things that were deleted, duplicate selections, JSON the frontend will fail
+//! to parse, and attachments whose files are gone.
+//!
+//! Some violations can be repaired without losing anything the user would miss
+//! (e.g. join rows for attachments that no longer exist); those have a repair
+//! statement. Everything else is only reported.
+//!
+//! Used by the `check` subcommand of generate_schema and the check_database command.
+
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+
+/// How many offending rows to list per check.
+const MAX_EXAMPLES: usize = 10;
+
+struct Invariant {
+    name: &'static str,
+    description: &'static str,
+    /// Returns one row per violation, with a single column identifying it.
+    query: &'static str,
+    /// Fixes every violation. Only set where nothing meaningful is lost.
+    repair: Option<&'static str>,
+}
+
+const INVARIANTS: &[Invariant] = &[
+    Invariant {
+        name: "chat_project_exists",
+        description: "every chats.project_id exists in projects (otherwise the chat is invisible)",
+        query: "SELECT id FROM chats WHERE project_id NOT IN (SELECT id FROM projects)",
+        // Move them to the default project, where the user can find them again
+        repair: Some(
+            "UPDATE chats SET project_id = 'default'
+            WHERE project_id NOT IN (SELECT id FROM projects)
+                AND EXISTS (SELECT 1 FROM projects WHERE id = 'default')",
+        ),
+    },
+    Invariant {
+        name: "one_selected_message",
+        // Migration 56 keeps one message selected per set; since block types were added,
+        // selection (selectMessage in MessageAPI.ts) is per set and block type
+        description: "at most one selected message per message set and block type",
+        query: "SELECT message_set_id || ' (' || COALESCE(block_type, 'null') || ')' FROM messages
+            WHERE selected = 1
+            GROUP BY message_set_id, block_type
+            HAVING COUNT(*) > 1",
+        // Keep whichever was inserted first
+        repair: Some(
+            "UPDATE messages SET selected = 0
+            WHERE selected = 1 AND rowid NOT IN (
+                SELECT MIN(rowid) FROM messages WHERE selected = 1 GROUP BY message_set_id, block_type
+            )",
+        ),
+    },
+    Invariant {
+        name: "message_attachments_orphans",
+        description: "every message_attachments row points at an existing message and attachment",
+        query: "SELECT message_id || ' -> ' || attachment_id FROM message_attachments
+            WHERE message_id NOT IN (SELECT id FROM messages)
+                OR attachment_id NOT IN (SELECT id FROM attachments)",
+        repair: Some(
+            "DELETE FROM message_attachments
+            WHERE message_id NOT IN (SELECT id FROM messages)
+                OR attachment_id NOT IN (SELECT id FROM attachments)",
+        ),
+    },
+    Invariant {
+        name: "draft_attachments_orphans",
+        description: "every draft_attachments row points at an existing chat and attachment",
+        query: "SELECT chat_id || ' -> ' || attachment_id FROM draft_attachments
+            WHERE chat_id NOT IN (SELECT id FROM chats)
+                OR attachment_id NOT IN (SELECT id FROM attachments)",
+        repair: Some(
+            "DELETE FROM draft_attachments
+            WHERE chat_id NOT IN (SELECT id FROM chats)
+                OR attachment_id NOT IN (SELECT id FROM attachments)",
+        ),
+    },
+    Invariant {
+        name: "model_config_model_exists",
+        description: "every model_configs.model_id exists in models",
+        query: "SELECT id FROM model_configs WHERE model_id NOT IN (SELECT id FROM models)",
+        repair: None,
+    },
+    Invariant {
+        name: "model_attachment_types_json",
+        description: "models.supported_attachment_types is valid JSON (read for every model config)",
+        query: "SELECT id FROM models WHERE NOT json_valid(supported_attachment_types)",
+        repair: None,
+    },
+    Invariant {
+        name: "custom_toolset_env_json",
+        description: "custom_toolsets.env is valid JSON",
+        query: "SELECT name FROM custom_toolsets WHERE env IS NOT NULL AND NOT json_valid(env)",
+        repair: None,
+    },
+    Invariant {
+        name: "message_part_tools_json",
+        description: "message_parts.tool_calls and tool_results are valid JSON",
+        query: "SELECT message_id || ':' || level FROM message_parts
+            WHERE (tool_calls IS NOT NULL AND NOT json_valid(tool_calls))
+                OR (tool_results IS NOT NULL AND NOT json_valid(tool_results))",
+        repair: None,
+    },
+    Invariant {
+        name: "saved_model_configs_json",
+        description: "saved_model_configs_chats.model_ids is valid JSON",
+        query: "SELECT id FROM saved_model_configs_chats WHERE NOT json_valid(model_ids)",
+        repair: None,
+    },
+    Invariant {
+        name: "message_set_level_set",
+        description: "every message set has a level (migration 86)",
+        query: "SELECT id FROM message_sets WHERE level IS NULL",
+        repair: None,
+    },
+    Invariant {
+        name: "message_set_level_matches_parent",
+        description: "message sets with a (deprecated) parent are in the same chat, one level below it",
+        query: "SELECT ms.id FROM message_sets ms
+            JOIN message_sets parent ON parent.id = ms.deprecated_parent_id
+            WHERE parent.chat_id != ms.chat_id OR parent.level IS NOT ms.level - 1",
+        repair: None,
+    },
+];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub violations: usize,
+    /// The first few offending rows.
+    pub examples: Vec<String>,
+    pub repairable: bool,
+    pub repaired: usize,
+    /// Set if the check couldn't run, e.g. because the database is on an old schema.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvariantReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl InvariantReport {
+    /// True if every check ran and found nothing left to fix.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.error.is_none() && check.violations == check.repaired)
+    }
+
+    pub fn print(&self) {
+        for check in &self.checks {
+            let status = match (&check.error, check.violations) {
+                (Some(_), _) => "SKIP",
+                (None, 0) => "OK",
+                (None, n) if n == check.repaired => "FIXED",
+                _ => "FAIL",
+            };
+            println!("[{}] {}: {}", status, check.name, check.description);
+            if let Some(error) = &check.error {
+                println!("       {}", error);
+            }
+            if check.violations > 0 {
+                println!(
+                    "       {} violation(s){}",
+                    check.violations,
+                    if check.repaired > 0 {
+                        format!(", {} repaired", check.repaired)
+                    } else if check.repairable {
+                        ", repairable with --repair".to_string()
+                    } else {
+                        String::new()
+                    }
+                );
+                for example in &check.examples {
+                    println!("         {}", example);
+                }
+                if check.examples.len() < check.violations {
+                    println!(
+                        "         ...and {} more",
+                        check.violations - check.examples.len()
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Runs every check against `conn`. With `repair`, safe fixes are applied in a
+/// single transaction and counted in each check's `repaired`.
+pub fn check_database(conn: &mut Connection, repair: bool) -> Result<InvariantReport, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut report = InvariantReport::default();
+
+    for invariant in INVARIANTS {
+        let mut result = CheckResult {
+            name: invariant.name,
+            description: invariant.description,
+            violations: 0,
+            examples: Vec::new(),
+            repairable: invariant.repair.is_some(),
+            repaired: 0,
+            error: None,
+        };
+
+        match violations(&tx, invariant.query) {
+            Ok(rows) => {
+                result.violations = rows.len();
+                result.examples = rows.into_iter().take(MAX_EXAMPLES).collect();
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+
+        if let (true, Some(sql), true) = (repair, invariant.repair, result.violations > 0) {
+            tx.execute(sql, []).map_err(|e| e.to_string())?;
+            // Count what's actually fixed rather than rows touched, since e.g. moving chats
+            // to the default project does nothing if that project is gone too
+            let remaining = violations(&tx, invariant.query)
+                .map_err(|e| e.to_string())?
+                .len();
+            result.repaired = result.violations - remaining;
+        }
+
+        report.checks.push(result);
+    }
+
+    report.checks.push(check_attachment_files(&tx));
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn violations(conn: &Connection, query: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, Option<String>>(0))?
+        .map(|row| row.map(Option::unwrap_or_default))
+        .collect();
+    rows
+}
+
+/// Webpage attachments store a URL, everything else should point at a file in uploads.
+fn check_attachment_files(conn: &Connection) -> CheckResult {
+    let mut result = CheckResult {
+        name: "attachment_files_exist",
+        description: "every non-webpage attachments.path exists on disk",
+        violations: 0,
+        examples: Vec::new(),
+        repairable: false,
+        repaired: 0,
+        error: None,
+    };
+
+    let rows: rusqlite::Result<Vec<(String, String)>> = conn
+        .prepare("SELECT id, path FROM attachments WHERE type != 'webpage'")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        });
+    match rows {
+        Ok(rows) => {
+            for (id, path) in rows {
+                if Path::new(&path).exists() {
+                    continue;
+                }
+                result.violations += 1;
+                if result.examples.len() < MAX_EXAMPLES {
+                    result.examples.push(format!("{} -> {}", id, path));
+                }
+            }
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+
+    const REPAIRABLE: [&str; 4] = [
+        "chat_project_exists",
+        "one_selected_message",
+        "message_attachments_orphans",
+        "draft_attachments_orphans",
+    ];
+
+    /// Breaks every repairable invariant once. Chats can't be inserted into a
+    /// missing project, so the project is renamed out from under one instead.
+    fn seed_repairable(conn: &Connection) {
+        conn.execute_batch(
+            "INSERT INTO projects (id, name) VALUES ('old', 'Old');
+            INSERT INTO chats (id, title, project_id) VALUES ('c1', 'Lost', 'old');
+            UPDATE projects SET id = 'renamed' WHERE id = 'old';
+            INSERT INTO chats (id, title, project_id) VALUES ('c2', 'Chat', 'default');
+            INSERT INTO message_sets (id, chat_id, type, level) VALUES ('s1', 'c2', 'ai', 1);
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, block_type, state)
+                VALUES ('m1', 's1', 'c2', 'One', 'a::one', 'chat', 'idle');
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, block_type, state)
+                VALUES ('m2', 's1', 'c2', 'Two', 'b::two', 'chat', 'idle');
+            UPDATE messages SET selected = 1 WHERE message_set_id = 's1';
+            INSERT INTO message_attachments (message_id, attachment_id) VALUES ('m-gone', 'a-gone');
+            INSERT INTO draft_attachments (chat_id, attachment_id) VALUES ('c-gone', 'a-gone');",
+        )
+        .unwrap();
+    }
+
+    /// Breaks every invariant that can only be reported. These are rows older
+    /// builds or hand edits could leave behind, so constraints are relaxed to write them.
+    fn seed_unrepairable(conn: &Connection) {
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+            PRAGMA ignore_check_constraints = ON;
+            INSERT INTO models (id, display_name, supported_attachment_types)
+                VALUES ('bad::json', 'Bad', '[\"image\"');
+            INSERT INTO model_configs (id, model_id, display_name, author, system_prompt)
+                VALUES ('mc1', 'gone::model', 'Gone', 'user', '');
+            INSERT INTO custom_toolsets (name, command, env) VALUES ('broken', 'run', '{');
+            INSERT INTO message_parts (chat_id, message_id, level, content, tool_calls)
+                VALUES ('c2', 'm1', 0, '', 'not json');
+            INSERT INTO saved_model_configs_chats (id, chat_id, model_ids) VALUES ('smc1', 'c2', 'nope');
+            INSERT INTO message_sets (id, chat_id, type) VALUES ('s-unlevelled', 'c2', 'user');
+            INSERT INTO message_sets (id, chat_id, type, level, deprecated_parent_id)
+                VALUES ('s-child', 'c2', 'user', 5, 's1');
+            INSERT INTO attachments (id, type, original_name, path)
+                VALUES ('a1', 'text', 'gone.txt', '/nonexistent/camp-invariants/gone.txt');
+            INSERT INTO attachments (id, type, original_name, path)
+                VALUES ('a2', 'webpage', 'Example', 'https://example.com');
+            PRAGMA ignore_check_constraints = OFF;
+            PRAGMA foreign_keys = ON;",
+        )
+        .unwrap();
+    }
+
+    fn check<'a>(report: &'a InvariantReport, name: &str) -> &'a CheckResult {
+        report
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn a_new_database_passes() {
+        let mut conn = migrator::open_in_memory();
+        let report = check_database(&mut conn, false).unwrap();
+        assert_eq!(report.checks.len(), INVARIANTS.len() + 1);
+        assert!(report.is_ok());
+        assert!(report.checks.iter().all(|check| check.violations == 0));
+    }
+
+    #[test]
+    fn reports_one_violation_per_invariant() {
+        let mut conn = migrator::open_in_memory();
+        seed_repairable(&conn);
+        seed_unrepairable(&conn);
+
+        let report = check_database(&mut conn, false).unwrap();
+        assert!(!report.is_ok());
+        for result in &report.checks {
+            assert_eq!(result.error, None, "{}", result.name);
+            assert_eq!(result.violations, 1, "{}", result.name);
+            assert_eq!(result.examples.len(), 1, "{}", result.name);
+            assert_eq!(result.repaired, 0, "{}", result.name);
+            assert_eq!(
+                result.repairable,
+                REPAIRABLE.contains(&result.name),
+                "{}",
+                result.name
+            );
+        }
+        assert_eq!(check(&report, "chat_project_exists").examples, ["c1"]);
+        assert_eq!(
+            check(&report, "one_selected_message").examples,
+            ["s1 (chat)"]
+        );
+        assert_eq!(
+            check(&report, "message_attachments_orphans").examples,
+            ["m-gone -> a-gone"]
+        );
+        assert_eq!(
+            check(&report, "message_set_level_matches_parent").examples,
+            ["s-child"]
+        );
+        assert_eq!(
+            check(&report, "attachment_files_exist").examples,
+            ["a1 -> /nonexistent/camp-invariants/gone.txt"]
+        );
+    }
+
+    #[test]
+    fn repairs_only_what_is_safe_to_repair() {
+        let mut conn = migrator::open_in_memory();
+        seed_repairable(&conn);
+        seed_unrepairable(&conn);
+
+        let report = check_database(&mut conn, true).unwrap();
+        for result in &report.checks {
+            let expected = if result.repairable { 1 } else { 0 };
+            assert_eq!(result.repaired, expected, "{}", result.name);
+        }
+        // Unrepairable violations are still there
+        assert!(!report.is_ok());
+
+        let (project_id, selected): (String, i64) = conn
+            .query_row(
+                "SELECT (SELECT project_id FROM chats WHERE id = 'c1'),
+                    (SELECT COUNT(*) FROM messages WHERE message_set_id = 's1' AND selected = 1)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(project_id, "default");
+        assert_eq!(selected, 1);
+
+        let report = check_database(&mut conn, false).unwrap();
+        for result in &report.checks {
+            let expected = if result.repairable { 0 } else { 1 };
+            assert_eq!(result.violations, expected, "{}", result.name);
+        }
+    }
+
+    #[test]
+    fn is_ok_once_every_violation_is_repaired() {
+        let mut conn = migrator::open_in_memory();
+        seed_repairable(&conn);
+
+        assert!(!check_database(&mut conn, false).unwrap().is_ok());
+        // The repair itself counts as ok, since nothing is left afterwards
+        assert!(check_database(&mut conn, true).unwrap().is_ok());
+        let report = check_database(&mut conn, false).unwrap();
+        assert!(report.is_ok());
+        assert!(report.checks.iter().all(|check| check.violations == 0));
+    }
+
+    #[test]
+    fn a_check_that_cannot_run_is_not_ok() {
+        let mut conn = migrator::open_in_memory();
+        conn.execute_batch("DROP TABLE saved_model_configs_chats")
+            .unwrap();
+
+        let report = check_database(&mut conn, true).unwrap();
+        let result = check(&report, "saved_model_configs_json");
+        assert!(result.error.as_deref().unwrap().contains("no such table"));
+        assert!(!report.is_ok());
+    }
+}
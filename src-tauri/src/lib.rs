@@ -1,117 +1,62 @@
 use tauri::menu::{MenuBuilder, MenuItem, PredefinedMenuItem, SubmenuBuilder};
-use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
 use tauri::{Emitter, Listener, Manager};
-#[cfg(target_os = "macos")]
-use tauri_nspanel::ManagerExt;
-use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut, ShortcutState};
-use tauri_plugin_store::StoreExt;
-
-#[cfg(target_os = "macos")]
-use window::WebviewWindowExt;
 
+mod archive;
+mod attachment_store;
+mod attachment_watcher;
 mod command;
+mod db;
+mod display_layout;
+mod document_text;
+mod file_access;
+mod file_inspection;
+mod folder_bundle;
+mod global_shortcuts;
+mod image_pipeline;
+mod importers;
+pub mod invariants;
 pub mod migrations;
+pub mod migrator;
+mod pdf_pages;
+mod quick_chat;
+mod redaction;
+mod region_capture;
+mod search;
+mod secrets;
+mod shortcut;
+mod tray;
 mod window;
-
-const DB_URL: &str = "sqlite:chats.db";
+mod window_capture;
 
 pub const SPOTLIGHT_LABEL: &str = "quick-chat";
 
-fn char_to_code(ch: char) -> Option<Code> {
-    match ch {
-        'A' => Some(Code::KeyA),
-        'B' => Some(Code::KeyB),
-        'C' => Some(Code::KeyC),
-        'D' => Some(Code::KeyD),
-        'E' => Some(Code::KeyE),
-        'F' => Some(Code::KeyF),
-        'G' => Some(Code::KeyG),
-        'H' => Some(Code::KeyH),
-        'I' => Some(Code::KeyI),
-        'J' => Some(Code::KeyJ),
-        'K' => Some(Code::KeyK),
-        'L' => Some(Code::KeyL),
-        'M' => Some(Code::KeyM),
-        'N' => Some(Code::KeyN),
-        'O' => Some(Code::KeyO),
-        'P' => Some(Code::KeyP),
-        'Q' => Some(Code::KeyQ),
-        'R' => Some(Code::KeyR),
-        'S' => Some(Code::KeyS),
-        'T' => Some(Code::KeyT),
-        'U' => Some(Code::KeyU),
-        'V' => Some(Code::KeyV),
-        'W' => Some(Code::KeyW),
-        'X' => Some(Code::KeyX),
-        'Y' => Some(Code::KeyY),
-        'Z' => Some(Code::KeyZ),
-        _ => None,
-    }
-}
-
-fn parse_shortcut(shortcut_str: &str) -> Option<Shortcut> {
-    println!("Attempting to parse shortcut: {}", shortcut_str);
-    let parts: Vec<&str> = shortcut_str.split('+').map(str::trim).collect();
-    println!("Split parts: {:?}", parts);
-    if parts.is_empty() {
-        println!("No parts found in shortcut string");
-        return None;
-    }
-
-    let mut modifiers = Modifiers::empty();
-    let key_str = parts.last()?;
-    println!("Key string: {}", key_str);
-
-    // Parse modifiers from all parts except last
-    for modifier in &parts[..parts.len() - 1] {
-        println!("Processing modifier: {}", modifier);
-        match modifier.to_lowercase().as_str() {
-            "alt" => modifiers |= Modifiers::ALT,
-            "ctrl" | "control" => modifiers |= Modifiers::CONTROL,
-            "shift" => modifiers |= Modifiers::SHIFT,
-            "super" | "cmd" | "command" => modifiers |= Modifiers::SUPER,
-            _ => {
-                println!("Unknown modifier: {}", modifier);
-                return None;
-            }
-        }
-    }
-    println!("Final modifiers: {:?}", modifiers);
-
-    let code = match key_str.to_lowercase().as_str() {
-        "space" => Code::Space,
-        "enter" => Code::Enter,
-        "tab" => Code::Tab,
-        "escape" => Code::Escape,
-        c if c.len() == 1 => {
-            let ch = c.chars().next()?;
-            char_to_code(ch.to_ascii_uppercase())?
-        }
-        _ => return None,
-    };
-
-    Some(Shortcut::new(Some(modifiers), code))
-}
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     #[cfg(debug_assertions)] // only enable instrumentation in development builds
     let devtools = tauri_plugin_devtools::init();
 
-    let migrations = migrations::migrations();
-
     let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_deep_link::init())
-        .plugin(tauri_plugin_stronghold::Builder::new(|_pass| todo!()).build())
+        // Migrations run here rather than through tauri_plugin_sql (see migrator.rs),
+        // so this has to be registered before the SQL plugin opens chats.db
         .plugin(
-            tauri_plugin_sql::Builder::default()
-                .add_migrations(DB_URL, migrations)
+            tauri::plugin::Builder::new("migrations")
+                .setup(|app_handle, _api| {
+                    db::migrate(app_handle)?;
+                    // A vault problem shouldn't keep the app from starting; the
+                    // credentials stay where they are and it's retried next launch
+                    if let Err(e) = secrets::secure_credentials(app_handle) {
+                        log::error!("Failed to move credentials into the secret vault: {}", e);
+                    }
+                    Ok(())
+                })
                 .build(),
         )
+        .plugin(tauri_plugin_sql::Builder::default().build())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -119,13 +64,24 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_macos_permissions::init());
+        .plugin(tauri_plugin_macos_permissions::init())
+        .manage(region_capture::PendingCaptures::default());
 
     #[cfg(debug_assertions)]
     {
         builder = builder.plugin(devtools);
     }
 
+    // devtools installs its own logger in development builds
+    #[cfg(not(debug_assertions))]
+    {
+        builder = builder.plugin(
+            tauri_plugin_log::Builder::new()
+                .level(log::LevelFilter::Info)
+                .build(),
+        );
+    }
+
     #[cfg(target_os = "macos")]
     {
         builder = builder.plugin(tauri_nspanel::init());
@@ -135,6 +91,11 @@ pub fn run() {
     let setup_fn = move |app: &mut tauri::App| {
         let handle = app.app_handle();
 
+        // Attachments still work without it, they just don't follow their sources
+        if let Err(e) = attachment_watcher::start(handle) {
+            log::error!("Failed to start the attachment watcher: {}", e);
+        }
+
         // Create the application menu using Tauri v2 API
         let app_menu = SubmenuBuilder::new(app, "Camp")
             .item(&MenuItem::with_id(
@@ -257,121 +218,21 @@ pub fn run() {
             }
         });
 
-        // Setup tray
-        let _tray = TrayIconBuilder::new()
-            .icon(app.default_window_icon().unwrap().clone())
-            .on_tray_icon_event(|tray, event| match event {
-                TrayIconEvent::Click {
-                    button: MouseButton::Left,
-                    button_state: MouseButtonState::Up,
-                    ..
-                } => {
-                    let app = tray.app_handle();
-                    if let Some(window) = app.get_webview_window(SPOTLIGHT_LABEL) {
-                        if window.is_visible().unwrap_or(false) {
-                            command::hide(app.clone());
-                        } else {
-                            command::show(app.clone());
-                        }
-                    }
-                }
-                _ => {
-                    // Silently ignore unhandled events
-                }
-            })
-            .build(app)?;
-
-        // Initialize the store
-        let store = handle.store("settings");
-        let window = handle.get_webview_window(SPOTLIGHT_LABEL).unwrap();
-
-        // Get theme mode from settings
-        let is_dark_mode = store
-            .ok()
-            .and_then(|store| store.get("settings"))
-            .and_then(|settings| {
-                settings
-                    .as_object()
-                    .and_then(|s| s.get("theme"))
-                    .and_then(|t| t.get("mode"))
-                    .and_then(|m| m.as_str().map(String::from))
-            })
-            .map(|mode| mode == "dark")
-            .unwrap_or(false); // Default to light mode if setting not found
-
-        // Convert the window to a spotlight panel
-        let _panel = window.to_spotlight_panel(is_dark_mode)?;
-
-        let cloned_handle = handle.clone();
-
-        // Listen for panel becoming key (gaining focus)
-        handle.listen(
-            format!("{}_panel_did_become_key", SPOTLIGHT_LABEL),
-            move |_| {
-                // Emit an event that the frontend can listen for
-                cloned_handle.emit("quick-chat-focused", ()).unwrap();
-            },
-        );
-
-        use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
-
-        // Read the quickChat shortcut from the settings store.
-        let store = app.store("settings");
-        let quick_chat_shortcut = store
-            .ok()
-            .and_then(|store| store.get("settings"))
-            .and_then(|settings| {
-                settings
-                    .as_object()
-                    .and_then(|s| s.get("quickChat"))
-                    .and_then(|t| t.get("shortcut"))
-                    .and_then(|m| m.as_str().map(String::from))
-            })
-            .unwrap_or("Alt+Space".to_string());
-
-        let shortcut = parse_shortcut(&quick_chat_shortcut)
-            .unwrap_or(Shortcut::new(Some(Modifiers::ALT), Code::Space));
-
-        // Register the quickChat shortcut.
-        app.handle().plugin(
-            tauri_plugin_global_shortcut::Builder::new()
-                .with_shortcuts([shortcut])
-                .expect("Failed to register shortcut")
-                .with_handler(move |app, shortcut, event| {
-                    if event.state == ShortcutState::Pressed && event.id == shortcut.id() {
-                        let panel = app.get_webview_panel(SPOTLIGHT_LABEL).unwrap();
-                        let store = app.store("settings");
-                        let quick_chat_enabled = store
-                            .ok()
-                            .and_then(|store| store.get("settings"))
-                            .and_then(|settings| {
-                                settings
-                                    .as_object()
-                                    .and_then(|s| s.get("quickChat"))
-                                    .and_then(|t| t.get("enabled"))
-                                    .and_then(|e| e.as_bool())
-                            })
-                            .unwrap_or(false); // Default to enabled if setting not found
-                        if quick_chat_enabled {
-                            if panel.is_visible() {
-                                panel.order_out(None);
-                            } else {
-                                let handle = app.app_handle();
-                                handle.emit("show_quick_chat", ()).unwrap();
-                                panel.show();
-                            }
-                        }
-                    }
-                })
-                .build(),
-        )?;
+        quick_chat::setup(handle)?;
+        tray::setup(handle)?;
+        global_shortcuts::setup(handle);
 
         Ok(())
     };
 
     #[cfg(not(target_os = "macos"))]
-    let setup_fn = move |_app: &mut tauri::App| {
-        // No macOS-specific setup needed for other platforms
+    let setup_fn = move |app: &mut tauri::App| {
+        if let Err(e) = attachment_watcher::start(app.handle()) {
+            log::error!("Failed to start the attachment watcher: {}", e);
+        }
+        quick_chat::setup(app.handle())?;
+        tray::setup(app.handle())?;
+        global_shortcuts::setup(app.handle());
         Ok(())
     };
 
@@ -409,6 +270,11 @@ pub fn run() {
                 {
                     tauri::AppHandle::hide(&window.app_handle()).unwrap();
                 }
+                // Alt+F4 on the quick chat should put it away, like Escape does
+                #[cfg(not(target_os = "macos"))]
+                if window.label() == SPOTLIGHT_LABEL {
+                    let _ = window.hide();
+                }
                 api.prevent_close();
             }
             _ => {}
@@ -418,17 +284,42 @@ pub fn run() {
             command::hide,
             command::open_in_main_window,
             command::new_quick_chat,
+            command::update_quick_chat_settings,
+            command::update_global_shortcuts,
             command::refresh_projects_state,
             command::chat_deleted,
             #[cfg(target_os = "macos")]
             command::update_panel_theme,
+            #[cfg(not(target_os = "macos"))]
+            command::list_windows,
             command::capture_window,
             command::capture_whole_screen,
+            command::capture_display_for_region,
+            command::crop_screenshot,
+            command::redact_image,
             command::resize_image,
             command::open_screen_recording_settings,
             command::get_instance_name,
             command::write_file_async,
             command::get_file_metadata,
+            command::choose_export_destination,
+            command::import_claude_conversations,
+            command::import_openai_conversations,
+            command::export_workspace,
+            command::read_workspace_archive,
+            command::import_workspace,
+            command::search_messages,
+            command::check_database,
+            command::store_attachment,
+            command::collect_attachment_garbage,
+            command::extract_attachment_text,
+            command::bundle_folder,
+            command::watch_attachment_source,
+            command::sync_attachment_watches,
+            command::put_secret,
+            command::get_secret,
+            command::list_secrets,
+            command::delete_secret,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
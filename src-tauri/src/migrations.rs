@@ -2458,5 +2458,197 @@ You have full access to bash commands on the user''''s computer. If you write a
                     ('selected_model_configs_compare', '["openrouter::anthropic/claude-opus-4.5"]');
             "#,
         },
+        Migration {
+            version: 132,
+            description: "add full-text search index over chats, messages and message parts",
+            kind: MigrationKind::Up,
+            sql: r#"
+                -- Maps each row of the FTS table back to the chat, message or message part it
+                -- indexes. The FTS rowid is the id here. message_id is set for messages and
+                -- parts so we can drop a message's parts along with it (message_parts has
+                -- no cascade of its own).
+                CREATE TABLE search_index_sources (
+                    id INTEGER PRIMARY KEY,
+                    kind TEXT NOT NULL CHECK (kind IN ('chat', 'message', 'message_part')),
+                    source_id TEXT NOT NULL,
+                    chat_id TEXT NOT NULL,
+                    message_id TEXT,
+                    UNIQUE (kind, source_id)
+                );
+                CREATE INDEX idx_search_index_sources_chat_id ON search_index_sources(chat_id);
+                CREATE INDEX idx_search_index_sources_message_id ON search_index_sources(message_id);
+
+                -- title holds chat titles, body holds chat summaries, message text and part content
+                CREATE VIRTUAL TABLE search_index USING fts5(
+                    title,
+                    body,
+                    tokenize = 'porter unicode61 remove_diacritics 2'
+                );
+
+                -- Chats
+                CREATE TRIGGER search_index_chat_insert
+                AFTER INSERT ON chats
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE kind = 'chat' AND source_id = NEW.id
+                    );
+                    DELETE FROM search_index_sources WHERE kind = 'chat' AND source_id = NEW.id;
+                    INSERT INTO search_index_sources (kind, source_id, chat_id) VALUES ('chat', NEW.id, NEW.id);
+                    INSERT INTO search_index (rowid, title, body) VALUES (last_insert_rowid(), NEW.title, NEW.summary);
+                END;
+
+                CREATE TRIGGER search_index_chat_update
+                AFTER UPDATE OF title, summary ON chats
+                WHEN OLD.title IS NOT NEW.title OR OLD.summary IS NOT NEW.summary
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE kind = 'chat' AND source_id = NEW.id
+                    );
+                    INSERT INTO search_index (rowid, title, body)
+                        SELECT id, NEW.title, NEW.summary FROM search_index_sources
+                        WHERE kind = 'chat' AND source_id = NEW.id;
+                END;
+
+                -- Also catches parts whose messages were deleted without them
+                CREATE TRIGGER search_index_chat_delete
+                AFTER DELETE ON chats
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE chat_id = OLD.id
+                    );
+                    DELETE FROM search_index_sources WHERE chat_id = OLD.id;
+                END;
+
+                -- Messages
+                CREATE TRIGGER search_index_message_insert
+                AFTER INSERT ON messages
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE kind = 'message' AND source_id = NEW.id
+                    );
+                    DELETE FROM search_index_sources WHERE kind = 'message' AND source_id = NEW.id;
+                    INSERT INTO search_index_sources (kind, source_id, chat_id, message_id) VALUES ('message', NEW.id, NEW.chat_id, NEW.id);
+                    INSERT INTO search_index (rowid, title, body) VALUES (last_insert_rowid(), NULL, NEW.text);
+                END;
+
+                CREATE TRIGGER search_index_message_update
+                AFTER UPDATE OF text ON messages
+                WHEN OLD.text IS NOT NEW.text
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE kind = 'message' AND source_id = NEW.id
+                    );
+                    INSERT INTO search_index (rowid, title, body)
+                        SELECT id, NULL, NEW.text FROM search_index_sources
+                        WHERE kind = 'message' AND source_id = NEW.id;
+                END;
+
+                CREATE TRIGGER search_index_message_delete
+                AFTER DELETE ON messages
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources WHERE message_id = OLD.id
+                    );
+                    DELETE FROM search_index_sources WHERE message_id = OLD.id;
+                END;
+
+                -- Message parts, keyed by message_id:level (their primary key)
+                CREATE TRIGGER search_index_message_part_insert
+                AFTER INSERT ON message_parts
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = NEW.message_id || ':' || NEW.level
+                    );
+                    DELETE FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = NEW.message_id || ':' || NEW.level;
+                    INSERT INTO search_index_sources (kind, source_id, chat_id, message_id)
+                        VALUES ('message_part', NEW.message_id || ':' || NEW.level, NEW.chat_id, NEW.message_id);
+                    INSERT INTO search_index (rowid, title, body) VALUES (last_insert_rowid(), NULL, NEW.content);
+                END;
+
+                CREATE TRIGGER search_index_message_part_update
+                AFTER UPDATE OF content ON message_parts
+                WHEN OLD.content IS NOT NEW.content
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = NEW.message_id || ':' || NEW.level
+                    );
+                    INSERT INTO search_index (rowid, title, body)
+                        SELECT id, NULL, NEW.content FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = NEW.message_id || ':' || NEW.level;
+                END;
+
+                CREATE TRIGGER search_index_message_part_delete
+                AFTER DELETE ON message_parts
+                BEGIN
+                    DELETE FROM search_index WHERE rowid IN (
+                        SELECT id FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = OLD.message_id || ':' || OLD.level
+                    );
+                    DELETE FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = OLD.message_id || ':' || OLD.level;
+                END;
+
+                -- Backfill existing history
+                INSERT INTO search_index_sources (kind, source_id, chat_id)
+                    SELECT 'chat', id, id FROM chats;
+                INSERT INTO search_index_sources (kind, source_id, chat_id, message_id)
+                    SELECT 'message', id, chat_id, id FROM messages;
+                INSERT INTO search_index_sources (kind, source_id, chat_id, message_id)
+                    SELECT 'message_part', message_id || ':' || level, chat_id, message_id FROM message_parts;
+
+                INSERT INTO search_index (rowid, title, body)
+                    SELECT s.id, c.title, c.summary FROM search_index_sources s
+                    JOIN chats c ON c.id = s.source_id WHERE s.kind = 'chat';
+                INSERT INTO search_index (rowid, title, body)
+                    SELECT s.id, NULL, m.text FROM search_index_sources s
+                    JOIN messages m ON m.id = s.source_id WHERE s.kind = 'message';
+                INSERT INTO search_index (rowid, title, body)
+                    SELECT (
+                        SELECT id FROM search_index_sources
+                        WHERE kind = 'message_part' AND source_id = p.message_id || ':' || p.level
+                    ), NULL, p.content FROM message_parts p;
+            "#,
+        },
+        Migration {
+            version: 133,
+            description: "add attachment_text_extractions cache for PDF and office text",
+            kind: MigrationKind::Up,
+            sql: r#"
+                -- Text extracted from PDF and office attachments (see document_text.rs), for
+                -- models that can't read the files themselves. Rows are deleted along with
+                -- their attachment by the attachment store's garbage collection.
+                CREATE TABLE attachment_text_extractions (
+                    attachment_id TEXT PRIMARY KEY,
+                    format TEXT NOT NULL CHECK (format IN ('pdf', 'docx', 'xlsx', 'pptx')),
+                    text TEXT NOT NULL,
+                    section_count INTEGER NOT NULL,
+                    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                );
+            "#,
+        },
+        Migration {
+            version: 134,
+            description: "track where project attachments came from, to keep them up to date",
+            kind: MigrationKind::Up,
+            sql: r#"
+                -- The file or folder an attachment was copied from, for project attachments
+                -- that attachment_watcher.rs keeps in step with their source. source_updated_at
+                -- is when the copy was last replaced because the source changed.
+                ALTER TABLE attachments ADD COLUMN source_path TEXT;
+                ALTER TABLE attachments ADD COLUMN source_updated_at DATETIME;
+            "#,
+        },
+        Migration {
+            version: 134,
+            description: "stop tracking where project attachments came from",
+            kind: MigrationKind::Down,
+            sql: r#"
+                ALTER TABLE attachments DROP COLUMN source_updated_at;
+                ALTER TABLE attachments DROP COLUMN source_path;
+            "#,
+        },
     ];
 }
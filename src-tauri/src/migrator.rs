@@ -0,0 +1,592 @@
+//! Applies the migrations in migrations.rs to a database ourselves rather than
+//! leaving it to tauri_plugin_sql, so that we can:
+//!
+//! - refuse to run if a migration has been edited after it was applied
+//! - back up the database before applying anything, and restore the backup if a
+//!   migration fails, so nobody is left with a half-migrated chats.db
+//! - roll back using `MigrationKind::Down` migrations
+//! - dry-run pending migrations against a copy of the database
+//!
+//! Bookkeeping lives in the same `_sqlx_migrations` table sqlx uses, with the
+//! same SHA-384 checksums, so databases stay interchangeable with builds that
+//! still migrate through the plugin.
+//!
+//! Down migrations share the version of the Up migration they undo, like sqlx's
+//! reversible migrations. They're only ever run explicitly (see the `migrate down`
+//! subcommand of generate_schema), never at startup.
+
+use chrono::Utc;
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName};
+use sha2::{Digest, Sha384};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri_plugin_sql::{Migration, MigrationKind};
+use thiserror::Error;
+
+/// Number of pre-migration backups to keep around per database.
+pub const MAX_BACKUPS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("Invalid migration list: {0}")]
+    InvalidList(String),
+    #[error("Migration {0} was modified after it was applied. Never change a migration that has shipped; add a new one instead.")]
+    ChecksumMismatch(i64),
+    #[error("Migration {0} was applied to this database but is missing from migrations.rs (was the database migrated by a newer version of the app?)")]
+    UnknownApplied(i64),
+    #[error("Migration {0} previously failed part way through. Restore a backup before migrating again.")]
+    Dirty(i64),
+    #[error("Migration {0} has no down migration, so it can't be rolled back")]
+    MissingDown(i64),
+    #[error("Migration {version} ({description}) failed: {source}{}", restored_note(.restored_from))]
+    Failed {
+        version: i64,
+        description: &'static str,
+        #[source]
+        source: rusqlite::Error,
+        restored_from: Option<PathBuf>,
+    },
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+fn restored_note(restored_from: &Option<PathBuf>) -> String {
+    match restored_from {
+        Some(path) => format!(". The database was restored from {}", path.display()),
+        None => String::new(),
+    }
+}
+
+/// A row of `_sqlx_migrations`.
+#[derive(Clone, Debug)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub checksum: Vec<u8>,
+    pub success: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    /// Where to write a backup before changing anything. None skips the backup.
+    pub backup_dir: Option<PathBuf>,
+    /// Run against a temporary copy of the database and throw it away afterwards.
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MigrationReport {
+    /// Latest applied version before the run, if any.
+    pub from_version: Option<i64>,
+    /// Latest applied version after the run, if any.
+    pub to_version: Option<i64>,
+    /// Versions applied (or reverted, when rolling back), in the order they ran.
+    pub versions: Vec<i64>,
+    pub backup: Option<PathBuf>,
+    pub dry_run: bool,
+}
+
+enum Step<'a> {
+    Apply(&'a Migration),
+    /// Holds the Down migration, which shares its version with the Up it undoes
+    Revert(&'a Migration),
+}
+
+impl Step<'_> {
+    fn version(&self) -> i64 {
+        match self {
+            Step::Apply(migration) => migration.version,
+            Step::Revert(down) => down.version,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Step::Apply(migration) => migration.description,
+            Step::Revert(down) => down.description,
+        }
+    }
+}
+
+/// The checksum sqlx records for a migration: SHA-384 of its SQL.
+pub fn checksum(migration: &Migration) -> Vec<u8> {
+    Sha384::digest(migration.sql.as_bytes()).to_vec()
+}
+
+/// Checks the migration list itself: Up versions must be unique and increasing,
+/// and every Down must undo an existing Up.
+pub fn validate_migrations(migrations: &[Migration]) -> Result<(), MigrationError> {
+    let mut last_up: Option<i64> = None;
+    let mut up_versions = HashSet::new();
+    let mut down_versions = HashSet::new();
+    for migration in migrations {
+        match migration.kind {
+            MigrationKind::Up => {
+                if last_up.is_some_and(|last| migration.version <= last) {
+                    return Err(MigrationError::InvalidList(format!(
+                        "version {} is out of order (comes after {})",
+                        migration.version,
+                        last_up.unwrap_or_default()
+                    )));
+                }
+                last_up = Some(migration.version);
+                up_versions.insert(migration.version);
+            }
+            MigrationKind::Down => {
+                if !down_versions.insert(migration.version) {
+                    return Err(MigrationError::InvalidList(format!(
+                        "more than one down migration for version {}",
+                        migration.version
+                    )));
+                }
+            }
+        }
+    }
+    if let Some(version) = down_versions.difference(&up_versions).min() {
+        return Err(MigrationError::InvalidList(format!(
+            "down migration {} has no matching up migration",
+            version
+        )));
+    }
+    Ok(())
+}
+
+/// Creates the bookkeeping table, exactly as sqlx does.
+pub fn ensure_migrations_table(conn: &Connection) -> Result<(), MigrationError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+            version BIGINT PRIMARY KEY,
+            description TEXT NOT NULL,
+            installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            success BOOLEAN NOT NULL,
+            checksum BLOB NOT NULL,
+            execution_time BIGINT NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+pub fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>, MigrationError> {
+    let mut stmt = conn.prepare(
+        "SELECT version, description, checksum, success FROM _sqlx_migrations ORDER BY version",
+    )?;
+    let applied = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                description: row.get(1)?,
+                checksum: row.get(2)?,
+                success: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(applied)
+}
+
+/// Checks every applied migration against migrations.rs, returning what has been applied.
+pub fn verify(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<Vec<AppliedMigration>, MigrationError> {
+    validate_migrations(migrations)?;
+    ensure_migrations_table(conn)?;
+
+    let ups: HashMap<i64, &Migration> = up_migrations(migrations)
+        .map(|migration| (migration.version, migration))
+        .collect();
+    let applied = applied_migrations(conn)?;
+    for migration in &applied {
+        if !migration.success {
+            return Err(MigrationError::Dirty(migration.version));
+        }
+        let Some(known) = ups.get(&migration.version) else {
+            return Err(MigrationError::UnknownApplied(migration.version));
+        };
+        if checksum(known) != migration.checksum {
+            return Err(MigrationError::ChecksumMismatch(migration.version));
+        }
+    }
+    Ok(applied)
+}
+
+/// Up migrations that haven't been applied yet, oldest first.
+pub fn pending<'a>(
+    conn: &Connection,
+    migrations: &'a [Migration],
+) -> Result<Vec<&'a Migration>, MigrationError> {
+    let applied: HashSet<i64> = verify(conn, migrations)?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    Ok(up_migrations(migrations)
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+/// Applies pending migrations up to and including `target` (or all of them).
+pub fn migrate_up(
+    db_path: &Path,
+    migrations: &[Migration],
+    target: Option<i64>,
+    options: &RunOptions,
+) -> Result<MigrationReport, MigrationError> {
+    run(db_path, options, |conn| {
+        Ok(pending(conn, migrations)?
+            .into_iter()
+            .filter(|migration| target.is_none_or(|target| migration.version <= target))
+            .map(Step::Apply)
+            .collect())
+    })
+}
+
+/// Reverts applied migrations newer than `target`, newest first. Every one of
+/// them needs a Down migration; we check before touching anything.
+pub fn migrate_down(
+    db_path: &Path,
+    migrations: &[Migration],
+    target: i64,
+    options: &RunOptions,
+) -> Result<MigrationReport, MigrationError> {
+    run(db_path, options, |conn| {
+        let downs: HashMap<i64, &Migration> = migrations
+            .iter()
+            .filter(|migration| matches!(migration.kind, MigrationKind::Down))
+            .map(|migration| (migration.version, migration))
+            .collect();
+
+        verify(conn, migrations)?
+            .iter()
+            .rev()
+            .filter(|applied| applied.version > target)
+            .map(|applied| {
+                downs
+                    .get(&applied.version)
+                    .map(|down| Step::Revert(down))
+                    .ok_or(MigrationError::MissingDown(applied.version))
+            })
+            .collect()
+    })
+}
+
+fn up_migrations(migrations: &[Migration]) -> impl Iterator<Item = &Migration> {
+    migrations
+        .iter()
+        .filter(|migration| matches!(migration.kind, MigrationKind::Up))
+}
+
+fn latest_version(conn: &Connection) -> Result<Option<i64>, MigrationError> {
+    Ok(
+        conn.query_row("SELECT MAX(version) FROM _sqlx_migrations", [], |row| {
+            row.get(0)
+        })?,
+    )
+}
+
+/// Opens a database with foreign keys enforced, as sqlx does, so migrations
+/// behave the same as they did under tauri_plugin_sql.
+fn open(db_path: &Path) -> Result<Connection, MigrationError> {
+    let conn = Connection::open(db_path)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+    Ok(conn)
+}
+
+fn run<'a>(
+    db_path: &Path,
+    options: &RunOptions,
+    plan: impl FnOnce(&Connection) -> Result<Vec<Step<'a>>, MigrationError>,
+) -> Result<MigrationReport, MigrationError> {
+    if let Some(parent) = db_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut conn = open(db_path)?;
+    let steps = plan(&conn)?;
+    let from_version = latest_version(&conn)?;
+
+    let mut report = MigrationReport {
+        from_version,
+        to_version: from_version,
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    if steps.is_empty() {
+        return Ok(report);
+    }
+
+    if options.dry_run {
+        let copy_path = std::env::temp_dir().join(format!(
+            "chats-dry-run-{}-{}.db",
+            std::process::id(),
+            Utc::now().timestamp_millis()
+        ));
+        conn.backup(DatabaseName::Main, &copy_path, None)?;
+        drop(conn);
+
+        let result =
+            open(&copy_path).and_then(|mut copy| apply_steps(&mut copy, &steps, None, &mut report));
+        let _ = fs::remove_file(&copy_path);
+        result?;
+        return Ok(report);
+    }
+
+    // Nothing to protect on a brand new database
+    if let (Some(backup_dir), Some(version)) = (&options.backup_dir, from_version) {
+        fs::create_dir_all(backup_dir)?;
+        let backup_path = backup_dir.join(format!(
+            "chats-v{}-{}.db",
+            version,
+            Utc::now().format("%Y%m%d-%H%M%S")
+        ));
+        conn.backup(DatabaseName::Main, &backup_path, None)?;
+        log::info!("Backed up database to {}", backup_path.display());
+        prune_backups(backup_dir)?;
+        report.backup = Some(backup_path);
+    }
+
+    let backup = report.backup.clone();
+    apply_steps(&mut conn, &steps, backup.as_deref(), &mut report)?;
+    Ok(report)
+}
+
+/// Runs each step in its own transaction, recording it the way sqlx does. If a
+/// step fails and we have a backup, the whole database is put back as it was.
+fn apply_steps(
+    conn: &mut Connection,
+    steps: &[Step],
+    backup: Option<&Path>,
+    report: &mut MigrationReport,
+) -> Result<(), MigrationError> {
+    for step in steps {
+        let start = Instant::now();
+        let result = (|| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            match step {
+                Step::Apply(migration) => {
+                    tx.execute_batch(migration.sql)?;
+                    tx.execute(
+                        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                        VALUES (?1, ?2, TRUE, ?3, ?4)",
+                        (
+                            migration.version,
+                            migration.description,
+                            checksum(migration),
+                            start.elapsed().as_nanos() as i64,
+                        ),
+                    )?;
+                }
+                Step::Revert(down) => {
+                    tx.execute_batch(down.sql)?;
+                    tx.execute(
+                        "DELETE FROM _sqlx_migrations WHERE version = ?1",
+                        [down.version],
+                    )?;
+                }
+            }
+            tx.commit()
+        })();
+
+        if let Err(source) = result {
+            let restored_from = match backup {
+                Some(backup) => {
+                    conn.restore(DatabaseName::Main, backup, None::<fn(Progress)>)?;
+                    report.versions.clear();
+                    report.to_version = report.from_version;
+                    Some(backup.to_path_buf())
+                }
+                None => None,
+            };
+            return Err(MigrationError::Failed {
+                version: step.version(),
+                description: step.description(),
+                source,
+                restored_from,
+            });
+        }
+
+        log::info!(
+            "{} migration {}: {}",
+            match step {
+                Step::Apply(_) => "Applied",
+                Step::Revert(_) => "Reverted",
+            },
+            step.version(),
+            step.description()
+        );
+        report.versions.push(step.version());
+        report.to_version = latest_version(conn)?;
+    }
+    Ok(())
+}
+
+/// Deletes all but the newest MAX_BACKUPS backups in `backup_dir`.
+fn prune_backups(backup_dir: &Path) -> Result<(), MigrationError> {
+    let mut backups: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(backup_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("chats-v") && name.ends_with(".db")
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    backups.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in backups.into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// An in-memory database with every migration in migrations.rs applied, for
+/// tests that need the real schema. Foreign keys are on, as they are in the SQL
+/// plugin's pool.
+#[cfg(test)]
+pub fn open_in_memory() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+    let migrations = crate::migrations::migrations();
+    let steps: Vec<Step> = pending(&conn, &migrations)
+        .unwrap()
+        .into_iter()
+        .map(Step::Apply)
+        .collect();
+    apply_steps(&mut conn, &steps, None, &mut MigrationReport::default()).unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::migrations;
+
+    /// A database path in a fresh temporary folder, removed when dropped.
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("camp-migrator-{}", uuid::Uuid::new_v4()));
+            TempDb(dir.join("chats.db"))
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            if let Some(dir) = self.0.parent() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    fn attachment_columns(db_path: &Path) -> Vec<String> {
+        let conn = Connection::open(db_path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info('attachments')")
+            .unwrap();
+        let columns = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        columns
+    }
+
+    fn latest(migrations: &[Migration]) -> i64 {
+        up_migrations(migrations)
+            .map(|migration| migration.version)
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_up_down_and_up_again() {
+        let db = TempDb::new();
+        let migrations = migrations();
+        let latest = latest(&migrations);
+        let options = RunOptions::default();
+
+        let report = migrate_up(&db.0, &migrations, None, &options).unwrap();
+        assert_eq!(report.to_version, Some(latest));
+        assert!(attachment_columns(&db.0).contains(&"source_path".to_string()));
+
+        let report = migrate_down(&db.0, &migrations, latest - 1, &options).unwrap();
+        assert_eq!(report.versions, vec![latest]);
+        assert_eq!(report.to_version, Some(latest - 1));
+        assert!(!attachment_columns(&db.0).contains(&"source_path".to_string()));
+
+        let report = migrate_up(&db.0, &migrations, None, &options).unwrap();
+        assert_eq!(report.versions, vec![latest]);
+        assert!(attachment_columns(&db.0).contains(&"source_path".to_string()));
+    }
+
+    #[test]
+    fn refuses_to_roll_back_without_a_down_migration() {
+        let db = TempDb::new();
+        let migrations = migrations();
+        let latest = latest(&migrations);
+        migrate_up(&db.0, &migrations, None, &RunOptions::default()).unwrap();
+
+        let error = migrate_down(&db.0, &migrations, latest - 2, &RunOptions::default());
+        assert!(
+            matches!(error, Err(MigrationError::MissingDown(version)) if version == latest - 1)
+        );
+        // Nothing was reverted, not even the migration that has a down migration
+        let applied = applied_migrations(&Connection::open(&db.0).unwrap()).unwrap();
+        assert_eq!(
+            applied.last().map(|migration| migration.version),
+            Some(latest)
+        );
+    }
+
+    #[test]
+    fn rejects_a_migration_edited_after_it_was_applied() {
+        let db = TempDb::new();
+        let migration = |sql| Migration {
+            version: 1,
+            description: "create notes",
+            sql,
+            kind: MigrationKind::Up,
+        };
+        migrate_up(
+            &db.0,
+            &[migration("CREATE TABLE notes (id TEXT PRIMARY KEY);")],
+            None,
+            &RunOptions::default(),
+        )
+        .unwrap();
+
+        let edited = [migration(
+            "CREATE TABLE notes (id TEXT PRIMARY KEY, body TEXT);",
+        )];
+        let error = migrate_up(&db.0, &edited, None, &RunOptions::default());
+        assert!(matches!(error, Err(MigrationError::ChecksumMismatch(1))));
+    }
+
+    #[test]
+    fn enforces_foreign_keys_while_migrating() {
+        let db = TempDb::new();
+        let migrations = [
+            Migration {
+                version: 1,
+                description: "create chats and messages",
+                sql: "CREATE TABLE chats (id TEXT PRIMARY KEY);
+                    CREATE TABLE messages (id TEXT PRIMARY KEY, chat_id TEXT REFERENCES chats (id));",
+                kind: MigrationKind::Up,
+            },
+            Migration {
+                version: 2,
+                description: "add an orphaned message",
+                sql: "INSERT INTO messages (id, chat_id) VALUES ('m1', 'missing');",
+                kind: MigrationKind::Up,
+            },
+        ];
+
+        let error = migrate_up(&db.0, &migrations, None, &RunOptions::default());
+        assert!(matches!(
+            error,
+            Err(MigrationError::Failed { version: 2, .. })
+        ));
+    }
+}
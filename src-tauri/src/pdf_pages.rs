@@ -0,0 +1,929 @@
+//! Counts the pages of a PDF without loading it. The count is stored in the
+//! root of the page tree, which the trailer leads to through the catalog, so
+//! only the cross-reference sections and those two objects have to be read.
+//! `lopdf` parses every object in the file, which for a large scanned PDF means
+//! reading hundreds of megabytes to get one number.
+//!
+//! Handles classic xref tables, the xref and object streams of PDF 1.5 and
+//! incremental updates. Anything else is an error: a stream filter other than
+//! Flate, an encrypted object stream, or offsets that are off because of junk
+//! before the header. Callers can fall back to a full parse.
+
+use flate2::read::ZlibDecoder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// `startxref` should be right at the end, but some writers leave junk after it.
+const TAIL_BYTES: u64 = 4096;
+/// Objects are parsed from a window of the file that starts at this size and
+/// doubles until the object fits, up to the maximum.
+const WINDOW_BYTES: usize = 4096;
+const MAX_WINDOW_BYTES: usize = 1024 * 1024;
+/// The most a stream may decode to. Xref and object streams rarely take more
+/// than a few megabytes.
+const MAX_STREAM_BYTES: u64 = 64 * 1024 * 1024;
+/// Limits for malformed files that refer back to themselves.
+const MAX_SECTIONS: usize = 256;
+const MAX_DEPTH: usize = 32;
+
+/// The number of pages in the PDF at `path`.
+pub fn page_count(path: &Path) -> Result<usize, String> {
+    let mut pdf = Pdf::open(path)?;
+    let root = pdf.read_xref()?;
+    let catalog = pdf.dictionary(root)?;
+    let pages = reference(&catalog, b"Pages").ok_or("The PDF catalog has no page tree")?;
+    let tree = pdf.dictionary(pages)?;
+    let count = pdf.integer(get(&tree, b"Count"), 0)?;
+    usize::try_from(count).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Integer(i64),
+    Name(Vec<u8>),
+    Reference(u32),
+    Array(Vec<Value>),
+    Dictionary(Dictionary),
+    /// Strings, reals, booleans and null, which nothing here needs the value of.
+    Other,
+}
+
+type Dictionary = Vec<(Vec<u8>, Value)>;
+
+fn get<'a>(dictionary: &'a Dictionary, key: &[u8]) -> Option<&'a Value> {
+    dictionary
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+}
+
+fn integer(dictionary: &Dictionary, key: &[u8]) -> Option<i64> {
+    match get(dictionary, key) {
+        Some(&Value::Integer(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn reference(dictionary: &Dictionary, key: &[u8]) -> Option<u32> {
+    match get(dictionary, key) {
+        Some(&Value::Reference(id)) => Some(id),
+        _ => None,
+    }
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn parse_unsigned(token: &[u8]) -> Option<u64> {
+    if token.is_empty() || !token.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+/// Parses objects from part of the file. Everything returns None both for
+/// malformed input and for input cut off at the end of the window, in which
+/// case the caller tries again with a larger one.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Whether `data` runs to the end of the file (or stream), so running out
+    /// of it means the input is malformed rather than cut off.
+    complete: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8], pos: usize, complete: bool) -> Self {
+        Parser {
+            data,
+            pos,
+            complete,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek() {
+            if byte == b'%' {
+                while self
+                    .peek()
+                    .is_some_and(|byte| byte != b'\n' && byte != b'\r')
+                {
+                    self.pos += 1;
+                }
+            } else if is_whitespace(byte) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// A run of regular characters, like a number or a keyword. Empty if the
+    /// next character is a delimiter.
+    fn token(&mut self) -> Option<&'a [u8]> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|byte| !is_whitespace(byte) && !is_delimiter(byte))
+        {
+            self.pos += 1;
+        }
+        if self.pos == self.data.len() && !self.complete {
+            return None;
+        }
+        Some(&self.data[start..self.pos])
+    }
+
+    fn keyword(&mut self, keyword: &[u8]) -> Option<()> {
+        self.skip_whitespace();
+        (self.token()? == keyword).then_some(())
+    }
+
+    fn unsigned(&mut self) -> Option<u64> {
+        self.skip_whitespace();
+        parse_unsigned(self.token()?)
+    }
+
+    /// `12 0 obj`
+    fn object_header(&mut self, id: u32) -> Option<()> {
+        (self.unsigned()? == id as u64).then_some(())?;
+        self.unsigned()?;
+        self.keyword(b"obj")
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'/' => {
+                self.pos += 1;
+                Some(Value::Name(self.token()?.to_vec()))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == b']' {
+                        self.pos += 1;
+                        return Some(Value::Array(items));
+                    }
+                    items.push(self.value(depth + 1)?);
+                }
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                let mut entries = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek()? {
+                        b'>' if *self.data.get(self.pos + 1)? == b'>' => {
+                            self.pos += 2;
+                            return Some(Value::Dictionary(entries));
+                        }
+                        b'/' => {
+                            self.pos += 1;
+                            let key = self.token()?.to_vec();
+                            entries.push((key, self.value(depth + 1)?));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            b'<' => {
+                let end = self.data[self.pos..]
+                    .iter()
+                    .position(|&byte| byte == b'>')?;
+                self.pos += end + 1;
+                Some(Value::Other)
+            }
+            b'(' => {
+                self.pos += 1;
+                let mut nesting = 1;
+                while nesting > 0 {
+                    match self.peek()? {
+                        b'\\' => self.pos += 1,
+                        b'(' => nesting += 1,
+                        b')' => nesting -= 1,
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                Some(Value::Other)
+            }
+            b'+' | b'-' | b'.' | b'0'..=b'9' => self.number(),
+            _ => {
+                let token = self.token()?;
+                (!token.is_empty()).then_some(Value::Other)
+            }
+        }
+    }
+
+    /// An integer, a real, or a reference like `12 0 R`.
+    fn number(&mut self) -> Option<Value> {
+        let token = self.token()?;
+        let Some(number) = std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse::<i64>().ok())
+        else {
+            return Some(Value::Other);
+        };
+
+        let after_number = self.pos;
+        if let Ok(id) = u32::try_from(number) {
+            self.skip_whitespace();
+            let generation = self.token()?;
+            self.skip_whitespace();
+            let keyword = self.token();
+            if parse_unsigned(generation).is_some() {
+                match keyword {
+                    Some(b"R") => return Some(Value::Reference(id)),
+                    // Can't tell yet
+                    None => return None,
+                    Some(_) => {}
+                }
+            }
+        }
+        self.pos = after_number;
+        Some(Value::Integer(number))
+    }
+}
+
+enum Section {
+    /// The subsections of a classic xref table: the first object number, how
+    /// many entries there are and where they start. Entries are 20 bytes each.
+    Table(Vec<(u32, u32, u64)>),
+    /// A decoded xref stream: the widths of its three fields, the object
+    /// numbers it covers as (first, count) ranges, and its rows.
+    Stream {
+        widths: [usize; 3],
+        index: Vec<(u32, u32)>,
+        rows: Vec<u8>,
+    },
+}
+
+enum Entry {
+    InUse(u64),
+    Compressed { stream: u32, index: u64 },
+    Free,
+}
+
+fn big_endian(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, &byte| value << 8 | byte as u64)
+}
+
+impl Section {
+    /// Where to look for object `id`, if this section covers it: an entry of a
+    /// classic table is read from the file only when it's needed.
+    fn locate(&self, id: u32) -> Option<Result<Entry, u64>> {
+        match self {
+            Section::Table(subsections) => {
+                subsections.iter().find_map(|&(first, count, entries)| {
+                    let offset = id.checked_sub(first).filter(|&offset| offset < count)?;
+                    Some(Err(entries + offset as u64 * 20))
+                })
+            }
+            Section::Stream {
+                widths,
+                index,
+                rows,
+            } => {
+                let row_len: usize = widths.iter().sum();
+                let mut row = 0;
+                for &(first, count) in index {
+                    if let Some(offset) = id.checked_sub(first).filter(|&offset| offset < count) {
+                        let start = (row + offset as usize) * row_len;
+                        let fields = rows.get(start..start + row_len)?;
+                        let (kind, fields) = fields.split_at(widths[0]);
+                        let (second, third) = fields.split_at(widths[1]);
+                        // The type defaults to 1 when its field is left out
+                        let kind = if kind.is_empty() { 1 } else { big_endian(kind) };
+                        return Some(Ok(match kind {
+                            1 => Entry::InUse(big_endian(second)),
+                            2 => Entry::Compressed {
+                                stream: u32::try_from(big_endian(second)).ok()?,
+                                index: big_endian(third),
+                            },
+                            // Free, or a type from a later version, which is to
+                            // be read as null
+                            _ => Entry::Free,
+                        }));
+                    }
+                    row += count as usize;
+                }
+                None
+            }
+        }
+    }
+}
+
+struct Pdf {
+    file: File,
+    len: u64,
+    /// Newest first, so the first section with an entry for an object has the
+    /// current one.
+    sections: Vec<Section>,
+}
+
+impl Pdf {
+    fn open(path: &Path) -> Result<Pdf, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        Ok(Pdf {
+            file,
+            len,
+            sections: Vec::new(),
+        })
+    }
+
+    /// Up to `len` bytes from `offset`, fewer at the end of the file.
+    fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        let mut data = Vec::with_capacity(len);
+        (&mut self.file)
+            .take(len as u64)
+            .read_to_end(&mut data)
+            .map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+
+    /// Runs `parse` on the file from `offset`, with more of it each time it
+    /// comes up short.
+    fn parse_at<T>(
+        &mut self,
+        offset: u64,
+        mut parse: impl FnMut(&mut Parser) -> Option<T>,
+    ) -> Result<T, String> {
+        let mut window = WINDOW_BYTES;
+        loop {
+            let data = self.read_at(offset, window)?;
+            let complete = offset + data.len() as u64 >= self.len;
+            if let Some(result) = parse(&mut Parser::new(&data, 0, complete)) {
+                return Ok(result);
+            }
+            if complete || window >= MAX_WINDOW_BYTES {
+                return Err(format!("Couldn't parse the PDF at offset {}", offset));
+            }
+            window *= 2;
+        }
+    }
+
+    fn startxref(&mut self) -> Result<u64, String> {
+        let tail = self.read_at(self.len.saturating_sub(TAIL_BYTES), TAIL_BYTES as usize)?;
+        let start = tail
+            .windows(9)
+            .rposition(|window| window == b"startxref")
+            .ok_or("The PDF has no startxref")?;
+        Parser::new(&tail, start + 9, true)
+            .unsigned()
+            .ok_or_else(|| "The PDF's startxref has no offset".to_string())
+    }
+
+    /// Reads every cross-reference section, following the trailers back from
+    /// the newest, and returns the object number of the catalog.
+    fn read_xref(&mut self) -> Result<u32, String> {
+        let mut next = Some(self.startxref()?);
+        let mut seen = HashSet::new();
+        let mut root = None;
+        while let Some(offset) = next {
+            if !seen.insert(offset) || self.sections.len() >= MAX_SECTIONS {
+                return Err("The PDF's cross-reference sections refer back to each other".into());
+            }
+            let is_table = self.parse_at(offset, |parser| {
+                parser.skip_whitespace();
+                Some(parser.token()? == b"xref")
+            })?;
+            let trailer = if is_table {
+                let (section, trailer) = self.read_xref_table(offset)?;
+                self.sections.push(section);
+                // A hybrid file lists its compressed objects in a stream that
+                // comes between this table and the previous section
+                if let Some(stream_offset) = integer(&trailer, b"XRefStm") {
+                    let (section, _) = self.read_xref_stream(stream_offset as u64)?;
+                    self.sections.push(section);
+                }
+                trailer
+            } else {
+                let (section, trailer) = self.read_xref_stream(offset)?;
+                self.sections.push(section);
+                trailer
+            };
+            root = root.or(reference(&trailer, b"Root"));
+            next = integer(&trailer, b"Prev").and_then(|prev| u64::try_from(prev).ok());
+        }
+        root.ok_or_else(|| "The PDF's trailer has no catalog".to_string())
+    }
+
+    fn read_xref_table(&mut self, offset: u64) -> Result<(Section, Dictionary), String> {
+        enum Next {
+            Subsection {
+                first: u32,
+                count: u32,
+                entries: u64,
+            },
+            Trailer(Dictionary),
+        }
+
+        let mut subsections = Vec::new();
+        let mut pos = offset
+            + self.parse_at(offset, |parser| {
+                parser.keyword(b"xref")?;
+                Some(parser.pos as u64)
+            })?;
+        loop {
+            let next = self.parse_at(pos, |parser| {
+                parser.skip_whitespace();
+                let start = parser.pos;
+                if parser.token()? == b"trailer" {
+                    return match parser.value(0)? {
+                        Value::Dictionary(trailer) => Some(Next::Trailer(trailer)),
+                        _ => None,
+                    };
+                }
+                parser.pos = start;
+                let first = u32::try_from(parser.unsigned()?).ok()?;
+                let count = u32::try_from(parser.unsigned()?).ok()?;
+                // The entries start on the next line
+                while parser.peek()? == b' ' {
+                    parser.pos += 1;
+                }
+                if parser.peek()? == b'\r' {
+                    parser.pos += 1;
+                }
+                if parser.peek()? == b'\n' {
+                    parser.pos += 1;
+                }
+                Some(Next::Subsection {
+                    first,
+                    count,
+                    entries: pos + parser.pos as u64,
+                })
+            })?;
+            match next {
+                Next::Subsection {
+                    first,
+                    count,
+                    entries,
+                } => {
+                    subsections.push((first, count, entries));
+                    pos = entries + count as u64 * 20;
+                }
+                Next::Trailer(trailer) => return Ok((Section::Table(subsections), trailer)),
+            }
+        }
+    }
+
+    fn read_xref_stream(&mut self, offset: u64) -> Result<(Section, Dictionary), String> {
+        let (dictionary, rows) = self.stream(None, offset, 0)?;
+        let invalid = || "Invalid xref stream in the PDF".to_string();
+        let widths = match get(&dictionary, b"W") {
+            Some(Value::Array(widths)) if widths.len() == 3 => {
+                let mut fields = [0; 3];
+                for (field, width) in fields.iter_mut().zip(widths) {
+                    *field = match width {
+                        &Value::Integer(width @ 0..=8) => width as usize,
+                        _ => return Err(invalid()),
+                    };
+                }
+                fields
+            }
+            _ => return Err(invalid()),
+        };
+        if widths.iter().sum::<usize>() == 0 {
+            return Err(invalid());
+        }
+        let index = match get(&dictionary, b"Index") {
+            Some(Value::Array(index)) => index
+                .chunks(2)
+                .map(|range| match range {
+                    [Value::Integer(first), Value::Integer(count)] => {
+                        Some((u32::try_from(*first).ok()?, u32::try_from(*count).ok()?))
+                    }
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(invalid)?,
+            _ => {
+                let size = integer(&dictionary, b"Size").ok_or_else(invalid)?;
+                vec![(0, u32::try_from(size).map_err(|_| invalid())?)]
+            }
+        };
+        Ok((
+            Section::Stream {
+                widths,
+                index,
+                rows,
+            },
+            dictionary,
+        ))
+    }
+
+    fn find(&mut self, id: u32) -> Result<Entry, String> {
+        let location = self.sections.iter().find_map(|section| section.locate(id));
+        match location {
+            Some(Ok(entry)) => Ok(entry),
+            Some(Err(entry_offset)) => {
+                let entry = self.read_at(entry_offset, 20)?;
+                let mut parser = Parser::new(&entry, 0, true);
+                let offset = parser.unsigned();
+                parser.unsigned();
+                match (offset, parser.keyword(b"n")) {
+                    (Some(offset), Some(())) => Ok(Entry::InUse(offset)),
+                    _ => Ok(Entry::Free),
+                }
+            }
+            None => Ok(Entry::Free),
+        }
+    }
+
+    /// Object `id`, from wherever it's stored.
+    fn object(&mut self, id: u32, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("The PDF's objects refer back to each other".into());
+        }
+        match self.find(id)? {
+            Entry::InUse(offset) => self.parse_at(offset, |parser| {
+                parser.object_header(id)?;
+                parser.value(0)
+            }),
+            Entry::Compressed { stream, index } => {
+                let Entry::InUse(offset) = self.find(stream)? else {
+                    return Err(format!("Object stream {} is missing from the PDF", stream));
+                };
+                let (dictionary, data) = self.stream(Some(stream), offset, depth + 1)?;
+                let invalid = || format!("Invalid object stream {} in the PDF", stream);
+                let count = integer(&dictionary, b"N").ok_or_else(invalid)?;
+                let first = integer(&dictionary, b"First").ok_or_else(invalid)?;
+                if index >= count as u64 {
+                    return Err(invalid());
+                }
+                // The stream starts with a pair of numbers for each object: its
+                // id and where it starts, counted from `first`
+                let mut header = Parser::new(&data, 0, true);
+                for _ in 0..index * 2 {
+                    header.unsigned().ok_or_else(invalid)?;
+                }
+                if header.unsigned() != Some(id as u64) {
+                    return Err(invalid());
+                }
+                let start = header.unsigned().ok_or_else(invalid)? + first as u64;
+                Parser::new(&data, start as usize, true)
+                    .value(0)
+                    .ok_or_else(invalid)
+            }
+            Entry::Free => Err(format!("Object {} is missing from the PDF", id)),
+        }
+    }
+
+    fn dictionary(&mut self, id: u32) -> Result<Dictionary, String> {
+        match self.object(id, 0)? {
+            Value::Dictionary(dictionary) => Ok(dictionary),
+            _ => Err(format!("Object {} in the PDF isn't a dictionary", id)),
+        }
+    }
+
+    /// A number that may be stored as an object of its own.
+    fn integer(&mut self, value: Option<&Value>, depth: usize) -> Result<i64, String> {
+        let value = match value {
+            Some(&Value::Reference(id)) => self.object(id, depth + 1)?,
+            Some(&Value::Integer(value)) => Value::Integer(value),
+            _ => Value::Other,
+        };
+        match value {
+            Value::Integer(value) => Ok(value),
+            _ => Err("Expected a number in the PDF".into()),
+        }
+    }
+
+    /// The dictionary and decoded data of the stream at `offset`. `id` is
+    /// checked against the object header unless it's None, as for an xref
+    /// stream found through `startxref`.
+    fn stream(
+        &mut self,
+        id: Option<u32>,
+        offset: u64,
+        depth: usize,
+    ) -> Result<(Dictionary, Vec<u8>), String> {
+        let (dictionary, data_start) = self.parse_at(offset, |parser| {
+            match id {
+                Some(id) => parser.object_header(id)?,
+                None => {
+                    parser.unsigned()?;
+                    parser.unsigned()?;
+                    parser.keyword(b"obj")?;
+                }
+            }
+            let Value::Dictionary(dictionary) = parser.value(0)? else {
+                return None;
+            };
+            parser.keyword(b"stream")?;
+            // The data starts on the next line
+            if parser.peek()? == b'\r' {
+                parser.pos += 1;
+            }
+            if parser.peek()? == b'\n' {
+                parser.pos += 1;
+            }
+            Some((dictionary, offset + parser.pos as u64))
+        })?;
+
+        let length = self.integer(get(&dictionary, b"Length"), depth)?;
+        let length = u64::try_from(length)
+            .ok()
+            .filter(|&length| data_start + length <= self.len)
+            .ok_or("A stream in the PDF runs past the end of the file")?;
+        let mut data = self.read_at(data_start, length as usize)?;
+
+        let filters = match get(&dictionary, b"Filter") {
+            None => Vec::new(),
+            Some(Value::Array(filters)) => filters.iter().collect(),
+            Some(filter) => vec![filter],
+        };
+        let parameters = match get(&dictionary, b"DecodeParms") {
+            Some(Value::Array(parameters)) => parameters.iter().collect(),
+            Some(parameters) => vec![parameters],
+            None => Vec::new(),
+        };
+        for (i, filter) in filters.into_iter().enumerate() {
+            if !matches!(filter, Value::Name(name) if name == b"FlateDecode") {
+                return Err("A stream in the PDF uses a filter other than Flate".into());
+            }
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(data.as_slice())
+                .take(MAX_STREAM_BYTES)
+                .read_to_end(&mut inflated)
+                .map_err(|e| e.to_string())?;
+            data = match parameters.get(i) {
+                Some(Value::Dictionary(parameters)) => unpredict(inflated, parameters)?,
+                _ => inflated,
+            };
+        }
+        Ok((dictionary, data))
+    }
+}
+
+/// Undoes the PNG predictors that xref streams are usually written with.
+fn unpredict(data: Vec<u8>, parameters: &Dictionary) -> Result<Vec<u8>, String> {
+    match integer(parameters, b"Predictor").unwrap_or(1) {
+        1 => return Ok(data),
+        10..=15 => {}
+        predictor => return Err(format!("Unsupported predictor {} in the PDF", predictor)),
+    }
+    let colors = integer(parameters, b"Colors").unwrap_or(1);
+    let bits = integer(parameters, b"BitsPerComponent").unwrap_or(8);
+    let columns = integer(parameters, b"Columns").unwrap_or(1);
+    if !(1..=4).contains(&colors)
+        || ![1, 2, 4, 8, 16].contains(&bits)
+        || !(1..=1 << 20).contains(&columns)
+    {
+        return Err("Invalid predictor parameters in the PDF".into());
+    }
+    let pixel_len = ((colors * bits + 7) / 8) as usize;
+    let row_len = ((colors * bits * columns + 7) / 8) as usize;
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_len];
+    for row in data.chunks(row_len + 1) {
+        let (&predictor, row) = row.split_first().unwrap_or((&0, &[]));
+        let start = output.len();
+        for (i, &byte) in row.iter().enumerate() {
+            let left = if i >= pixel_len {
+                output[start + i - pixel_len]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= pixel_len {
+                previous[i - pixel_len]
+            } else {
+                0
+            };
+            let prediction = match predictor {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err("Invalid predictor in the PDF".into()),
+            };
+            output.push(byte.wrapping_add(prediction));
+        }
+        previous[..row.len()].copy_from_slice(&output[start..]);
+    }
+    Ok(output)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance = |value: u8| (estimate - value as i16).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Object, SaveOptions};
+
+    /// A PDF with `pages` blank pages, saved with classic xref tables or with
+    /// xref and object streams.
+    fn pdf(pages: usize, compressed: bool) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                    })
+                    .into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => pages as i64,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        if compressed {
+            // lopdf leaves every object stream after the first out of the xref
+            // stream it writes, so they all go in one
+            let options = SaveOptions::builder()
+                .use_object_streams(true)
+                .use_xref_streams(true)
+                .max_objects_per_stream(1000)
+                .build();
+            document.save_with_options(&mut bytes, options).unwrap();
+        } else {
+            document.save_to(&mut bytes).unwrap();
+        }
+        bytes
+    }
+
+    fn count(bytes: &[u8]) -> Result<usize, String> {
+        let path = std::env::temp_dir().join(format!("camp-pdf-{}.pdf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, bytes).unwrap();
+        let result = page_count(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn startxref(bytes: &[u8]) -> u64 {
+        let start = bytes
+            .windows(9)
+            .rposition(|window| window == b"startxref")
+            .unwrap();
+        Parser::new(bytes, start + 9, true).unsigned().unwrap()
+    }
+
+    #[test]
+    fn counts_pages_with_an_xref_table() {
+        let bytes = pdf(3, false);
+        assert!(!bytes.windows(7).any(|window| window == b"/ObjStm"));
+        assert_eq!(count(&bytes), Ok(3));
+        assert_eq!(count(&pdf(150, false)), Ok(150));
+    }
+
+    #[test]
+    fn counts_pages_with_xref_and_object_streams() {
+        let bytes = pdf(3, true);
+        assert!(bytes.windows(7).any(|window| window == b"/ObjStm"));
+        assert!(bytes.windows(5).any(|window| window == b"/XRef"));
+        assert_eq!(count(&bytes), Ok(3));
+        assert_eq!(count(&pdf(150, true)), Ok(150));
+    }
+
+    #[test]
+    fn counts_pages_after_an_incremental_update() {
+        let mut bytes = pdf(3, false);
+        let previous = startxref(&bytes);
+        let document = Document::load_mem(&bytes).unwrap();
+        let pages_id = document.catalog().unwrap().get(b"Pages").unwrap();
+        let pages_id = pages_id.as_reference().unwrap().0;
+
+        // Replaces the page tree root with one claiming 7 pages, in a section
+        // whose trailer leaves the catalog to the previous one
+        let object_offset = bytes.len();
+        bytes.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< /Type /Pages /Kids [] /Count 7 >>\nendobj\n",
+                pages_id
+            )
+            .as_bytes(),
+        );
+        let xref_offset = bytes.len();
+        bytes.extend_from_slice(
+            format!(
+                "xref\n0 1\n0000000000 65535 f \n{} 1\n{:010} 00000 n \ntrailer\n\
+                << /Size {} /Prev {} >>\nstartxref\n{}\n%%EOF\n",
+                pages_id,
+                object_offset,
+                document.max_id + 1,
+                previous,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+        assert_eq!(count(&bytes), Ok(7));
+    }
+
+    #[test]
+    fn fails_without_cross_reference_data() {
+        assert!(count(b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n").is_err());
+
+        let mut bytes = pdf(3, false);
+        let xref = startxref(&bytes) as usize;
+        bytes[xref..xref + 4].copy_from_slice(b"junk");
+        assert!(count(&bytes).is_err());
+    }
+
+    #[test]
+    fn parses_dictionaries_and_references() {
+        let data = b"<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /Title (a (nested) \\) string) \
+            /Id <0A1B> /MediaBox [0 0 612.5 792] % a comment\n /Extra << /Nested true >> >>";
+        let Some(Value::Dictionary(dictionary)) = Parser::new(data, 0, true).value(0) else {
+            panic!("not a dictionary");
+        };
+        assert_eq!(
+            get(&dictionary, b"Kids"),
+            Some(&Value::Array(vec![
+                Value::Reference(3),
+                Value::Reference(4)
+            ]))
+        );
+        assert_eq!(integer(&dictionary, b"Count"), Some(2));
+        assert_eq!(get(&dictionary, b"Title"), Some(&Value::Other));
+        assert_eq!(
+            get(&dictionary, b"MediaBox"),
+            Some(&Value::Array(vec![
+                Value::Integer(0),
+                Value::Integer(0),
+                Value::Other,
+                Value::Integer(792),
+            ]))
+        );
+        assert!(matches!(
+            get(&dictionary, b"Extra"),
+            Some(Value::Dictionary(_))
+        ));
+
+        // Cut off part way, and where a number might still turn out to be a
+        // reference
+        assert_eq!(Parser::new(&data[..40], 0, false).value(0), None);
+        assert_eq!(Parser::new(b"<< /Count 12 0", 0, false).value(0), None);
+        assert_eq!(
+            Parser::new(b"12 0 obj", 0, true).value(0),
+            Some(Value::Integer(12))
+        );
+    }
+
+    #[test]
+    fn undoes_png_predictors() {
+        let parameters = vec![
+            (b"Predictor".to_vec(), Value::Integer(12)),
+            (b"Columns".to_vec(), Value::Integer(3)),
+        ];
+        // Two rows predicted from the one above: [1, 2, 3] then [2, 4, 6]
+        let data = vec![2, 1, 2, 3, 2, 1, 2, 3];
+        assert_eq!(unpredict(data, &parameters), Ok(vec![1, 2, 3, 2, 4, 6]));
+        // Sub, predicted from the left: [5, 6, 7]
+        let data = vec![1, 5, 1, 1];
+        assert_eq!(unpredict(data, &parameters), Ok(vec![5, 6, 7]));
+    }
+}
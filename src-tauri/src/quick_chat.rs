@@ -0,0 +1,160 @@
+//! The quick chat window: a small window that floats over everything else,
+//! toggled from anywhere with a global shortcut or from the tray icon.
+//!
+//! On macOS it's turned into a non-activating panel (see window.rs), so it can
+//! show over full screen apps without bringing the main window forward. On Linux
+//! and Windows it's a frameless, always-on-top window that stays out of the
+//! taskbar.
+//!
+//! Global shortcuts work on macOS, Windows and X11. Wayland doesn't let apps
+//! grab keys, so there the shortcut only fires while an XWayland window has
+//! focus, and compositors may ignore always-on-top. The tray icon works
+//! everywhere.
+
+use crate::command;
+use crate::global_shortcuts;
+use crate::shortcut;
+use crate::SPOTLIGHT_LABEL;
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+
+#[cfg(not(target_os = "macos"))]
+use tauri::WindowEvent;
+
+#[cfg(target_os = "macos")]
+use crate::window::WebviewWindowExt;
+#[cfg(target_os = "macos")]
+use tauri::Listener;
+#[cfg(target_os = "macos")]
+use tauri_nspanel::ManagerExt;
+
+/// Same as the default in Settings.ts.
+pub const DEFAULT_SHORTCUT: &str = "Alt+Space";
+
+/// `quickChat` in the settings store (see Settings.ts). New installs save it
+/// enabled; if it's missing, the quick chat stays off until it's turned on.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuickChatSettings {
+    pub enabled: bool,
+    pub shortcut: String,
+}
+
+impl Default for QuickChatSettings {
+    fn default() -> Self {
+        QuickChatSettings {
+            enabled: false,
+            shortcut: DEFAULT_SHORTCUT.to_string(),
+        }
+    }
+}
+
+impl QuickChatSettings {
+    pub fn load(app_handle: &AppHandle) -> Self {
+        app_handle
+            .store("settings")
+            .ok()
+            .and_then(|store| store.get("settings"))
+            .and_then(|settings| settings.get("quickChat").cloned())
+            .and_then(|quick_chat| serde_json::from_value(quick_chat).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn is_dark_mode(app_handle: &AppHandle) -> bool {
+    app_handle
+        .store("settings")
+        .ok()
+        .and_then(|store| store.get("settings"))
+        .and_then(|settings| {
+            settings
+                .get("theme")
+                .and_then(|theme| theme.get("mode"))
+                .and_then(|mode| mode.as_str().map(|mode| mode == "dark"))
+        })
+        .unwrap_or(false)
+}
+
+pub fn is_visible(app_handle: &AppHandle) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        app_handle
+            .get_webview_panel(SPOTLIGHT_LABEL)
+            .map(|panel| panel.is_visible())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        app_handle
+            .get_webview_window(SPOTLIGHT_LABEL)
+            .and_then(|window| window.is_visible().ok())
+            .unwrap_or(false)
+    }
+}
+
+/// Shows the quick chat if it's hidden, and hides it otherwise.
+pub fn toggle(app_handle: &AppHandle) {
+    if is_visible(app_handle) {
+        command::hide(app_handle.clone());
+    } else {
+        let _ = app_handle.emit("show_quick_chat", ());
+        command::show(app_handle.clone());
+    }
+}
+
+/// Sets up the quick chat window. Its shortcut is registered with the others
+/// (see global_shortcuts.rs), and the tray icon is set up in tray.rs.
+#[cfg(target_os = "macos")]
+pub fn setup(app_handle: &AppHandle) -> tauri::Result<()> {
+    let Some(window) = app_handle.get_webview_window(SPOTLIGHT_LABEL) else {
+        return Ok(());
+    };
+    window.to_spotlight_panel(is_dark_mode(app_handle))?;
+
+    let handle = app_handle.clone();
+    app_handle.listen(
+        format!("{}_panel_did_become_key", SPOTLIGHT_LABEL),
+        move |_| {
+            // Lets the frontend focus the input
+            let _ = handle.emit("quick-chat-focused", ());
+        },
+    );
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn setup(app_handle: &AppHandle) -> tauri::Result<()> {
+    let Some(window) = app_handle.get_webview_window(SPOTLIGHT_LABEL) else {
+        return Ok(());
+    };
+    window.set_decorations(false)?;
+    window.set_always_on_top(true)?;
+    window.set_skip_taskbar(true)?;
+    // Follow the user to whichever workspace they're on, like the macOS panel
+    window.set_visible_on_all_workspaces(true)?;
+
+    let handle = app_handle.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Focused(true) = event {
+            // Lets the frontend focus the input
+            let _ = handle.emit("quick-chat-focused", ());
+        }
+    });
+    Ok(())
+}
+
+/// Applies changed quick chat settings straight away, without a restart. If the
+/// new shortcut can't be registered, the old one stays. Returns the shortcut in
+/// canonical form, which is what should be saved.
+pub fn update_settings(
+    app_handle: &AppHandle,
+    enabled: bool,
+    shortcut: &str,
+) -> Result<String, String> {
+    let parsed = shortcut::parse_shortcut(shortcut).map_err(|e| e.to_string())?;
+    global_shortcuts::set_quick_chat_shortcut(app_handle, enabled.then_some(parsed))?;
+    Ok(shortcut::format_shortcut(&parsed))
+}
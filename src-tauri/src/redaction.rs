@@ -0,0 +1,347 @@
+//! Hiding sensitive parts of screenshots before they're sent to a model: areas
+//! the user marks, and optionally the windows of apps like password managers,
+//! which are masked automatically in captures of the whole screen.
+//!
+//! Redaction works on decoded images, so it applies to any image file, not just
+//! our own captures. It runs before `resize_image`, so nothing unredacted is
+//! ever written out at the size that gets attached.
+
+use crate::display_layout::Rect;
+use crate::window_capture::WindowInfo;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+/// Blurred areas are shrunk by this factor and scaled back up, which leaves
+/// nothing legible even for large text.
+const BLUR_FACTOR: u32 = 24;
+
+/// Apps masked by default: the common password managers.
+const DEFAULT_MASKED_APPS: &[&str] = &[
+    "1Password",
+    "Bitwarden",
+    "Dashlane",
+    "KeePassXC",
+    "Keychain Access",
+    "LastPass",
+    "Proton Pass",
+];
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionStyle {
+    #[default]
+    Blur,
+    Blackout,
+}
+
+/// `screenshotRedaction` in the settings store.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RedactionSettings {
+    /// Mask the windows of `apps` in captures of the whole screen.
+    pub mask_apps: bool,
+    /// Matched case-insensitively against the app name of each window.
+    pub apps: Vec<String>,
+    pub style: RedactionStyle,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            mask_apps: false,
+            apps: DEFAULT_MASKED_APPS
+                .iter()
+                .map(|app| app.to_string())
+                .collect(),
+            style: RedactionStyle::default(),
+        }
+    }
+}
+
+impl RedactionSettings {
+    pub fn load(app_handle: &AppHandle) -> Self {
+        app_handle
+            .store("settings")
+            .ok()
+            .and_then(|store| store.get("settings"))
+            .and_then(|settings| settings.get("screenshotRedaction").cloned())
+            .and_then(|redaction| serde_json::from_value(redaction).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether a window belongs to one of `apps`. App names vary a little between
+/// versions and platforms ("1Password 7", "1password"), so a configured name
+/// only has to appear in the window's app name.
+pub fn is_masked_app(app_name: &str, apps: &[String]) -> bool {
+    let app_name = app_name.to_lowercase();
+    apps.iter()
+        .map(|app| app.trim().to_lowercase())
+        .any(|app| !app.is_empty() && app_name.contains(&app))
+}
+
+/// Maps windows (in display coordinates) onto the pixels of a `width` x
+/// `height` capture of `display`. Edges are rounded outwards, and windows off
+/// the display are dropped.
+pub fn window_areas(windows: &[Rect], display: Rect, width: u32, height: u32) -> Vec<Rect> {
+    if display.width == 0 || display.height == 0 {
+        return Vec::new();
+    }
+    let scale_x = width as f64 / display.width as f64;
+    let scale_y = height as f64 / display.height as f64;
+    windows
+        .iter()
+        .filter_map(|window| window.intersect(&display))
+        .map(|visible| {
+            let left = ((visible.x - display.x) as f64 * scale_x).floor();
+            let top = ((visible.y - display.y) as f64 * scale_y).floor();
+            let right = ((visible.x - display.x + visible.width as i32) as f64 * scale_x).ceil();
+            let bottom = ((visible.y - display.y + visible.height as i32) as f64 * scale_y).ceil();
+            Rect {
+                x: left as i32,
+                y: top as i32,
+                width: (right - left) as u32,
+                height: (bottom - top) as u32,
+            }
+        })
+        .collect()
+}
+
+/// Redacts `areas` (in pixels, clipped to the image) in place.
+pub fn redact(image: &mut RgbaImage, areas: &[Rect], style: RedactionStyle) {
+    let bounds = Rect {
+        x: 0,
+        y: 0,
+        width: image.width(),
+        height: image.height(),
+    };
+    for area in areas.iter().filter_map(|area| area.intersect(&bounds)) {
+        let (x, y) = (area.x as u32, area.y as u32);
+        match style {
+            RedactionStyle::Blackout => {
+                for py in y..y + area.height {
+                    for px in x..x + area.width {
+                        image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+            RedactionStyle::Blur => {
+                let region = imageops::crop_imm(image, x, y, area.width, area.height).to_image();
+                let small = imageops::resize(
+                    &region,
+                    (area.width / BLUR_FACTOR).max(1),
+                    (area.height / BLUR_FACTOR).max(1),
+                    FilterType::Triangle,
+                );
+                let blurred =
+                    imageops::resize(&small, area.width, area.height, FilterType::Triangle);
+                imageops::replace(image, &blurred, x as i64, y as i64);
+            }
+        }
+    }
+}
+
+/// Masks the windows of the configured apps in a capture of `display`, if that's
+/// turned on. Returns how many windows were masked.
+pub fn mask_app_windows(
+    image: &mut RgbaImage,
+    display: Rect,
+    settings: &RedactionSettings,
+) -> Result<usize, String> {
+    if !settings.mask_apps {
+        return Ok(0);
+    }
+    let windows: Vec<Rect> = crate::window_capture::list_windows()?
+        .iter()
+        .filter(|window| is_masked_app(&window.app_name, &settings.apps))
+        .map(WindowInfo::bounds)
+        .collect();
+    let areas = window_areas(&windows, display, image.width(), image.height());
+    redact(image, &areas, settings.style);
+    log::info!("Masked {} app windows in screenshot", areas.len());
+    Ok(areas.len())
+}
+
+/// Same as `mask_app_windows`, for a capture that's already been saved.
+pub fn mask_app_windows_in_file(
+    path: &Path,
+    display: Rect,
+    settings: &RedactionSettings,
+) -> Result<(), String> {
+    if !settings.mask_apps {
+        return Ok(());
+    }
+    let mut image = image::open(path).map_err(|e| e.to_string())?.to_rgba8();
+    if mask_app_windows(&mut image, display, settings)? > 0 {
+        image.save(path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Redacts `areas` of the image at `path` and saves the result as a new PNG in
+/// `output_dir`, leaving the original alone.
+pub fn redact_file(
+    path: &Path,
+    areas: &[Rect],
+    style: RedactionStyle,
+    output_dir: &Path,
+) -> Result<PathBuf, String> {
+    let mut image = image::open(path).map_err(|e| e.to_string())?.to_rgba8();
+    redact(&mut image, areas, style);
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    // Unique per call, like resize_image's output in the same folder, so
+    // redacting two files with the same name can't overwrite the first result
+    let output_path = output_dir.join(format!("{}_redacted_{}.png", stem, uuid::Uuid::new_v4()));
+    image.save(&output_path).map_err(|e| e.to_string())?;
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255])
+        })
+    }
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+    #[test]
+    fn maps_windows_onto_a_hidpi_display_left_of_the_main_one() {
+        // A 1440x900pt display at 2x, to the left of the main display
+        let display = rect(-1440, 0, 1440, 900);
+        assert_eq!(
+            window_areas(&[rect(-1000, 100, 300, 200)], display, 2880, 1800),
+            [rect(880, 200, 600, 400)]
+        );
+    }
+
+    #[test]
+    fn clips_windows_partly_off_the_display() {
+        let display = rect(-1440, 0, 1440, 900);
+        let windows = [
+            // Straddles the edge with the main display
+            rect(-100, 800, 500, 500),
+            // Entirely on the main display
+            rect(10, 10, 10, 10),
+        ];
+        assert_eq!(
+            window_areas(&windows, display, 2880, 1800),
+            [rect(2680, 1600, 200, 200)]
+        );
+        assert!(window_areas(&windows, rect(0, 0, 0, 0), 2880, 1800).is_empty());
+    }
+
+    #[test]
+    fn rounds_window_edges_outwards() {
+        // 1.5x: 11..22pt is 16.5..33px, and 11..23pt is 16.5..34.5px
+        let display = rect(0, 0, 100, 100);
+        assert_eq!(
+            window_areas(&[rect(11, 11, 11, 12)], display, 150, 150),
+            [rect(16, 16, 17, 19)]
+        );
+    }
+
+    #[test]
+    fn blacks_out_only_the_part_of_an_area_inside_the_image() {
+        let mut image = gradient(400, 300);
+        let original = image.clone();
+        redact(
+            &mut image,
+            &[
+                rect(-10, -10, 50, 50),
+                rect(390, 290, 100, 100),
+                rect(500, 0, 10, 10),
+            ],
+            RedactionStyle::Blackout,
+        );
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = (x < 40 && y < 40) || (x >= 390 && y >= 290);
+            if inside {
+                assert_eq!(*pixel, BLACK, "({}, {})", x, y);
+            } else {
+                assert_eq!(pixel, original.get_pixel(x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn blurs_only_inside_the_area() {
+        let mut image = gradient(400, 300);
+        let original = image.clone();
+        redact(
+            &mut image,
+            &[rect(300, 200, 200, 200)],
+            RedactionStyle::Blur,
+        );
+        assert_eq!(image.dimensions(), (400, 300));
+        assert_eq!(image.get_pixel(299, 199), original.get_pixel(299, 199));
+        assert_eq!(image.get_pixel(350, 199), original.get_pixel(350, 199));
+        let changed = (300..400)
+            .flat_map(|x| (200..300).map(move |y| (x, y)))
+            .filter(|&(x, y)| image.get_pixel(x, y) != original.get_pixel(x, y))
+            .count();
+        assert!(changed > 100 * 100 / 2, "only {} pixels changed", changed);
+    }
+
+    #[test]
+    fn writes_a_new_file_for_every_redaction() {
+        let dir = std::env::temp_dir().join(format!("camp-redaction-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("screenshot.png");
+        gradient(40, 30).save(&source).unwrap();
+
+        let first = redact_file(
+            &source,
+            &[rect(0, 0, 10, 10)],
+            RedactionStyle::Blackout,
+            &dir,
+        );
+        let second = redact_file(
+            &source,
+            &[rect(20, 20, 10, 10)],
+            RedactionStyle::Blackout,
+            &dir,
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_ne!(first, second);
+        for path in [&first, &second] {
+            let name = path.file_name().unwrap().to_string_lossy();
+            assert!(name.starts_with("screenshot_redacted_"), "{}", name);
+        }
+        assert_eq!(
+            *image::open(&first).unwrap().to_rgba8().get_pixel(0, 0),
+            BLACK
+        );
+        assert_ne!(
+            *image::open(&first).unwrap().to_rgba8().get_pixel(25, 25),
+            BLACK
+        );
+        assert_eq!(
+            *image::open(&second).unwrap().to_rgba8().get_pixel(25, 25),
+            BLACK
+        );
+        assert_eq!(image::open(&source).unwrap().to_rgba8(), gradient(40, 30));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,310 @@
+//! Region screenshots: capture a whole display, let the user draw a rectangle on
+//! it in the app, then keep only that part. A focused crop is easier for the
+//! model to read than a full display, and costs fewer tokens.
+//!
+//! The frontend only ever sees a small preview of the display and an id for the
+//! capture, which stays in the app cache until it's cropped. Selections come
+//! back in logical points (what the user sees, before HiDPI scaling), and are
+//! mapped onto the full-resolution capture here.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Longest side of the preview the selection is drawn on.
+const PREVIEW_MAX_DIMENSION: u32 = 1600;
+const PREVIEW_QUALITY: u8 = 80;
+
+/// Selections smaller than this (in logical points) are treated as stray clicks.
+const MIN_SELECTION_SIZE: f64 = 4.0;
+
+/// Folder in the app cache for captures waiting to be cropped.
+const CAPTURES_DIR: &str = "region_captures";
+
+/// How many captures can wait for a selection at once. Captures the user
+/// cancelled are deleted once this many newer ones have been taken.
+const MAX_PENDING_CAPTURES: usize = 4;
+
+/// A display captured for region selection.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayCapture {
+    /// Identifies the full-resolution capture to `crop_screenshot`.
+    pub id: String,
+    /// Base64 JPEG, scaled down to fit PREVIEW_MAX_DIMENSION.
+    pub preview: String,
+    /// Size of the capture in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Size of the display in logical points; selections use these units.
+    pub logical_width: f64,
+    pub logical_height: f64,
+    /// Pixels per logical point (2.0 on a typical Retina display).
+    pub scale_factor: f64,
+}
+
+/// A rectangle in logical points, relative to the top left of the display.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Selection {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A rectangle in pixels of the capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Captures taken by `capture_display_for_region` and not cropped yet, oldest
+/// first. The frontend only gets their ids, so `crop_screenshot` never opens or
+/// deletes a path that came from the webview.
+#[derive(Default)]
+pub struct PendingCaptures(Mutex<Vec<PendingCapture>>);
+
+struct PendingCapture {
+    id: String,
+    path: PathBuf,
+    scale_factor: f64,
+}
+
+impl PendingCaptures {
+    /// A new, unique file in `cache_dir` to save a capture to.
+    pub fn new_path(cache_dir: &Path) -> Result<PathBuf, String> {
+        let dir = cache_dir.join(CAPTURES_DIR);
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Ok(dir.join(format!("{}.png", uuid::Uuid::new_v4())))
+    }
+
+    /// Keeps track of the capture saved at `path` until it's taken back out.
+    pub fn insert(&self, capture: &DisplayCapture, path: PathBuf) {
+        let mut pending = self.0.lock().unwrap();
+        pending.push(PendingCapture {
+            id: capture.id.clone(),
+            path,
+            scale_factor: capture.scale_factor,
+        });
+        if pending.len() > MAX_PENDING_CAPTURES {
+            let oldest = pending.remove(0);
+            let _ = fs::remove_file(oldest.path);
+        }
+    }
+
+    /// The path and scale factor of a capture.
+    pub fn get(&self, id: &str) -> Option<(PathBuf, f64)> {
+        let pending = self.0.lock().unwrap();
+        let capture = pending.iter().find(|capture| capture.id == id)?;
+        Some((capture.path.clone(), capture.scale_factor))
+    }
+
+    /// Forgets a capture once it's been cropped, and deletes it.
+    pub fn remove(&self, id: &str) {
+        let mut pending = self.0.lock().unwrap();
+        if let Some(index) = pending.iter().position(|capture| capture.id == id) {
+            let _ = fs::remove_file(pending.remove(index).path);
+        }
+    }
+}
+
+/// Builds the preview for a display capture saved at `path`.
+pub fn prepare(path: &Path, scale_factor: f64) -> Result<DisplayCapture, String> {
+    let img = image::open(path).map_err(|e| e.to_string())?;
+    let (width, height) = (img.width(), img.height());
+
+    let preview = img.resize(
+        PREVIEW_MAX_DIMENSION,
+        PREVIEW_MAX_DIMENSION,
+        FilterType::Triangle,
+    );
+    let mut preview_data = Vec::new();
+    JpegEncoder::new_with_quality(&mut preview_data, PREVIEW_QUALITY)
+        .encode_image(&preview.to_rgb8())
+        .map_err(|e| e.to_string())?;
+
+    let scale_factor = if scale_factor > 0.0 {
+        scale_factor
+    } else {
+        1.0
+    };
+    Ok(DisplayCapture {
+        id: uuid::Uuid::new_v4().to_string(),
+        preview: BASE64.encode(&preview_data),
+        width,
+        height,
+        logical_width: width as f64 / scale_factor,
+        logical_height: height as f64 / scale_factor,
+        scale_factor,
+    })
+}
+
+/// Maps a selection in logical points onto the pixels of a `width` x `height`
+/// capture taken at `scale_factor`, clamped to the capture. Edges are rounded
+/// outwards so the crop never loses part of what was selected.
+pub fn selection_to_pixels(
+    selection: Selection,
+    scale_factor: f64,
+    width: u32,
+    height: u32,
+) -> Option<PixelRect> {
+    if !(selection.width >= MIN_SELECTION_SIZE && selection.height >= MIN_SELECTION_SIZE) {
+        return None;
+    }
+    let left = (selection.x * scale_factor)
+        .floor()
+        .clamp(0.0, width as f64);
+    let top = (selection.y * scale_factor)
+        .floor()
+        .clamp(0.0, height as f64);
+    let right = ((selection.x + selection.width) * scale_factor)
+        .ceil()
+        .clamp(0.0, width as f64);
+    let bottom = ((selection.y + selection.height) * scale_factor)
+        .ceil()
+        .clamp(0.0, height as f64);
+    if right - left < 1.0 || bottom - top < 1.0 {
+        return None;
+    }
+    Some(PixelRect {
+        x: left as u32,
+        y: top as u32,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    })
+}
+
+/// Crops the capture at `path` to `selection` and saves it as a PNG next to it.
+/// With `logical_resolution`, HiDPI crops are scaled down to one pixel per
+/// point, which is usually still legible and a quarter of the size.
+pub fn crop(
+    path: &Path,
+    selection: Selection,
+    scale_factor: f64,
+    logical_resolution: bool,
+) -> Result<PathBuf, String> {
+    let img = image::open(path).map_err(|e| e.to_string())?;
+    let rect = selection_to_pixels(selection, scale_factor, img.width(), img.height())
+        .ok_or("The selected area is empty")?;
+    log::debug!(
+        "Cropping {}x{} capture to {}x{} at ({}, {})",
+        img.width(),
+        img.height(),
+        rect.width,
+        rect.height,
+        rect.x,
+        rect.y
+    );
+
+    let mut cropped = img.crop_imm(rect.x, rect.y, rect.width, rect.height);
+    if logical_resolution && scale_factor > 1.0 {
+        let width = ((rect.width as f64 / scale_factor).round() as u32).max(1);
+        let height = ((rect.height as f64 / scale_factor).round() as u32).max(1);
+        cropped = cropped.resize_exact(width, height, FilterType::CatmullRom);
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let output_path = path.with_file_name(format!("{}_cropped.png", stem));
+    cropped.save(&output_path).map_err(|e| e.to_string())?;
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(x: f64, y: f64, width: f64, height: f64) -> Selection {
+        Selection {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Option<PixelRect> {
+        Some(PixelRect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    #[test]
+    fn scales_selections_to_hidpi_pixels() {
+        assert_eq!(
+            selection_to_pixels(selection(10.0, 20.0, 100.0, 50.0), 2.0, 2880, 1800),
+            rect(20, 40, 200, 100)
+        );
+        assert_eq!(
+            selection_to_pixels(selection(10.0, 20.0, 100.0, 50.0), 1.0, 1440, 900),
+            rect(10, 20, 100, 50)
+        );
+    }
+
+    #[test]
+    fn clamps_selections_to_the_capture() {
+        // Dragged past the bottom right corner of a 1440x900pt display
+        assert_eq!(
+            selection_to_pixels(selection(1400.0, 850.0, 100.0, 100.0), 2.0, 2880, 1800),
+            rect(2800, 1700, 80, 100)
+        );
+        // Dragged past the top left
+        assert_eq!(
+            selection_to_pixels(selection(-20.0, -10.0, 50.0, 30.0), 2.0, 2880, 1800),
+            rect(0, 0, 60, 40)
+        );
+        // Entirely outside
+        assert_eq!(
+            selection_to_pixels(selection(1500.0, 0.0, 100.0, 100.0), 2.0, 2880, 1800),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_selections_below_the_minimum_size() {
+        let too_small = MIN_SELECTION_SIZE - 0.5;
+        assert_eq!(
+            selection_to_pixels(selection(10.0, 10.0, too_small, 50.0), 2.0, 2880, 1800),
+            None
+        );
+        assert_eq!(
+            selection_to_pixels(selection(10.0, 10.0, 50.0, too_small), 2.0, 2880, 1800),
+            None
+        );
+        assert_eq!(
+            selection_to_pixels(selection(10.0, 10.0, f64::NAN, 50.0), 2.0, 2880, 1800),
+            None
+        );
+        assert_eq!(
+            selection_to_pixels(
+                selection(10.0, 10.0, MIN_SELECTION_SIZE, MIN_SELECTION_SIZE),
+                1.0,
+                1440,
+                900
+            ),
+            rect(10, 10, 4, 4)
+        );
+    }
+
+    #[test]
+    fn rounds_fractional_edges_outwards() {
+        // 10.3..60.6pt at 1.5x is 15.45..90.9px
+        assert_eq!(
+            selection_to_pixels(selection(10.3, 10.3, 50.3, 50.3), 1.5, 2160, 1350),
+            rect(15, 15, 76, 76)
+        );
+        assert_eq!(
+            selection_to_pixels(selection(0.25, 0.75, 9.5, 9.5), 1.0, 100, 100),
+            rect(0, 0, 10, 11)
+        );
+    }
+}
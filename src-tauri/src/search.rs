@@ -0,0 +1,311 @@
+//! Full-text search over chat history, backed by the search_index FTS5 table
+//! that migration 132 keeps in sync with chats, messages and message_parts.
+
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 200;
+
+/// Characters snippet() wraps around matched terms. They can't appear in
+/// tokenized text, so we can strip them back out and record offsets instead.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+/// Roughly how many tokens of context to return around a match.
+const SNIPPET_TOKENS: i64 = 24;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    /// "chat" for title/summary matches, otherwise "message" or "message_part".
+    pub kind: String,
+    pub chat_id: String,
+    pub project_id: String,
+    pub chat_title: Option<String>,
+    /// None when the match was in the chat title or summary.
+    pub message_id: Option<String>,
+    pub snippet: String,
+    /// Start and end of each matched term in `snippet`, in UTF-16 code units so the
+    /// frontend can pass them straight to String.slice.
+    pub highlights: Vec<(usize, usize)>,
+    /// bm25 score; lower is better.
+    pub rank: f64,
+    /// When the matching message was sent, or when the chat was last updated.
+    pub created_at: Option<String>,
+}
+
+/// Searches chat titles, summaries, messages and message parts for `query`,
+/// best matches first. A message's text and its parts usually overlap, so each
+/// message (or chat, for title matches) appears at most once.
+pub fn search_messages(
+    conn: &Connection,
+    query: &str,
+    project_id: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchResult>, String> {
+    let Some(fts_query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT s.kind, s.chat_id, c.project_id, c.title, s.message_id,
+                snippet(search_index, -1, ?2, ?3, '...', ?4),
+                bm25(search_index, 5.0, 1.0) AS rank,
+                COALESCE(m.created_at, c.updated_at, c.created_at)
+            FROM search_index
+            JOIN search_index_sources s ON s.id = search_index.rowid
+            JOIN chats c ON c.id = s.chat_id
+            LEFT JOIN messages m ON m.id = s.message_id
+            WHERE search_index MATCH ?1
+                AND (?5 IS NULL OR c.project_id = ?5)
+            ORDER BY rank
+            LIMIT ?6",
+        )
+        .map_err(|e| e.to_string())?;
+
+    // Over-fetch so there are still enough results after collapsing duplicates
+    let rows = stmt
+        .query_map(
+            (
+                &fts_query,
+                HIGHLIGHT_START.to_string(),
+                HIGHLIGHT_END.to_string(),
+                SNIPPET_TOKENS,
+                project_id,
+                (limit * 3) as i64,
+            ),
+            |row| {
+                let (snippet, highlights) = extract_highlights(&row.get::<_, String>(5)?);
+                Ok(SearchResult {
+                    kind: row.get(0)?,
+                    chat_id: row.get(1)?,
+                    project_id: row.get(2)?,
+                    chat_title: row.get(3)?,
+                    message_id: row.get(4)?,
+                    snippet,
+                    highlights,
+                    rank: row.get(6)?,
+                    created_at: row.get(7)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+    for result in rows {
+        let result = result.map_err(|e| e.to_string())?;
+        let key = result
+            .message_id
+            .clone()
+            .unwrap_or_else(|| result.chat_id.clone());
+        if !seen.insert(key) {
+            continue;
+        }
+        results.push(result);
+        if results.len() == limit {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Turns free-form input into an FTS5 query. Every word has to match, and the
+/// last one is treated as a prefix so results show up while the user is typing.
+/// Words are quoted so punctuation and FTS5 operators are matched literally.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| word.replace([HIGHLIGHT_START, HIGHLIGHT_END], ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    let (last, rest) = terms.split_last()?;
+    let mut query = rest.join(" ");
+    if !query.is_empty() {
+        query.push(' ');
+    }
+    query.push_str(last);
+    query.push('*');
+    Some(query)
+}
+
+/// Strips highlight markers out of a snippet, returning the plain text and the
+/// UTF-16 ranges that were highlighted.
+fn extract_highlights(marked: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut highlights = Vec::new();
+    let mut offset = 0;
+    let mut start = None;
+    for ch in marked.chars() {
+        match ch {
+            HIGHLIGHT_START => start = Some(offset),
+            HIGHLIGHT_END => {
+                if let Some(start) = start.take() {
+                    highlights.push((start, offset));
+                }
+            }
+            _ => {
+                text.push(ch);
+                offset += ch.len_utf16();
+            }
+        }
+    }
+    (text, highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrator;
+
+    fn search(conn: &Connection, query: &str) -> Vec<(String, Option<String>)> {
+        search_messages(conn, query, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.kind, result.message_id))
+            .collect()
+    }
+
+    /// Every indexed row should still have a source, and every source a row.
+    fn assert_index_matches_sources(conn: &Connection) {
+        let orphans: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM search_index WHERE rowid NOT IN (SELECT id FROM search_index_sources))
+                    + (SELECT COUNT(*) FROM search_index_sources WHERE id NOT IN (SELECT rowid FROM search_index))",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphans, 0);
+    }
+
+    #[test]
+    fn quotes_every_term_and_prefixes_the_last() {
+        assert_eq!(fts_query("hello"), Some("\"hello\"*".to_string()));
+        assert_eq!(
+            fts_query("  rust   search  "),
+            Some("\"rust\" \"search\"*".to_string())
+        );
+        assert_eq!(
+            fts_query("say \"hi\" NEAR(a b) OR -x col:y"),
+            Some("\"say\" \"\"\"hi\"\"\" \"NEAR(a\" \"b)\" \"OR\" \"-x\" \"col:y\"*".to_string())
+        );
+        assert_eq!(fts_query("\u{2}mark\u{3}"), Some("\"mark\"*".to_string()));
+        assert_eq!(fts_query(""), None);
+        assert_eq!(fts_query(" \u{2}\u{3} "), None);
+    }
+
+    #[test]
+    fn highlights_are_utf16_offsets() {
+        let (text, highlights) = extract_highlights("\u{1F600} \u{2}cat\u{3} and \u{2}dog\u{3}");
+        assert_eq!(text, "\u{1F600} cat and dog");
+        assert_eq!(highlights, [(3, 6), (11, 14)]);
+
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        let (start, end) = highlights[1];
+        assert_eq!(String::from_utf16(&utf16[start..end]).unwrap(), "dog");
+    }
+
+    #[test]
+    fn unclosed_highlights_are_dropped() {
+        let (text, highlights) = extract_highlights("a \u{2}b");
+        assert_eq!(text, "a b");
+        assert!(highlights.is_empty());
+    }
+
+    #[test]
+    fn triggers_keep_the_index_in_sync() {
+        let conn = migrator::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO chats (id, title, project_id) VALUES ('c1', 'Aardvark notes', 'default');
+            INSERT INTO message_sets (id, chat_id, type, level) VALUES ('s1', 'c1', 'ai', 1);
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, state)
+                VALUES ('m1', 's1', 'c1', '\u{1F600} pangolin facts', 'a::one', 'idle');
+            INSERT INTO message_parts (chat_id, message_id, level, content)
+                VALUES ('c1', 'm1', 0, 'kestrel');",
+        )
+        .unwrap();
+        assert_index_matches_sources(&conn);
+
+        assert_eq!(search(&conn, "aardv"), [("chat".to_string(), None)]);
+        assert_eq!(
+            search(&conn, "kestrel"),
+            [("message_part".to_string(), Some("m1".to_string()))]
+        );
+        let results = search_messages(&conn, "pangolin", None, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind, "message");
+        assert_eq!(results[0].project_id, "default");
+        assert_eq!(results[0].chat_title.as_deref(), Some("Aardvark notes"));
+        let snippet: Vec<u16> = results[0].snippet.encode_utf16().collect();
+        let (start, end) = results[0].highlights[0];
+        assert_eq!(
+            String::from_utf16(&snippet[start..end]).unwrap(),
+            "pangolin"
+        );
+        assert!(search_messages(&conn, "pangolin", Some("quick-chat"), None)
+            .unwrap()
+            .is_empty());
+        // Operators and stray quotes are searched for, not parsed
+        assert!(search(&conn, "pangolin\" OR NEAR( col:x -").is_empty());
+        assert_eq!(search(&conn, "\"pangolin\"").len(), 1);
+
+        conn.execute_batch(
+            "UPDATE chats SET title = 'Badger notes' WHERE id = 'c1';
+            UPDATE messages SET text = 'wombat facts' WHERE id = 'm1';
+            UPDATE message_parts SET content = 'osprey' WHERE message_id = 'm1';",
+        )
+        .unwrap();
+        assert_index_matches_sources(&conn);
+        assert!(search(&conn, "aardvark").is_empty());
+        assert!(search(&conn, "pangolin").is_empty());
+        assert!(search(&conn, "kestrel").is_empty());
+        assert_eq!(search(&conn, "badger"), [("chat".to_string(), None)]);
+        assert_eq!(
+            search(&conn, "wombat"),
+            [("message".to_string(), Some("m1".to_string()))]
+        );
+        assert_eq!(
+            search(&conn, "osprey"),
+            [("message_part".to_string(), Some("m1".to_string()))]
+        );
+
+        conn.execute_batch("DELETE FROM message_parts WHERE message_id = 'm1'")
+            .unwrap();
+        assert!(search(&conn, "osprey").is_empty());
+        conn.execute_batch("DELETE FROM messages WHERE id = 'm1'")
+            .unwrap();
+        assert!(search(&conn, "wombat").is_empty());
+        conn.execute_batch("DELETE FROM chats WHERE id = 'c1'")
+            .unwrap();
+        assert!(search(&conn, "badger").is_empty());
+        assert_index_matches_sources(&conn);
+        let sources: i64 = conn
+            .query_row("SELECT COUNT(*) FROM search_index_sources", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(sources, 0);
+    }
+
+    #[test]
+    fn each_message_appears_once() {
+        let conn = migrator::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO chats (id, title, project_id) VALUES ('c1', 'Chat', 'default');
+            INSERT INTO message_sets (id, chat_id, type, level) VALUES ('s1', 'c1', 'ai', 1);
+            INSERT INTO messages (id, message_set_id, chat_id, text, model, state)
+                VALUES ('m1', 's1', 'c1', 'heron', 'a::one', 'idle');
+            INSERT INTO message_parts (chat_id, message_id, level, content)
+                VALUES ('c1', 'm1', 0, 'heron');",
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "heron").len(), 1);
+    }
+}
@@ -0,0 +1,861 @@
+//! Encrypted storage for credentials (toolset API tokens, MCP server env vars...)
+//! so they don't sit in chats.db as plaintext.
+//!
+//! The vault is a single file in the app data dir holding a JSON map of secret
+//! names to values, encrypted with XChaCha20-Poly1305. The encryption key is
+//! derived with Argon2id from a random master key, which lives in the OS
+//! keychain. If the keychain can't be used (e.g. no Secret Service on Linux), the
+//! master key goes in a key file next to the vault instead, readable only by the
+//! current user. Neither file is in a folder the webview can have us write to
+//! (see file_access.rs).
+//!
+//! chats.db keeps a reference in place of each secret (`vault:<name>`), which the
+//! frontend resolves through the `get_secret` command when it needs the value.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Prefix marking a value in chats.db as a reference to a vault entry.
+pub const REFERENCE_PREFIX: &str = "vault:";
+
+const VAULT_FILE_NAME: &str = "secrets.vault";
+const KEY_FILE_NAME: &str = "secrets.key";
+const FORMAT_VERSION: u32 = 1;
+
+/// Keychain entry holding the master key.
+const KEYCHAIN_SERVICE: &str = "ai.getcamp.app";
+const KEYCHAIN_USER: &str = "secret-vault";
+
+const MASTER_KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// Argon2id cost for new vaults: OWASP's recommended minimum (19 MiB, 2 passes).
+/// The parameters are stored in the vault, so these can go up later without
+/// locking anyone out of an existing vault.
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+/// toolsets_config parameters that aren't credentials and stay in chats.db: the
+/// on/off switch, a custom toolset's command line, and Slack's team ID. Every
+/// other parameter goes in the vault. Must match NON_SECRET_PARAMETERS in
+/// ToolsetsAPI.ts.
+const NON_SECRET_PARAMETERS: &[&str] = &["enabled", "command", "args", "teamId"];
+
+#[derive(Error, Debug)]
+pub enum VaultError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Couldn't read the secret vault: {0}")]
+    Corrupt(String),
+    #[error("Couldn't decrypt the secret vault. Was the master key in the keychain replaced?")]
+    Decrypt,
+    #[error("Encryption failed")]
+    Encrypt,
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    #[error("Couldn't read the master key from the keychain: {0}")]
+    Keychain(String),
+    #[error("Invalid secret name: {0:?}")]
+    InvalidName(String),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KdfParams {
+    algorithm: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+/// What's on disk. Everything but the ciphertext is needed to decrypt it.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An unlocked vault. Changes are written to disk immediately.
+pub struct Vault {
+    path: PathBuf,
+    kdf: KdfParams,
+    key: Zeroizing<[u8; 32]>,
+    entries: BTreeMap<String, Zeroizing<String>>,
+}
+
+impl Vault {
+    /// Opens the vault at `path` with `master_key`, or starts an empty one if the
+    /// file doesn't exist yet (it's created on the first write).
+    pub fn open(path: &Path, master_key: &[u8]) -> Result<Vault, VaultError> {
+        if !path.exists() {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let kdf = KdfParams {
+                algorithm: "argon2id".to_string(),
+                memory_kib: KDF_MEMORY_KIB,
+                iterations: KDF_ITERATIONS,
+                parallelism: KDF_PARALLELISM,
+                salt: hex::encode(salt),
+            };
+            let key = derive_key(master_key, &kdf)?;
+            return Ok(Vault {
+                path: path.to_path_buf(),
+                kdf,
+                key,
+                entries: BTreeMap::new(),
+            });
+        }
+
+        let file: VaultFile = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| VaultError::Corrupt(e.to_string()))?;
+        if file.version != FORMAT_VERSION {
+            return Err(VaultError::Corrupt(format!(
+                "unsupported format version {}",
+                file.version
+            )));
+        }
+        let key = derive_key(master_key, &file.kdf)?;
+        let nonce = hex::decode(&file.nonce).map_err(|e| VaultError::Corrupt(e.to_string()))?;
+        let ciphertext =
+            hex::decode(&file.ciphertext).map_err(|e| VaultError::Corrupt(e.to_string()))?;
+        if nonce.len() != 24 {
+            return Err(VaultError::Corrupt("bad nonce".to_string()));
+        }
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&key[..]));
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: file.kdf.salt.as_bytes(),
+                    },
+                )
+                .map_err(|_| VaultError::Decrypt)?,
+        );
+        let entries: BTreeMap<String, String> =
+            serde_json::from_slice(&plaintext).map_err(|e| VaultError::Corrupt(e.to_string()))?;
+
+        Ok(Vault {
+            path: path.to_path_buf(),
+            kdf: file.kdf,
+            key,
+            entries: entries
+                .into_iter()
+                .map(|(name, value)| (name, Zeroizing::new(value)))
+                .collect(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(|value| value.as_str())
+    }
+
+    /// Names of all secrets, sorted. Values are never listed.
+    pub fn names(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    pub fn put(&mut self, name: &str, value: &str) -> Result<(), VaultError> {
+        self.put_all([(name.to_string(), value.to_string())])
+    }
+
+    /// Stores several secrets with a single write.
+    pub fn put_all(
+        &mut self,
+        secrets: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), VaultError> {
+        let secrets: Vec<(String, String)> = secrets.into_iter().collect();
+        for (name, _) in &secrets {
+            validate_name(name)?;
+        }
+        let previous = self.entries.clone();
+        for (name, value) in secrets {
+            self.entries.insert(name, Zeroizing::new(value));
+        }
+        if let Err(e) = self.save() {
+            self.entries = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Returns whether there was a secret to delete.
+    pub fn delete(&mut self, name: &str) -> Result<bool, VaultError> {
+        let Some(value) = self.entries.remove(name) else {
+            return Ok(false);
+        };
+        if let Err(e) = self.save() {
+            self.entries.insert(name.to_string(), value);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Encrypts the entries with a fresh nonce and replaces the vault file.
+    fn save(&self) -> Result<(), VaultError> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(
+                &self
+                    .entries
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+                    .collect::<BTreeMap<_, _>>(),
+            )
+            .map_err(|e| VaultError::Corrupt(e.to_string()))?,
+        );
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key[..]));
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: self.kdf.salt.as_bytes(),
+                },
+            )
+            .map_err(|_| VaultError::Encrypt)?;
+
+        let file = VaultFile {
+            version: FORMAT_VERSION,
+            kdf: self.kdf.clone(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        let json =
+            serde_json::to_vec_pretty(&file).map_err(|e| VaultError::Corrupt(e.to_string()))?;
+        write_private(&self.path, &json)
+    }
+}
+
+fn derive_key(master_key: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, VaultError> {
+    if kdf.algorithm != "argon2id" {
+        return Err(VaultError::Kdf(format!(
+            "unsupported algorithm {}",
+            kdf.algorithm
+        )));
+    }
+    let salt = hex::decode(&kdf.salt).map_err(|e| VaultError::Corrupt(e.to_string()))?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| VaultError::Kdf(e.to_string()))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(master_key, &salt, &mut key[..])
+        .map_err(|e| VaultError::Kdf(e.to_string()))?;
+    Ok(key)
+}
+
+/// Secret names are path-like (`toolsets/github/personalAccessToken`) so related
+/// secrets can be found by prefix.
+fn validate_name(name: &str) -> Result<(), VaultError> {
+    if name.is_empty() || name.len() > 512 || name.chars().any(|c| c.is_control()) {
+        return Err(VaultError::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Writes via a temp file and rename so a crash never leaves a half-written
+/// vault or key file, and keeps both private to the current user.
+fn write_private(path: &Path, data: &[u8]) -> Result<(), VaultError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Loads the master key, creating one on first use. A key file from an earlier
+/// keychain failure takes precedence, so the vault keeps opening with the key it
+/// was created with.
+fn master_key(dir: &Path) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    let key_file = dir.join(KEY_FILE_NAME);
+    if key_file.exists() {
+        return decode_master_key(&fs::read_to_string(&key_file)?);
+    }
+
+    let keychain_error = match keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_USER) {
+        Ok(entry) => match entry.get_password() {
+            Ok(encoded) => return decode_master_key(&encoded),
+            Err(keyring::Error::NoEntry) => {
+                let key = new_master_key();
+                match entry.set_password(&hex::encode(key.as_slice())) {
+                    Ok(()) => return Ok(key),
+                    Err(e) => e,
+                }
+            }
+            Err(e) => e,
+        },
+        Err(e) => e,
+    };
+
+    // Only fall back to a key file if there's no vault yet; otherwise a
+    // temporarily locked keychain would leave us with a key that can't open it
+    if dir.join(VAULT_FILE_NAME).exists() {
+        return Err(VaultError::Keychain(keychain_error.to_string()));
+    }
+    log::warn!(
+        "Keychain unavailable ({}), storing the secret vault key in {}",
+        keychain_error,
+        key_file.display()
+    );
+    let key = new_master_key();
+    write_private(&key_file, hex::encode(key.as_slice()).as_bytes())?;
+    Ok(key)
+}
+
+fn new_master_key() -> Zeroizing<Vec<u8>> {
+    let mut key = Zeroizing::new(vec![0u8; MASTER_KEY_LEN]);
+    OsRng.fill_bytes(&mut key[..]);
+    key
+}
+
+fn decode_master_key(encoded: &str) -> Result<Zeroizing<Vec<u8>>, VaultError> {
+    hex::decode(encoded.trim())
+        .map(Zeroizing::new)
+        .map_err(|e| VaultError::Corrupt(format!("master key: {}", e)))
+}
+
+pub fn reference(name: &str) -> String {
+    format!("{}{}", REFERENCE_PREFIX, name)
+}
+
+pub fn toolset_secret_name(toolset_name: &str, parameter_id: &str) -> String {
+    format!("toolsets/{}/{}", toolset_name, parameter_id)
+}
+
+pub fn custom_toolset_env_secret_name(toolset_name: &str, variable: &str) -> String {
+    format!("custom_toolsets/{}/env/{}", toolset_name, variable)
+}
+
+/// Credentials still stored as plaintext in chats.db.
+#[derive(Default)]
+struct PlaintextCredentials {
+    /// (toolset_name, parameter_id)
+    parameters: Vec<(String, String)>,
+    /// (toolset name, env with every plaintext value moved into `secrets`)
+    envs: Vec<(String, String)>,
+    secrets: Vec<(String, String)>,
+}
+
+fn find_plaintext_credentials(conn: &Connection) -> Result<PlaintextCredentials, String> {
+    let mut found = PlaintextCredentials::default();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT toolset_name, parameter_id, parameter_value FROM toolsets_config
+             WHERE parameter_value IS NOT NULL AND parameter_value != ''",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (toolset_name, parameter_id, value): (String, String, String) =
+            row.map_err(|e| e.to_string())?;
+        if !NON_SECRET_PARAMETERS.contains(&parameter_id.as_str())
+            && !value.starts_with(REFERENCE_PREFIX)
+        {
+            found
+                .secrets
+                .push((toolset_secret_name(&toolset_name, &parameter_id), value));
+            found.parameters.push((toolset_name, parameter_id));
+        }
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT name, env FROM custom_toolsets WHERE env IS NOT NULL AND json_valid(env)")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (name, env): (String, String) = row.map_err(|e| e.to_string())?;
+        let Ok(serde_json::Value::Object(mut env)) = serde_json::from_str(&env) else {
+            continue;
+        };
+        let mut moved = false;
+        for (variable, value) in env.iter_mut() {
+            let Some(plaintext) = value.as_str() else {
+                continue;
+            };
+            if plaintext.is_empty() || plaintext.starts_with(REFERENCE_PREFIX) {
+                continue;
+            }
+            let secret_name = custom_toolset_env_secret_name(&name, variable);
+            found
+                .secrets
+                .push((secret_name.clone(), plaintext.to_string()));
+            *value = serde_json::Value::String(reference(&secret_name));
+            moved = true;
+        }
+        if moved {
+            found
+                .envs
+                .push((name, serde_json::Value::Object(env).to_string()));
+        }
+    }
+
+    Ok(found)
+}
+
+/// Moves credentials stored as plaintext in `toolsets_config.parameter_value` and
+/// `custom_toolsets.env` into the vault, leaving references behind. Returns how
+/// many secrets were moved.
+///
+/// Values that are already references are skipped, so this is safe to run on
+/// every launch; after the first run it finds nothing to do and doesn't touch
+/// the vault (or the keychain) at all.
+pub fn move_plaintext_credentials(
+    conn: &mut Connection,
+    store: impl FnOnce(Vec<(String, String)>) -> Result<(), String>,
+) -> Result<usize, String> {
+    let found = find_plaintext_credentials(conn)?;
+    if found.secrets.is_empty() {
+        return Ok(0);
+    }
+
+    // Vault first: if updating the database fails, the plaintext is still there
+    // and the next launch will store it again
+    let count = found.secrets.len();
+    store(found.secrets)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (toolset_name, parameter_id) in &found.parameters {
+        tx.execute(
+            "UPDATE toolsets_config SET parameter_value = ?3 WHERE toolset_name = ?1 AND parameter_id = ?2",
+            params![
+                toolset_name,
+                parameter_id,
+                reference(&toolset_secret_name(toolset_name, parameter_id))
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    for (name, env) in &found.envs {
+        tx.execute(
+            "UPDATE custom_toolsets SET env = ?2 WHERE name = ?1",
+            params![name, env],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// Rewrites the database so replaced values don't linger in freed pages, then
+/// empties the WAL, which still holds the pages written before the rewrite.
+fn purge_freed_pages(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| e.to_string())
+}
+
+/// Replaces plaintext credentials in a copy of chats.db (a pre-migration backup)
+/// with vault references. The values aren't stored: the copy was taken from
+/// chats.db, whose credentials are already in the vault under the same names.
+/// Returns how many were replaced.
+pub fn scrub_database_copy(path: &Path) -> Result<usize, String> {
+    let mut conn = Connection::open(path).map_err(|e| e.to_string())?;
+    // Backups from before toolsets were added have nothing to scrub
+    let has_toolsets: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'toolsets_config')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !has_toolsets {
+        return Ok(0);
+    }
+    let replaced = move_plaintext_credentials(&mut conn, |_| Ok(()))?;
+    if replaced > 0 {
+        purge_freed_pages(&conn)?;
+    }
+    Ok(replaced)
+}
+
+/// The vault is opened once (which means a keychain read and an Argon2 run) and
+/// then kept unlocked for the life of the process. The lock also serializes
+/// writes between commands.
+static VAULT: Mutex<Option<Vault>> = Mutex::new(None);
+
+pub fn vault_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+fn open_vault(dir: &Path) -> Result<Vault, VaultError> {
+    let master_key = master_key(dir)?;
+    Vault::open(&dir.join(VAULT_FILE_NAME), &master_key)
+}
+
+/// Runs `f` with the app's vault, unlocking it first if this is the first use.
+pub fn with_vault<T>(
+    app_handle: &AppHandle,
+    f: impl FnOnce(&mut Vault) -> Result<T, VaultError>,
+) -> Result<T, String> {
+    let mut guard = VAULT.lock().map_err(|e| e.to_string())?;
+    if guard.is_none() {
+        *guard = Some(open_vault(&vault_dir(app_handle)?).map_err(|e| e.to_string())?);
+    }
+    let vault = guard.as_mut().expect("vault was just opened");
+    f(vault).map_err(|e| e.to_string())
+}
+
+/// Startup step that moves plaintext credentials out of chats.db. Runs right
+/// after migrations, before the frontend can read the tables.
+///
+/// The migration backups were taken before this runs, so they're scrubbed too.
+/// That's checked on every launch rather than only after a move, in case an
+/// earlier launch stopped in between.
+pub fn secure_credentials(app_handle: &AppHandle) -> Result<(), String> {
+    let mut conn = crate::db::open(app_handle)?;
+    let moved = move_plaintext_credentials(&mut conn, |secrets| {
+        with_vault(app_handle, |vault| vault.put_all(secrets))
+    })?;
+    if moved > 0 {
+        log::info!(
+            "Moved {} plaintext credentials into the secret vault",
+            moved
+        );
+        purge_freed_pages(&conn)?;
+    }
+
+    let backup_dir = crate::db::backup_dir(app_handle)?;
+    let Ok(entries) = fs::read_dir(&backup_dir) else {
+        return Ok(());
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("db") {
+            continue;
+        }
+        match scrub_database_copy(&path) {
+            Ok(0) => {}
+            Ok(replaced) => log::info!(
+                "Removed {} plaintext credentials from {}",
+                replaced,
+                path.display()
+            ),
+            Err(e) => log::warn!("Couldn't scrub credentials from {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use crate::migrator::{self, RunOptions};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            let dir = std::env::temp_dir().join(format!("camp-secrets-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn contains(path: &Path, needle: &str) -> bool {
+        fs::read(path)
+            .map(|bytes| {
+                bytes
+                    .windows(needle.len())
+                    .any(|window| window == needle.as_bytes())
+            })
+            .unwrap_or(false)
+    }
+
+    fn toolset_rows(conn: &Connection) -> Vec<(String, String, String)> {
+        conn.prepare(
+            "SELECT toolset_name, parameter_id, parameter_value FROM toolsets_config
+             ORDER BY toolset_name, parameter_id",
+        )
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn secrets_survive_reopening_the_vault() {
+        let dir = TempDir::new();
+        let path = dir.0.join(VAULT_FILE_NAME);
+        let key = [7u8; MASTER_KEY_LEN];
+
+        let mut vault = Vault::open(&path, &key).unwrap();
+        assert!(!path.exists());
+        vault
+            .put("toolsets/github/personalAccessToken", "github_pat_abc")
+            .unwrap();
+        vault
+            .put_all([
+                (
+                    "custom_toolsets/mine/env/API_KEY".to_string(),
+                    "sk-1".to_string(),
+                ),
+                ("scratch".to_string(), "x".to_string()),
+            ])
+            .unwrap();
+        assert!(vault.delete("scratch").unwrap());
+        assert!(!vault.delete("scratch").unwrap());
+        assert!(matches!(
+            vault.put("bad\nname", "x"),
+            Err(VaultError::InvalidName(_))
+        ));
+        assert!(!contains(&path, "github_pat_abc"));
+
+        let vault = Vault::open(&path, &key).unwrap();
+        assert_eq!(
+            vault.get("toolsets/github/personalAccessToken"),
+            Some("github_pat_abc")
+        );
+        assert_eq!(vault.get("custom_toolsets/mine/env/API_KEY"), Some("sk-1"));
+        assert_eq!(vault.get("scratch"), None);
+        assert_eq!(
+            vault.names(),
+            [
+                "custom_toolsets/mine/env/API_KEY",
+                "toolsets/github/personalAccessToken"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_wrong_key_or_damaged_file_fails_to_open() {
+        let dir = TempDir::new();
+        let path = dir.0.join(VAULT_FILE_NAME);
+        let key = [7u8; MASTER_KEY_LEN];
+        Vault::open(&path, &key)
+            .unwrap()
+            .put("toolsets/github/personalAccessToken", "github_pat_abc")
+            .unwrap();
+
+        assert!(matches!(
+            Vault::open(&path, &[8u8; MASTER_KEY_LEN]),
+            Err(VaultError::Decrypt)
+        ));
+
+        let mut file: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let ciphertext = file["ciphertext"].as_str().unwrap().to_string();
+        let flipped = if ciphertext.starts_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        file["ciphertext"] = serde_json::Value::String(format!("{}{}", flipped, &ciphertext[1..]));
+        fs::write(&path, file.to_string()).unwrap();
+        assert!(matches!(Vault::open(&path, &key), Err(VaultError::Decrypt)));
+
+        fs::write(&path, "not a vault").unwrap();
+        assert!(matches!(
+            Vault::open(&path, &key),
+            Err(VaultError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn moves_only_credentials_into_the_vault() {
+        let mut conn = migrator::open_in_memory();
+        conn.execute_batch(
+            r#"INSERT OR REPLACE INTO toolsets_config (toolset_name, parameter_id, parameter_value) VALUES
+                ('github', 'enabled', 'true'),
+                ('github', 'personalAccessToken', 'github_pat_abc'),
+                ('slack', 'teamId', 'T123'),
+                ('slack', 'apiToken', ''),
+                ('mine', 'command', 'npx'),
+                ('mine', 'args', '-y server'),
+                ('notion', 'apiToken', 'vault:toolsets/notion/apiToken');
+            INSERT INTO custom_toolsets (name, command, env) VALUES
+                ('my server', 'npx', '{"API_KEY":"sk-1","EMPTY":"","PORT":3}'),
+                ('plain', 'x', '{}');"#,
+        )
+        .unwrap();
+
+        let mut stored = Vec::new();
+        let moved = move_plaintext_credentials(&mut conn, |secrets| {
+            stored = secrets;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(moved, 2);
+        stored.sort();
+        assert_eq!(
+            stored,
+            [
+                (
+                    "custom_toolsets/my server/env/API_KEY".to_string(),
+                    "sk-1".to_string()
+                ),
+                (
+                    "toolsets/github/personalAccessToken".to_string(),
+                    "github_pat_abc".to_string()
+                ),
+            ]
+        );
+
+        let row = |toolset: &str, parameter: &str, value: &str| {
+            (
+                toolset.to_string(),
+                parameter.to_string(),
+                value.to_string(),
+            )
+        };
+        assert_eq!(
+            toolset_rows(&conn),
+            [
+                row("github", "enabled", "true"),
+                row(
+                    "github",
+                    "personalAccessToken",
+                    "vault:toolsets/github/personalAccessToken"
+                ),
+                row("mine", "args", "-y server"),
+                row("mine", "command", "npx"),
+                row("notion", "apiToken", "vault:toolsets/notion/apiToken"),
+                row("slack", "apiToken", ""),
+                row("slack", "teamId", "T123"),
+                row("web", "enabled", "true"),
+            ]
+        );
+        let env: String = conn
+            .query_row(
+                "SELECT env FROM custom_toolsets WHERE name = 'my server'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&env).unwrap(),
+            serde_json::json!({
+                "API_KEY": "vault:custom_toolsets/my server/env/API_KEY",
+                "EMPTY": "",
+                "PORT": 3,
+            })
+        );
+
+        let before = toolset_rows(&conn);
+        let moved = move_plaintext_credentials(&mut conn, |_| {
+            panic!("nothing should be stored on a second run")
+        })
+        .unwrap();
+        assert_eq!(moved, 0);
+        assert_eq!(toolset_rows(&conn), before);
+    }
+
+    #[test]
+    fn a_failed_store_leaves_the_plaintext_in_place() {
+        let mut conn = migrator::open_in_memory();
+        conn.execute_batch(
+            "INSERT INTO toolsets_config (toolset_name, parameter_id, parameter_value)
+                VALUES ('github', 'personalAccessToken', 'github_pat_abc')",
+        )
+        .unwrap();
+
+        let result = move_plaintext_credentials(&mut conn, |_| Err("keychain locked".to_string()));
+        assert_eq!(result, Err("keychain locked".to_string()));
+        assert!(toolset_rows(&conn).contains(&(
+            "github".to_string(),
+            "personalAccessToken".to_string(),
+            "github_pat_abc".to_string()
+        )));
+    }
+
+    #[test]
+    fn scrubs_credentials_out_of_a_database_copy() {
+        let dir = TempDir::new();
+        let path = dir.0.join("chats-v133.db");
+        migrator::migrate_up(
+            &path,
+            &migrations::migrations(),
+            None,
+            &RunOptions {
+                backup_dir: None,
+                dry_run: false,
+            },
+        )
+        .unwrap();
+        // Stays open throughout, like the SQL plugin's pool, so closing the
+        // scrubbing connection doesn't checkpoint the WAL for us
+        let conn = Connection::open(&path).unwrap();
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .unwrap();
+        conn.execute_batch(
+            r#"INSERT INTO toolsets_config VALUES ('github', 'personalAccessToken', 'github_pat_scrubme');
+            INSERT INTO custom_toolsets (name, command, env) VALUES ('mine', 'npx', '{"API_KEY":"sk-scrubme"}');"#,
+        )
+        .unwrap();
+        conn.query_row("PRAGMA wal_checkpoint", [], |_| Ok(()))
+            .unwrap();
+        assert!(contains(&path, "github_pat_scrubme"));
+
+        assert_eq!(scrub_database_copy(&path).unwrap(), 2);
+        for file in [path.clone(), path.with_extension("db-wal")] {
+            assert!(
+                !contains(&file, "scrubme"),
+                "{} still holds a secret",
+                file.display()
+            );
+        }
+        let value: String = conn
+            .query_row(
+                "SELECT parameter_value FROM toolsets_config WHERE toolset_name = 'github'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, "vault:toolsets/github/personalAccessToken");
+
+        assert_eq!(scrub_database_copy(&path).unwrap(), 0);
+    }
+
+    #[test]
+    fn skips_copies_from_before_toolsets() {
+        let dir = TempDir::new();
+        let path = dir.0.join("chats-v1.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE chats (id TEXT PRIMARY KEY)")
+            .unwrap();
+        assert_eq!(scrub_database_copy(&path).unwrap(), 0);
+    }
+}
@@ -0,0 +1,535 @@
+//! Parsing and formatting global shortcuts like `Ctrl+Shift+1` or `Alt+F12`.
+//!
+//! A shortcut is any number of modifiers and exactly one key, separated by `+`.
+//! Names are matched case-insensitively and modifiers can come in any order, so
+//! both what ShortcutRecorder.tsx records (`Command+/`, `Control+ArrowUp`) and
+//! Electron-style accelerators (`CmdOrCtrl+Shift+K`) are understood. `+`, which
+//! is Shift+= on a US keyboard, is written `Ctrl++`.
+//!
+//! `format_shortcut` renders a shortcut in one canonical form, which always
+//! parses back to the same shortcut.
+
+use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ShortcutError {
+    #[error("Shortcut is empty")]
+    Empty,
+    #[error("\"{0}\" has an empty part; modifiers and the key are separated by +")]
+    EmptyPart(String),
+    #[error("\"{0}\" isn't a key or modifier Camp knows about")]
+    UnknownKey(String),
+    #[error("{0} appears more than once")]
+    DuplicateModifier(String),
+    #[error("\"{0}\" only has modifiers; add a key, like {0}+Space")]
+    MissingKey(String),
+    #[error("\"{shortcut}\" has two keys ({first} and {second}); only modifiers can be combined")]
+    MultipleKeys {
+        shortcut: String,
+        first: String,
+        second: String,
+    },
+}
+
+/// Modifier names, with the canonical one first.
+const MODIFIERS: &[(Modifiers, &[&str])] = &[
+    (Modifiers::CONTROL, &["Control", "Ctrl"]),
+    (Modifiers::ALT, &["Alt", "Option", "Opt"]),
+    (Modifiers::SHIFT, &["Shift"]),
+    (
+        Modifiers::SUPER,
+        &["Super", "Command", "Cmd", "Meta", "Win"],
+    ),
+];
+
+/// Command on macOS and Control everywhere else.
+const CMD_OR_CTRL: &[&str] = &[
+    "CmdOrCtrl",
+    "CmdOrControl",
+    "CommandOrCtrl",
+    "CommandOrControl",
+];
+
+/// Key names, with the canonical one first. Besides what ShortcutRecorder.tsx
+/// records (`KeyboardEvent.key`), the `KeyboardEvent.code` names are accepted.
+const KEYS: &[(Code, &[&str])] = &[
+    (Code::KeyA, &["A", "KeyA"]),
+    (Code::KeyB, &["B", "KeyB"]),
+    (Code::KeyC, &["C", "KeyC"]),
+    (Code::KeyD, &["D", "KeyD"]),
+    (Code::KeyE, &["E", "KeyE"]),
+    (Code::KeyF, &["F", "KeyF"]),
+    (Code::KeyG, &["G", "KeyG"]),
+    (Code::KeyH, &["H", "KeyH"]),
+    (Code::KeyI, &["I", "KeyI"]),
+    (Code::KeyJ, &["J", "KeyJ"]),
+    (Code::KeyK, &["K", "KeyK"]),
+    (Code::KeyL, &["L", "KeyL"]),
+    (Code::KeyM, &["M", "KeyM"]),
+    (Code::KeyN, &["N", "KeyN"]),
+    (Code::KeyO, &["O", "KeyO"]),
+    (Code::KeyP, &["P", "KeyP"]),
+    (Code::KeyQ, &["Q", "KeyQ"]),
+    (Code::KeyR, &["R", "KeyR"]),
+    (Code::KeyS, &["S", "KeyS"]),
+    (Code::KeyT, &["T", "KeyT"]),
+    (Code::KeyU, &["U", "KeyU"]),
+    (Code::KeyV, &["V", "KeyV"]),
+    (Code::KeyW, &["W", "KeyW"]),
+    (Code::KeyX, &["X", "KeyX"]),
+    (Code::KeyY, &["Y", "KeyY"]),
+    (Code::KeyZ, &["Z", "KeyZ"]),
+    (Code::Digit0, &["0", "Digit0"]),
+    (Code::Digit1, &["1", "Digit1"]),
+    (Code::Digit2, &["2", "Digit2"]),
+    (Code::Digit3, &["3", "Digit3"]),
+    (Code::Digit4, &["4", "Digit4"]),
+    (Code::Digit5, &["5", "Digit5"]),
+    (Code::Digit6, &["6", "Digit6"]),
+    (Code::Digit7, &["7", "Digit7"]),
+    (Code::Digit8, &["8", "Digit8"]),
+    (Code::Digit9, &["9", "Digit9"]),
+    (Code::F1, &["F1"]),
+    (Code::F2, &["F2"]),
+    (Code::F3, &["F3"]),
+    (Code::F4, &["F4"]),
+    (Code::F5, &["F5"]),
+    (Code::F6, &["F6"]),
+    (Code::F7, &["F7"]),
+    (Code::F8, &["F8"]),
+    (Code::F9, &["F9"]),
+    (Code::F10, &["F10"]),
+    (Code::F11, &["F11"]),
+    (Code::F12, &["F12"]),
+    (Code::F13, &["F13"]),
+    (Code::F14, &["F14"]),
+    (Code::F15, &["F15"]),
+    (Code::F16, &["F16"]),
+    (Code::F17, &["F17"]),
+    (Code::F18, &["F18"]),
+    (Code::F19, &["F19"]),
+    (Code::F20, &["F20"]),
+    (Code::F21, &["F21"]),
+    (Code::F22, &["F22"]),
+    (Code::F23, &["F23"]),
+    (Code::F24, &["F24"]),
+    (Code::Minus, &["-", "Minus"]),
+    (Code::Equal, &["=", "Equal"]),
+    (Code::BracketLeft, &["[", "BracketLeft"]),
+    (Code::BracketRight, &["]", "BracketRight"]),
+    (Code::Backslash, &["\\", "Backslash"]),
+    (Code::Semicolon, &[";", "Semicolon"]),
+    (Code::Quote, &["'", "Quote"]),
+    (Code::Backquote, &["`", "Backquote"]),
+    (Code::Comma, &[",", "Comma"]),
+    (Code::Period, &[".", "Period"]),
+    (Code::Slash, &["/", "Slash"]),
+    (Code::Space, &["Space"]),
+    (Code::Enter, &["Enter", "Return"]),
+    (Code::Tab, &["Tab"]),
+    (Code::Escape, &["Escape", "Esc"]),
+    (Code::Backspace, &["Backspace"]),
+    (Code::Delete, &["Delete", "Del"]),
+    (Code::Insert, &["Insert", "Ins"]),
+    (Code::Home, &["Home"]),
+    (Code::End, &["End"]),
+    (Code::PageUp, &["PageUp", "PgUp"]),
+    (Code::PageDown, &["PageDown", "PgDn"]),
+    (Code::ArrowUp, &["ArrowUp", "Up"]),
+    (Code::ArrowDown, &["ArrowDown", "Down"]),
+    (Code::ArrowLeft, &["ArrowLeft", "Left"]),
+    (Code::ArrowRight, &["ArrowRight", "Right"]),
+    (Code::CapsLock, &["CapsLock"]),
+    (Code::NumLock, &["NumLock"]),
+    (Code::ScrollLock, &["ScrollLock"]),
+    (Code::PrintScreen, &["PrintScreen", "PrtSc"]),
+    (Code::Pause, &["Pause", "PauseBreak"]),
+    (Code::Numpad0, &["Numpad0", "Num0"]),
+    (Code::Numpad1, &["Numpad1", "Num1"]),
+    (Code::Numpad2, &["Numpad2", "Num2"]),
+    (Code::Numpad3, &["Numpad3", "Num3"]),
+    (Code::Numpad4, &["Numpad4", "Num4"]),
+    (Code::Numpad5, &["Numpad5", "Num5"]),
+    (Code::Numpad6, &["Numpad6", "Num6"]),
+    (Code::Numpad7, &["Numpad7", "Num7"]),
+    (Code::Numpad8, &["Numpad8", "Num8"]),
+    (Code::Numpad9, &["Numpad9", "Num9"]),
+    (Code::NumpadAdd, &["NumpadAdd", "NumpadPlus"]),
+    (Code::NumpadSubtract, &["NumpadSubtract", "NumpadMinus"]),
+    (Code::NumpadMultiply, &["NumpadMultiply"]),
+    (Code::NumpadDivide, &["NumpadDivide"]),
+    (Code::NumpadDecimal, &["NumpadDecimal"]),
+    (Code::NumpadEnter, &["NumpadEnter"]),
+    (Code::NumpadEqual, &["NumpadEqual"]),
+    (Code::MediaPlayPause, &["MediaPlayPause"]),
+    (Code::MediaPlay, &["MediaPlay"]),
+    (Code::MediaPause, &["MediaPause"]),
+    (Code::MediaStop, &["MediaStop"]),
+    (Code::MediaTrackNext, &["MediaTrackNext", "MediaNextTrack"]),
+    (
+        Code::MediaTrackPrevious,
+        &["MediaTrackPrevious", "MediaPreviousTrack", "MediaTrackPrev"],
+    ),
+    (Code::AudioVolumeUp, &["AudioVolumeUp", "VolumeUp"]),
+    (Code::AudioVolumeDown, &["AudioVolumeDown", "VolumeDown"]),
+    (Code::AudioVolumeMute, &["AudioVolumeMute", "VolumeMute"]),
+];
+
+/// Characters typed with Shift on a US keyboard, and the key they're on. The
+/// recorder reports `Shift+/` as `Shift+?`, so these imply Shift.
+const SHIFTED_KEYS: &[(&str, Code)] = &[
+    ("!", Code::Digit1),
+    ("@", Code::Digit2),
+    ("#", Code::Digit3),
+    ("$", Code::Digit4),
+    ("%", Code::Digit5),
+    ("^", Code::Digit6),
+    ("&", Code::Digit7),
+    ("*", Code::Digit8),
+    ("(", Code::Digit9),
+    (")", Code::Digit0),
+    ("_", Code::Minus),
+    ("+", Code::Equal),
+    ("{", Code::BracketLeft),
+    ("}", Code::BracketRight),
+    ("|", Code::Backslash),
+    (":", Code::Semicolon),
+    ("\"", Code::Quote),
+    ("~", Code::Backquote),
+    ("<", Code::Comma),
+    (">", Code::Period),
+    ("?", Code::Slash),
+];
+
+fn cmd_or_ctrl() -> Modifiers {
+    if cfg!(target_os = "macos") {
+        Modifiers::SUPER
+    } else {
+        Modifiers::CONTROL
+    }
+}
+
+fn find_modifier(name: &str) -> Option<Modifiers> {
+    if CMD_OR_CTRL
+        .iter()
+        .any(|alias| alias.eq_ignore_ascii_case(name))
+    {
+        return Some(cmd_or_ctrl());
+    }
+    MODIFIERS
+        .iter()
+        .find(|(_, names)| names.iter().any(|alias| alias.eq_ignore_ascii_case(name)))
+        .map(|(modifier, _)| *modifier)
+}
+
+/// The key called `name`, and any modifiers it implies.
+fn find_key(name: &str) -> Option<(Code, Modifiers)> {
+    if let Some((code, _)) = KEYS
+        .iter()
+        .find(|(_, names)| names.iter().any(|alias| alias.eq_ignore_ascii_case(name)))
+    {
+        return Some((*code, Modifiers::empty()));
+    }
+    SHIFTED_KEYS
+        .iter()
+        .find(|(shifted, _)| *shifted == name)
+        .map(|(_, code)| (*code, Modifiers::SHIFT))
+}
+
+/// Splits a shortcut into its parts, keeping a trailing `+` key (`Ctrl++`).
+fn split_parts(shortcut: &str) -> Vec<&str> {
+    if shortcut == "+" {
+        return vec!["+"];
+    }
+    match shortcut.strip_suffix("++") {
+        Some(rest) if !rest.is_empty() => {
+            let mut parts: Vec<&str> = rest.split('+').map(str::trim).collect();
+            parts.push("+");
+            parts
+        }
+        _ => shortcut.split('+').map(str::trim).collect(),
+    }
+}
+
+/// Parses a shortcut like `CmdOrCtrl+Shift+K`.
+pub fn parse_shortcut(shortcut: &str) -> Result<Shortcut, ShortcutError> {
+    let shortcut = shortcut.trim();
+    if shortcut.is_empty() {
+        return Err(ShortcutError::Empty);
+    }
+
+    let mut modifiers = Modifiers::empty();
+    let mut key: Option<(&str, Code)> = None;
+    for part in split_parts(shortcut) {
+        if part.is_empty() {
+            return Err(ShortcutError::EmptyPart(shortcut.to_string()));
+        }
+        if let Some(modifier) = find_modifier(part) {
+            if modifiers.contains(modifier) {
+                return Err(ShortcutError::DuplicateModifier(
+                    modifier_name(modifier).to_string(),
+                ));
+            }
+            modifiers |= modifier;
+            continue;
+        }
+        let (code, implied) =
+            find_key(part).ok_or_else(|| ShortcutError::UnknownKey(part.to_string()))?;
+        if let Some((first, _)) = key {
+            return Err(ShortcutError::MultipleKeys {
+                shortcut: shortcut.to_string(),
+                first: first.to_string(),
+                second: part.to_string(),
+            });
+        }
+        // "Shift+?" is fine, since that's how the recorder writes Shift+/
+        modifiers |= implied;
+        key = Some((part, code));
+    }
+
+    match key {
+        Some((_, code)) => Ok(Shortcut::new(Some(modifiers), code)),
+        None => Err(ShortcutError::MissingKey(shortcut.to_string())),
+    }
+}
+
+/// The canonical name of a single modifier.
+fn modifier_name(modifier: Modifiers) -> &'static str {
+    if modifier == Modifiers::SUPER && cfg!(target_os = "macos") {
+        return "Command";
+    }
+    MODIFIERS
+        .iter()
+        .find(|(known, _)| *known == modifier)
+        .map(|(_, names)| names[0])
+        .unwrap_or("Unknown")
+}
+
+/// Renders a shortcut in canonical form: modifiers in the order Control, Alt,
+/// Shift, Super (Command on macOS), then the key, e.g. `Control+Shift+1`.
+pub fn format_shortcut(shortcut: &Shortcut) -> String {
+    let mut parts: Vec<String> = MODIFIERS
+        .iter()
+        .filter(|(modifier, _)| shortcut.mods.contains(*modifier))
+        .map(|(modifier, _)| modifier_name(*modifier).to_string())
+        .collect();
+    let key = KEYS
+        .iter()
+        .find(|(code, _)| *code == shortcut.key)
+        .map(|(_, names)| names[0].to_string())
+        // Keys without a name here can't come from parse_shortcut, but the
+        // plugin's own name is still better than nothing
+        .unwrap_or_else(|| shortcut.key.to_string());
+    parts.push(key);
+    parts.join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcut(modifiers: Modifiers, key: Code) -> Shortcut {
+        Shortcut::new(Some(modifiers), key)
+    }
+
+    #[test]
+    fn rejects_empty_shortcuts() {
+        assert_eq!(parse_shortcut(""), Err(ShortcutError::Empty));
+        assert_eq!(parse_shortcut("  "), Err(ShortcutError::Empty));
+    }
+
+    #[test]
+    fn rejects_empty_parts() {
+        for input in ["Ctrl+", "+A", "Ctrl++A", "++", "Ctrl+ +A"] {
+            assert_eq!(
+                parse_shortcut(input),
+                Err(ShortcutError::EmptyPart(input.to_string())),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert_eq!(
+            parse_shortcut("Ctrl+Hyper+A"),
+            Err(ShortcutError::UnknownKey("Hyper".to_string()))
+        );
+        assert_eq!(
+            parse_shortcut("Alt+F25"),
+            Err(ShortcutError::UnknownKey("F25".to_string()))
+        );
+        // Shifted characters are matched exactly
+        assert_eq!(
+            parse_shortcut("Alt+é"),
+            Err(ShortcutError::UnknownKey("é".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_modifiers() {
+        assert_eq!(
+            parse_shortcut("Ctrl+Control+A"),
+            Err(ShortcutError::DuplicateModifier("Control".to_string()))
+        );
+        assert_eq!(
+            parse_shortcut("Option+alt+A"),
+            Err(ShortcutError::DuplicateModifier("Alt".to_string()))
+        );
+        assert_eq!(
+            parse_shortcut("Win+Meta+A"),
+            Err(ShortcutError::DuplicateModifier(
+                modifier_name(Modifiers::SUPER).to_string()
+            ))
+        );
+        // CmdOrCtrl is one of the two, depending on the platform
+        assert_eq!(
+            parse_shortcut("CmdOrCtrl+Ctrl+Cmd+A"),
+            Err(ShortcutError::DuplicateModifier(
+                modifier_name(cmd_or_ctrl()).to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_shortcuts_without_a_key() {
+        assert_eq!(
+            parse_shortcut("Ctrl+Shift"),
+            Err(ShortcutError::MissingKey("Ctrl+Shift".to_string()))
+        );
+        assert_eq!(
+            parse_shortcut(" Alt "),
+            Err(ShortcutError::MissingKey("Alt".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_shortcuts_with_two_keys() {
+        let error = parse_shortcut("Ctrl+A+Space").unwrap_err();
+        assert_eq!(
+            error,
+            ShortcutError::MultipleKeys {
+                shortcut: "Ctrl+A+Space".to_string(),
+                first: "A".to_string(),
+                second: "Space".to_string(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "\"Ctrl+A+Space\" has two keys (A and Space); only modifiers can be combined"
+        );
+        assert!(matches!(
+            parse_shortcut("Ctrl+?+/"),
+            Err(ShortcutError::MultipleKeys { .. })
+        ));
+    }
+
+    #[test]
+    fn shifted_keys_imply_shift() {
+        for (name, code) in SHIFTED_KEYS {
+            let expected = shortcut(Modifiers::SHIFT, *code);
+            assert_eq!(parse_shortcut(name), Ok(expected), "{}", name);
+            // As the recorder writes them, with Shift already there
+            assert_eq!(
+                parse_shortcut(&format!("Shift+{}", name)),
+                Ok(expected),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            parse_shortcut("Ctrl+?"),
+            Ok(shortcut(Modifiers::CONTROL | Modifiers::SHIFT, Code::Slash))
+        );
+        assert_eq!(
+            format_shortcut(&parse_shortcut("Ctrl+?").unwrap()),
+            "Control+Shift+/"
+        );
+    }
+
+    #[test]
+    fn reads_a_trailing_plus_as_the_key() {
+        let ctrl_plus = shortcut(Modifiers::CONTROL | Modifiers::SHIFT, Code::Equal);
+        assert_eq!(parse_shortcut("Ctrl++"), Ok(ctrl_plus));
+        assert_eq!(parse_shortcut("Ctrl+Shift++"), Ok(ctrl_plus));
+        assert_eq!(
+            parse_shortcut("+"),
+            Ok(shortcut(Modifiers::SHIFT, Code::Equal))
+        );
+        assert_eq!(
+            parse_shortcut("Ctrl+="),
+            Ok(shortcut(Modifiers::CONTROL, Code::Equal))
+        );
+    }
+
+    #[test]
+    fn accepts_every_modifier_alias_in_any_case() {
+        for (modifier, names) in MODIFIERS {
+            for name in *names {
+                for name in [name.to_string(), name.to_lowercase(), name.to_uppercase()] {
+                    assert_eq!(
+                        parse_shortcut(&format!("{}+K", name)),
+                        Ok(shortcut(*modifier, Code::KeyK)),
+                        "{}",
+                        name
+                    );
+                }
+            }
+        }
+        for name in CMD_OR_CTRL {
+            for name in [name.to_string(), name.to_lowercase(), name.to_uppercase()] {
+                assert_eq!(
+                    parse_shortcut(&format!("{}+K", name)),
+                    Ok(shortcut(cmd_or_ctrl(), Code::KeyK)),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn accepts_modifiers_in_any_order_and_spacing() {
+        let expected = shortcut(Modifiers::CONTROL | Modifiers::ALT, Code::Space);
+        for input in [
+            "Ctrl+Alt+Space",
+            "alt+ctrl+space",
+            "Space+Alt+Control",
+            " Alt + Ctrl + Space ",
+        ] {
+            assert_eq!(parse_shortcut(input), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn formats_modifiers_in_canonical_order() {
+        let all = parse_shortcut("Cmd+Shift+Option+Ctrl+k").unwrap();
+        assert_eq!(
+            format_shortcut(&all),
+            format!("Control+Alt+Shift+{}+K", modifier_name(Modifiers::SUPER))
+        );
+        assert_eq!(format_shortcut(&parse_shortcut("f12").unwrap()), "F12");
+    }
+
+    #[test]
+    fn round_trips_every_key() {
+        let all_modifiers =
+            Modifiers::CONTROL | Modifiers::ALT | Modifiers::SHIFT | Modifiers::SUPER;
+        for (code, names) in KEYS {
+            for name in *names {
+                for name in [name.to_string(), name.to_lowercase(), name.to_uppercase()] {
+                    let parsed = parse_shortcut(&name);
+                    assert_eq!(parsed, Ok(shortcut(Modifiers::empty(), *code)), "{}", name);
+                    assert_eq!(format_shortcut(&parsed.unwrap()), names[0], "{}", name);
+                }
+            }
+            for modifiers in [Modifiers::empty(), Modifiers::ALT, all_modifiers] {
+                let original = shortcut(modifiers, *code);
+                let formatted = format_shortcut(&original);
+                assert_eq!(parse_shortcut(&formatted), Ok(original), "{}", formatted);
+            }
+        }
+    }
+}
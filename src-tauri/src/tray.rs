@@ -0,0 +1,280 @@
+//! The tray icon and its menu.
+//!
+//! Clicking the icon toggles the quick chat, and the menu (a right click, or any
+//! click on Linux, where tray icons don't report clicks) lists pinned chats, the
+//! most recently updated ones and projects, along with a few actions. It's read
+//! straight from chats.db and rebuilt whenever `refresh_projects_state` or
+//! `chat_deleted` fire. The main window fires `refresh_projects_state` whenever
+//! its chats or projects change.
+
+use crate::global_shortcuts::{self, ShortcutAction};
+use crate::quick_chat;
+use rusqlite::{Connection, OptionalExtension};
+use tauri::menu::{
+    CheckMenuItemBuilder, Menu, MenuBuilder, MenuEvent, MenuItemBuilder, SubmenuBuilder,
+};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Listener, Manager, Wry};
+
+const TRAY_ID: &str = "camp";
+
+/// Emitted to the main window with a chat id when a chat is picked from the menu.
+pub const OPEN_CHAT_EVENT: &str = "tray_open_chat";
+
+/// How many chats the menu lists besides the pinned ones.
+const RECENT_CHATS: usize = 5;
+
+/// Chat and project names longer than this are cut short.
+const MAX_LABEL_CHARS: usize = 40;
+
+/// `app_metadata` key that ToolsetsManager checks before running any tool.
+const TOOLS_PAUSED_KEY: &str = "tools_paused";
+
+// Tray menu events also go through the app menu's handler in lib.rs, so these
+// are prefixed to keep them apart from the app menu's ids
+const TOGGLE_QUICK_CHAT_ID: &str = "tray-toggle-quick-chat";
+const NEW_CHAT_ID: &str = "tray-new-chat";
+const CAPTURE_SCREEN_ID: &str = "tray-capture-screen";
+const PAUSE_TOOLS_ID: &str = "tray-pause-tools";
+const QUIT_ID: &str = "tray-quit";
+const CHAT_ID_PREFIX: &str = "tray-chat:";
+const PROJECT_ID_PREFIX: &str = "tray-project:";
+
+struct MenuChat {
+    id: String,
+    title: Option<String>,
+}
+
+struct MenuProject {
+    id: String,
+    name: String,
+}
+
+/// What the menu shows, besides the actions that are always there.
+#[derive(Default)]
+struct MenuContents {
+    pinned_chats: Vec<MenuChat>,
+    recent_chats: Vec<MenuChat>,
+    projects: Vec<MenuProject>,
+    tools_paused: bool,
+}
+
+/// Adds the tray icon and keeps its menu up to date.
+pub fn setup(app_handle: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app_handle, &load_contents(app_handle))?;
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(app_handle.default_window_icon().unwrap().clone())
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_tray_icon_event(|tray, event| match event {
+            TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } => quick_chat::toggle(tray.app_handle()),
+            _ => {
+                // Silently ignore unhandled events
+            }
+        })
+        .on_menu_event(handle_menu_event)
+        .build(app_handle)?;
+
+    for event in ["refresh_projects_state", "chat_deleted"] {
+        let handle = app_handle.clone();
+        app_handle.listen(event, move |_| {
+            let handle = handle.clone();
+            // Off the main thread, which the database could otherwise hold up
+            tauri::async_runtime::spawn_blocking(move || refresh(&handle));
+        });
+    }
+    Ok(())
+}
+
+/// Rebuilds the menu from the database.
+fn refresh(app_handle: &AppHandle) {
+    let Some(tray) = app_handle.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let result = build_menu(app_handle, &load_contents(app_handle))
+        .and_then(|menu| tray.set_menu(Some(menu)));
+    if let Err(e) = result {
+        log::error!("Failed to update the tray menu: {}", e);
+    }
+}
+
+fn handle_menu_event(app_handle: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    match id {
+        TOGGLE_QUICK_CHAT_ID => quick_chat::toggle(app_handle),
+        NEW_CHAT_ID => global_shortcuts::dispatch(app_handle, &ShortcutAction::NewChat),
+        CAPTURE_SCREEN_ID => global_shortcuts::dispatch(app_handle, &ShortcutAction::CaptureScreen),
+        PAUSE_TOOLS_ID => {
+            let handle = app_handle.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = toggle_tools_paused(&handle) {
+                    log::error!("Failed to pause or resume tools: {}", e);
+                }
+                // The item checks itself when clicked, so this puts it back if
+                // saving failed
+                refresh(&handle);
+            });
+        }
+        QUIT_ID => app_handle.exit(0),
+        _ => {
+            if let Some(chat_id) = id.strip_prefix(CHAT_ID_PREFIX) {
+                if let Some(window) = app_handle.get_webview_window("main") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+                let _ = app_handle.emit_to("main", OPEN_CHAT_EVENT, chat_id);
+            } else if let Some(project_id) = id.strip_prefix(PROJECT_ID_PREFIX) {
+                global_shortcuts::dispatch(
+                    app_handle,
+                    &ShortcutAction::OpenProject {
+                        project_id: project_id.to_string(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn build_menu(app_handle: &AppHandle, contents: &MenuContents) -> tauri::Result<Menu<Wry>> {
+    let mut menu = MenuBuilder::new(app_handle)
+        .text(TOGGLE_QUICK_CHAT_ID, "Quick Chat")
+        .text(NEW_CHAT_ID, "New Chat")
+        .text(CAPTURE_SCREEN_ID, "Capture Screen");
+
+    for (heading, chats) in [
+        ("Pinned", &contents.pinned_chats),
+        ("Recent", &contents.recent_chats),
+    ] {
+        if chats.is_empty() {
+            continue;
+        }
+        menu = menu.separator().item(
+            &MenuItemBuilder::new(heading)
+                .enabled(false)
+                .build(app_handle)?,
+        );
+        for chat in chats {
+            let title = chat
+                .title
+                .as_deref()
+                .filter(|title| !title.trim().is_empty())
+                .unwrap_or("Untitled Chat");
+            menu = menu.text(format!("{}{}", CHAT_ID_PREFIX, chat.id), label(title));
+        }
+    }
+
+    if !contents.projects.is_empty() {
+        let mut projects = SubmenuBuilder::new(app_handle, "Projects");
+        for project in &contents.projects {
+            projects = projects.text(
+                format!("{}{}", PROJECT_ID_PREFIX, project.id),
+                label(&project.name),
+            );
+        }
+        menu = menu.separator().item(&projects.build()?);
+    }
+
+    menu.separator()
+        .item(
+            &CheckMenuItemBuilder::with_id(PAUSE_TOOLS_ID, "Pause Tool Execution")
+                .checked(contents.tools_paused)
+                .build(app_handle)?,
+        )
+        .separator()
+        .text(QUIT_ID, "Quit Camp")
+        .build()
+}
+
+/// A name as a menu item's text: on one line, cut short if it's long, and with
+/// `&` escaped so it isn't taken for a mnemonic.
+fn label(name: &str) -> String {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = if name.chars().count() > MAX_LABEL_CHARS {
+        let cut: String = name.chars().take(MAX_LABEL_CHARS - 1).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        name
+    };
+    name.replace('&', "&&")
+}
+
+/// Falls back to a menu of just the actions if the database can't be read.
+fn load_contents(app_handle: &AppHandle) -> MenuContents {
+    crate::db::open(app_handle)
+        .and_then(|conn| query_contents(&conn).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            log::error!("Failed to load chats and projects for the tray menu: {}", e);
+            MenuContents::default()
+        })
+}
+
+fn query_contents(conn: &Connection) -> rusqlite::Result<MenuContents> {
+    // The chats the sidebar lists: no replies, quick chats or empty new chats
+    let load_chats = |pinned: bool, limit: i64| {
+        conn.prepare(
+            "SELECT id, title FROM chats
+            WHERE reply_to_id IS NULL AND quick_chat = 0 AND is_new_chat = 0 AND pinned = ?1
+            ORDER BY updated_at DESC
+            LIMIT ?2",
+        )?
+        .query_map((pinned, limit), |row| {
+            Ok(MenuChat {
+                id: row.get(0)?,
+                title: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+    };
+
+    let projects = conn
+        .prepare(
+            "SELECT id, name FROM projects
+            WHERE id NOT IN ('default', 'quick-chat')
+            ORDER BY updated_at DESC",
+        )?
+        .query_map([], |row| {
+            Ok(MenuProject {
+                id: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(MenuContents {
+        // A negative limit means no limit
+        pinned_chats: load_chats(true, -1)?,
+        recent_chats: load_chats(false, RECENT_CHATS as i64)?,
+        projects,
+        tools_paused: tools_paused(conn)?,
+    })
+}
+
+fn tools_paused(conn: &Connection) -> rusqlite::Result<bool> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_metadata WHERE key = ?1",
+            [TOOLS_PAUSED_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value.as_deref() == Some("true"))
+}
+
+fn toggle_tools_paused(app_handle: &AppHandle) -> Result<(), String> {
+    let conn = crate::db::open(app_handle)?;
+    let paused = tools_paused(&conn).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_metadata (key, value) VALUES (?1, ?2)",
+        (TOOLS_PAUSED_KEY, if paused { "false" } else { "true" }),
+    )
+    .map_err(|e| e.to_string())?;
+    log::info!(
+        "Tool execution {}",
+        if paused { "resumed" } else { "paused" }
+    );
+    Ok(())
+}
@@ -1,5 +1,5 @@
 import { appDataDir } from "@tauri-apps/api/path";
-import { mkdir, readFile } from "@tauri-apps/plugin-fs";
+import { readFile, remove } from "@tauri-apps/plugin-fs";
 import { allowedExtensions, AttachmentType } from "@core/chorus/Models";
 import { v4 as uuidv4 } from "uuid";
 import FirecrawlApp from "@mendable/firecrawl-js";
@@ -37,12 +37,25 @@ export const canScrape = () => {
     return scrapeTimestamps.length < MAX_SCRAPES_PER_MINUTE;
 };
 
+// Returned by the resize_image command (see image_pipeline.rs)
+type ResizedImage = {
+    path: string;
+    width: number;
+    height: number;
+    format: "jpeg" | "png" | "original";
+    size: number;
+};
+
 // Core image resizing function that both other functions can use
 export async function resizeImageCore(
     fileData: Uint8Array,
     fileName: string,
     targetSizeBytes: number = TARGET_IMAGE_SIZE_BYTES,
-): Promise<{ resizedData: Uint8Array; wasResized: boolean }> {
+): Promise<{
+    resizedData: Uint8Array;
+    wasResized: boolean;
+    format?: "jpeg" | "png"; // set if the image was re-encoded
+}> {
     // Check if file is already smaller than the target size
     const fileSizeMB = fileData.length / (1024 * 1024);
     const targetSizeMB = targetSizeBytes / (1024 * 1024);
@@ -59,8 +72,9 @@ export async function resizeImageCore(
         `Compressing file from ${fileSizeMB.toFixed(2)}MB to target ${targetSizeMB}MB`,
     );
 
-    // Create a temporary file path to store the original image
-    const tempDir = await appDataDir();
+    // Create a temporary file path to store the original image (uploads is
+    // the only folder in app data write_file_async can write to)
+    const tempDir = path.join(await appDataDir(), "uploads");
     const tempFilePath = path.join(tempDir, `temp_${Date.now()}_${fileName}`);
 
     // Write the file to the temp location
@@ -71,25 +85,34 @@ export async function resizeImageCore(
 
     try {
         // Call the Rust function to resize the image
-        const resizedPath = await invoke<string>("resize_image", {
+        const resized = await invoke<ResizedImage>("resize_image", {
             filePath: tempFilePath,
             targetSizeBytes,
         });
 
         // Read the resized file
-        const resizedData = await readFile(resizedPath);
+        const resizedData = await readFile(resized.path);
 
         // Log compression result
         const compressedSizeMB = resizedData.length / (1024 * 1024);
         console.log(
-            `Compressed to ${compressedSizeMB.toFixed(2)}MB (${Math.round((compressedSizeMB / fileSizeMB) * 100)}% of original)`,
+            `Compressed to ${compressedSizeMB.toFixed(2)}MB (${Math.round((compressedSizeMB / fileSizeMB) * 100)}% of original), ${resized.width}x${resized.height} ${resized.format}`,
         );
 
-        return { resizedData, wasResized: true };
+        if (resized.format === "original") {
+            return { resizedData, wasResized: false };
+        }
+        return { resizedData, wasResized: true, format: resized.format };
     } catch (error) {
         console.error("Error resizing image:", error);
         // If there's an error, return the original data
         return { resizedData: fileData, wasResized: false };
+    } finally {
+        // The attachment store and GC don't know about this copy, so nothing
+        // else would ever delete it
+        await remove(tempFilePath).catch((error) =>
+            console.warn(`Failed to delete ${tempFilePath}:`, error),
+        );
     }
 }
 
@@ -119,30 +142,29 @@ export const fileTypeToAttachmentType = (
     )?.[0] as AttachmentType;
 };
 
-export const ensureUploadsOriginalsDirectory = async () => {
-    const appDir = await appDataDir();
-    const originalsDir = path.join(appDir, "uploads", "originals");
-    await mkdir(originalsDir, { recursive: true });
-    return originalsDir;
-};
-
-export const generateStorePath = async (
-    inputFileName: string,
-    extension = "",
-) => {
-    const originalsDir = await ensureUploadsOriginalsDirectory();
-
-    // Get the file extension: either provided, from filename, or default to "bin"
-    let ext = extension;
-    if (!ext) {
-        const fileExt = path.extname(inputFileName);
-        ext = fileExt ? fileExt.slice(1) : "bin"; // Remove leading dot or use "bin" if no extension
-    }
-
-    // Use uuid library for consistent UUID generation
-    const uuid = uuidv4();
-    const storedFileName = `${uuid}.${ext}`;
-    return path.join(originalsDir, storedFileName);
+/**
+ * Adds a file to the attachment store (see attachment_store.rs) and returns the
+ * path to save in attachments.path. Identical files are only stored once, so the
+ * path may already be used by other attachments.
+ */
+export const storeAttachment = async ({
+    content,
+    sourcePath,
+    extension,
+}: {
+    content?: Uint8Array;
+    sourcePath?: string; // copies from here, avoiding IPC for large files
+    extension?: string; // defaults to sourcePath's extension, or "bin"
+}) => {
+    const blob = await invoke<{ path: string; deduplicated: boolean }>(
+        "store_attachment",
+        {
+            content: content ? Array.from(content) : undefined,
+            sourcePath,
+            extension,
+        },
+    );
+    return blob.path;
 };
 
 /**
@@ -166,64 +188,82 @@ export async function getFileFromPath(filePath: string): Promise<File> {
     });
 }
 
-export const resizeAndStoreFileData = async (
-    file: File,
-    storePath?: string, // use if you want to ensure a particular name
-) => {
+export const resizeAndStoreFileData = async (file: File) => {
     const arrayBuffer = await file.arrayBuffer();
     const uint8Arr = new Uint8Array(arrayBuffer);
     const typeInfo = await fileTypeFromBuffer(uint8Arr);
 
     const fileExt = path.extname(file.name).slice(1); // Remove leading dot
-    const realExtension = typeInfo?.ext || fileExt || "bin";
-    const resolvedStorePath =
-        storePath || (await generateStorePath(file.name, realExtension));
+    let realExtension = typeInfo?.ext || fileExt || "bin";
 
     // check if it's an image and if so, resize it
+    let data = uint8Arr;
     if (typeInfo?.mime?.startsWith("image/")) {
         console.log("resizing image...");
 
-        const { resizedData } = await resizeImageCore(uint8Arr, file.name);
-
-        // Write the resized data to the final storage path
-        await invoke("write_file_async", {
-            path: resolvedStorePath,
-            content: Array.from(resizedData),
-        });
+        const { resizedData, format } = await resizeImageCore(
+            uint8Arr,
+            file.name,
+        );
+        data = resizedData;
+        // Resizing can change the format, e.g. a large PNG photo becomes a JPEG
+        if (format) {
+            realExtension = format === "jpeg" ? "jpg" : "png";
+        }
 
         // print final size in mb
         console.log("final size", resizedData.length / 1024 / 1024);
-    } else {
-        await invoke("write_file_async", {
-            path: resolvedStorePath,
-            content: Array.from(uint8Arr),
-        });
     }
-    return { storedPath: resolvedStorePath, realExtension };
+
+    const storedPath = await storeAttachment({
+        content: data,
+        extension: realExtension,
+    });
+    return { storedPath, realExtension };
 };
 
 // Storage handlers
 export const storeFile = async (filePath: string) => {
     console.log("storing file", filePath);
-    const storedPath = await generateStorePath(filePath);
 
     // Check if file is an image that needs resizing
     const fileType = fileTypeToAttachmentType(filePath);
     if (fileType === "image") {
         // For images, we need to read and potentially resize
         const file = await getFileFromPath(filePath);
-        await resizeAndStoreFileData(file, storedPath);
-    } else {
-        // For non-images (PDFs, text files, etc.), copy directly without reading into memory
-        await invoke("write_file_async", {
-            path: storedPath,
-            sourcePath: filePath, // Use camelCase for Tauri
-        });
+        const { storedPath } = await resizeAndStoreFileData(file);
+        return { storedPath };
     }
 
+    // For non-images (PDFs, text files, etc.), copy directly without reading into memory
+    const storedPath = await storeAttachment({ sourcePath: filePath });
     return { storedPath };
 };
 
+/** StoredFolder in command.rs */
+export type StoredFolder = {
+    storedPath: string;
+    fileCount: number;
+    skipped: {
+        path: string;
+        reason: "binary" | "lockFile" | "overBudget" | "unreadable";
+    }[];
+    estimatedTokens: number;
+    truncated: boolean;
+};
+
+/**
+ * Bundles a folder's file tree and text files into one file in the attachment
+ * store, skipping ignored files (.gitignore and .campignore) and binaries.
+ */
+export const storeFolder = async (folderPath: string, maxTokens?: number) => {
+    console.log("bundling folder", folderPath);
+    return await invoke<StoredFolder>("bundle_folder", {
+        path: folderPath,
+        maxTokens,
+    });
+};
+
 export async function convertPdfToPng(filePath: string): Promise<string[]> {
     // Read the PDF file data
     const fileData = await readFile(filePath);
@@ -276,15 +316,9 @@ export const getScreenshotAttachment = async (
 ): Promise<Attachment> => {
     // skip attachment limit because we're lenient
 
-    // Create a storage path with the correct extension
-    const path = await generateStorePath(
-        file.name, // doesn't matter -- file name not used
-        "png",
-    );
-
     // skip creating a preview (loading attachment) since
     // we won't actually render it and anyway it's fast
-    const { storedPath } = await resizeAndStoreFileData(file, path);
+    const { storedPath } = await resizeAndStoreFileData(file);
 
     const attachment: Attachment = {
         id: uuidv4(),
@@ -298,11 +332,10 @@ export const getScreenshotAttachment = async (
     return attachment;
 };
 
-export async function scrapeUrlAndWriteToPath(
+export async function scrapeUrlAndStore(
     url: string,
-    path: string,
     firecrawlApiKey?: string,
-): Promise<{ success: boolean; error?: string }> {
+): Promise<{ success: boolean; storedPath?: string; error?: string }> {
     if (!firecrawlApiKey) {
         return { success: false, error: "Firecrawl API key not configured" };
     }
@@ -328,11 +361,8 @@ export async function scrapeUrlAndWriteToPath(
         const content = new TextEncoder().encode(
             `URL: ${url}\n\n${scrapeResult.markdown}`,
         );
-        await invoke("write_file_async", {
-            path,
-            content: Array.from(content),
-        });
-        return { success: true };
+        const storedPath = await storeAttachment({ content, extension: "md" });
+        return { success: true, storedPath };
     } catch (error) {
         console.warn("Error scraping URL:", error);
         const errorMessage =
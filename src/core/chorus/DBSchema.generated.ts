@@ -0,0 +1,303 @@
+// This file is auto-generated from migrations.rs by `pnpm generate-schema`.
+// Do not edit manually.
+//
+// Schema version: 134
+
+export type AppMetadataDBRow = {
+    key: string;
+    value: string;
+    created_at: string | null;
+};
+
+export type AttachmentTextExtractionsDBRow = {
+    attachment_id: string;
+    format: string;
+    text: string;
+    section_count: number;
+    created_at: string;
+};
+
+export type AttachmentsDBRow = {
+    id: string;
+    created_at: string;
+    type: string;
+    is_loading: number;
+    original_name: string | null;
+    path: string;
+    ephemeral: number;
+    source_path: string | null;
+    source_updated_at: string | null;
+};
+
+export type ChatsDBRow = {
+    id: string;
+    title: string | null;
+    created_at: string | null;
+    updated_at: string | null;
+    pinned: number;
+    quick_chat: number;
+    project_id: string;
+    summary: string | null;
+    is_new_chat: number;
+    parent_chat_id: string | null;
+    project_context_summary: string | null;
+    project_context_summary_is_stale: number;
+    reply_to_id: string | null;
+    gc_prototype_chat: number;
+};
+
+export type CustomToolsetsDBRow = {
+    name: string;
+    command: string | null;
+    args: string | null;
+    env: string | null;
+    updated_at: string | null;
+    default_permission: string;
+};
+
+export type DraftAttachmentsDBRow = {
+    chat_id: string;
+    attachment_id: string;
+};
+
+export type GcPrototypeConductorsDBRow = {
+    chat_id: string;
+    scope_id: string;
+    conductor_model_id: string;
+    turn_count: number | null;
+    is_active: number | null;
+    created_at: string | null;
+};
+
+export type GcPrototypeMessagesDBRow = {
+    chat_id: string;
+    id: string;
+    text: string;
+    model_config_id: string;
+    created_at: string | null;
+    updated_at: string | null;
+    is_deleted: number;
+    thread_root_message_id: string | null;
+    promoted_from_message_id: string | null;
+};
+
+export type MessageAttachmentsDBRow = {
+    message_id: string;
+    attachment_id: string;
+};
+
+export type MessageDraftsDBRow = {
+    chat_id: string;
+    content: string;
+};
+
+export type MessagePartsDBRow = {
+    chat_id: string;
+    message_id: string;
+    level: number;
+    content: string;
+    tool_calls: string | null;
+    tool_results: string | null;
+};
+
+export type MessageSetsDBRow = {
+    id: string;
+    chat_id: string;
+    deprecated_parent_id: string | null;
+    type: string;
+    created_at: string | null;
+    selected_block_type: string;
+    level: number | null;
+};
+
+export type MessagesDBRow = {
+    id: string;
+    message_set_id: string;
+    chat_id: string;
+    text: string;
+    model: string;
+    selected: number | null;
+    created_at: string | null;
+    streaming_token: string | null;
+    state: string | null;
+    error_message: string | null;
+    is_review: number | null;
+    review_state: string | null;
+    block_type: string | null;
+    level: number | null;
+    dep_attachments_archive: string | null;
+    reply_chat_id: string | null;
+    branched_from_id: string | null;
+};
+
+export type MessagesArchive20250102DBRow = {
+    id: string;
+    chat_id: string;
+    parent_id: string | null;
+    text: string;
+    model: string;
+    selected: number | null;
+    created_at: string | null;
+    attachments: string | null;
+};
+
+export type ModelConfigsDBRow = {
+    id: string;
+    model_id: string;
+    display_name: string;
+    author: string;
+    created_at: string | null;
+    system_prompt: string;
+    is_default: number | null;
+    budget_tokens: number | null;
+    reasoning_effort: string | null;
+    new_until: string | null;
+};
+
+export type ModelsDBRow = {
+    id: string;
+    display_name: string;
+    is_enabled: number | null;
+    supported_attachment_types: string;
+    is_internal: number;
+    is_deprecated: number;
+};
+
+export type ModelsArchive20250111DBRow = {
+    id: string;
+    name: string;
+    type: string;
+    api_key: string | null;
+    model_id: string;
+    system_prompt: string | null;
+    request_template: string;
+    created_at: string | null;
+    is_enabled: number | null;
+    display_order: number | null;
+    is_selected: number | null;
+    short_name: string | null;
+    server_url: string | null;
+};
+
+export type ProjectAttachmentsDBRow = {
+    project_id: string;
+    attachment_id: string;
+};
+
+export type ProjectsDBRow = {
+    id: string;
+    name: string;
+    created_at: string;
+    updated_at: string;
+    is_collapsed: number;
+    context_text: string | null;
+    magic_projects_enabled: number;
+    is_imported: number;
+};
+
+export type SavedModelConfigsChatsDBRow = {
+    id: string;
+    chat_id: string | null;
+    model_ids: string;
+    created_at: string | null;
+    updated_at: string | null;
+};
+
+export type SearchIndexDBRow = {
+    title: unknown | null;
+    body: unknown | null;
+};
+
+export type SearchIndexSourcesDBRow = {
+    id: number;
+    kind: string;
+    source_id: string;
+    chat_id: string;
+    message_id: string | null;
+};
+
+export type TempGroupParentDBRow = {
+    group_key: unknown | null;
+    chat_id: string | null;
+    parent_id: string | null;
+    type: unknown | null;
+    level: unknown | null;
+    parent_group_key: unknown | null;
+};
+
+export type TempGroupingsDBRow = {
+    chat_id: string | null;
+    parent_id: string | null;
+    type: unknown | null;
+    level: unknown | null;
+    group_key: unknown | null;
+};
+
+export type TempHierarchyDBRow = {
+    id: string | null;
+    chat_id: string | null;
+    parent_id: string | null;
+    text: string | null;
+    model: string | null;
+    attachments: string | null;
+    has_children: unknown | null;
+    level: unknown | null;
+    created_at: number | null;
+};
+
+export type TempMessageSetsDBRow = {
+    group_key: string;
+    message_set_id: string | null;
+    chat_id: string;
+    parent_group_key: string | null;
+    parent_message_set_id: string | null;
+    type: string;
+    level: number;
+};
+
+export type ToolPermissionsDBRow = {
+    toolset_name: string;
+    tool_name: string;
+    permission_type: string;
+    last_asked_at: string | null;
+    last_response: string | null;
+    created_at: string | null;
+    updated_at: string | null;
+};
+
+export type ToolsetsConfigDBRow = {
+    toolset_name: string;
+    parameter_id: string;
+    parameter_value: string | null;
+};
+
+export type DBTables = {
+    app_metadata: AppMetadataDBRow;
+    attachment_text_extractions: AttachmentTextExtractionsDBRow;
+    attachments: AttachmentsDBRow;
+    chats: ChatsDBRow;
+    custom_toolsets: CustomToolsetsDBRow;
+    draft_attachments: DraftAttachmentsDBRow;
+    gc_prototype_conductors: GcPrototypeConductorsDBRow;
+    gc_prototype_messages: GcPrototypeMessagesDBRow;
+    message_attachments: MessageAttachmentsDBRow;
+    message_drafts: MessageDraftsDBRow;
+    message_parts: MessagePartsDBRow;
+    message_sets: MessageSetsDBRow;
+    messages: MessagesDBRow;
+    messages_archive_20250102: MessagesArchive20250102DBRow;
+    model_configs: ModelConfigsDBRow;
+    models: ModelsDBRow;
+    models_archive_20250111: ModelsArchive20250111DBRow;
+    project_attachments: ProjectAttachmentsDBRow;
+    projects: ProjectsDBRow;
+    saved_model_configs_chats: SavedModelConfigsChatsDBRow;
+    search_index: SearchIndexDBRow;
+    search_index_sources: SearchIndexSourcesDBRow;
+    temp_group_parent: TempGroupParentDBRow;
+    temp_groupings: TempGroupingsDBRow;
+    temp_hierarchy: TempHierarchyDBRow;
+    temp_message_sets: TempMessageSetsDBRow;
+    tool_permissions: ToolPermissionsDBRow;
+    toolsets_config: ToolsetsConfigDBRow;
+};
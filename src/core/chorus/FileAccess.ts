@@ -0,0 +1,78 @@
+import { invoke } from "@tauri-apps/api/core";
+
+/**
+ * Error from write_file_async and get_file_metadata (see file_access.rs).
+ * Writes are limited to app data's uploads folder and destinations from
+ * chooseExportDestination; reads also allow images from redact_image and
+ * resize_image and files the user dropped or picked in a dialog.
+ */
+export type FileAccessError = {
+    kind:
+        | "invalidPath"
+        | "outsideAllowedRoots"
+        | "symlinkEscape"
+        | "missingContent"
+        | "io";
+    message: string;
+    path: string | null;
+};
+
+export function isFileAccessError(error: unknown): error is FileAccessError {
+    return (
+        typeof error === "object" &&
+        error !== null &&
+        "kind" in error &&
+        "message" in error
+    );
+}
+
+/**
+ * Shows a save dialog and returns the chosen path, which write_file_async can
+ * then write to. Returns undefined if the user cancels.
+ */
+export async function chooseExportDestination(defaultName?: string) {
+    return (
+        (await invoke<string | null>("choose_export_destination", {
+            defaultName,
+        })) ?? undefined
+    );
+}
+
+/** Why a model can't take a file (see LimitIssue in file_inspection.rs) */
+export type LimitIssue =
+    | { kind: "unsupportedFile" }
+    | { kind: "unsupportedType"; attachmentType: "image" | "pdf" | "text" }
+    | { kind: "tooLarge"; size: number; maxSize: number }
+    | { kind: "tooManyPages"; pageCount: number; maxPages: number }
+    | {
+          kind: "imageTooLarge";
+          width: number;
+          height: number;
+          maxDimension: number;
+      };
+
+export type FileInspection = {
+    size: number;
+    isFile: boolean;
+    isDirectory: boolean;
+    mimeType: string | null;
+    /** Null for directories and binary files that can't be attached */
+    attachmentType: "image" | "pdf" | "text" | null;
+    sha256: string | null;
+    image: { width: number; height: number } | null;
+    pdf: { pageCount: number } | null;
+    text: { encoding: string; hasBom: boolean; lineCount: number } | null;
+    /** One entry per enabled model; empty issues means the model can take it */
+    models: { modelId: string; displayName: string; issues: LimitIssue[] }[];
+};
+
+/**
+ * Inspects a file without reading it into the webview. Pass modelIds to only
+ * check those models.
+ */
+export async function inspectFile(path: string, modelIds?: string[]) {
+    return await invoke<FileInspection>("get_file_metadata", {
+        path,
+        modelIds,
+    });
+}
@@ -6,6 +6,7 @@ import {
     StreamResponseParams,
     readTextAttachment,
     readWebpageAttachment,
+    readFolderAttachment,
 } from "../Models";
 import { IProvider } from "./IProvider";
 import { canProceedWithProvider } from "@core/utilities/ProxyUtils";
@@ -266,6 +267,22 @@ async function formatMessageWithAttachments(
                 });
                 break;
             }
+            case "folder": {
+                attachmentBlocks.push({
+                    // @ts-expect-error: Anthropic sdk types are outdated
+                    type: "document",
+                    source: {
+                        type: "text",
+                        media_type: "text/plain",
+                        data: await readFolderAttachment(attachment),
+                    },
+                    title: attachment.originalName,
+                    citations: {
+                        enabled: false,
+                    },
+                });
+                break;
+            }
             case "image": {
                 const fileExtension = attachment.path
                     .split(".")
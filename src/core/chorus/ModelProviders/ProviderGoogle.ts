@@ -100,6 +100,8 @@ export class ProviderGoogle implements IProvider {
                 {
                     imageSupport: true,
                     functionSupport: true,
+                    pdfSupport:
+                        modelConfig.supportedAttachmentTypes.includes("pdf"),
                 },
             );
 
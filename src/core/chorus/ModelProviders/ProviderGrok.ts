@@ -70,6 +70,8 @@ export class ProviderGrok implements IProvider {
                 {
                     imageSupport: true,
                     functionSupport: false,
+                    pdfSupport:
+                        modelConfig.supportedAttachmentTypes.includes("pdf"),
                 },
             );
 
@@ -5,6 +5,8 @@ import {
     llmMessageToString,
     readTextAttachment,
     readWebpageAttachment,
+    extractDocumentText,
+    readFolderAttachment,
 } from "../Models";
 import { ollamaClient } from "../OllamaClient";
 
@@ -69,9 +71,14 @@ async function formatMessageWithAttachments(
         );
     }
 
-    // Handle text and webpage attachments by appending their content
+    // Handle text and webpage attachments by appending their content. Ollama
+    // models can't read PDFs, so those are sent as their extracted text
     const textAndWebAttachments = message.attachments.filter(
-        (a) => a.type === "text" || a.type === "webpage",
+        (a) =>
+            a.type === "text" ||
+            a.type === "webpage" ||
+            a.type === "pdf" ||
+            a.type === "folder",
     );
 
     if (textAndWebAttachments.length) {
@@ -82,6 +89,10 @@ async function formatMessageWithAttachments(
                     content = await readTextAttachment(attachment);
                 } else if (attachment.type === "webpage") {
                     content = await readWebpageAttachment(attachment);
+                } else if (attachment.type === "pdf") {
+                    content = await extractDocumentText(attachment);
+                } else if (attachment.type === "folder") {
+                    content = await readFolderAttachment(attachment);
                 }
                 return `\n\n[${attachment.originalName}]:\n${content}`;
             }),
@@ -6,6 +6,7 @@ import {
     encodeTextAttachment,
     attachmentMissingFlag,
     encodeWebpageAttachment,
+    encodeFolderAttachment,
     readPdfAttachment,
     LLMMessageUser,
     LLMMessageAssistant,
@@ -399,6 +400,10 @@ async function formamtUserMessageWithAttachments(
                 attachmentTexts += await encodeWebpageAttachment(attachment);
                 break;
             }
+            case "folder": {
+                attachmentTexts += await encodeFolderAttachment(attachment);
+                break;
+            }
             case "image": {
                 if (!imageSupport) {
                     attachmentTexts += attachmentMissingFlag(attachment);
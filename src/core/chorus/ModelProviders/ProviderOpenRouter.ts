@@ -73,6 +73,8 @@ export class ProviderOpenRouter implements IProvider {
                 {
                     imageSupport: supportsImages,
                     functionSupport: true,
+                    pdfSupport:
+                        modelConfig.supportedAttachmentTypes.includes("pdf"),
                 },
             );
 
@@ -47,6 +47,8 @@ export class ProviderPerplexity implements IProvider {
             {
                 imageSupport: false,
                 functionSupport: false,
+                pdfSupport:
+                    modelConfig.supportedAttachmentTypes.includes("pdf"),
             },
         );
 
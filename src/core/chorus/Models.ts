@@ -12,6 +12,7 @@ import { ProviderPerplexity } from "./ModelProviders/ProviderPerplexity";
 import { IProvider } from "./ModelProviders/IProvider";
 import Database from "@tauri-apps/plugin-sql";
 import { readFile } from "@tauri-apps/plugin-fs";
+import { invoke } from "@tauri-apps/api/core";
 import { ProviderGoogle } from "./ModelProviders/ProviderGoogle";
 import { ollamaClient } from "./OllamaClient";
 import { ProviderOllama } from "./ModelProviders/ProviderOllama";
@@ -25,10 +26,12 @@ import { Attachment } from "./api/AttachmentsAPI";
 /// Basic Types
 /// ------------------------------------------------------------------------------------------------
 
-export type AttachmentType = "image" | "pdf" | "text" | "webpage";
+export type AttachmentType = "image" | "pdf" | "text" | "webpage" | "folder";
 
 export const allowedExtensions: Record<AttachmentType, string[]> = {
     image: ["png", "jpg", "jpeg", "gif", "webp"],
+    // Folders are bundled into one text file by bundle_folder in Rust
+    folder: [],
     pdf: ["pdf"],
     text: [
         // Documentation
@@ -491,12 +494,56 @@ export async function downloadLMStudioModels(db: Database): Promise<void> {
 /// Helpers
 /// ------------------------------------------------------------------------------------------------
 
+/** Office documents are attached as text, and read through extractDocumentText */
+const OFFICE_DOCUMENT_EXTENSIONS = ["docx", "xlsx", "pptx"];
+
+/** ExtractedText in document_text.rs */
+type ExtractedText = {
+    format: "pdf" | "docx" | "xlsx" | "pptx";
+    text: string;
+    sectionCount: number;
+    cached: boolean;
+};
+
+function isOfficeDocument(attachment: Attachment): boolean {
+    const extension = attachment.path.split(".").pop()?.toLowerCase() || "";
+    return OFFICE_DOCUMENT_EXTENSIONS.includes(extension);
+}
+
+/**
+ * Text of a PDF or office document attachment, with a marker before each page,
+ * sheet or slide. Extracted in Rust (see document_text.rs) and cached per
+ * attachment, so only the first call is slow.
+ */
+export async function extractDocumentText(
+    attachment: Attachment,
+): Promise<string> {
+    const { text } = await invoke<ExtractedText>("extract_attachment_text", {
+        attachmentId: attachment.id,
+    });
+    return text;
+}
+
 export async function readTextAttachment(
     attachment: Attachment,
 ): Promise<string> {
     if (attachment.type !== "text") {
         throw new Error("Attachment is not a text file");
     }
+    if (isOfficeDocument(attachment)) {
+        return await extractDocumentText(attachment);
+    }
+    const fileContent = await readFile(attachment.path);
+    return new TextDecoder().decode(fileContent);
+}
+
+/** The bundled file tree and contents (see folder_bundle.rs) */
+export async function readFolderAttachment(
+    attachment: Attachment,
+): Promise<string> {
+    if (attachment.type !== "folder") {
+        throw new Error("Attachment is not a folder");
+    }
     const fileContent = await readFile(attachment.path);
     return new TextDecoder().decode(fileContent);
 }
@@ -564,6 +611,33 @@ export async function encodeTextAttachment(
     return `<attachment name="${attachment.originalName}">\n${await readTextAttachment(attachment)}\n</attachment>\n\n`;
 }
 
+export async function encodeFolderAttachment(
+    attachment: Attachment,
+): Promise<string> {
+    if (attachment.type !== "folder") {
+        throw new Error("Attachment is not a folder");
+    }
+    return `<attachment name="${attachment.originalName}" type="folder">\n${await readFolderAttachment(attachment)}\n</attachment>\n\n`;
+}
+
+/**
+ * For models without "pdf" in supportedAttachmentTypes. Falls back to the
+ * missing flag if the PDF has no text (e.g. scanned pages).
+ */
+export async function encodePdfAttachmentAsText(
+    attachment: Attachment,
+): Promise<string> {
+    if (attachment.type !== "pdf") {
+        throw new Error("Attachment is not a PDF file");
+    }
+    try {
+        return `<attachment name="${attachment.originalName}" type="pdf">\n${await extractDocumentText(attachment)}\n</attachment>\n\n`;
+    } catch (error) {
+        console.error("Failed to extract PDF text:", error);
+        return attachmentMissingFlag(attachment);
+    }
+}
+
 export function attachmentMissingFlag(attachment: Attachment): string {
     return `<attachment name="${attachment.originalName}" type="${attachment.type}">
 [This attachment type is not supported by the model. Respond anyway if you can.]
@@ -5,6 +5,8 @@ import {
     encodeTextAttachment,
     attachmentMissingFlag,
     encodeWebpageAttachment,
+    encodePdfAttachmentAsText,
+    encodeFolderAttachment,
 } from "@core/chorus/Models";
 import {
     getUserToolNamespacedName,
@@ -39,9 +41,14 @@ async function convertMessage(
     options?: {
         imageSupport?: boolean;
         functionSupport?: boolean;
+        pdfSupport?: boolean;
     },
 ): Promise<OpenAI.ChatCompletionMessageParam[]> {
-    const { imageSupport = true, functionSupport = true } = options ?? {};
+    const {
+        imageSupport = true,
+        functionSupport = true,
+        pdfSupport = true,
+    } = options ?? {};
     if (message.role === "tool_results") {
         if (!functionSupport) {
             return [
@@ -95,6 +102,10 @@ async function convertMessage(
                         await encodeWebpageAttachment(attachment);
                     break;
                 }
+                case "folder": {
+                    attachmentTexts += await encodeFolderAttachment(attachment);
+                    break;
+                }
                 case "image": {
                     if (!imageSupport) {
                         attachmentTexts += attachmentMissingFlag(attachment);
@@ -113,6 +124,11 @@ async function convertMessage(
                     break;
                 }
                 case "pdf": {
+                    if (!pdfSupport) {
+                        attachmentTexts +=
+                            await encodePdfAttachmentAsText(attachment);
+                        break;
+                    }
                     try {
                         console.log("Converting PDF to PNG:", attachment.path);
                         const pngUrls = await convertPdfToPng(attachment.path);
@@ -176,6 +192,7 @@ async function convertConversation(
     options?: {
         imageSupport?: boolean;
         functionSupport?: boolean;
+        pdfSupport?: boolean;
     },
 ): Promise<OpenAI.ChatCompletionMessageParam[]> {
     return _.flatten(
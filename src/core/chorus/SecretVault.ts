@@ -0,0 +1,133 @@
+import { invoke } from "@tauri-apps/api/core";
+
+/**
+ * Credentials live in an encrypted vault on the Rust side (see secrets.rs).
+ * Tables that used to hold them store a reference like
+ * `vault:toolsets/github/personalAccessToken` instead.
+ */
+export const SECRET_REFERENCE_PREFIX = "vault:";
+
+export function isSecretReference(value: string): boolean {
+    return value.startsWith(SECRET_REFERENCE_PREFIX);
+}
+
+export function toolsetSecretName(toolsetName: string, parameterId: string) {
+    return `toolsets/${toolsetName}/${parameterId}`;
+}
+
+export function customToolsetEnvSecretName(
+    toolsetName: string,
+    variable: string,
+) {
+    return `custom_toolsets/${toolsetName}/env/${variable}`;
+}
+
+/**
+ * Stores a secret and returns the reference to save in its place
+ */
+export async function putSecret(name: string, value: string) {
+    return await invoke<string>("put_secret", { name, value });
+}
+
+export async function getSecret(name: string) {
+    return (await invoke<string | null>("get_secret", { name })) ?? undefined;
+}
+
+/**
+ * Secret names (never values), optionally only those starting with `prefix`
+ */
+export async function listSecrets(prefix?: string) {
+    return await invoke<string[]>("list_secrets", { prefix });
+}
+
+export async function deleteSecret(name: string) {
+    return await invoke<boolean>("delete_secret", { name });
+}
+
+export async function deleteSecretsWithPrefix(prefix: string) {
+    for (const name of await listSecrets(prefix)) {
+        await deleteSecret(name);
+    }
+}
+
+/**
+ * Returns the secret a reference points to, or the value unchanged if it isn't
+ * a reference. A reference to a missing secret resolves to "".
+ */
+export async function resolveSecretReference(value: string) {
+    if (!isSecretReference(value)) {
+        return value;
+    }
+    return (
+        (await getSecret(value.slice(SECRET_REFERENCE_PREFIX.length))) ?? ""
+    );
+}
+
+function isEnvObject(value: unknown): value is Record<string, unknown> {
+    return typeof value === "object" && value !== null && !Array.isArray(value);
+}
+
+function parseEnv(env: string): Record<string, unknown> | undefined {
+    let parsed: unknown;
+    try {
+        parsed = JSON.parse(env);
+    } catch {
+        return undefined;
+    }
+    return isEnvObject(parsed) ? parsed : undefined;
+}
+
+/**
+ * Replaces references in a custom toolset's env JSON with the secrets they
+ * point to. Env that has no references is returned as is.
+ */
+export async function resolveEnvSecrets(env: string) {
+    const parsed = parseEnv(env);
+    const hasReferences = Object.values(parsed ?? {}).some(
+        (value) => typeof value === "string" && isSecretReference(value),
+    );
+    if (!parsed || !hasReferences) {
+        return env;
+    }
+    const resolved: Record<string, unknown> = {};
+    for (const [variable, value] of Object.entries(parsed)) {
+        resolved[variable] =
+            typeof value === "string"
+                ? await resolveSecretReference(value)
+                : value;
+    }
+    return JSON.stringify(resolved);
+}
+
+/**
+ * Moves every value in a custom toolset's env JSON into the vault and returns
+ * the env with references in their place. Secrets for variables that are no
+ * longer in the env are deleted. Invalid JSON is returned unchanged.
+ */
+export async function storeEnvSecrets(toolsetName: string, env: string) {
+    const parsed = parseEnv(env);
+    if (!parsed) {
+        return env;
+    }
+    const kept = new Set<string>();
+    const stored: Record<string, unknown> = {};
+    for (const [variable, value] of Object.entries(parsed)) {
+        if (typeof value !== "string" || value === "") {
+            stored[variable] = value;
+            continue;
+        }
+        const name = customToolsetEnvSecretName(toolsetName, variable);
+        kept.add(name);
+        stored[variable] = isSecretReference(value)
+            ? value
+            : await putSecret(name, value);
+    }
+    for (const name of await listSecrets(
+        customToolsetEnvSecretName(toolsetName, ""),
+    )) {
+        if (!kept.has(name)) {
+            await deleteSecret(name);
+        }
+    }
+    return JSON.stringify(stored);
+}
@@ -75,8 +75,17 @@ export class ToolsetsManager {
         }
 
         try {
-            // Check if YOLO mode is enabled
             const appMetadata = await fetchAppMetadata();
+
+            // Paused from the tray menu (see tray.rs)
+            if (appMetadata?.["tools_paused"] === "true") {
+                return {
+                    id: toolCall.id,
+                    content: `<system_message>Tool execution is paused by the user</system_message>`,
+                };
+            }
+
+            // Check if YOLO mode is enabled
             const yoloMode = appMetadata?.["yolo_mode"] === "true";
 
             if (yoloMode) {
@@ -17,6 +17,7 @@ export type AttachmentDBRow = {
     path: string;
     is_loading: number;
     ephemeral: number;
+    source_updated_at?: string | null;
 };
 
 export type Attachment = {
@@ -26,6 +27,20 @@ export type Attachment = {
     path: string;
     isLoading: boolean;
     ephemeral: boolean;
+    /** When the watcher last replaced it because its source file changed */
+    sourceUpdatedAt?: string;
+};
+
+/**
+ * Payload of project_attachment_updated, emitted when a project attachment is
+ * replaced because its source file changed (AttachmentUpdate in
+ * attachment_watcher.rs)
+ */
+export type AttachmentUpdate = {
+    attachmentId: string;
+    projectIds: string[];
+    path: string;
+    updatedAt: string;
 };
 
 export function readAttachment(row: AttachmentDBRow): Attachment {
@@ -36,6 +51,7 @@ export function readAttachment(row: AttachmentDBRow): Attachment {
         path: row.path,
         isLoading: row.is_loading === 1,
         ephemeral: row.ephemeral === 1,
+        sourceUpdatedAt: row.source_updated_at ?? undefined,
     };
 }
 
@@ -50,6 +66,9 @@ export function useAttachmentContents(attachment: Attachment) {
                 case "webpage": {
                     return Models.readWebpageAttachment(attachment);
                 }
+                case "folder": {
+                    return Models.readFolderAttachment(attachment);
+                }
                 case "image": {
                     return Models.readImageAttachment(attachment);
                 }
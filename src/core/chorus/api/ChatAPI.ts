@@ -5,7 +5,7 @@ import { db } from "../DB";
 import { getVersion } from "@tauri-apps/api/app";
 import { usePostHog } from "posthog-js/react";
 
-const chatKeys = {
+export const chatKeys = {
     all: () => ["chats"] as const,
     allDetails: () => [...chatKeys.all(), "detail"] as const,
 };
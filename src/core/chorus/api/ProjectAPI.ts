@@ -10,6 +10,7 @@ import { simpleLLM, simpleSummarizeLLM } from "../simpleLLM";
 import _ from "lodash";
 import { useNavigate } from "react-router-dom";
 import { db } from "../DB";
+import { invoke } from "@tauri-apps/api/core";
 import { Attachment, AttachmentDBRow, readAttachment } from "./AttachmentsAPI";
 
 export const projectKeys = {
@@ -98,7 +99,7 @@ export async function fetchProjectContextAttachments(
     projectId: string,
 ): Promise<Attachment[]> {
     const result = await db.select<AttachmentDBRow[]>(
-        `SELECT attachments.id, attachments.type, attachments.original_name, attachments.path, attachments.is_loading, attachments.ephemeral
+        `SELECT attachments.id, attachments.type, attachments.original_name, attachments.path, attachments.is_loading, attachments.ephemeral, attachments.source_updated_at
         FROM project_attachments
         JOIN attachments ON project_attachments.attachment_id = attachments.id
         WHERE project_attachments.project_id = ?
@@ -514,6 +515,15 @@ export function useSetChatProject() {
     });
 }
 
+/** Stops watching sources of attachments no longer in a project */
+async function syncAttachmentWatches() {
+    try {
+        await invoke("sync_attachment_watches");
+    } catch (error) {
+        console.error("Failed to sync attachment watches:", error);
+    }
+}
+
 export function useDeleteAttachmentFromProject() {
     const queryClient = useQueryClient();
     return useMutation({
@@ -533,6 +543,7 @@ export function useDeleteAttachmentFromProject() {
             ]);
         },
         onSuccess: async (_data, variables) => {
+            void syncAttachmentWatches();
             await queryClient.invalidateQueries(
                 projectContextQueries.attachments(variables.projectId),
             );
@@ -597,6 +608,7 @@ export function useDeleteProject() {
             await db.execute("DELETE FROM projects WHERE id = $1", [projectId]);
         },
         onSuccess: async () => {
+            void syncAttachmentWatches();
             await queryClient.invalidateQueries(projectQueries.list());
         },
     });
@@ -610,11 +622,14 @@ export function useFinalizeAttachmentForProject() {
             attachmentId,
             storedPath,
             type,
+            sourcePath,
         }: {
             projectId: string;
             attachmentId: string;
             storedPath: string;
             type?: string;
+            /** File or folder on disk to keep the attachment up to date with */
+            sourcePath?: string;
         }) => {
             if (type) {
                 await db.execute(
@@ -627,6 +642,17 @@ export function useFinalizeAttachmentForProject() {
                     [storedPath, attachmentId],
                 );
             }
+            if (sourcePath) {
+                try {
+                    await invoke("watch_attachment_source", {
+                        attachmentId,
+                        sourcePath,
+                    });
+                } catch (error) {
+                    // The attachment still works, it just won't follow its source
+                    console.error("Failed to watch attachment source:", error);
+                }
+            }
         },
         onSuccess: async (_data, variables) => {
             // TODOJDC do an optimistic update instead
@@ -4,6 +4,15 @@ import { CustomToolsetConfig, ToolPermissionType } from "../Toolsets";
 import { ToolsetsManager } from "../ToolsetsManager";
 import { homeDir, join } from "@tauri-apps/api/path";
 import { exists, readTextFile } from "@tauri-apps/plugin-fs";
+import {
+    deleteSecret,
+    deleteSecretsWithPrefix,
+    putSecret,
+    resolveEnvSecrets,
+    resolveSecretReference,
+    storeEnvSecrets,
+    toolsetSecretName,
+} from "../SecretVault";
 
 export const toolsetsKeys = {
     // toolset configs
@@ -15,18 +24,30 @@ export const toolsetsKeys = {
     toolsets: () => ["toolsets"] as const,
 };
 
+/**
+ * Parameters that aren't credentials, so they stay in toolsets_config. Must match
+ * NON_SECRET_PARAMETERS in secrets.rs.
+ */
+const NON_SECRET_PARAMETERS = ["enabled", "command", "args", "teamId"];
+
 async function fetchToolsetsConfig() {
-    return (
-        await db.select<
-            {
-                toolset_name: string;
-                parameter_id: string;
-                parameter_value: string;
-            }[]
-        >(
-            "SELECT toolset_name, parameter_id, parameter_value FROM toolsets_config",
-        )
-    ).reduce(
+    const rows = await db.select<
+        {
+            toolset_name: string;
+            parameter_id: string;
+            parameter_value: string;
+        }[]
+    >(
+        "SELECT toolset_name, parameter_id, parameter_value FROM toolsets_config",
+    );
+    // credentials are stored in the secret vault, with a reference in the table
+    const resolved = await Promise.all(
+        rows.map(async (c) => ({
+            ...c,
+            parameter_value: await resolveSecretReference(c.parameter_value),
+        })),
+    );
+    return resolved.reduce(
         (acc, c) => {
             acc[c.toolset_name] = {
                 ...acc[c.toolset_name],
@@ -63,11 +84,20 @@ export function readCustomToolset(
 export async function fetchCustomToolsetConfigs(): Promise<
     CustomToolsetConfig[]
 > {
-    return (
+    const toolsets = (
         await db.select<CustomToolsetConfigDBRow[]>(
             "SELECT name, command, args, env, default_permission, updated_at FROM custom_toolsets ORDER BY name",
         )
     ).map(readCustomToolset);
+    // env values are stored in the secret vault, with references in the table
+    return await Promise.all(
+        toolsets.map(async (toolset) => ({
+            ...toolset,
+            env: toolset.env
+                ? await resolveEnvSecrets(toolset.env)
+                : toolset.env,
+        })),
+    );
 }
 
 export function useToolsetsConfig() {
@@ -113,13 +143,16 @@ export function useUpdateCustomToolsetConfig() {
     return useMutation({
         mutationKey: ["updateCustomToolsetConfig"] as const,
         mutationFn: async ({ toolset }: { toolset: CustomToolsetConfig }) => {
+            const env = toolset.env
+                ? await storeEnvSecrets(toolset.name, toolset.env)
+                : toolset.env;
             await db.execute(
                 "INSERT OR REPLACE INTO custom_toolsets (name, command, args, env, default_permission, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
                 [
                     toolset.name,
                     toolset.command,
                     toolset.args,
-                    toolset.env,
+                    env,
                     toolset.defaultPermission ?? "ask",
                     new Date().toISOString(),
                 ],
@@ -160,6 +193,9 @@ export function useDeleteCustomToolsetConfig() {
                 "DELETE FROM toolsets_config WHERE toolset_name = ?",
                 [name],
             );
+            // delete env and parameter secrets
+            await deleteSecretsWithPrefix(`custom_toolsets/${name}/`);
+            await deleteSecretsWithPrefix(toolsetSecretName(name, ""));
         },
         onSuccess: async () => {
             // Invalidate both custom toolsets and general toolsets queries
@@ -245,9 +281,19 @@ export function useUpdateToolsetsConfig() {
             parameterId: string;
             value: string;
         }) => {
+            // credentials go in the secret vault
+            let storedValue = value;
+            if (!NON_SECRET_PARAMETERS.includes(parameterId)) {
+                const secretName = toolsetSecretName(toolsetName, parameterId);
+                if (value) {
+                    storedValue = await putSecret(secretName, value);
+                } else {
+                    await deleteSecret(secretName);
+                }
+            }
             await db.execute(
                 "INSERT OR REPLACE INTO toolsets_config (toolset_name, parameter_id, parameter_value) VALUES (?, ?, ?)",
-                [toolsetName, parameterId, value],
+                [toolsetName, parameterId, storedValue],
             );
         },
         onSuccess: async () => {
@@ -1,4 +1,24 @@
 import { invoke } from "@tauri-apps/api/core";
+import { readImage } from "@tauri-apps/plugin-clipboard-manager";
+
+export type CapturableWindow = {
+    id: number;
+    title: string;
+    appName: string;
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+};
+
+async function base64ToFile(base64Image: string, name: string) {
+    const response = await fetch(`data:image/png;base64,${base64Image}`);
+    const blob = await response.blob();
+
+    return new File([blob], name, {
+        type: "image/png",
+    });
+}
 
 export async function captureWholeScreenCompressed() {
     // The Rust function optimizes the image size to be under 3.5MB
@@ -12,19 +32,143 @@ export async function captureWholeScreenCompressed() {
 
         console.timeEnd("captureWholeScreen");
 
-        const response = await fetch(`data:image/png;base64,${base64Image}`);
-        const blob = await response.blob();
+        return await base64ToFile(base64Image, `screenshot.png`);
+    } catch (error) {
+        console.timeEnd("captureWholeScreen");
+        console.error("Screenshot capture failed:", error);
+        throw new Error(
+            typeof error === "string" ? error : "Failed to capture screenshot",
+        );
+    }
+}
+
+/**
+ * Windows that captureWindowCompressed can capture, topmost first.
+ * Not available on macOS, where capture_window shows the system's own picker.
+ */
+export async function listCapturableWindows() {
+    return await invoke<CapturableWindow[]>("list_windows");
+}
 
-        const file = new File([blob], `screenshot.png`, {
-            type: "image/png",
+/**
+ * Captures a window from listCapturableWindows (or the topmost window if no id
+ * is given). On macOS the user picks the window instead and windowId is ignored.
+ */
+export async function captureWindowCompressed(windowId?: number) {
+    try {
+        const base64Image = await invoke<string>("capture_window", {
+            windowId,
         });
+        return await base64ToFile(base64Image, `window.png`);
+    } catch (error) {
+        console.error("Window capture failed:", error);
+        throw new Error(
+            typeof error === "string" ? error : "Failed to capture window",
+        );
+    }
+}
+
+/**
+ * A capture of the display the quick chat window is on, for the user to select
+ * a region of. Selections are in logical points (logicalWidth x logicalHeight).
+ */
+export type DisplayCapture = {
+    /** Identifies the full-resolution capture, which stays on the Rust side */
+    id: string;
+    /** Base64 JPEG, scaled down from the full capture */
+    preview: string;
+    width: number;
+    height: number;
+    logicalWidth: number;
+    logicalHeight: number;
+    scaleFactor: number;
+};
 
-        return file;
+export type RegionSelection = {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+};
+
+export async function captureDisplayForRegion() {
+    try {
+        return await invoke<DisplayCapture>("capture_display_for_region");
     } catch (error) {
-        console.timeEnd("captureWholeScreen");
-        console.error("Screenshot capture failed:", error);
+        console.error("Display capture failed:", error);
         throw new Error(
             typeof error === "string" ? error : "Failed to capture screenshot",
         );
     }
 }
+
+/**
+ * Crops a display capture to the selected region. HiDPI crops are scaled down
+ * to one pixel per point to keep them small.
+ */
+export async function cropScreenshot(
+    capture: DisplayCapture,
+    selection: RegionSelection,
+) {
+    try {
+        const base64Image = await invoke<string>("crop_screenshot", {
+            captureId: capture.id,
+            selection,
+            logicalResolution: true,
+        });
+        return await base64ToFile(base64Image, `screenshot.png`);
+    } catch (error) {
+        console.error("Screenshot crop failed:", error);
+        throw new Error(
+            typeof error === "string" ? error : "Failed to crop screenshot",
+        );
+    }
+}
+
+/**
+ * An area to redact, in pixels of the image
+ */
+export type RedactionArea = {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+};
+
+/**
+ * Blurs or blacks out areas of any image file and returns the path of a
+ * redacted copy. The original is left alone.
+ */
+export async function redactImage(
+    filePath: string,
+    areas: RedactionArea[],
+    style: "blur" | "blackout" = "blur",
+) {
+    return await invoke<string>("redact_image", { filePath, areas, style });
+}
+
+/**
+ * The image on the clipboard as a PNG, or undefined if there isn't one.
+ */
+export async function readClipboardImage() {
+    let image;
+    try {
+        image = await readImage();
+    } catch {
+        // Thrown when the clipboard holds something other than an image
+        return undefined;
+    }
+    const { width, height } = await image.size();
+    const pixels = new Uint8ClampedArray(await image.rgba());
+    const canvas = document.createElement("canvas");
+    canvas.width = width;
+    canvas.height = height;
+    canvas.getContext("2d")?.putImageData(new ImageData(pixels, width), 0, 0);
+    const blob = await new Promise<Blob | null>((resolve) =>
+        canvas.toBlob(resolve, "image/png"),
+    );
+    if (!blob) {
+        throw new Error("Failed to read the image on the clipboard");
+    }
+    return new File([blob], "clipboard.png", { type: "image/png" });
+}
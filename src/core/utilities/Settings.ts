@@ -28,8 +28,53 @@ export interface Settings {
     };
     lmStudioBaseUrl?: string;
     cautiousEnter?: boolean;
+    screenshotRedaction?: ScreenshotRedactionSettings;
+    globalShortcuts?: GlobalShortcutBinding[];
 }
 
+/**
+ * Read by the Rust side (redaction.rs) when taking screenshots
+ */
+export interface ScreenshotRedactionSettings {
+    /** Mask the windows of `apps` in screenshots of the whole screen */
+    maskApps?: boolean;
+    /** App names; a window is masked if its app name contains one of them */
+    apps?: string[];
+    style?: "blur" | "blackout";
+}
+
+/**
+ * What a global shortcut does. Mirrors ShortcutAction in global_shortcuts.rs,
+ * which sends it to the window that carries it out as a
+ * `global_shortcut_action` event.
+ */
+export type GlobalShortcutAction =
+    | { type: "captureScreen" }
+    | { type: "captureWindow" }
+    | { type: "pasteClipboard" }
+    | { type: "newChat" }
+    | { type: "openProject"; projectId: string };
+
+/**
+ * A global shortcut other than the quick chat's. Saved by Settings only after
+ * update_global_shortcuts has registered it.
+ */
+export interface GlobalShortcutBinding {
+    shortcut: string;
+    action: GlobalShortcutAction;
+}
+
+// Same as DEFAULT_MASKED_APPS in redaction.rs
+export const DEFAULT_MASKED_APPS = [
+    "1Password",
+    "Bitwarden",
+    "Dashlane",
+    "KeePassXC",
+    "Keychain Access",
+    "LastPass",
+    "Proton Pass",
+];
+
 export class SettingsManager {
     private static instance: SettingsManager;
     private storeName = "settings";
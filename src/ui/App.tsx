@@ -43,6 +43,7 @@ import { config } from "@core/config";
 import Database from "@tauri-apps/plugin-sql";
 import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
+import { invoke } from "@tauri-apps/api/core";
 import { Progress } from "./components/ui/progress";
 import RetroSpinner from "./components/ui/retro-spinner";
 import {
@@ -61,12 +62,14 @@ import { getVersion } from "@tauri-apps/api/app";
 import { platform, arch, version } from "@tauri-apps/plugin-os";
 import { confirm } from "@tauri-apps/plugin-dialog";
 import { writeText } from "@tauri-apps/plugin-clipboard-manager";
+import { GlobalShortcutAction } from "@core/utilities/Settings";
 import { resourceDir, homeDir } from "@tauri-apps/api/path";
 import { v4 as uuidv4 } from "uuid";
 import {
     MutationCache,
     QueryClient,
     QueryClientProvider,
+    useQuery,
 } from "@tanstack/react-query";
 import { AppMetadataProvider } from "@ui/providers/AppMetadataProvider";
 import { ReactQueryDevtools } from "@tanstack/react-query-devtools";
@@ -79,6 +82,12 @@ import * as AppMetadataAPI from "@core/chorus/api/AppMetadataAPI";
 import * as ToolsetsAPI from "@core/chorus/api/ToolsetsAPI";
 import * as ChatAPI from "@core/camp/api/UnifiedChatAPI";
 import * as ProjectAPI from "@core/camp/api/UnifiedProjectAPI";
+import {
+    AttachmentUpdate,
+    attachmentKeys,
+} from "@core/chorus/api/AttachmentsAPI";
+import { chatKeys, chatQueries } from "@core/chorus/api/ChatAPI";
+import { projectQueries } from "@core/chorus/api/ProjectAPI";
 
 scan({
     enabled: true,
@@ -696,6 +705,63 @@ function AppContent() {
         isSettingsDialogOpen,
     ]);
 
+    // Global shortcuts for the main window (see global_shortcuts.rs). Rust
+    // brings the window forward before sending these.
+    useEffect(() => {
+        if (isQuickChatWindow) {
+            return;
+        }
+        const unlisten = getCurrentWindow().listen<GlobalShortcutAction>(
+            "global_shortcut_action",
+            (event) => {
+                const action = event.payload;
+                if (isDialogOpen) {
+                    dialogActions.closeDialog();
+                }
+                if (action.type === "newChat") {
+                    getOrCreateNewChat.mutate({ projectId: "default" });
+                } else if (action.type === "openProject") {
+                    navigate(`/projects/${action.projectId}`);
+                }
+            },
+        );
+
+        return () => {
+            void unlisten.then((fn) => fn()).catch(console.error);
+        };
+    }, [getOrCreateNewChat, isDialogOpen, isQuickChatWindow, navigate]);
+
+    // Chats picked from the tray menu (see tray.rs)
+    useEffect(() => {
+        if (isQuickChatWindow) {
+            return;
+        }
+        const unlisten = getCurrentWindow().listen<string>(
+            "tray_open_chat",
+            (event) => {
+                if (isDialogOpen) {
+                    dialogActions.closeDialog();
+                }
+                navigate(`/chat/${event.payload}`);
+            },
+        );
+
+        return () => {
+            void unlisten.then((fn) => fn()).catch(console.error);
+        };
+    }, [isDialogOpen, isQuickChatWindow, navigate]);
+
+    // The tray menu lists chats and projects, and is rebuilt whenever
+    // refresh_projects_state fires
+    const chatsQuery = useQuery(chatQueries.list());
+    const projectsQuery = useQuery(projectQueries.list());
+    useEffect(() => {
+        if (isQuickChatWindow || !chatsQuery.data || !projectsQuery.data) {
+            return;
+        }
+        void invoke("refresh_projects_state").catch(console.error);
+    }, [chatsQuery.data, projectsQuery.data, isQuickChatWindow]);
+
     const convertQuickChatToRegularChat =
         ChatAPI.useConvertQuickChatToRegularChat();
 
@@ -751,6 +817,38 @@ function AppContent() {
         void checkReviewsDialog();
     }, [db]);
 
+    // Refresh project attachments the watcher replaced because their source
+    // files changed. It has already marked the project's context summaries stale.
+    useEffect(() => {
+        const unlisten = listen(
+            "project_attachment_updated",
+            (event: { payload: AttachmentUpdate }) => {
+                const update = event.payload;
+                void (async () => {
+                    await queryClient.invalidateQueries({
+                        queryKey: attachmentKeys.attachmentContents(
+                            update.attachmentId,
+                        ),
+                    });
+                    for (const projectId of update.projectIds) {
+                        await queryClient.invalidateQueries(
+                            ProjectAPI.projectContextQueries.attachments(
+                                projectId,
+                            ),
+                        );
+                    }
+                    await queryClient.invalidateQueries({
+                        queryKey: chatKeys.all(),
+                    });
+                })();
+            },
+        );
+
+        return () => {
+            void unlisten.then((fn) => fn()).catch(console.error);
+        };
+    }, []);
+
     // Listen for events to open API keys settings
     useEffect(() => {
         const unlisten = listen(
@@ -4,6 +4,7 @@ import { convertFileSrc } from "@tauri-apps/api/core";
 import { Tooltip, TooltipContent, TooltipTrigger } from "./ui/tooltip";
 import {
     FileTextIcon,
+    FolderIcon,
     GlobeIcon,
     X,
     Loader2,
@@ -17,7 +18,8 @@ import { Attachment } from "@core/chorus/api/AttachmentsAPI";
 import { QuestionMarkCircledIcon } from "@radix-ui/react-icons";
 import { dialogActions } from "@core/infra/DialogStore";
 import { useDialogStore } from "@core/infra/DialogStore";
-import { cn } from "@ui/lib/utils";
+import { cn, convertDate } from "@ui/lib/utils";
+import { formatDistanceToNow } from "date-fns";
 import SimpleCopyButton from "./CopyButton";
 
 function truncate(text: string | undefined, maxLength: number = 35) {
@@ -37,6 +39,8 @@ function AttachmentSmallView({ attachment }: { attachment: Attachment }) {
             <FileTextIcon className="size-4 text-muted-foreground" />
         ) : attachment.type === "webpage" ? (
             <GlobeIcon className="size-4 text-blue-500" />
+        ) : attachment.type === "folder" ? (
+            <FolderIcon className="size-4 text-muted-foreground" />
         ) : (
             <QuestionMarkCircledIcon className="size-4 text-muted-foreground" />
         );
@@ -63,6 +67,15 @@ function AttachmentSmallView({ attachment }: { attachment: Attachment }) {
                 <span className="uppercase -mt-0.5 text-[10px] text-muted-foreground font-geist-mono tracking-wider">
                     {attachment.type}
                 </span>
+                {attachment.sourceUpdatedAt && (
+                    <span className="text-[10px] text-muted-foreground">
+                        updated{" "}
+                        {formatDistanceToNow(
+                            convertDate(attachment.sourceUpdatedAt),
+                            { addSuffix: true },
+                        )}
+                    </span>
+                )}
             </div>
         </div>
     );
@@ -72,7 +85,9 @@ function AttachmentBigView({ attachment }: { attachment: Attachment }) {
     // For images and PDFs, use the file path directly
     // For text and webpage, we still need to load the content
     const needsContent =
-        attachment.type === "text" || attachment.type === "webpage";
+        attachment.type === "text" ||
+        attachment.type === "webpage" ||
+        attachment.type === "folder";
     const contentQuery = AttachmentsAPI.useAttachmentContents(attachment);
 
     // Truncate text content to first 100000 characters
@@ -115,6 +130,7 @@ function AttachmentBigView({ attachment }: { attachment: Attachment }) {
                 </pre>
             );
         case "webpage":
+        case "folder":
             return (
                 <pre className="max-h-[80vh] overflow-auto whitespace-pre-wrap">
                     {displayContent ?? (
@@ -132,7 +148,9 @@ const attachmentPreviewDialogId = (attachmentId: string) =>
 
 function AttachmentPreviewHeader({ attachment }: { attachment: Attachment }) {
     const needsContent =
-        attachment.type === "text" || attachment.type === "webpage";
+        attachment.type === "text" ||
+        attachment.type === "webpage" ||
+        attachment.type === "folder";
     const contentQuery = AttachmentsAPI.useAttachmentContents(attachment);
     const content = contentQuery.data;
 
@@ -33,7 +33,7 @@ import {
 } from "@ui/hooks/useAttachments";
 import { dialogActions, useDialogStore } from "@core/infra/DialogStore";
 import { ChatSuggestions } from "./ChatSuggestions";
-import { ArrowUp, ChevronDownIcon } from "lucide-react";
+import { ArrowUp, ChevronDownIcon, Crop } from "lucide-react";
 import { Tooltip, TooltipContent, TooltipTrigger } from "./ui/tooltip";
 import { EmptyState } from "./EmptyState";
 import { handleInputPasteWithAttachments } from "@ui/lib/utils";
@@ -44,6 +44,19 @@ import * as DraftAPI from "@core/chorus/api/DraftAPI";
 import * as ModelConfigChatAPI from "@core/chorus/api/ModelConfigChatAPI";
 import * as ProjectAPI from "@core/camp/api/UnifiedProjectAPI";
 import * as ChatAPI from "@core/camp/api/UnifiedChatAPI";
+import RegionCaptureDialog, {
+    REGION_CAPTURE_DIALOG_ID,
+} from "./RegionCaptureDialog";
+import {
+    captureDisplayForRegion,
+    captureWholeScreenCompressed,
+    captureWindowCompressed,
+    DisplayCapture,
+    readClipboardImage,
+} from "@core/chorus/screenshot";
+import { readText } from "@tauri-apps/plugin-clipboard-manager";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import { GlobalShortcutAction } from "@core/utilities/Settings";
 
 const DEFAULT_CHAT_INPUT_ID = "default-chat-input";
 const REPLY_CHAT_INPUT_ID = "reply-chat-input";
@@ -119,6 +132,19 @@ export function ChatInput({
         association: { type: "draft", chatId },
     });
 
+    const [regionCapture, setRegionCapture] = useState<DisplayCapture>();
+    const handleCaptureRegion = async () => {
+        try {
+            setRegionCapture(await captureDisplayForRegion());
+            dialogActions.openDialog(REGION_CAPTURE_DIALOG_ID);
+        } catch (error) {
+            toast.error("Error capturing screen", {
+                description:
+                    error instanceof Error ? error.message : "Unknown error",
+            });
+        }
+    };
+
     const { isQuickChatWindow } = useAppContext();
     const focusedChatInputId = useInputStore((state) => state.focusedInputId);
 
@@ -546,6 +572,64 @@ export function ChatInput({
         }
     }, [inputRef, isQuickChatWindow]);
 
+    // Global shortcuts that capture or paste into the quick chat (see
+    // global_shortcuts.rs). Captures happen with the quick chat hidden, so it's
+    // shown again afterwards.
+    useEffect(() => {
+        if (!isQuickChatWindow || isReply) {
+            return;
+        }
+        const unlistenPromise = getCurrentWindow().listen<GlobalShortcutAction>(
+            "global_shortcut_action",
+            (event) => {
+                void (async () => {
+                    try {
+                        switch (event.payload.type) {
+                            case "captureScreen":
+                                await filePaste.mutateAsync([
+                                    await captureWholeScreenCompressed(),
+                                ]);
+                                break;
+                            case "captureWindow":
+                                await filePaste.mutateAsync([
+                                    await captureWindowCompressed(),
+                                ]);
+                                break;
+                            case "pasteClipboard": {
+                                const image = await readClipboardImage();
+                                if (image) {
+                                    await filePaste.mutateAsync([image]);
+                                    break;
+                                }
+                                // Throws if there's no text either
+                                const text = await readText().catch(() => "");
+                                if (text) {
+                                    setDraft(
+                                        draft ? `${draft}\n${text}` : text,
+                                    );
+                                }
+                                break;
+                            }
+                        }
+                    } catch (error) {
+                        toast.error("Global shortcut failed", {
+                            description:
+                                error instanceof Error
+                                    ? error.message
+                                    : "Unknown error",
+                        });
+                    } finally {
+                        await invoke("show");
+                    }
+                })();
+            },
+        );
+
+        return () => {
+            void unlistenPromise.then((unlisten) => unlisten());
+        };
+    }, [draft, filePaste, isQuickChatWindow, isReply, setDraft]);
+
     const defaultChatComposer = !isQuickChatWindow && (
         <div
             className={
@@ -814,6 +898,22 @@ export function ChatInput({
                     ⌘L to focus
                 </div>
             )}
+
+            <Tooltip>
+                <TooltipTrigger asChild>
+                    <button
+                        className="absolute bottom-2 right-2 p-1 rounded-md text-muted-foreground hover:text-foreground hover:bg-foreground/10"
+                        onClick={() => void handleCaptureRegion()}
+                    >
+                        <Crop className="size-4" />
+                    </button>
+                </TooltipTrigger>
+                <TooltipContent>Screenshot a region</TooltipContent>
+            </Tooltip>
+            <RegionCaptureDialog
+                capture={regionCapture}
+                onAttach={(file) => filePaste.mutate([file])}
+            />
         </div>
     ) : isReply ? (
         // Relative positioning variant for reply drawer
@@ -0,0 +1,234 @@
+import { useEffect, useMemo, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { toast } from "sonner";
+import { XIcon } from "lucide-react";
+import {
+    GlobalShortcutAction,
+    GlobalShortcutBinding,
+    SettingsManager,
+} from "@core/utilities/Settings";
+import * as ProjectAPI from "@core/camp/api/UnifiedProjectAPI";
+import ShortcutRecorder from "./ShortcutRecorder";
+import { Button } from "./ui/button";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "./ui/select";
+
+const settingsManager = SettingsManager.getInstance();
+
+const ACTIONS: {
+    action: GlobalShortcutAction;
+    label: string;
+    description: string;
+}[] = [
+    {
+        action: { type: "captureScreen" },
+        label: "Capture screen",
+        description: "Screenshot your screen into an ambient chat",
+    },
+    {
+        action: { type: "captureWindow" },
+        label: "Capture window",
+        description: "Screenshot the frontmost window into an ambient chat",
+    },
+    {
+        action: { type: "pasteClipboard" },
+        label: "Paste clipboard",
+        description: "Paste what you've copied into an ambient chat",
+    },
+    {
+        action: { type: "newChat" },
+        label: "New chat",
+        description: "Start a new chat in the main window",
+    },
+];
+
+function isSameAction(a: GlobalShortcutAction, b: GlobalShortcutAction) {
+    if (a.type === "openProject" && b.type === "openProject") {
+        return a.projectId === b.projectId;
+    }
+    return a.type === b.type;
+}
+
+function ShortcutRow({
+    label,
+    description,
+    shortcut,
+    onChange,
+}: {
+    label: string;
+    description?: string;
+    shortcut: string | undefined;
+    onChange: (_shortcut: string | undefined) => void;
+}) {
+    return (
+        <div className="flex items-center justify-between gap-4">
+            <div className="space-y-0.5 min-w-0">
+                <div className="text-sm font-medium truncate">{label}</div>
+                {description && (
+                    <p className="text-sm text-muted-foreground">
+                        {description}
+                    </p>
+                )}
+            </div>
+            <div className="flex items-center gap-1 w-56 shrink-0">
+                <ShortcutRecorder value={shortcut ?? ""} onChange={onChange} />
+                <Button
+                    variant="ghost"
+                    size="iconSm"
+                    onClick={() => onChange(undefined)}
+                    aria-label={`Remove the ${label} shortcut`}
+                >
+                    <XIcon className="w-3.5 h-3.5" />
+                </Button>
+            </div>
+        </div>
+    );
+}
+
+/**
+ * Global shortcuts other than the ambient chat's, which work even when Camp is
+ * in the background (see global_shortcuts.rs).
+ */
+export function GlobalShortcutsSettings() {
+    const [bindings, setBindings] = useState<GlobalShortcutBinding[]>([]);
+    // Projects picked to get a shortcut, before one has been recorded
+    const [newProjectIds, setNewProjectIds] = useState<string[]>([]);
+    const [recorderKey, setRecorderKey] = useState(0);
+    const projectsQuery = ProjectAPI.useProjectsQuery();
+    const projects = useMemo(
+        () =>
+            (projectsQuery.data ?? []).filter(
+                (project) => !["default", "quick-chat"].includes(project.id),
+            ),
+        [projectsQuery.data],
+    );
+
+    useEffect(() => {
+        void settingsManager
+            .get()
+            .then((settings) => setBindings(settings.globalShortcuts ?? []));
+    }, []);
+
+    // The shortcuts are registered before anything is saved, so if one is
+    // invalid or taken by another app they all stay as they were
+    const applyBindings = async (next: GlobalShortcutBinding[]) => {
+        let registered: GlobalShortcutBinding[];
+        try {
+            registered = await invoke<GlobalShortcutBinding[]>(
+                "update_global_shortcuts",
+                { bindings: next },
+            );
+        } catch (error) {
+            toast.error("Couldn't change global shortcuts", {
+                description: String(error),
+            });
+            // Remount the recorders so they show the current shortcuts again
+            setRecorderKey((key) => key + 1);
+            return;
+        }
+        setBindings(registered);
+        const currentSettings = await settingsManager.get();
+        void settingsManager.set({
+            ...currentSettings,
+            globalShortcuts: registered,
+        });
+    };
+
+    const setShortcut = (
+        action: GlobalShortcutAction,
+        shortcut: string | undefined,
+    ) => {
+        const others = bindings.filter(
+            (binding) => !isSameAction(binding.action, action),
+        );
+        void applyBindings(
+            shortcut ? [...others, { shortcut, action }] : others,
+        );
+    };
+
+    const shortcutFor = (action: GlobalShortcutAction) =>
+        bindings.find((binding) => isSameAction(binding.action, action))
+            ?.shortcut;
+
+    const projectIds = [
+        ...bindings.flatMap((binding) =>
+            binding.action.type === "openProject"
+                ? [binding.action.projectId]
+                : [],
+        ),
+        ...newProjectIds.filter((id) =>
+            bindings.every(
+                (binding) =>
+                    !isSameAction(binding.action, {
+                        type: "openProject",
+                        projectId: id,
+                    }),
+            ),
+        ),
+    ];
+    const projectsWithoutShortcuts = projects.filter(
+        (project) => !projectIds.includes(project.id),
+    );
+
+    return (
+        <div className="space-y-4" key={recorderKey}>
+            {ACTIONS.map(({ action, label, description }) => (
+                <ShortcutRow
+                    key={action.type}
+                    label={label}
+                    description={description}
+                    shortcut={shortcutFor(action)}
+                    onChange={(shortcut) => setShortcut(action, shortcut)}
+                />
+            ))}
+            {projectIds.map((projectId) => {
+                const action: GlobalShortcutAction = {
+                    type: "openProject",
+                    projectId,
+                };
+                const project = projects.find(({ id }) => id === projectId);
+                return (
+                    <ShortcutRow
+                        key={projectId}
+                        label={`Open ${project?.name ?? "a deleted project"}`}
+                        shortcut={shortcutFor(action)}
+                        onChange={(shortcut) => {
+                            if (!shortcut) {
+                                setNewProjectIds((ids) =>
+                                    ids.filter((id) => id !== projectId),
+                                );
+                            }
+                            setShortcut(action, shortcut);
+                        }}
+                    />
+                );
+            })}
+            {projectsWithoutShortcuts.length > 0 && (
+                <Select
+                    value=""
+                    onValueChange={(projectId) =>
+                        setNewProjectIds((ids) => [...ids, projectId])
+                    }
+                >
+                    <SelectTrigger className="w-full">
+                        <SelectValue
+                            placeholder="Add a shortcut to open a project"
+                        />
+                    </SelectTrigger>
+                    <SelectContent>
+                        {projectsWithoutShortcuts.map((project) => (
+                            <SelectItem key={project.id} value={project.id}>
+                                {project.name}
+                            </SelectItem>
+                        ))}
+                    </SelectContent>
+                </Select>
+            )}
+        </div>
+    );
+}
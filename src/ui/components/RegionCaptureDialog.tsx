@@ -0,0 +1,193 @@
+import { useRef, useState } from "react";
+import {
+    Dialog,
+    DialogContent,
+    DialogHeader,
+    DialogTitle,
+    DialogDescription,
+} from "@ui/components/ui/dialog";
+import { Button } from "@ui/components/ui/button";
+import { toast } from "sonner";
+import { Crop, Loader2 } from "lucide-react";
+import { dialogActions, useDialogStore } from "@core/infra/DialogStore";
+import {
+    cropScreenshot,
+    DisplayCapture,
+    RegionSelection,
+} from "@core/chorus/screenshot";
+
+export const REGION_CAPTURE_DIALOG_ID = "region-capture-dialog";
+
+type Point = { x: number; y: number };
+
+interface RegionCaptureDialogProps {
+    capture: DisplayCapture | undefined;
+    onAttach: (file: File) => void;
+}
+
+export default function RegionCaptureDialog({
+    capture,
+    onAttach,
+}: RegionCaptureDialogProps) {
+    const imageRef = useRef<HTMLImageElement>(null);
+    // Corners of the selection, in pixels of the displayed preview
+    const [start, setStart] = useState<Point>();
+    const [end, setEnd] = useState<Point>();
+    const [isDragging, setIsDragging] = useState(false);
+    const [isCropping, setIsCropping] = useState(false);
+
+    const isOpen = useDialogStore(
+        (state) => state.activeDialogId === REGION_CAPTURE_DIALOG_ID,
+    );
+
+    const pointFromEvent = (e: React.PointerEvent): Point | undefined => {
+        const rect = imageRef.current?.getBoundingClientRect();
+        if (!rect) return undefined;
+        return {
+            x: Math.min(Math.max(e.clientX - rect.left, 0), rect.width),
+            y: Math.min(Math.max(e.clientY - rect.top, 0), rect.height),
+        };
+    };
+
+    const box =
+        start && end
+            ? {
+                  left: Math.min(start.x, end.x),
+                  top: Math.min(start.y, end.y),
+                  width: Math.abs(end.x - start.x),
+                  height: Math.abs(end.y - start.y),
+              }
+            : undefined;
+
+    // The preview is scaled to fit the dialog, so convert back to the
+    // display's logical points before sending the selection to Rust
+    const toSelection = (): RegionSelection | undefined => {
+        const rect = imageRef.current?.getBoundingClientRect();
+        if (!capture || !box || !rect || rect.width === 0) return undefined;
+        const scale = capture.logicalWidth / rect.width;
+        return {
+            x: box.left * scale,
+            y: box.top * scale,
+            width: box.width * scale,
+            height: box.height * scale,
+        };
+    };
+
+    const reset = () => {
+        setStart(undefined);
+        setEnd(undefined);
+        setIsDragging(false);
+    };
+
+    const handleClose = () => {
+        if (!isCropping) {
+            reset();
+            dialogActions.closeDialog();
+        }
+    };
+
+    const handleAttach = async () => {
+        const selection = toSelection();
+        if (!capture || !selection) return;
+
+        setIsCropping(true);
+        try {
+            onAttach(await cropScreenshot(capture, selection));
+            reset();
+            dialogActions.closeDialog();
+        } catch (error) {
+            toast.error("Failed to crop screenshot", {
+                description:
+                    error instanceof Error ? error.message : "Unknown error",
+            });
+        } finally {
+            setIsCropping(false);
+        }
+    };
+
+    const hasSelection = !!box && box.width >= 2 && box.height >= 2;
+
+    return (
+        <Dialog
+            id={REGION_CAPTURE_DIALOG_ID}
+            open={isOpen}
+            onOpenChange={(open) => {
+                if (!open) handleClose();
+            }}
+        >
+            <DialogContent className="sm:max-w-3xl p-5">
+                <DialogHeader>
+                    <DialogTitle className="flex items-center gap-2">
+                        <Crop className="size-5" />
+                        Screenshot a region
+                    </DialogTitle>
+                    <DialogDescription className="text-sm text-muted-foreground">
+                        Drag to select the part of the screen to attach.
+                    </DialogDescription>
+                </DialogHeader>
+
+                {capture && (
+                    <div
+                        className="relative mt-2 mx-auto w-fit select-none cursor-crosshair touch-none"
+                        onPointerDown={(e) => {
+                            const point = pointFromEvent(e);
+                            if (!point) return;
+                            e.currentTarget.setPointerCapture(e.pointerId);
+                            setStart(point);
+                            setEnd(point);
+                            setIsDragging(true);
+                        }}
+                        onPointerMove={(e) => {
+                            if (!isDragging) return;
+                            setEnd(pointFromEvent(e));
+                        }}
+                        onPointerUp={() => setIsDragging(false)}
+                    >
+                        <img
+                            ref={imageRef}
+                            src={`data:image/jpeg;base64,${capture.preview}`}
+                            alt="Screenshot"
+                            draggable={false}
+                            className="block max-w-full max-h-[60vh] rounded-md"
+                        />
+                        {box && (
+                            <div
+                                className="absolute border-2 border-primary bg-primary/10 pointer-events-none"
+                                style={{
+                                    left: box.left,
+                                    top: box.top,
+                                    width: box.width,
+                                    height: box.height,
+                                }}
+                            />
+                        )}
+                    </div>
+                )}
+
+                <div className="flex justify-end gap-2 mt-4">
+                    <Button
+                        variant="outline"
+                        onClick={handleClose}
+                        disabled={isCropping}
+                    >
+                        Cancel
+                    </Button>
+                    <Button
+                        onClick={() => void handleAttach()}
+                        disabled={isCropping || !hasSelection}
+                        className="min-w-[100px]"
+                    >
+                        {isCropping ? (
+                            <>
+                                <Loader2 className="size-4 mr-2 animate-spin" />
+                                Cropping...
+                            </>
+                        ) : (
+                            "Attach"
+                        )}
+                    </Button>
+                </div>
+            </DialogContent>
+        </Dialog>
+    );
+}
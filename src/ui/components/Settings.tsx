@@ -7,6 +7,8 @@ import {
     SelectValue,
 } from "@ui/components/ui/select";
 import {
+    DEFAULT_MASKED_APPS,
+    ScreenshotRedactionSettings,
     SettingsManager,
     // Settings as SettingsType,
 } from "@core/utilities/Settings";
@@ -58,7 +60,7 @@ import ApiKeysForm from "./ApiKeysForm";
 import Database from "@tauri-apps/plugin-sql";
 import { Input } from "./ui/input";
 import { Textarea } from "./ui/textarea";
-import { relaunch } from "@tauri-apps/plugin-process";
+import { invoke } from "@tauri-apps/api/core";
 import { useDatabase } from "@ui/hooks/useDatabase";
 import {
     Collapsible,
@@ -96,6 +98,7 @@ import { SiElevenlabs } from "react-icons/si";
 import { ToolsetsManager } from "@core/chorus/ToolsetsManager";
 import { getToolsetIcon } from "@core/chorus/Toolsets";
 import ShortcutRecorder from "./ShortcutRecorder";
+import { GlobalShortcutsSettings } from "./GlobalShortcutsSettings";
 import FeedbackButton from "./FeedbackButton";
 import { SiOpenai } from "react-icons/si";
 import ImportChatDialog from "./ImportChatDialog";
@@ -1649,6 +1652,7 @@ interface Settings {
     autoScrapeUrls: boolean;
     cautiousEnter?: boolean;
     customToolsets?: CustomToolsetConfig[];
+    screenshotRedaction?: ScreenshotRedactionSettings;
 }
 
 export default function Settings({ tab = "general" }: SettingsProps) {
@@ -1657,12 +1661,17 @@ export default function Settings({ tab = "general" }: SettingsProps) {
     const [autoConvertLongText, setAutoConvertLongText] = useState(true);
     const [autoScrapeUrls, setAutoScrapeUrls] = useState(true);
     const [cautiousEnter, setCautiousEnter] = useState(false);
+    const [maskAppsInScreenshots, setMaskAppsInScreenshots] = useState(false);
+    const [maskedApps, setMaskedApps] = useState(
+        DEFAULT_MASKED_APPS.join(", "),
+    );
     const { db } = useDatabase();
     const [searchParams] = useSearchParams();
     const defaultTab =
         tab || (searchParams.get("tab") as SettingsTabId) || "general";
-    const [quickChatEnabled, setQuickChatEnabled] = useState(true);
+    const [quickChatEnabled, setQuickChatEnabled] = useState(false);
     const [quickChatShortcut, setQuickChatShortcut] = useState("Alt+Space");
+    const [shortcutRecorderKey, setShortcutRecorderKey] = useState(0);
     const queryClient = useQueryClient();
 
     // Use React Query hooks for custom base URL
@@ -1720,38 +1729,59 @@ export default function Settings({ tab = "general" }: SettingsProps) {
             const settings = (await settingsManager.get()) as Settings;
             setSansFont(settings.sansFont ?? "Geist");
             setMonoFont(settings.monoFont ?? "Fira Code");
-            setQuickChatEnabled(settings.quickChat?.enabled ?? true);
+            // Off if missing, like QuickChatSettings in quick_chat.rs
+            setQuickChatEnabled(settings.quickChat?.enabled ?? false);
             setQuickChatShortcut(settings.quickChat?.shortcut ?? "Alt+Space");
             setAutoConvertLongText(settings.autoConvertLongText ?? true);
             setAutoScrapeUrls(settings.autoScrapeUrls ?? true);
             setCautiousEnter(settings.cautiousEnter ?? false);
+            const redaction = settings.screenshotRedaction;
+            setMaskAppsInScreenshots(redaction?.maskApps ?? false);
+            setMaskedApps((redaction?.apps ?? DEFAULT_MASKED_APPS).join(", "));
         };
 
         void loadSettings();
     }, [db, setMonoFont, setSansFont, settingsManager]);
 
-    const handleQuickChatShortcutChange = async (value: string) => {
-        setQuickChatShortcut(value);
+    // The shortcut is registered before anything is saved, so one that's
+    // invalid or taken by another app leaves the current one in place
+    const applyQuickChatSettings = async (
+        enabled: boolean,
+        shortcut: string,
+    ) => {
+        let registeredShortcut: string;
+        try {
+            registeredShortcut = await invoke<string>(
+                "update_quick_chat_settings",
+                { enabled, shortcut },
+            );
+        } catch (error) {
+            toast.error(`Couldn't use ${shortcut}`, {
+                description: String(error),
+            });
+            // Remount the recorder so it shows the current shortcut again
+            setShortcutRecorderKey((key) => key + 1);
+            return;
+        }
+        setQuickChatEnabled(enabled);
+        setQuickChatShortcut(registeredShortcut);
         const currentSettings = await settingsManager.get();
         void settingsManager.set({
             ...currentSettings,
             quickChat: {
                 ...currentSettings.quickChat,
-                shortcut: value,
+                enabled,
+                shortcut: registeredShortcut,
             },
         });
     };
 
+    const handleQuickChatShortcutChange = async (value: string) => {
+        await applyQuickChatSettings(quickChatEnabled, value);
+    };
+
     const handleQuickChatEnabledChange = async (enabled: boolean) => {
-        setQuickChatEnabled(enabled);
-        const currentSettings = await settingsManager.get();
-        void settingsManager.set({
-            ...currentSettings,
-            quickChat: {
-                ...currentSettings.quickChat,
-                enabled,
-            },
-        });
+        await applyQuickChatSettings(enabled, quickChatShortcut);
     };
 
     const handleAutoConvertLongTextChange = async (enabled: boolean) => {
@@ -1772,6 +1802,33 @@ export default function Settings({ tab = "general" }: SettingsProps) {
         });
     };
 
+    const handleScreenshotRedactionChange = async (
+        redaction: ScreenshotRedactionSettings,
+    ) => {
+        const currentSettings = await settingsManager.get();
+        void settingsManager.set({
+            ...currentSettings,
+            screenshotRedaction: {
+                ...currentSettings.screenshotRedaction,
+                ...redaction,
+            },
+        });
+    };
+
+    const handleMaskAppsInScreenshotsChange = async (enabled: boolean) => {
+        setMaskAppsInScreenshots(enabled);
+        await handleScreenshotRedactionChange({ maskApps: enabled });
+    };
+
+    const handleMaskedAppsBlur = async () => {
+        const apps = maskedApps
+            .split(",")
+            .map((app) => app.trim())
+            .filter((app) => app.length > 0);
+        setMaskedApps(apps.join(", "));
+        await handleScreenshotRedactionChange({ apps });
+    };
+
     const handleCautiousEnterChange = async (enabled: boolean) => {
         setCautiousEnter(enabled);
         const currentSettings = await settingsManager.get();
@@ -1793,17 +1850,7 @@ export default function Settings({ tab = "general" }: SettingsProps) {
     };
 
     const onDefaultQcShortcutClick = async () => {
-        setQuickChatShortcut("Alt+Space");
-        setQuickChatEnabled(true);
-        const currentSettings = await settingsManager.get();
-        void settingsManager.set({
-            ...currentSettings,
-            quickChat: {
-                ...currentSettings.quickChat,
-                shortcut: "Alt+Space",
-                enabled: true,
-            },
-        });
+        await applyQuickChatSettings(true, "Alt+Space");
     };
 
     const onCustomBaseUrlChange = (e: React.ChangeEvent<HTMLInputElement>) => {
@@ -2146,6 +2193,39 @@ export default function Settings({ tab = "general" }: SettingsProps) {
                                         }
                                     />
                                 </div>
+
+                                <div className="flex items-center justify-between pt-2">
+                                    <div className="space-y-0.5">
+                                        <div className="font-semibold ">
+                                            Hide apps in screenshots
+                                        </div>
+                                        <div className=" ">
+                                            Blur the windows of password
+                                            managers and other apps below in
+                                            screenshots of your screen
+                                        </div>
+                                    </div>
+                                    <Switch
+                                        checked={maskAppsInScreenshots}
+                                        onCheckedChange={(enabled) =>
+                                            void handleMaskAppsInScreenshotsChange(
+                                                enabled,
+                                            )
+                                        }
+                                    />
+                                </div>
+                                {maskAppsInScreenshots && (
+                                    <Input
+                                        value={maskedApps}
+                                        onChange={(e) =>
+                                            setMaskedApps(e.target.value)
+                                        }
+                                        onBlur={() =>
+                                            void handleMaskedAppsBlur()
+                                        }
+                                        placeholder="App names, separated by commas"
+                                    />
+                                )}
                             </div>
 
                             <div className="flex justify-end mt-4 mb-2"></div>
@@ -2288,6 +2368,7 @@ export default function Settings({ tab = "general" }: SettingsProps) {
                                         start an ambient chat.
                                     </p>
                                     <ShortcutRecorder
+                                        key={shortcutRecorderKey}
                                         value={quickChatShortcut}
                                         onChange={(shortcut) =>
                                             void handleQuickChatShortcutChange(
@@ -2305,32 +2386,26 @@ export default function Settings({ tab = "general" }: SettingsProps) {
                                         >
                                             Set to default
                                         </Button>
-                                        <Button
-                                            variant="default"
-                                            size="sm"
-                                            onClick={() => {
-                                                if (!quickChatShortcut.trim()) {
-                                                    toast.error(
-                                                        "Invalid shortcut",
-                                                        {
-                                                            description:
-                                                                "Shortcut cannot be empty",
-                                                        },
-                                                    );
-                                                    return;
-                                                }
-                                                void relaunch().catch(
-                                                    console.error,
-                                                );
-                                            }}
-                                        >
-                                            Save and restart
-                                        </Button>
                                     </div>
                                 </div>
 
                                 <Separator />
 
+                                <div className="space-y-4">
+                                    <div className="space-y-0.5">
+                                        <label className="font-semibold">
+                                            Global Shortcuts
+                                        </label>
+                                        <p className="text-sm text-muted-foreground">
+                                            Shortcuts that work even when Camp
+                                            is in the background.
+                                        </p>
+                                    </div>
+                                    <GlobalShortcutsSettings />
+                                </div>
+
+                                <Separator />
+
                                 <div className="space-y-4">
                                     <AccessibilitySettings />
                                 </div>
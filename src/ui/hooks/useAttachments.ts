@@ -1,12 +1,12 @@
 import { allowedExtensions } from "@core/chorus/Models";
 import {
     fileTypeToAttachmentType,
-    generateStorePath,
     resizeAndStoreFileData,
     canScrape,
     scrapeTimestamps,
-    scrapeUrlAndWriteToPath,
+    scrapeUrlAndStore,
     getScreenshotAttachment,
+    storeFolder,
 } from "@core/chorus/AttachmentsHelpers";
 import { useMutation } from "@tanstack/react-query";
 import { storeFile } from "@core/chorus/AttachmentsHelpers";
@@ -16,8 +16,8 @@ import { toast } from "sonner";
 import { captureWholeScreenCompressed } from "@core/chorus/screenshot";
 import * as ProjectAPI from "@core/camp/api/UnifiedProjectAPI";
 import * as DraftAPI from "@core/chorus/api/DraftAPI";
-import { invoke } from "@tauri-apps/api/core";
 import { getApiKeys } from "@core/chorus/api/AppMetadataAPI";
+import { FileInspection, inspectFile } from "@core/chorus/FileAccess";
 
 export function useFilePaste({
     association,
@@ -101,17 +101,14 @@ export function useFileDrop({
             await Promise.all(
                 paths.map(async (path) => {
                     // Check file size using Tauri command
+                    let inspection: FileInspection | undefined;
                     try {
-                        const metadata = await invoke<{
-                            size: number;
-                            isFile: boolean;
-                            isDirectory: boolean;
-                        }>("get_file_metadata", { path });
+                        inspection = await inspectFile(path);
 
-                        if (metadata.size > MAX_FILE_SIZE) {
+                        if (inspection.size > MAX_FILE_SIZE) {
                             const fileName = path.split("/").pop() || path;
                             toast.error(`File "${fileName}" is too large`, {
-                                description: `Size: ${(metadata.size / 1024 / 1024).toFixed(1)}MB. Maximum size is 30MB.`,
+                                description: `Size: ${(inspection.size / 1024 / 1024).toFixed(1)}MB. Maximum size is 30MB.`,
                             });
                             return;
                         }
@@ -120,16 +117,37 @@ export function useFileDrop({
                         // Continue without size check if metadata fails
                     }
 
+                    const isFolder = inspection?.isDirectory ?? false;
+
                     // 1. create attachment
                     const attachmentId = await createAttachment.mutateAsync({
-                        type: fileTypeToAttachmentType(path) || "text",
+                        // The sniffed type doesn't depend on the extension
+                        type: isFolder
+                            ? "folder"
+                            : inspection?.attachmentType ||
+                              fileTypeToAttachmentType(path) ||
+                              "text",
                         originalName: path.split("/").pop() || path,
                         path,
                         association,
                     });
 
                     // 2. store
-                    const { storedPath } = await storeFile(path); // storeFile handles generating a new path
+                    let storedPath: string;
+                    if (isFolder) {
+                        const folder = await storeFolder(path);
+                        storedPath = folder.storedPath;
+                        if (folder.skipped.length > 0 || folder.truncated) {
+                            toast(`Attached ${folder.fileCount} files`, {
+                                description: folder.truncated
+                                    ? "The folder has too many files, so only the first ones were included."
+                                    : `${folder.skipped.length} binary, lock or oversized files were left out.`,
+                            });
+                        }
+                    } else {
+                        // storeFile handles generating a new path
+                        storedPath = (await storeFile(path)).storedPath;
+                    }
 
                     // 3. finalize
                     if (association.type === "project") {
@@ -137,6 +155,8 @@ export function useFileDrop({
                             attachmentId,
                             storedPath,
                             projectId: association.projectId,
+                            // Project context follows edits to the original
+                            sourcePath: path,
                         });
                     } else if (association.type === "draft") {
                         await finalizeAttachmentForDraft.mutateAsync({
@@ -198,8 +218,6 @@ export function useAttachUrl({
     return useMutation({
         mutationKey: ["attachUrl"] as const,
         mutationFn: async ({ url }: { url: string }) => {
-            const path = await generateStorePath(url, "md");
-
             // Get Firecrawl API key (includes default from env fallback)
             const apiKeys = await getApiKeys();
 
@@ -227,17 +245,13 @@ export function useAttachUrl({
             const attachmentId = await createAttachment.mutateAsync({
                 type: "webpage",
                 originalName: url,
-                path,
+                path: url,
                 association,
             });
 
-            const result = await scrapeUrlAndWriteToPath(
-                url,
-                path,
-                firecrawlApiKey,
-            );
+            const result = await scrapeUrlAndStore(url, firecrawlApiKey);
 
-            if (!result.success) {
+            if (!result.success || !result.storedPath) {
                 const isApiKeyError = result.error?.includes("API key");
                 toast.error(
                     isApiKeyError
@@ -267,13 +281,13 @@ export function useAttachUrl({
             if (association.type === "draft") {
                 await finalizeAttachmentForDraft.mutateAsync({
                     attachmentId,
-                    storedPath: path,
+                    storedPath: result.storedPath,
                     chatId: association.chatId,
                 });
             } else if (association.type === "project") {
                 await finalizeAttachmentForProject.mutateAsync({
                     attachmentId,
-                    storedPath: path,
+                    storedPath: result.storedPath,
                     projectId: association.projectId,
                 });
             }